@@ -0,0 +1,349 @@
+use std::net::{Ipv4Addr, Ipv6Addr, ToSocketAddrs};
+
+use reqwest::Url;
+
+/// The scheme kept on the addresses of devices reached over HTTPS
+const HTTPS: &str = "https://";
+
+/// Parse a device address: an IP address or hostname, optionally with a port, like
+/// `192.168.1.40`, `wled-porch.local` or `[fe80::1]:8080`. A leading `http://` and
+/// trailing `/` are dropped, so an address copied from the browser works too. Hostnames
+/// are kept as they're given, and looked up each time they're used.
+///
+/// A device behind an HTTPS reverse proxy can be given as a full base URL like
+/// `https://wled.example.com`, which is kept with its scheme.
+pub fn parse_address(value: &str) -> Result<String, String> {
+    let address = value.trim();
+    let address = address.strip_suffix('/').unwrap_or(address);
+
+    // Ports that are the default for the scheme are dropped
+    let (scheme, address, default_port) = match address.strip_prefix(HTTPS) {
+        Some(address) => (HTTPS, address, ":443"),
+        None => (
+            "",
+            address.strip_prefix("http://").unwrap_or(address),
+            ":80",
+        ),
+    };
+    let host = parse_host(value, address)?;
+    let host = host.strip_suffix(default_port).unwrap_or(&host);
+
+    Ok(format!("{scheme}{host}"))
+}
+
+/// Parse the host and optional port of an address, as given in `value`
+fn parse_host(value: &str, address: &str) -> Result<String, String> {
+    let invalid = || {
+        format!(
+            "Invalid device address '{value}': expected an IP address or hostname, optionally with a port, e.g. 192.168.1.40:8080"
+        )
+    };
+
+    // IPv6 addresses need brackets to be told apart from a port
+    if let Ok(ip) = address.parse::<Ipv6Addr>() {
+        return Ok(format!("[{ip}]"));
+    }
+
+    // A zone ID picks the network interface for a link-local address, which only means
+    // something on the computer it's given on, and isn't allowed in a URL
+    if let Some((ip, zone)) = address.trim_start_matches('[').split_once('%') {
+        if ip.parse::<Ipv6Addr>().is_ok() {
+            let zone = zone.split(']').next().unwrap_or_default();
+            return Err(format!(
+                "Invalid device address '{value}': IPv6 zone IDs like '%{zone}' aren't supported, so use the device's unique local or global IPv6 address, or its IPv4 address"
+            ));
+        }
+    }
+
+    let (host, port) = match address.strip_prefix('[') {
+        Some(bracketed) => {
+            let (ip, rest) = bracketed.split_once(']').ok_or_else(invalid)?;
+            let ip = ip.parse::<Ipv6Addr>().map_err(|_| invalid())?;
+            if !rest.is_empty() && !rest.starts_with(':') {
+                return Err(invalid());
+            }
+            (format!("[{ip}]"), rest.strip_prefix(':'))
+        }
+        None => match address.split_once(':') {
+            Some((host, port)) => (host.to_string(), Some(port)),
+            None => (address.to_string(), None),
+        },
+    };
+
+    let valid_host = host.starts_with('[') || host.parse::<Ipv4Addr>().is_ok() || {
+        // Anything made of digits and dots is meant as an IPv4 address, so it has to be one
+        !host.chars().all(|c| c.is_ascii_digit() || c == '.') && is_hostname(&host)
+    };
+    if !valid_host {
+        return Err(invalid());
+    }
+
+    match port.map(str::parse::<u16>) {
+        None => Ok(host),
+        Some(Ok(port)) if port > 0 => Ok(format!("{host}:{port}")),
+        Some(_) => Err(invalid()),
+    }
+}
+
+/// Whether `host` is a plausible hostname: dot-separated labels of letters, digits and
+/// hyphens, like `wled-porch.local`
+fn is_hostname(host: &str) -> bool {
+    host.len() <= 253
+        && host.split('.').all(|label| {
+            (1..=63).contains(&label.len())
+                && !label.starts_with('-')
+                && !label.ends_with('-')
+                && label.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+}
+
+/// Whether a value is an IP address, optionally with a scheme or port, as opposed to a
+/// hostname or a device name
+pub fn is_ip_address(value: &str) -> bool {
+    let Ok(address) = parse_address(value) else {
+        return false;
+    };
+    let host = address.strip_prefix(HTTPS).unwrap_or(&address);
+    host.starts_with('[')
+        || host
+            .split(':')
+            .next()
+            .is_some_and(|host| host.parse::<Ipv4Addr>().is_ok())
+}
+
+/// Whether the device at an address, as saved by `wld add`, is reached over HTTPS
+pub fn is_https(address: &str) -> bool {
+    address.starts_with(HTTPS)
+}
+
+/// The base URL of the device at an address, as saved by `wld add`. Addresses without a
+/// scheme are reached over plain HTTP. An IPv6 address written into the config by hand
+/// without brackets is bracketed, as a URL needs.
+pub fn base_url(address: &str) -> Result<Url, String> {
+    let url = if is_https(address) {
+        address.to_string()
+    } else if let Ok(ip) = address.parse::<Ipv6Addr>() {
+        format!("http://[{ip}]")
+    } else {
+        format!("http://{address}")
+    };
+    Url::parse(&url).map_err(|e| format!("Invalid device address '{address}': {e}"))
+}
+
+/// The address of the device at a base URL, as saved by `wld add`, for naming it in
+/// messages. The reverse of [`base_url`].
+pub fn address_of(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    let host = match url.port() {
+        Some(port) => format!("{host}:{port}"),
+        None => host.to_string(),
+    };
+    if url.scheme() == "https" {
+        format!("{HTTPS}{host}")
+    } else {
+        host
+    }
+}
+
+/// Whether two addresses are of the same device, even if they're written differently,
+/// like `fd00::42` and `[fd00:0::42]`
+pub fn same_address(a: &str, b: &str) -> bool {
+    a == b || matches!((parse_address(a), parse_address(b)), (Ok(a), Ok(b)) if a == b)
+}
+
+/// Look up the hostname in a device's URL, if it has one rather than an IP address, so a
+/// name that can't be found gets a clearer error than failing to connect
+pub fn resolve(url: &Url) -> Result<(), String> {
+    let Some(host) = url.domain() else {
+        return Ok(());
+    };
+    let port = url.port_or_known_default().unwrap_or(80);
+
+    let found = (host, port)
+        .to_socket_addrs()
+        .is_ok_and(|mut addresses| addresses.next().is_some());
+    if found {
+        Ok(())
+    } else {
+        Err(format!("Could not resolve host '{host}'"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_address() {
+        assert_eq!(
+            parse_address("192.168.1.40"),
+            Ok("192.168.1.40".to_string())
+        );
+        assert_eq!(
+            parse_address("192.168.1.40:8080"),
+            Ok("192.168.1.40:8080".to_string())
+        );
+        assert_eq!(
+            parse_address("wled-office.local:8080"),
+            Ok("wled-office.local:8080".to_string())
+        );
+        assert_eq!(
+            parse_address("http://192.168.1.40:8080/"),
+            Ok("192.168.1.40:8080".to_string())
+        );
+        // Port 80 is what http:// means anyway
+        assert_eq!(parse_address("office:80"), Ok("office".to_string()));
+    }
+
+    #[test]
+    fn test_parse_https_address() {
+        assert_eq!(
+            parse_address("https://wled.example.com/"),
+            Ok("https://wled.example.com".to_string())
+        );
+        assert_eq!(
+            parse_address("https://wled.example.com:443"),
+            Ok("https://wled.example.com".to_string())
+        );
+        assert_eq!(
+            parse_address("https://192.168.1.40:8443"),
+            Ok("https://192.168.1.40:8443".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_hostname_keeps_case() {
+        assert_eq!(
+            parse_address("WLED-Porch.local"),
+            Ok("WLED-Porch.local".to_string())
+        );
+        assert_eq!(parse_address("localhost"), Ok("localhost".to_string()));
+    }
+
+    #[test]
+    fn test_parse_ipv6_address() {
+        assert_eq!(parse_address("fe80::1"), Ok("[fe80::1]".to_string()));
+        assert_eq!(parse_address("fd00::42"), Ok("[fd00::42]".to_string()));
+        assert_eq!(
+            parse_address("[fe80::1]:8080"),
+            Ok("[fe80::1]:8080".to_string())
+        );
+        assert_eq!(
+            parse_address("http://[fd00::42]:80/"),
+            Ok("[fd00::42]".to_string())
+        );
+        assert_eq!(parse_address("FD00:0:0::42"), Ok("[fd00::42]".to_string()));
+    }
+
+    #[test]
+    fn test_parse_ipv6_zone_id() {
+        for value in ["fe80::1%eth0", "[fe80::1%eth0]", "[fe80::1%eth0]:8080"] {
+            let error = parse_address(value).unwrap_err();
+            assert!(
+                error.contains("IPv6 zone IDs like '%eth0' aren't supported"),
+                "{error}"
+            );
+        }
+    }
+
+    #[test]
+    fn test_same_address() {
+        assert!(same_address("fd00::42", "[fd00::42]"));
+        assert!(same_address("[fd00:0::42]:80", "fd00::42"));
+        assert!(same_address("192.168.1.40", "192.168.1.40"));
+        assert!(!same_address("[fd00::42]:8080", "[fd00::42]"));
+        assert!(!same_address("192.168.1.40", "192.168.1.41"));
+    }
+
+    #[test]
+    fn test_parse_invalid_address() {
+        for value in [
+            "",
+            "192.168.1.40:99999",
+            "192.168.1.40:port",
+            "192.168.1.40/json",
+            "https://192.168.1.40/json",
+            "ftp://192.168.1.40",
+            "user@192.168.1.40",
+            "192.168.1",
+            "300.1.1.1",
+            "wled porch",
+            "-porch.local",
+            "porch..local",
+            "porch_light",
+            "[fe80::1",
+            "[fe80::1]8080",
+            "192.168.1.40:0",
+        ] {
+            assert!(parse_address(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_is_ip_address() {
+        for value in [
+            "192.168.1.40",
+            "192.168.1.40:8080",
+            "http://192.168.1.40/",
+            "https://192.168.1.40:8443",
+            "fe80::1",
+            "[fe80::1]:8080",
+        ] {
+            assert!(is_ip_address(value), "{value}");
+        }
+        for value in ["bedroom", "wled-porch.local", "localhost:8080", "192.168.1"] {
+            assert!(!is_ip_address(value), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_resolve() {
+        assert!(resolve(&base_url("127.0.0.1:9").unwrap()).is_ok());
+        assert!(resolve(&base_url("localhost:9").unwrap()).is_ok());
+        assert_eq!(
+            resolve(&base_url("wled-missing.invalid").unwrap()),
+            Err("Could not resolve host 'wled-missing.invalid'".to_string())
+        );
+    }
+
+    #[test]
+    fn test_base_url_brackets_ipv6() {
+        assert_eq!(base_url("fd00::42").unwrap().as_str(), "http://[fd00::42]/");
+        assert_eq!(
+            base_url("[fd00::42]:8080").unwrap().as_str(),
+            "http://[fd00::42]:8080/"
+        );
+        assert_eq!(
+            address_of(&base_url("[fd00::42]:8080").unwrap()),
+            "[fd00::42]:8080"
+        );
+    }
+
+    #[test]
+    fn test_base_url_keeps_port() {
+        let url = base_url("192.168.1.40:8080").unwrap();
+        assert_eq!(url.port(), Some(8080));
+        assert_eq!(url.as_str(), "http://192.168.1.40:8080/");
+    }
+
+    #[test]
+    fn test_base_url_keeps_scheme() {
+        assert_eq!(
+            base_url("https://wled.example.com").unwrap().as_str(),
+            "https://wled.example.com/"
+        );
+        assert!(is_https("https://wled.example.com"));
+        assert!(!is_https("192.168.1.40"));
+    }
+
+    #[test]
+    fn test_address_of() {
+        for address in [
+            "192.168.1.40",
+            "192.168.1.40:8080",
+            "https://wled.example.com",
+        ] {
+            assert_eq!(address_of(&base_url(address).unwrap()), address);
+        }
+    }
+}
@@ -0,0 +1,672 @@
+use std::collections::BTreeMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use base64::prelude::{Engine, BASE64_STANDARD};
+use reqwest::header::{HeaderMap, HeaderValue, AUTHORIZATION};
+use reqwest::Url;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use wled_json_api_library::wled::Wled;
+
+use crate::address;
+use crate::error::{AtDevice, WldError};
+use crate::retry;
+use crate::track;
+
+/// How long to wait to connect to a device, unless it's given a timeout. A device that's
+/// switched off at the wall never answers, so this stops it holding up a command for as
+/// long as the OS would wait.
+pub const CONNECT_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long to wait for a device to answer a request, unless it's given a timeout
+pub const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Settings for a device that apply to every request to it, so need their own HTTP client
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ClientOptions {
+    /// Don't check the device's TLS certificate
+    pub insecure: bool,
+    /// Username and password to send with HTTP basic auth
+    pub login: Option<(String, String)>,
+    /// How long to wait for the device to answer, rather than `TIMEOUT`
+    pub timeout: Option<Duration>,
+}
+
+impl ClientOptions {
+    /// How long to wait for the device to answer a request
+    pub fn timeout(&self) -> Duration {
+        self.timeout.unwrap_or(TIMEOUT)
+    }
+
+    /// The HTTP client with these settings, shared by every request that uses them for the
+    /// rest of the process. Connections are kept open and reused, so a long-running
+    /// session like `wld repl` or `wld mcp`, or a command for several devices, doesn't set
+    /// up a new connection for every request. Devices with their own settings, like a
+    /// login, get a client of their own.
+    pub fn client(&self, ip: &str) -> Result<reqwest::blocking::Client, WldError> {
+        static CLIENTS: Mutex<Vec<(ClientOptions, reqwest::blocking::Client)>> =
+            Mutex::new(Vec::new());
+
+        let mut clients = CLIENTS.lock().unwrap();
+        if let Some((_, client)) = clients.iter().find(|(o, _)| o == self) {
+            return Ok(client.clone());
+        }
+        let client = self.builder()?.gzip(true).build().at(ip)?;
+        clients.push((self.clone(), client.clone()));
+        Ok(client)
+    }
+
+    /// A builder for an HTTP client with these settings
+    pub fn builder(&self) -> Result<reqwest::blocking::ClientBuilder, WldError> {
+        let mut headers = HeaderMap::new();
+        if let Some((username, password)) = &self.login {
+            let encoded = BASE64_STANDARD.encode(format!("{username}:{password}"));
+            let mut value = HeaderValue::from_str(&format!("Basic {encoded}"))
+                .map_err(|e| format!("Invalid login for device: {e}"))?;
+            value.set_sensitive(true);
+            headers.insert(AUTHORIZATION, value);
+        }
+
+        Ok(reqwest::blocking::Client::builder()
+            .danger_accept_invalid_certs(self.insecure)
+            .default_headers(headers)
+            .connect_timeout(CONNECT_TIMEOUT.min(self.timeout()))
+            .timeout(self.timeout()))
+    }
+}
+
+/// Fields of JSON sent to or from a device that are left out of logs, since they hold a
+/// PIN, password or key
+const SECRETS: [&str; 5] = ["pin", "psk", "pass", "pwd", "password"];
+
+/// Send a request to a device. With `-v`, each request is logged with its status and how
+/// long it took, and with `-vv`, so is what was sent.
+pub(crate) fn send(
+    client: &reqwest::blocking::Client,
+    request: reqwest::blocking::RequestBuilder,
+) -> Result<reqwest::blocking::Response, reqwest::Error> {
+    let request = request.build()?;
+    let method = request.method().clone();
+    let url = request.url().clone();
+    if let Some(body) = request.body().and_then(|body| body.as_bytes()) {
+        tracing::trace!(
+            "{method} {url} sent {}",
+            redact(&String::from_utf8_lossy(body))
+        );
+    }
+
+    let started = Instant::now();
+    let response = client.execute(request);
+    let took = started.elapsed().as_millis();
+    match &response {
+        Ok(response) => {
+            tracing::debug!("{method} {url} {} in {took}ms", response.status());
+            if response.status().is_success() {
+                track::seen(&address::address_of(&url), None);
+            }
+        }
+        Err(e) => tracing::debug!("{method} {url} failed after {took}ms: {e}"),
+    }
+    response
+}
+
+/// Read the body of a response from a device, logging it with `-vv`
+pub(crate) fn read_text(response: reqwest::blocking::Response) -> Result<String, reqwest::Error> {
+    let url = response.url().clone();
+    let body = response.text()?;
+    tracing::trace!("{url} responded {}", redact(&body));
+    Ok(body)
+}
+
+/// A JSON body with its secrets replaced, for logging. Anything else is kept as it is.
+fn redact(body: &str) -> String {
+    fn redact_value(value: &mut serde_json::Value) {
+        match value {
+            serde_json::Value::Object(fields) => {
+                for (key, value) in fields {
+                    if SECRETS.contains(&key.to_lowercase().as_str()) {
+                        *value = serde_json::Value::from("<redacted>");
+                    } else {
+                        redact_value(value);
+                    }
+                }
+            }
+            serde_json::Value::Array(values) => values.iter_mut().for_each(redact_value),
+            _ => {}
+        }
+    }
+
+    match serde_json::from_str(body) {
+        Ok(mut value) => {
+            redact_value(&mut value);
+            value.to_string()
+        }
+        Err(_) => body.to_string(),
+    }
+}
+
+/// Fetch and deserialize a JSON document from the device, e.g. `json/eff`
+///
+/// This is used for endpoints that `wled-json-api-library` either doesn't expose
+/// or models too strictly to deserialize across firmware versions.
+pub fn get_json<T: DeserializeOwned>(wled: &Wled, path: &str) -> Result<T, WldError> {
+    serde_json::from_str(&get_text(wled, path)?).at(&address::address_of(&wled.url))
+}
+
+/// Fetch a JSON document from the device as it is, e.g. `cfg.json` for a backup
+pub fn get_text(wled: &Wled, path: &str) -> Result<String, WldError> {
+    let ip = address::address_of(&wled.url);
+    let mut url = wled.url.clone();
+    url.set_path(path);
+
+    retry::retrying(&ip, || {
+        let request = wled
+            .client
+            .get(url.clone())
+            .header(reqwest::header::ACCEPT, "application/json");
+        send(&wled.client, request)
+            .and_then(|response| response.error_for_status())
+            .and_then(read_text)
+            .at(&ip)
+    })
+}
+
+/// Upload a file to the device's filesystem, as the file editor in the web UI does.
+/// Uploading `cfg.json` makes the device reboot to apply it.
+pub fn upload_file(wled: &Wled, name: &str, contents: String) -> Result<(), WldError> {
+    let ip = address::address_of(&wled.url);
+    let mut url = wled.url.clone();
+    url.set_path("upload");
+
+    let part = reqwest::blocking::multipart::Part::text(contents)
+        .file_name(format!("/{name}"))
+        .mime_str("application/json")
+        .at(&ip)?;
+    let form = reqwest::blocking::multipart::Form::new().part("data", part);
+
+    send(&wled.client, wled.client.post(url).multipart(form))
+        .and_then(|response| response.error_for_status())
+        .at(&ip)?;
+
+    Ok(())
+}
+
+/// Post a raw JSON state update to the device, returning the response body
+///
+/// Used for fields `wled-json-api-library` can't express, like preset names. Fails with
+/// the device's message if it rejects the update, either with an error status or with an
+/// `"error"` in the response. The update is sent again if it doesn't reach the device, so
+/// use `post_state_once` for one that does something different the second time.
+pub fn post_state(wled: &Wled, state: &serde_json::Value) -> Result<String, WldError> {
+    retry::retrying(&address::address_of(&wled.url), || {
+        post_state_once(wled, state)
+    })
+}
+
+/// Post a raw JSON state update to the device once, like `post_state` but without
+/// sending it again if it doesn't get through. For updates like `"on": "t"`, saving a
+/// preset or rebooting, which shouldn't happen twice.
+pub fn post_state_once(wled: &Wled, state: &serde_json::Value) -> Result<String, WldError> {
+    let ip = address::address_of(&wled.url);
+    let mut url = wled.url.clone();
+    url.set_path("json/state");
+
+    let request = wled
+        .client
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(state.to_string());
+    let response = send(&wled.client, request).at(&ip)?;
+    let status = response.status();
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(WldError::status(&ip, status));
+    }
+    let body = read_text(response).at(&ip)?;
+
+    if let Some(error) = device_error(&body) {
+        return Err(WldError::rejected(
+            &ip,
+            format!("Device rejected the state update ({status}): {error}"),
+        ));
+    }
+    if !status.is_success() {
+        return Err(WldError::rejected(
+            &ip,
+            format!("Device rejected the state update ({status})"),
+        ));
+    }
+
+    Ok(body)
+}
+
+/// Post a change to the device's settings, like the settings pages in the web UI do.
+/// Only the settings given are changed. Some, like the Wi-Fi network, only take effect
+/// once the device restarts.
+pub fn post_config(wled: &Wled, config: &serde_json::Value) -> Result<(), WldError> {
+    let ip = address::address_of(&wled.url);
+    let mut url = wled.url.clone();
+    url.set_path("json/cfg");
+
+    let body = retry::retrying(&ip, || {
+        let request = wled
+            .client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(config.to_string());
+        send(&wled.client, request)
+            .and_then(|response| response.error_for_status())
+            .and_then(read_text)
+            .at(&ip)
+    })?;
+
+    match device_error(&body) {
+        Some(error) => Err(WldError::rejected(
+            &ip,
+            format!("Device rejected the settings: {error}"),
+        )),
+        None => Ok(()),
+    }
+}
+
+/// The error message in a response from the device, e.g. `{"error": 9}`, if there is one
+fn device_error(body: &str) -> Option<String> {
+    let response: serde_json::Value = serde_json::from_str(body).ok()?;
+    match response.get("error")? {
+        serde_json::Value::String(message) => Some(message.clone()),
+        error => Some(error.to_string()),
+    }
+}
+
+/// Deserialize a field as `None` if it has an unexpected type, rather than failing to
+/// parse the whole document, since field types vary across firmware versions
+fn lenient<'de, D, T>(deserializer: D) -> Result<Option<T>, D::Error>
+where
+    D: serde::Deserializer<'de>,
+    T: DeserializeOwned,
+{
+    let value = serde_json::Value::deserialize(deserializer)?;
+    Ok(serde_json::from_value(value).ok())
+}
+
+/// The subset of `json/info` that wld uses. Every field is optional, since older
+/// firmware omits some of them.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Info {
+    /// "WLED" on WLED devices
+    #[serde(default, deserialize_with = "lenient")]
+    pub brand: Option<String>,
+    /// Name of the device, as set in its settings
+    #[serde(default, deserialize_with = "lenient")]
+    pub name: Option<String>,
+    /// Firmware version, e.g. "0.14.0"
+    #[serde(default, deserialize_with = "lenient")]
+    pub ver: Option<String>,
+    /// Firmware build ID
+    #[serde(default, deserialize_with = "lenient")]
+    pub vid: Option<u64>,
+    /// Chip architecture, e.g. "esp32"
+    #[serde(default, deserialize_with = "lenient")]
+    pub arch: Option<String>,
+    /// Number of effects on the device
+    #[serde(default, deserialize_with = "lenient")]
+    pub fxcount: Option<u16>,
+    /// Number of built-in palettes on the device
+    #[serde(default, deserialize_with = "lenient")]
+    pub palcount: Option<u16>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub leds: Option<Leds>,
+    /// Whether the device is currently receiving realtime data (e.g. E1.31)
+    #[serde(default, deserialize_with = "lenient")]
+    pub live: Option<bool>,
+    /// Name of the realtime data source, e.g. "E1.31"
+    #[serde(default, deserialize_with = "lenient")]
+    pub lm: Option<String>,
+    /// IP address the realtime data is coming from
+    #[serde(default, deserialize_with = "lenient")]
+    pub lip: Option<String>,
+    /// Free heap memory in bytes
+    #[serde(default, deserialize_with = "lenient")]
+    pub freeheap: Option<u64>,
+    /// Seconds since the device booted
+    #[serde(default, deserialize_with = "lenient")]
+    pub uptime: Option<u64>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub wifi: Option<Wifi>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub ip: Option<String>,
+    /// MAC address as 12 hex digits without separators
+    #[serde(default, deserialize_with = "lenient")]
+    pub mac: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Leds {
+    /// Total number of LEDs on the device
+    #[serde(default, deserialize_with = "lenient")]
+    pub count: Option<u16>,
+    /// Estimated current draw in milliamps
+    #[serde(default, deserialize_with = "lenient")]
+    pub pwr: Option<u32>,
+    /// Current power limit in milliamps, or 0 if the limiter is disabled
+    #[serde(default, deserialize_with = "lenient")]
+    pub maxpwr: Option<u32>,
+    /// Light capabilities: bit 0 is RGB, bit 1 a white channel and bit 2 CCT
+    #[serde(default, deserialize_with = "lenient")]
+    pub lc: Option<u8>,
+    /// Whether the LEDs have a white channel, reported by firmware older than 0.13
+    #[serde(default, deserialize_with = "lenient")]
+    pub rgbw: Option<bool>,
+}
+
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct Wifi {
+    /// Signal strength in dBm
+    #[serde(default, deserialize_with = "lenient")]
+    pub rssi: Option<i32>,
+    /// Signal quality as a percentage
+    #[serde(default, deserialize_with = "lenient")]
+    pub signal: Option<u8>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub channel: Option<u8>,
+}
+
+impl Leds {
+    /// Whether the LEDs have a white channel, if the device says
+    pub fn has_white(&self) -> Option<bool> {
+        self.lc.map(|lc| lc & 0b010 != 0).or(self.rgbw)
+    }
+
+    /// Whether the LEDs support adjusting color temperature, if the device says
+    pub fn has_cct(&self) -> Option<bool> {
+        self.lc.map(|lc| lc & 0b100 != 0)
+    }
+}
+
+pub fn get_info(wled: &Wled) -> Result<Info, WldError> {
+    let info: Info = get_json(wled, "json/info")?;
+    track::seen(&address::address_of(&wled.url), info.ver.as_deref());
+    Ok(info)
+}
+
+/// Fetch `json/info` from the device at an IP address, giving up after `timeout`. Used to
+/// poll devices that may not be responding, e.g. while they reboot.
+pub fn probe_info(ip: &str, timeout: Duration, options: &ClientOptions) -> Result<Info, WldError> {
+    let client = options.client(ip)?;
+
+    let mut url = address::base_url(ip)?;
+    url.set_path("json/info");
+
+    let request = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json")
+        .timeout(timeout);
+    let body = send(&client, request)
+        .and_then(|response| response.error_for_status())
+        .and_then(read_text)
+        .at(ip)?;
+
+    let info: Info = serde_json::from_str(&body).at(ip)?;
+    track::seen(ip, info.ver.as_deref());
+    Ok(info)
+}
+
+/// Time a GET of `json/info` from the device at a base URL, as a lightweight check of
+/// how quickly it responds. Uses `client`, so its timeout applies.
+pub fn ping(
+    client: &reqwest::blocking::Client,
+    base_url: &Url,
+) -> Result<Duration, reqwest::Error> {
+    let mut url = base_url.clone();
+    url.set_path("json/info");
+    let started = Instant::now();
+
+    let request = client
+        .get(url)
+        .header(reqwest::header::ACCEPT, "application/json");
+    send(client, request)?.error_for_status()?.bytes()?;
+
+    Ok(started.elapsed())
+}
+
+/// Another WLED device on the network, as discovered by the device itself
+#[derive(Debug, Default, Deserialize)]
+pub struct Node {
+    #[serde(default, deserialize_with = "lenient")]
+    pub name: Option<String>,
+    #[serde(default, deserialize_with = "lenient")]
+    pub ip: Option<String>,
+    /// Chip type, e.g. 32 for ESP32 or 82 for ESP8266. Newer firmware sets the top bit
+    /// when the node is on.
+    #[serde(default, rename = "type", deserialize_with = "lenient")]
+    pub node_type: Option<u8>,
+}
+
+impl Node {
+    /// A readable name for the node's chip type
+    pub fn chip(&self) -> Option<&'static str> {
+        match self.node_type? & 0x7f {
+            32 => Some("ESP32"),
+            33 => Some("ESP32-S2"),
+            34 => Some("ESP32-S3"),
+            35 => Some("ESP32-C3"),
+            82 => Some("ESP8266"),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Deserialize)]
+struct Nodes {
+    #[serde(default)]
+    nodes: Vec<Node>,
+}
+
+/// Other WLED devices the device has discovered on the network
+pub fn get_nodes(wled: &Wled) -> Result<Vec<Node>, WldError> {
+    Ok(get_json::<Nodes>(wled, "json/nodes")?.nodes)
+}
+
+/// Names of the effects on the device, indexed by effect ID
+pub fn get_effect_names(wled: &Wled) -> Result<Vec<String>, WldError> {
+    get_json(wled, "json/eff")
+}
+
+/// Names of the palettes on the device, indexed by palette ID
+pub fn get_palette_names(wled: &Wled) -> Result<Vec<String>, WldError> {
+    get_json(wled, "json/pal")
+}
+
+/// A preset saved on the device
+#[derive(Debug, Default, Deserialize)]
+pub struct Preset {
+    #[serde(rename = "n")]
+    pub name: Option<String>,
+    /// Present if this preset is a playlist rather than a single look
+    pub playlist: Option<serde_json::Value>,
+}
+
+/// Saved presets on the device, sorted by ID
+pub fn get_presets(wled: &Wled) -> Result<Vec<(u16, Preset)>, WldError> {
+    let raw: BTreeMap<String, serde_json::Value> = get_json(wled, "presets.json")?;
+    Ok(parse_presets(raw))
+}
+
+/// Turn the raw `presets.json` map into presets sorted by ID, skipping empty slots
+fn parse_presets(raw: BTreeMap<String, serde_json::Value>) -> Vec<(u16, Preset)> {
+    let mut presets: Vec<(u16, Preset)> = raw
+        .into_iter()
+        .filter_map(|(id, value)| {
+            let id = id.parse().ok()?;
+            let is_empty = value.as_object().is_none_or(|o| o.is_empty());
+            if is_empty {
+                return None;
+            }
+            Some((id, serde_json::from_value(value).unwrap_or_default()))
+        })
+        .collect();
+
+    presets.sort_by_key(|(id, _)| *id);
+    presets
+}
+
+/// The ID of the preset with a name, matched case-insensitively. If no preset has the
+/// name, the error lists the names there are.
+pub fn find_preset(presets: &[(u16, Preset)], name: &str) -> Result<u16, String> {
+    let named = || {
+        presets
+            .iter()
+            .filter_map(|(id, preset)| Some((*id, preset.name.as_deref()?)))
+    };
+
+    if let Some((id, _)) = named().find(|(_, preset)| preset.eq_ignore_ascii_case(name.trim())) {
+        return Ok(id);
+    }
+
+    let available: Vec<String> = named()
+        .map(|(id, name)| format!("'{name}' ({id})"))
+        .collect();
+    if available.is_empty() {
+        Err(format!(
+            "No preset named '{name}': the device has no named presets"
+        ))
+    } else {
+        Err(format!(
+            "No preset named '{name}'. The presets on the device are {}",
+            available.join(", ")
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact() {
+        assert_eq!(
+            redact(r#"{"pin":"1234","on":true}"#),
+            r#"{"on":true,"pin":"<redacted>"}"#
+        );
+        assert_eq!(
+            redact(r#"{"nw":{"ins":[{"ssid":"home","psk":"hunter2"}]}}"#),
+            r#"{"nw":{"ins":[{"psk":"<redacted>","ssid":"home"}]}}"#
+        );
+        assert_eq!(redact("Not Found"), "Not Found");
+    }
+
+    #[test]
+    fn test_device_error() {
+        assert_eq!(device_error(r#"{"error": 9}"#), Some("9".to_string()));
+        assert_eq!(
+            device_error(r#"{"error": "Busy"}"#),
+            Some("Busy".to_string())
+        );
+        assert_eq!(device_error(r#"{"success": true}"#), None);
+        assert_eq!(device_error("not json"), None);
+    }
+
+    #[test]
+    fn test_parse_presets_skips_empty_slots() {
+        let raw = serde_json::from_str(
+            r#"{
+                "0": {},
+                "10": {"n": "Movie", "on": true, "bri": 40},
+                "2": {"n": "Reading", "on": true},
+                "3": {"playlist": {"ps": [2, 10]}, "n": "Cycle"},
+                "4": {"on": false}
+            }"#,
+        )
+        .unwrap();
+
+        let presets = parse_presets(raw);
+        let ids: Vec<u16> = presets.iter().map(|(id, _)| *id).collect();
+        assert_eq!(ids, vec![2, 3, 4, 10]);
+        assert_eq!(presets[0].1.name.as_deref(), Some("Reading"));
+        assert!(presets[0].1.playlist.is_none());
+        assert!(presets[1].1.playlist.is_some());
+        assert_eq!(presets[2].1.name, None);
+        assert_eq!(presets[3].1.name.as_deref(), Some("Movie"));
+    }
+
+    #[test]
+    fn test_parse_presets_ignores_non_numeric_keys() {
+        let raw = serde_json::from_str(r#"{"x": {"n": "Bad"}, "1": {"n": "Good"}}"#).unwrap();
+
+        let presets = parse_presets(raw);
+        assert_eq!(presets.len(), 1);
+        assert_eq!(presets[0].0, 1);
+    }
+
+    #[test]
+    fn test_find_preset() {
+        let raw = serde_json::from_str(
+            r#"{"1": {"n": "Movie"}, "2": {"n": "Reading"}, "3": {"on": true}}"#,
+        )
+        .unwrap();
+        let presets = parse_presets(raw);
+
+        assert_eq!(find_preset(&presets, "movie"), Ok(1));
+        assert_eq!(find_preset(&presets, " READING "), Ok(2));
+        assert_eq!(
+            find_preset(&presets, "party"),
+            Err(
+                "No preset named 'party'. The presets on the device are 'Movie' (1), 'Reading' (2)"
+                    .to_string()
+            )
+        );
+        assert_eq!(
+            find_preset(&[], "party"),
+            Err("No preset named 'party': the device has no named presets".to_string())
+        );
+    }
+
+    #[test]
+    fn test_leds_capabilities() {
+        let leds: Leds = serde_json::from_str(r#"{"count": 30, "lc": 7}"#).unwrap();
+        assert_eq!(leds.has_white(), Some(true));
+        assert_eq!(leds.has_cct(), Some(true));
+
+        let leds: Leds = serde_json::from_str(r#"{"lc": 1, "rgbw": true}"#).unwrap();
+        assert_eq!(leds.has_white(), Some(false));
+        assert_eq!(leds.has_cct(), Some(false));
+
+        let leds: Leds = serde_json::from_str(r#"{"rgbw": true}"#).unwrap();
+        assert_eq!(leds.has_white(), Some(true));
+        assert_eq!(leds.has_cct(), None);
+    }
+
+    #[test]
+    fn test_node_chip() {
+        let nodes: Nodes = serde_json::from_str(
+            r#"{"nodes": [{"name": "Desk", "type": 32, "ip": "192.168.1.5"},
+                          {"name": "Porch", "type": 210, "ip": "192.168.1.6"},
+                          {"name": "Odd", "type": 99}]}"#,
+        )
+        .unwrap();
+
+        assert_eq!(nodes.nodes[0].chip(), Some("ESP32"));
+        // The top bit means the node is on
+        assert_eq!(nodes.nodes[1].chip(), Some("ESP8266"));
+        assert_eq!(nodes.nodes[2].chip(), None);
+        assert_eq!(nodes.nodes[2].ip, None);
+    }
+
+    #[test]
+    fn test_info_tolerates_missing_and_mistyped_fields() {
+        let info: Info = serde_json::from_str(
+            r#"{"ver": "0.13.3", "vid": "not a number", "leds": {"count": 30, "maxpwr": null},
+                "wifi": {"rssi": -71}, "arch": 8266, "unknown": true}"#,
+        )
+        .unwrap();
+
+        assert_eq!(info.ver.as_deref(), Some("0.13.3"));
+        assert_eq!(info.vid, None);
+        assert_eq!(info.arch, None);
+        assert_eq!(info.leds.as_ref().unwrap().count, Some(30));
+        assert_eq!(info.leds.as_ref().unwrap().maxpwr, None);
+        assert_eq!(info.wifi.as_ref().unwrap().rssi, Some(-71));
+        assert_eq!(info.wifi.as_ref().unwrap().channel, None);
+        assert_eq!(info.mac, None);
+    }
+}
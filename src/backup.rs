@@ -0,0 +1,194 @@
+use std::path::{Path, PathBuf};
+
+/// A file WLED keeps its settings in, which `wld backup` saves and `wld restore` uploads
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BackupFile {
+    Config,
+    Presets,
+}
+
+impl BackupFile {
+    /// Every file backed up, in the order to restore them. The configuration goes last,
+    /// since the device reboots once it's uploaded.
+    pub const ALL: [BackupFile; 2] = [BackupFile::Presets, BackupFile::Config];
+
+    /// The file's name on the device, e.g. `cfg.json`
+    pub fn device_name(self) -> &'static str {
+        match self {
+            BackupFile::Config => "cfg.json",
+            BackupFile::Presets => "presets.json",
+        }
+    }
+
+    fn prefix(self) -> &'static str {
+        match self {
+            BackupFile::Config => "cfg",
+            BackupFile::Presets => "presets",
+        }
+    }
+
+    /// The name to save a backup under, e.g. `cfg-20261015-143000.json`
+    pub fn backup_name(self, timestamp: &str) -> String {
+        format!("{}-{timestamp}.json", self.prefix())
+    }
+
+    /// Which file a backup is of, judging by its name. Accepts names from `wld backup` and
+    /// files downloaded straight from the device, like `cfg.json`.
+    pub fn from_backup_name(name: &str) -> Option<Self> {
+        BackupFile::ALL.into_iter().find(|file| {
+            name == file.device_name()
+                || (name.starts_with(&format!("{}-", file.prefix())) && name.ends_with(".json"))
+        })
+    }
+}
+
+/// Format seconds since the Unix epoch as a UTC timestamp for file names, e.g.
+/// `20261015-143000`, so backups sort in the order they were taken
+pub fn timestamp(unix_secs: u64) -> String {
+    let days = (unix_secs / 86_400) as i64;
+    let seconds = unix_secs % 86_400;
+
+    // Convert days since the epoch to a calendar date, using Howard Hinnant's algorithm
+    let z = days + 719_468;
+    let era = z.div_euclid(146_097);
+    let day_of_era = z.rem_euclid(146_097);
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + i64::from(month <= 2);
+
+    format!(
+        "{year:04}{month:02}{day:02}-{:02}{:02}{:02}",
+        seconds / 3600,
+        seconds % 3600 / 60,
+        seconds % 60
+    )
+}
+
+/// A directory name for a device's backups with `--all`, replacing anything that isn't
+/// safe in a path
+pub fn directory_name(device: &str) -> String {
+    device
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The backups to restore from `path`: the file itself, or the newest backup of each file
+/// in a directory. Returned in the order to restore them.
+pub fn find_backups(path: &Path) -> Result<Vec<(BackupFile, PathBuf)>, String> {
+    if !path.is_dir() {
+        let name = path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default();
+        let file = BackupFile::from_backup_name(name).ok_or_else(|| {
+            format!(
+                "Can't tell what '{}' is a backup of. Expected a name like cfg-<time>.json or presets-<time>.json",
+                path.display()
+            )
+        })?;
+        return Ok(vec![(file, path.to_path_buf())]);
+    }
+
+    let mut names: Vec<String> = std::fs::read_dir(path)
+        .map_err(|e| format!("Failed to read '{}': {e}", path.display()))?
+        .flatten()
+        .filter(|entry| entry.path().is_file())
+        .filter_map(|entry| entry.file_name().into_string().ok())
+        .collect();
+    names.sort();
+
+    let backups: Vec<(BackupFile, PathBuf)> = BackupFile::ALL
+        .into_iter()
+        .filter_map(|file| {
+            // Timestamps sort in time order, so the last match is the newest
+            let newest = names
+                .iter()
+                .rfind(|name| BackupFile::from_backup_name(name) == Some(file))?;
+            Some((file, path.join(newest)))
+        })
+        .collect();
+
+    if backups.is_empty() {
+        return Err(format!("No backups found in '{}'", path.display()));
+    }
+    Ok(backups)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_timestamp() {
+        assert_eq!(timestamp(0), "19700101-000000");
+        assert_eq!(timestamp(951_782_400), "20000229-000000");
+        assert_eq!(timestamp(1_792_067_415), "20261015-123015");
+    }
+
+    #[test]
+    fn test_from_backup_name() {
+        assert_eq!(
+            BackupFile::from_backup_name("cfg-20261015-143000.json"),
+            Some(BackupFile::Config)
+        );
+        assert_eq!(
+            BackupFile::from_backup_name("presets.json"),
+            Some(BackupFile::Presets)
+        );
+        assert_eq!(BackupFile::from_backup_name("cfg-notes.txt"), None);
+        assert_eq!(BackupFile::from_backup_name("palette0.json"), None);
+    }
+
+    #[test]
+    fn test_directory_name() {
+        assert_eq!(directory_name("desk-light_2"), "desk-light_2");
+        assert_eq!(directory_name("living room/tv"), "living_room_tv");
+    }
+
+    #[test]
+    fn test_find_backups_picks_newest() {
+        let dir = std::env::temp_dir().join(format!("wld-backup-test-{}", std::process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        for name in [
+            "cfg-20260101-000000.json",
+            "cfg-20261015-143000.json",
+            "presets-20260101-000000.json",
+            "notes.txt",
+        ] {
+            fs::write(dir.join(name), "{}").unwrap();
+        }
+
+        let backups = find_backups(&dir);
+        let single = find_backups(&dir.join("cfg-20260101-000000.json"));
+        let unknown = find_backups(&dir.join("notes.txt"));
+        fs::remove_dir_all(&dir).unwrap();
+
+        assert_eq!(
+            backups.unwrap(),
+            vec![
+                (
+                    BackupFile::Presets,
+                    dir.join("presets-20260101-000000.json")
+                ),
+                (BackupFile::Config, dir.join("cfg-20261015-143000.json")),
+            ]
+        );
+        assert_eq!(
+            single.unwrap(),
+            vec![(BackupFile::Config, dir.join("cfg-20260101-000000.json"))]
+        );
+        assert!(unknown.is_err());
+    }
+}
@@ -0,0 +1,235 @@
+use std::str::FromStr;
+
+/// A brightness given on the command line, either as a level or as a change to the
+/// current level (e.g. `+20` or `-20`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BrightnessValue {
+    Absolute(u8),
+    Relative(i16),
+}
+
+impl FromStr for BrightnessValue {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+
+        let (sign, digits) = match value.chars().next() {
+            Some('+') => (1, &value[1..]),
+            Some('-') => (-1, &value[1..]),
+            _ => {
+                return value
+                    .parse()
+                    .map(BrightnessValue::Absolute)
+                    .map_err(|_| "must be a number between 0 and 255, or +N/-N".to_string())
+            }
+        };
+
+        let delta: u8 = digits
+            .parse()
+            .map_err(|_| "change must be between -255 and +255".to_string())?;
+        Ok(BrightnessValue::Relative(sign * i16::from(delta)))
+    }
+}
+
+/// A brightness argument as given on the command line, where a trailing `%` (e.g. `50%`
+/// or `+10%`) means the value is a percentage
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BrightnessArg {
+    value: BrightnessValue,
+    percent: bool,
+}
+
+impl FromStr for BrightnessArg {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value.trim().strip_suffix('%') {
+            Some(percent) => {
+                // A number too big to parse is out of range too, so it gets the same error
+                let out_of_range = || {
+                    if percent.trim_start().starts_with(['+', '-']) {
+                        format!("Percentage change must be between -100% and +100%, got {value}")
+                    } else {
+                        format!("Percentage must be between 0% and 100%, got {value}")
+                    }
+                };
+                let scaled = percent
+                    .parse::<BrightnessValue>()
+                    .map_err(|_| out_of_range())?
+                    .percentage_to_scale()
+                    .map_err(|_| out_of_range())?;
+                Ok(BrightnessArg {
+                    value: scaled,
+                    percent: true,
+                })
+            }
+            None => Ok(BrightnessArg {
+                value: value.parse()?,
+                percent: false,
+            }),
+        }
+    }
+}
+
+impl BrightnessArg {
+    /// The brightness on the 0-255 scale. `percentage` treats a value given without a `%`
+    /// as a percentage anyway, as `--percentage` does.
+    pub fn resolve(self, percentage: bool) -> Result<BrightnessValue, String> {
+        if percentage && !self.percent {
+            self.value.percentage_to_scale()
+        } else {
+            Ok(self.value)
+        }
+    }
+}
+
+impl BrightnessValue {
+    /// Apply this value to the current brightness, clamping changes to 0-255
+    pub fn apply(self, current: u8) -> u8 {
+        match self {
+            BrightnessValue::Absolute(value) => value,
+            BrightnessValue::Relative(delta) => (i16::from(current) + delta).clamp(0, 255) as u8,
+        }
+    }
+
+    /// Reinterpret this value as a percentage (0-100), or a change in percentage points,
+    /// on the 0-255 scale, rounding to the nearest step
+    pub fn percentage_to_scale(self) -> Result<Self, String> {
+        match self {
+            BrightnessValue::Absolute(value) if value > 100 => {
+                Err(format!("Percentage must be between 0 and 100, got {value}"))
+            }
+            BrightnessValue::Absolute(value) => Ok(BrightnessValue::Absolute(
+                ((value as u16 * 255 + 50) / 100) as u8,
+            )),
+            BrightnessValue::Relative(delta) if delta.abs() > 100 => Err(format!(
+                "Percentage change must be between -100 and +100, got {delta:+}"
+            )),
+            BrightnessValue::Relative(delta) => Ok(BrightnessValue::Relative(
+                (delta * 255 + 50 * delta.signum()) / 100,
+            )),
+        }
+    }
+}
+
+/// A brightness on the 0-255 scale as a percentage, rounded to the nearest one, so it
+/// reads back as what `percentage_to_scale` was given
+pub fn percentage(brightness: u8) -> u8 {
+    ((u16::from(brightness) * 100 + 127) / 255) as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_absolute() {
+        assert_eq!("128".parse(), Ok(BrightnessValue::Absolute(128)));
+        assert_eq!("0".parse(), Ok(BrightnessValue::Absolute(0)));
+        assert!("256".parse::<BrightnessValue>().is_err());
+    }
+
+    #[test]
+    fn test_parse_relative() {
+        assert_eq!("+20".parse(), Ok(BrightnessValue::Relative(20)));
+        assert_eq!("-20".parse(), Ok(BrightnessValue::Relative(-20)));
+        assert_eq!("-255".parse(), Ok(BrightnessValue::Relative(-255)));
+        assert!("+256".parse::<BrightnessValue>().is_err());
+        assert!("+".parse::<BrightnessValue>().is_err());
+        assert!("+-5".parse::<BrightnessValue>().is_err());
+    }
+
+    #[test]
+    fn test_apply_clamps() {
+        assert_eq!(BrightnessValue::Relative(-20).apply(180), 160);
+        assert_eq!(BrightnessValue::Relative(50).apply(230), 255);
+        assert_eq!(BrightnessValue::Relative(-50).apply(30), 0);
+        assert_eq!(BrightnessValue::Absolute(42).apply(200), 42);
+    }
+
+    #[test]
+    fn test_percentage_to_scale() {
+        assert_eq!(
+            BrightnessValue::Absolute(100).percentage_to_scale(),
+            Ok(BrightnessValue::Absolute(255))
+        );
+        assert_eq!(
+            BrightnessValue::Relative(-20).percentage_to_scale(),
+            Ok(BrightnessValue::Relative(-51))
+        );
+        assert!(BrightnessValue::Absolute(101)
+            .percentage_to_scale()
+            .is_err());
+        assert!(BrightnessValue::Relative(101)
+            .percentage_to_scale()
+            .is_err());
+    }
+
+    #[test]
+    fn test_percentage_rounding() {
+        let scale = |percent: u8| BrightnessValue::Absolute(percent).percentage_to_scale();
+        assert_eq!(scale(0), Ok(BrightnessValue::Absolute(0)));
+        assert_eq!(scale(1), Ok(BrightnessValue::Absolute(3)));
+        assert_eq!(scale(50), Ok(BrightnessValue::Absolute(128)));
+        assert_eq!(scale(100), Ok(BrightnessValue::Absolute(255)));
+        assert_eq!(
+            BrightnessValue::Relative(-10).percentage_to_scale(),
+            Ok(BrightnessValue::Relative(-26))
+        );
+    }
+
+    #[test]
+    fn test_percentage() {
+        assert_eq!(percentage(0), 0);
+        assert_eq!(percentage(128), 50);
+        assert_eq!(percentage(255), 100);
+        for percent in 0..=100 {
+            let Ok(BrightnessValue::Absolute(value)) =
+                BrightnessValue::Absolute(percent).percentage_to_scale()
+            else {
+                panic!("{percent}% isn't a brightness");
+            };
+            assert_eq!(percentage(value), percent);
+        }
+    }
+
+    #[test]
+    fn test_parse_percent_suffix() {
+        let arg: BrightnessArg = "50%".parse().unwrap();
+        assert_eq!(arg.resolve(false), Ok(BrightnessValue::Absolute(128)));
+        // A % suffix isn't scaled a second time by --percentage
+        assert_eq!(arg.resolve(true), Ok(BrightnessValue::Absolute(128)));
+
+        let arg: BrightnessArg = "+10%".parse().unwrap();
+        assert_eq!(arg.resolve(false), Ok(BrightnessValue::Relative(26)));
+    }
+
+    #[test]
+    fn test_parse_percent_suffix_out_of_range() {
+        // Whether the number is over 100 or too big to parse at all, the error is the same
+        for value in ["150%", "300%", "lots%"] {
+            assert_eq!(
+                value.parse::<BrightnessArg>(),
+                Err(format!(
+                    "Percentage must be between 0% and 100%, got {value}"
+                ))
+            );
+        }
+        for value in ["+150%", "-300%"] {
+            assert_eq!(
+                value.parse::<BrightnessArg>(),
+                Err(format!(
+                    "Percentage change must be between -100% and +100%, got {value}"
+                ))
+            );
+        }
+    }
+
+    #[test]
+    fn test_resolve_without_suffix() {
+        let arg: BrightnessArg = "50".parse().unwrap();
+        assert_eq!(arg.resolve(false), Ok(BrightnessValue::Absolute(50)));
+        assert_eq!(arg.resolve(true), Ok(BrightnessValue::Absolute(128)));
+    }
+}
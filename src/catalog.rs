@@ -0,0 +1,263 @@
+use std::str::FromStr;
+
+use crate::fuzzy;
+
+/// Picks an entry from a device's list of effects or palettes, either by ID or by name
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Selector {
+    Id(u16),
+    Name(String),
+}
+
+impl FromStr for Selector {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        if value.is_empty() {
+            return Err("Value must not be empty".to_string());
+        }
+
+        Ok(match value.parse() {
+            Ok(id) => Selector::Id(id),
+            Err(_) => Selector::Name(value.to_string()),
+        })
+    }
+}
+
+/// WLED pads its effect list with placeholder names for removed effects
+pub fn is_reserved(name: &str) -> bool {
+    name == "RSVD" || name == "-"
+}
+
+/// Lowercase a name for matching, ignoring the "* " WLED puts in front of the names of
+/// palettes that are built from the segment colors (e.g. "* Color Gradient")
+fn comparable(name: &str) -> String {
+    name.trim_start_matches("* ").to_lowercase()
+}
+
+/// Entries whose name contains `search` (case-insensitively), or every entry if no
+/// search is given. Reserved placeholder entries are always skipped.
+pub fn list<'a>(names: &'a [String], search: Option<&str>) -> Vec<(u16, &'a str)> {
+    let search = search.map(str::to_lowercase);
+    names
+        .iter()
+        .enumerate()
+        .filter(|(_, name)| !is_reserved(name))
+        .filter(|(_, name)| {
+            search
+                .as_ref()
+                .is_none_or(|search| name.to_lowercase().contains(search))
+        })
+        .map(|(id, name)| (id as u16, name.as_str()))
+        .collect()
+}
+
+/// Resolve a selector against `names` (indexed by ID), returning the ID and its name.
+///
+/// Names match case-insensitively, falling back to a prefix match if exactly one entry
+/// starts with the given name. `count` is the number of entries the device reports, and
+/// `kind` (e.g. "effect") is used in error messages.
+pub fn resolve(
+    selector: &Selector,
+    names: &[String],
+    count: usize,
+    kind: &str,
+) -> Result<(u16, String), String> {
+    match selector {
+        Selector::Id(id) => {
+            if usize::from(*id) >= count {
+                return Err(format!(
+                    "Invalid {kind} ID {id}: the device has {count} {kind}s (0-{})",
+                    count.saturating_sub(1)
+                ));
+            }
+            let name = names.get(usize::from(*id)).cloned().unwrap_or_default();
+            Ok((*id, name))
+        }
+        Selector::Name(name) => {
+            let lower = comparable(name);
+            let candidates = || {
+                names
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, candidate)| !is_reserved(candidate))
+            };
+
+            if let Some((id, found)) = candidates().find(|(_, c)| comparable(c) == lower) {
+                return Ok((id as u16, found.clone()));
+            }
+
+            let prefix_matches: Vec<(usize, &String)> = candidates()
+                .filter(|(_, c)| comparable(c).starts_with(&lower))
+                .collect();
+
+            match prefix_matches.as_slice() {
+                [(id, found)] => Ok((*id as u16, (*found).clone())),
+                [] => {
+                    let suggestions =
+                        fuzzy::closest_matches(name, candidates().map(|(_, c)| c.as_str()), 3);
+                    if suggestions.is_empty() {
+                        Err(format!("Unknown {kind} '{name}'"))
+                    } else {
+                        Err(format!(
+                            "Unknown {kind} '{name}'. Did you mean: {}?",
+                            suggestions.join(", ")
+                        ))
+                    }
+                }
+                many => {
+                    let matches: Vec<&str> = many.iter().map(|(_, c)| c.as_str()).collect();
+                    Err(format!(
+                        "'{name}' matches several {kind}s: {}",
+                        matches.join(", ")
+                    ))
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn effects() -> Vec<String> {
+        [
+            "Solid",
+            "Blink",
+            "Rainbow",
+            "Rainbow Runner",
+            "RSVD",
+            "Rain",
+            "Fire 2012",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect()
+    }
+
+    #[test]
+    fn test_selector_from_str() {
+        assert_eq!("9".parse(), Ok(Selector::Id(9)));
+        assert_eq!("Rainbow".parse(), Ok(Selector::Name("Rainbow".to_string())));
+        assert!("  ".parse::<Selector>().is_err());
+    }
+
+    #[test]
+    fn test_list_all_skips_reserved() {
+        let names = effects();
+        let listed = list(&names, None);
+        assert_eq!(listed.len(), 6);
+        assert_eq!(listed[0], (0, "Solid"));
+        assert_eq!(listed[4], (5, "Rain"));
+    }
+
+    #[test]
+    fn test_list_search_case_insensitive() {
+        let names = effects();
+        assert_eq!(
+            list(&names, Some("RAIN")),
+            vec![(2, "Rainbow"), (3, "Rainbow Runner"), (5, "Rain")]
+        );
+    }
+
+    #[test]
+    fn test_list_search_no_matches() {
+        let names = effects();
+        assert!(list(&names, Some("sparkle")).is_empty());
+    }
+
+    #[test]
+    fn test_resolve_by_id() {
+        let names = effects();
+        assert_eq!(
+            resolve(&Selector::Id(2), &names, names.len(), "effect"),
+            Ok((2, "Rainbow".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_resolve_id_out_of_range() {
+        let names = effects();
+        assert_eq!(
+            resolve(&Selector::Id(7), &names, names.len(), "effect"),
+            Err("Invalid effect ID 7: the device has 7 effects (0-6)".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_exact_name_case_insensitive() {
+        let names = effects();
+        let selector = Selector::Name("rainbow".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "effect"),
+            Ok((2, "Rainbow".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_resolve_exact_name_wins_over_prefix() {
+        let names = effects();
+        let selector = Selector::Name("RAIN".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "effect"),
+            Ok((5, "Rain".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_resolve_unambiguous_prefix() {
+        let names = effects();
+        let selector = Selector::Name("fire".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "effect"),
+            Ok((6, "Fire 2012".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_resolve_ambiguous_prefix() {
+        let names = effects();
+        let selector = Selector::Name("rainb".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "effect"),
+            Err("'rainb' matches several effects: Rainbow, Rainbow Runner".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_unknown_name_suggests() {
+        let names = effects();
+        let selector = Selector::Name("blnk".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "effect"),
+            Err("Unknown effect 'blnk'. Did you mean: Blink?".to_string())
+        );
+    }
+
+    #[test]
+    fn test_resolve_ignores_palette_star_prefix() {
+        let names: Vec<String> = ["Default", "* Random Cycle", "* Color 1", "Party"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let selector = Selector::Name("random cycle".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "palette"),
+            Ok((1, "* Random Cycle".to_string()))
+        );
+        let selector = Selector::Name("* Color 1".to_string());
+        assert_eq!(
+            resolve(&selector, &names, names.len(), "palette"),
+            Ok((2, "* Color 1".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_resolve_skips_reserved_names() {
+        let names = effects();
+        let selector = Selector::Name("rsvd".to_string());
+        assert!(resolve(&selector, &names, names.len(), "effect").is_err());
+    }
+}
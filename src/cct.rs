@@ -0,0 +1,70 @@
+use crate::kelvin;
+
+/// Friendly names for common color temperatures, on WLED's CCT scale where 0 is the
+/// warmest white the strip can produce and 255 the coolest
+const NAMED: &[(&str, u8)] = &[("warm", 0), ("neutral", 128), ("cool", 255)];
+
+/// Parse a color temperature given as a CCT value (0-255), in Kelvin (e.g. `4500K`) or
+/// as a name like "warm"
+pub fn parse_cct(value: &str) -> Result<u8, String> {
+    let value = value.trim();
+
+    if let Ok(cct) = value.parse::<u8>() {
+        return Ok(cct);
+    }
+
+    if value.ends_with(['K', 'k']) && value[..value.len() - 1].parse::<u16>().is_ok() {
+        return kelvin::parse_kelvin(value);
+    }
+
+    NAMED
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(value))
+        .map(|(_, cct)| *cct)
+        .ok_or_else(|| {
+            let names: Vec<&str> = NAMED.iter().map(|(name, _)| *name).collect();
+            format!(
+                "Invalid color temperature '{value}': expected 0-255, Kelvin (e.g. 4500K) or one of {}",
+                names.join(", ")
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_numbers() {
+        assert_eq!(parse_cct("0"), Ok(0));
+        assert_eq!(parse_cct("200"), Ok(200));
+        assert!(parse_cct("256").is_err());
+    }
+
+    #[test]
+    fn test_parse_names() {
+        assert_eq!(parse_cct("warm"), Ok(0));
+        assert_eq!(parse_cct("Neutral"), Ok(128));
+        assert_eq!(parse_cct("COOL"), Ok(255));
+    }
+
+    #[test]
+    fn test_parse_unknown() {
+        assert_eq!(
+            parse_cct("toasty"),
+            Err(
+                "Invalid color temperature 'toasty': expected 0-255, Kelvin (e.g. 4500K) or one of warm, neutral, cool"
+                    .to_string()
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse_kelvin() {
+        assert_eq!(parse_cct("4500K"), Ok(81));
+        assert_eq!(parse_cct("1900k"), Ok(0));
+        assert!(parse_cct("1800K")
+            .unwrap_err()
+            .contains("must be between 1900K and 10091K"));
+    }
+}
@@ -0,0 +1,399 @@
+use std::fmt;
+
+use crate::address;
+use crate::config::{self, Config};
+
+/// How serious a problem found by `check` is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Severity {
+    /// Something that works, but probably isn't what was meant
+    Warning,
+    /// Something that stops a command from working
+    Error,
+}
+
+/// A problem with the config, with the key it's at, like `groups.downstairs`
+#[derive(Debug, PartialEq, Eq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub key: String,
+    pub message: String,
+}
+
+impl Problem {
+    pub fn error(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Problem {
+            severity: Severity::Error,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(key: impl Into<String>, message: impl Into<String>) -> Self {
+        Problem {
+            severity: Severity::Warning,
+            key: key.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl fmt::Display for Problem {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let severity = match self.severity {
+            Severity::Warning => "warning",
+            Severity::Error => "error",
+        };
+        write!(f, "{severity}: {}: {}", self.key, self.message)
+    }
+}
+
+/// Look for problems in a config that loaded, like one edited by hand: device addresses
+/// that aren't valid, and names that don't match a saved device
+pub fn check(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+    problems.extend(check_devices(config));
+    problems.extend(check_default_device(config));
+    problems.extend(check_groups(config));
+    problems.extend(check_scenes(config));
+    problems.extend(check_device_settings(config));
+    problems
+}
+
+/// A message for a name that should be a saved device
+fn not_saved(name: &str) -> String {
+    format!("'{name}' isn't a saved device")
+}
+
+/// Device addresses and tags are valid, and no two devices share an address
+fn check_devices(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for (name, device) in &config.devices {
+        let key = format!("devices.{name}");
+        if let Err(e) = address::parse_address(&device.ip) {
+            problems.push(Problem::error(format!("{key}.ip"), e));
+        }
+
+        if let Some(other) = config
+            .devices
+            .iter()
+            .take_while(|(other, _)| *other != name)
+            .find(|(_, other)| address::same_address(&other.ip, &device.ip))
+            .map(|(other, _)| other)
+        {
+            problems.push(Problem::warning(
+                &key,
+                format!("has the same address as '{other}' ({})", device.ip),
+            ));
+        }
+
+        for tag in &device.tags {
+            if let Err(e) = config::parse_tag(tag) {
+                problems.push(Problem::warning(format!("{key}.tags"), e));
+            }
+        }
+        if device.default_brightness == Some(0) {
+            problems.push(Problem::warning(
+                format!("{key}.default_brightness"),
+                "a brightness of 0 turns the device straight back off",
+            ));
+        }
+        if device
+            .default_preset
+            .is_some_and(|preset| preset == 0 || preset > 250)
+        {
+            problems.push(Problem::error(
+                format!("{key}.default_preset"),
+                "presets are numbered 1 to 250",
+            ));
+        }
+    }
+
+    problems
+}
+
+/// The default device is one that's saved
+fn check_default_device(config: &Config) -> Vec<Problem> {
+    match &config.default_device {
+        Some(name) if !config.devices.contains_key(name) => {
+            vec![Problem::error("default_device", not_saved(name))]
+        }
+        None if !config.devices.is_empty() => vec![Problem::warning(
+            "default_device",
+            "isn't set, so commands need --device",
+        )],
+        _ => Vec::new(),
+    }
+}
+
+/// Groups contain saved devices, and don't share a name with one
+fn check_groups(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for (group, members) in &config.groups {
+        let key = format!("groups.{group}");
+        if members.is_empty() {
+            problems.push(Problem::warning(&key, "has no devices"));
+        }
+        for member in members {
+            if !config.devices.contains_key(member) {
+                problems.push(Problem::error(&key, not_saved(member)));
+            }
+        }
+        if config.devices.contains_key(group) {
+            problems.push(Problem::warning(
+                &key,
+                "has the same name as a device, so --device picks the device",
+            ));
+        }
+    }
+
+    problems
+}
+
+/// Scenes are made of saved devices
+fn check_scenes(config: &Config) -> Vec<Problem> {
+    config
+        .scenes
+        .iter()
+        .flat_map(|(scene, devices)| {
+            devices
+                .iter()
+                .filter(|d| !config.devices.contains_key(&d.device))
+                .map(move |d| Problem::error(format!("scenes.{scene}"), not_saved(&d.device)))
+        })
+        .collect()
+}
+
+/// Settings kept apart from devices, like logins, are for saved devices, and make sense
+fn check_device_settings(config: &Config) -> Vec<Problem> {
+    let mut problems = Vec::new();
+
+    for name in &config.insecure {
+        match config.devices.get(name) {
+            None => problems.push(Problem::warning("insecure", not_saved(name))),
+            Some(device) if !address::is_https(&device.ip) => problems.push(Problem::warning(
+                "insecure",
+                format!(
+                    "'{name}' isn't reached over HTTPS, so there's no certificate to skip checking"
+                ),
+            )),
+            Some(_) => {}
+        }
+    }
+
+    for (name, credentials) in &config.credentials {
+        let key = format!("credentials.{name}");
+        if !config.devices.contains_key(name) {
+            problems.push(Problem::warning(&key, not_saved(name)));
+        }
+        if let Some(Err(e)) = credentials.pin.as_deref().map(config::parse_pin) {
+            problems.push(Problem::error(format!("{key}.pin"), e));
+        }
+        if credentials.password.is_some() && credentials.username.is_none() {
+            problems.push(Problem::error(
+                format!("{key}.password"),
+                "is set without a username",
+            ));
+        }
+    }
+
+    problems
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Credentials;
+
+    fn config() -> Config {
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.add_device("kitchen".to_string(), "wled-kitchen.local".to_string());
+        config
+            .add_to_group("downstairs", &["kitchen".to_string()])
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn test_valid_config() {
+        assert_eq!(check(&config()), vec![]);
+        assert_eq!(check(&Config::new()), vec![]);
+    }
+
+    #[test]
+    fn test_invalid_address() {
+        let mut config = config();
+        config.device_mut("porch").unwrap().ip = "192.168.1.400".to_string();
+
+        let problems = check(&config);
+        assert_eq!(problems.len(), 1);
+        assert_eq!(problems[0].severity, Severity::Error);
+        assert_eq!(problems[0].key, "devices.porch.ip");
+        assert!(problems[0].message.contains("Invalid device address"));
+    }
+
+    #[test]
+    fn test_duplicate_address() {
+        let mut config = config();
+        config.add_device("tv".to_string(), "192.168.1.40".to_string());
+
+        // Only the second device is flagged
+        assert_eq!(
+            check(&config),
+            vec![Problem::warning(
+                "devices.tv",
+                "has the same address as 'porch' (192.168.1.40)"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_ipv6_addresses() {
+        let mut config = config();
+        config.add_device("lab".to_string(), "fd00::42".to_string());
+        config.add_device("office".to_string(), "[fd00::43]:8080".to_string());
+        assert_eq!(check(&config), vec![]);
+
+        // However it's written, the same address is the same device
+        config.add_device("tv".to_string(), "[fd00:0::42]".to_string());
+        assert_eq!(
+            check(&config),
+            vec![Problem::warning(
+                "devices.tv",
+                "has the same address as 'lab' ([fd00:0::42])"
+            )]
+        );
+
+        config.add_device("desk".to_string(), "fe80::1%eth0".to_string());
+        assert!(check(&config)[0].message.contains("zone IDs"));
+    }
+
+    #[test]
+    fn test_bad_tags_and_defaults() {
+        let mut config = config();
+        let porch = config.device_mut("porch").unwrap();
+        porch.tags.insert("front porch".to_string());
+        porch.default_brightness = Some(0);
+        porch.default_preset = Some(251);
+
+        let problems = check(&config);
+        let keys: Vec<(&str, Severity)> = problems
+            .iter()
+            .map(|p| (p.key.as_str(), p.severity))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("devices.porch.tags", Severity::Warning),
+                ("devices.porch.default_brightness", Severity::Warning),
+                ("devices.porch.default_preset", Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_missing_default_device() {
+        let mut config = config();
+        config.default_device = Some("tv".to_string());
+        assert_eq!(
+            check(&config),
+            vec![Problem::error(
+                "default_device",
+                "'tv' isn't a saved device"
+            )]
+        );
+
+        config.default_device = None;
+        assert_eq!(check(&config)[0].severity, Severity::Warning);
+    }
+
+    #[test]
+    fn test_group_problems() {
+        let mut config = config();
+        config
+            .groups
+            .insert("upstairs".to_string(), vec!["attic".to_string()]);
+        config.groups.insert("porch".to_string(), vec![]);
+
+        assert_eq!(
+            check(&config),
+            vec![
+                Problem::warning("groups.porch", "has no devices"),
+                Problem::warning(
+                    "groups.porch",
+                    "has the same name as a device, so --device picks the device"
+                ),
+                Problem::error("groups.upstairs", "'attic' isn't a saved device"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_scene_with_missing_device() {
+        let mut config = config();
+        let porch = crate::scene::SceneDevice {
+            device: "porch".to_string(),
+            on: Some(true),
+            brightness: None,
+            color: None,
+            effect: None,
+            palette: None,
+        };
+        let attic = crate::scene::SceneDevice {
+            device: "attic".to_string(),
+            ..porch.clone()
+        };
+        config
+            .scenes
+            .insert("evening".to_string(), vec![porch, attic]);
+
+        assert_eq!(
+            check(&config),
+            vec![Problem::error(
+                "scenes.evening",
+                "'attic' isn't a saved device"
+            )]
+        );
+    }
+
+    #[test]
+    fn test_device_settings() {
+        let mut config = config();
+        config.insecure.insert("porch".to_string());
+        config.insecure.insert("attic".to_string());
+        config.credentials.insert(
+            "kitchen".to_string(),
+            Credentials {
+                username: None,
+                password: Some("hunter2".to_string()),
+                pin: Some("12".to_string()),
+            },
+        );
+
+        let problems = check(&config);
+        let keys: Vec<(&str, Severity)> = problems
+            .iter()
+            .map(|p| (p.key.as_str(), p.severity))
+            .collect();
+        assert_eq!(
+            keys,
+            vec![
+                ("insecure", Severity::Warning),
+                ("insecure", Severity::Warning),
+                ("credentials.kitchen.pin", Severity::Error),
+                ("credentials.kitchen.password", Severity::Error),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(
+            Problem::error("default_device", "'tv' isn't a saved device").to_string(),
+            "error: default_device: 'tv' isn't a saved device"
+        );
+    }
+}
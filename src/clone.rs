@@ -0,0 +1,112 @@
+use serde_json::{Map, Value};
+
+/// Top-level state fields that only make sense on the device they came from: preset and
+/// playlist IDs, sync and live data settings, and read-only details
+const DEVICE_FIELDS: [&str; 7] = ["ps", "pl", "udpn", "lor", "time", "error", "lastDur"];
+
+/// Segment fields that describe which LEDs a segment covers, which depend on the strip
+const BOUNDS_FIELDS: [&str; 6] = ["start", "stop", "len", "grp", "spc", "of"];
+
+/// The state update that makes a target device look like the source, given the source's
+/// full state and the IDs of the target's segments. Only segments the target has are
+/// copied, without their bounds, and `mainseg` is dropped if the target lacks it.
+pub fn prepare(source: &Value, target_segments: &[u64]) -> Value {
+    let Some(source) = source.as_object() else {
+        return Value::Object(Map::new());
+    };
+
+    let mut update: Map<String, Value> = source
+        .iter()
+        .filter(|(key, _)| !DEVICE_FIELDS.contains(&key.as_str()))
+        .map(|(key, value)| (key.clone(), value.clone()))
+        .collect();
+
+    if let Some(main) = update.get("mainseg").and_then(Value::as_u64) {
+        if !target_segments.contains(&main) {
+            update.remove("mainseg");
+        }
+    }
+
+    if let Some(Value::Array(segments)) = update.get("seg") {
+        let segments: Vec<Value> = segments
+            .iter()
+            .filter(|s| {
+                s["id"]
+                    .as_u64()
+                    .is_some_and(|id| target_segments.contains(&id))
+            })
+            .map(|segment| match segment {
+                Value::Object(fields) => Value::Object(
+                    fields
+                        .iter()
+                        .filter(|(key, _)| !BOUNDS_FIELDS.contains(&key.as_str()))
+                        .map(|(key, value)| (key.clone(), value.clone()))
+                        .collect(),
+                ),
+                other => other.clone(),
+            })
+            .collect();
+        update.insert("seg".to_string(), Value::Array(segments));
+    }
+
+    Value::Object(update)
+}
+
+/// The IDs of the segments in a device's state
+pub fn segment_ids(state: &Value) -> Vec<u64> {
+    state["seg"]
+        .as_array()
+        .map(|segments| segments.iter().filter_map(|s| s["id"].as_u64()).collect())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn source() -> Value {
+        json!({
+            "on": true, "bri": 180, "transition": 7, "ps": 4, "pl": -1, "mainseg": 1,
+            "udpn": {"send": true, "recv": true}, "lor": 0,
+            "nl": {"on": false, "dur": 60},
+            "seg": [
+                {"id": 0, "start": 0, "stop": 100, "len": 100, "grp": 1, "spc": 0, "of": 0,
+                 "on": true, "bri": 255, "col": [[255, 0, 0]], "fx": 9, "sx": 128, "pal": 3},
+                {"id": 1, "start": 100, "stop": 150, "len": 50, "on": true, "bri": 200,
+                 "col": [[0, 0, 255]], "fx": 0, "pal": 0}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_prepare_strips_device_fields() {
+        assert_eq!(
+            prepare(&source(), &[0, 1]),
+            json!({
+                "on": true, "bri": 180, "transition": 7, "mainseg": 1,
+                "nl": {"on": false, "dur": 60},
+                "seg": [
+                    {"id": 0, "on": true, "bri": 255, "col": [[255, 0, 0]], "fx": 9, "sx": 128,
+                     "pal": 3},
+                    {"id": 1, "on": true, "bri": 200, "col": [[0, 0, 255]], "fx": 0, "pal": 0}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_prepare_for_target_with_fewer_segments() {
+        let update = prepare(&source(), &[0]);
+
+        assert!(update.get("mainseg").is_none());
+        assert_eq!(update["seg"].as_array().unwrap().len(), 1);
+        assert_eq!(update["seg"][0]["id"], 0);
+    }
+
+    #[test]
+    fn test_segment_ids() {
+        assert_eq!(segment_ids(&source()), vec![0, 1]);
+        assert_eq!(segment_ids(&json!({"on": true})), Vec::<u64>::new());
+    }
+}
@@ -0,0 +1,172 @@
+use std::fmt;
+
+use crate::color_names;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rgb {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+}
+
+impl Rgb {
+    pub fn new(r: u8, g: u8, b: u8) -> Self {
+        Rgb { r, g, b }
+    }
+
+    /// The color in the `[r, g, b]` form used by WLED's `seg[].col` field
+    pub fn to_wled(self) -> Vec<u8> {
+        vec![self.r, self.g, self.b]
+    }
+
+    /// The color as `#RRGGBB`, which `parse_color` accepts
+    pub fn to_hex(self) -> String {
+        format!("#{:02X}{:02X}{:02X}", self.r, self.g, self.b)
+    }
+}
+
+impl fmt::Display for Rgb {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "RGB({}, {}, {})", self.r, self.g, self.b)
+    }
+}
+
+/// Parse a color given as `#RRGGBB`, `RRGGBB`, `r,g,b` or a CSS color name
+pub fn parse_color(value: &str) -> Result<Rgb, String> {
+    let value = value.trim();
+
+    if value.contains(',') {
+        return parse_rgb_triple(value);
+    }
+
+    let hex = value.strip_prefix('#').unwrap_or(value);
+    let is_hex = hex.len() == 6 && hex.chars().all(|c| c.is_ascii_hexdigit());
+
+    if !is_hex {
+        if value.starts_with('#') || value.chars().all(|c| c.is_ascii_hexdigit()) {
+            return Err(format!(
+                "Invalid color '{value}': expected #RRGGBB, RRGGBB, r,g,b or a color name"
+            ));
+        }
+
+        return color_names::lookup(value).ok_or_else(|| {
+            let suggestions = color_names::suggestions(value);
+            if suggestions.is_empty() {
+                format!("Unknown color '{value}': expected #RRGGBB, RRGGBB, r,g,b or a color name")
+            } else {
+                format!(
+                    "Unknown color '{value}'. Did you mean: {}?",
+                    suggestions.join(", ")
+                )
+            }
+        });
+    }
+
+    let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).unwrap();
+    Ok(Rgb::new(channel(0), channel(2), channel(4)))
+}
+
+fn parse_rgb_triple(value: &str) -> Result<Rgb, String> {
+    let channels: Vec<&str> = value.split(',').map(str::trim).collect();
+    if channels.len() != 3 {
+        return Err(format!(
+            "Invalid color '{value}': expected three comma-separated values (r,g,b)"
+        ));
+    }
+
+    let mut parsed = [0u8; 3];
+    for (slot, channel) in parsed.iter_mut().zip(&channels) {
+        *slot = channel.parse().map_err(|_| {
+            format!("Invalid color '{value}': '{channel}' is not a number between 0 and 255")
+        })?;
+    }
+
+    Ok(Rgb::new(parsed[0], parsed[1], parsed[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_to_hex_round_trips() {
+        let color = Rgb::new(255, 8, 160);
+        assert_eq!(color.to_hex(), "#FF08A0");
+        assert_eq!(parse_color(&color.to_hex()), Ok(color));
+    }
+
+    #[test]
+    fn test_parse_hex_with_hash() {
+        assert_eq!(parse_color("#FF8000"), Ok(Rgb::new(255, 128, 0)));
+    }
+
+    #[test]
+    fn test_parse_hex_without_hash() {
+        assert_eq!(parse_color("00ff7f"), Ok(Rgb::new(0, 255, 127)));
+    }
+
+    #[test]
+    fn test_parse_rgb_triple() {
+        assert_eq!(parse_color("10, 20,30"), Ok(Rgb::new(10, 20, 30)));
+    }
+
+    #[test]
+    fn test_parse_invalid_hex() {
+        let result = parse_color("#GG0000");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("expected #RRGGBB"));
+    }
+
+    #[test]
+    fn test_parse_named_color() {
+        assert_eq!(parse_color("orange"), Ok(Rgb::new(255, 165, 0)));
+        assert_eq!(parse_color("Warm White"), Ok(Rgb::new(255, 180, 107)));
+    }
+
+    #[test]
+    fn test_parse_hex_like_name() {
+        // Six hex digits are always treated as a hex color
+        assert_eq!(parse_color("beaded"), Ok(Rgb::new(190, 173, 237)));
+    }
+
+    #[test]
+    fn test_parse_unknown_name_suggests() {
+        let result = parse_color("yelow");
+        assert_eq!(
+            result.unwrap_err(),
+            "Unknown color 'yelow'. Did you mean: yellow?"
+        );
+    }
+
+    #[test]
+    fn test_parse_unknown_name_without_suggestions() {
+        let result = parse_color("xyzzyplugh");
+        assert!(result
+            .unwrap_err()
+            .starts_with("Unknown color 'xyzzyplugh'"));
+    }
+
+    #[test]
+    fn test_parse_hex_wrong_length() {
+        assert!(parse_color("#FFF").is_err());
+        assert!(parse_color("FF00000").is_err());
+    }
+
+    #[test]
+    fn test_parse_rgb_triple_out_of_range() {
+        let result = parse_color("256,0,0");
+        assert!(result.is_err());
+        assert!(result.unwrap_err().contains("'256' is not a number"));
+    }
+
+    #[test]
+    fn test_parse_rgb_triple_wrong_count() {
+        assert!(parse_color("1,2").is_err());
+        assert!(parse_color("1,2,3,4").is_err());
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Rgb::new(1, 2, 3).to_string(), "RGB(1, 2, 3)");
+    }
+}
@@ -0,0 +1,105 @@
+use clap_complete::Shell;
+
+/// Completes `-d`/`--device` with saved device and group names in bash, by wrapping the
+/// generated completion function
+const BASH_DEVICES: &str = r#"
+_wld_with_devices() {
+    local prev="${COMP_WORDS[COMP_CWORD-1]}"
+    if [[ "$prev" == "-d" || "$prev" == "--device" ]]; then
+        local IFS=$'\n'
+        COMPREPLY=($(compgen -W "$(wld _complete devices 2>/dev/null)" -- "${COMP_WORDS[COMP_CWORD]}"))
+        return 0
+    fi
+    _wld "$@"
+}
+
+if [[ "${BASH_VERSINFO[0]}" -eq 4 && "${BASH_VERSINFO[1]}" -ge 4 || "${BASH_VERSINFO[0]}" -gt 4 ]]; then
+    complete -F _wld_with_devices -o nosort -o bashdefault -o default wld
+else
+    complete -F _wld_with_devices -o bashdefault -o default wld
+fi
+"#;
+
+/// Completes `-d`/`--device` with saved device and group names in zsh. The generated
+/// script completes device values with `_default`, which this replaces.
+const ZSH_DEVICES: &str = r#"
+(( $+functions[_wld_devices] )) ||
+_wld_devices() {
+    local -a devices
+    devices=("${(@f)$(wld _complete devices 2>/dev/null)}")
+    compadd -a devices
+}
+"#;
+
+/// Completes `-d`/`--device` with saved device and group names in fish
+const FISH_DEVICES: &str = r#"
+complete -c wld -s d -l device -x -a "(wld _complete devices 2>/dev/null)"
+"#;
+
+/// A completion script for `shell`, with device names completed for `-d`/`--device` where
+/// the shell supports it
+pub fn script(shell: Shell, command: &mut clap::Command) -> String {
+    let mut buffer = Vec::new();
+    clap_complete::generate(shell, command, "wld", &mut buffer);
+    let generated = String::from_utf8_lossy(&buffer).into_owned();
+
+    match shell {
+        Shell::Bash => generated + BASH_DEVICES,
+        Shell::Zsh => {
+            // zsh runs the script as the body of `_wld` when it's installed on `$fpath`,
+            // so the helper has to be defined before the completion is first run
+            let completes_devices = generated.replace(":DEVICE:_default", ":DEVICE:_wld_devices");
+            match completes_devices.split_once("\n(( $+functions[") {
+                Some((head, tail)) => format!("{head}{ZSH_DEVICES}\n(( $+functions[{tail}"),
+                None => completes_devices + ZSH_DEVICES,
+            }
+        }
+        Shell::Fish => generated + FISH_DEVICES,
+        _ => generated,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn command() -> clap::Command {
+        clap::Command::new("wld").subcommand(
+            clap::Command::new("on").arg(
+                clap::Arg::new("device")
+                    .short('d')
+                    .long("device")
+                    .value_name("DEVICE"),
+            ),
+        )
+    }
+
+    #[test]
+    fn test_bash_completes_devices() {
+        let script = script(Shell::Bash, &mut command());
+
+        assert!(script.contains("_wld() {"));
+        assert!(script.contains("wld _complete devices"));
+        assert!(script.trim_end().ends_with("fi"));
+        assert!(script.contains("complete -F _wld_with_devices"));
+    }
+
+    #[test]
+    fn test_zsh_completes_devices() {
+        let script = script(Shell::Zsh, &mut command());
+
+        assert!(script.contains(":DEVICE:_wld_devices"));
+        assert!(!script.contains(":DEVICE:_default"));
+        // The helper is defined before the script runs the completion
+        let helper = script.find("_wld_devices() {").unwrap();
+        let run = script.find("_wld \"$@\"").unwrap();
+        assert!(helper < run);
+    }
+
+    #[test]
+    fn test_powershell_is_unchanged() {
+        let script = script(Shell::PowerShell, &mut command());
+
+        assert!(!script.contains("_complete devices"));
+    }
+}
@@ -1,258 +1,2094 @@
+use crate::address;
+use crate::discover;
+use crate::duration;
+use crate::error::WldError;
+use crate::fuzzy;
+use crate::retry;
+use crate::scene::SceneDevice;
+use chrono::{DateTime, TimeDelta, Utc};
 use directories::BaseDirs;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
-use std::fs;
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet};
+use std::ffi::OsString;
+use std::fs::{self, File};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::Duration;
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The config file given with `--config`, set by `Config::use_path`
+static PATH: Mutex<Option<PathBuf>> = Mutex::new(None);
+
+/// The environment variable that picks a config file other than the usual one
+pub const PATH_VARIABLE: &str = "WLD_CONFIG";
+
+/// The profile given with `--profile`, set by `Config::use_profile`
+static PROFILE: Mutex<Option<String>> = Mutex::new(None);
+
+/// The timeout given with `--timeout`, set by `Config::use_timeout`
+static TIMEOUT: Mutex<Option<Duration>> = Mutex::new(None);
+
+/// How old a device's last seen time has to be before a newer one is saved over it
+const SEEN_INTERVAL: TimeDelta = TimeDelta::hours(1);
+
+/// The number of retries given with `--retries`, set by `Config::use_retries`
+static RETRIES: Mutex<Option<u32>> = Mutex::new(None);
+
+/// The environment variable that picks a profile
+pub const PROFILE_VARIABLE: &str = "WLD_PROFILE";
+
+/// The profile that's used unless another is picked, kept in `config.toml`
+pub const DEFAULT_PROFILE: &str = "default";
+
+/// The environment variable that picks the device to use when a command isn't given one
+/// with `--device`, ahead of the default device
+pub const DEVICE_VARIABLE: &str = "WLD_DEVICE";
+
+/// The device named or addressed in `$WLD_DEVICE`, if it's set
+pub fn device_from_env() -> Option<String> {
+    std::env::var(DEVICE_VARIABLE)
+        .ok()
+        .filter(|device| !device.trim().is_empty())
+}
+
+/// The config kept in memory by `Config::keep_loaded`, so a long-running session like
+/// `wld repl` doesn't re-read it for every command
+static KEPT: Mutex<Option<Config>> = Mutex::new(None);
+
+/// An exclusive lock on the config file, from `Config::load_locked`. It's released when
+/// this is dropped.
+pub struct ConfigLock {
+    _file: File,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Config {
-    pub devices: HashMap<String, String>, // name -> ip mapping
+    /// The version of the layout the config was written with. See `MIGRATIONS`.
+    pub version: u32,
+    pub devices: BTreeMap<String, Device>, // name -> device
     pub default_device: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub groups: BTreeMap<String, Vec<String>>, // group name -> device names
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub scenes: BTreeMap<String, Vec<SceneDevice>>, // scene name -> how each device looks
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub insecure: BTreeSet<String>, // devices whose TLS certificates aren't checked
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub credentials: BTreeMap<String, Credentials>, // device name -> how to log in
+    /// How long to wait for a device to respond, unless it has a timeout of its own
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_timeout",
+        deserialize_with = "deserialize_timeout"
+    )]
+    pub timeout: Option<Duration>,
+    /// How many times to ask a device again when a request doesn't reach it, unless it has
+    /// a number of its own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+}
+
+/// A saved device
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Device {
+    /// The device's address, as given to `wld add`
+    pub ip: String,
+    /// Tags like `outdoor` or `room=bedroom`, for picking out devices with `--tag`
+    #[serde(default, skip_serializing_if = "BTreeSet::is_empty")]
+    pub tags: BTreeSet<String>,
+    /// Brightness `wld on` sets, unless it's given one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_brightness: Option<u8>,
+    /// Preset `wld on` applies, unless it's given a color
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub default_preset: Option<u8>,
+    /// How long to wait for the device to respond, e.g. for one on a slow network
+    #[serde(
+        default,
+        skip_serializing_if = "Option::is_none",
+        serialize_with = "serialize_timeout",
+        deserialize_with = "deserialize_timeout"
+    )]
+    pub timeout: Option<Duration>,
+    /// How many times to ask the device again when a request doesn't reach it, e.g. for
+    /// one with a weak WiFi signal
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub retries: Option<u32>,
+    /// When a command last got an answer from the device, unless it was given `--no-track`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    /// The firmware version the device last reported, e.g. `0.15.0`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_version: Option<String>,
+}
+
+impl Device {
+    pub fn new(ip: String) -> Self {
+        Device {
+            ip,
+            tags: BTreeSet::new(),
+            default_brightness: None,
+            default_preset: None,
+            timeout: None,
+            retries: None,
+            last_seen: None,
+            last_version: None,
+        }
+    }
+}
+
+/// Write a timeout like `5s`, as it's given to `--timeout`
+fn serialize_timeout<S: serde::Serializer>(
+    timeout: &Option<Duration>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match timeout {
+        Some(timeout) => serializer.serialize_str(&duration::format_duration(*timeout)),
+        None => serializer.serialize_none(),
+    }
+}
+
+/// Read a timeout like `5s`, or a number of seconds
+fn deserialize_timeout<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<Duration>, D::Error> {
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Timeout {
+        Seconds(u64),
+        Text(String),
+    }
+
+    let timeout = match Timeout::deserialize(deserializer)? {
+        Timeout::Seconds(seconds) => seconds.to_string(),
+        Timeout::Text(text) => text,
+    };
+    duration::parse_timeout(&timeout)
+        .map(Some)
+        .map_err(serde::de::Error::custom)
+}
+
+/// The changes to the config file's layout, in order. The one at index `n` upgrades a
+/// config from version `n` to version `n + 1`, so loading an older config runs every
+/// migration from its version on.
+const MIGRATIONS: [fn(&mut toml::Table); 1] = [devices_as_tables];
+
+/// The version of the config file's layout that this version of wld writes
+pub const VERSION: u32 = MIGRATIONS.len() as u32;
+
+/// Version 1: devices are tables with an `ip` key, rather than just their address
+fn devices_as_tables(config: &mut toml::Table) {
+    let Some(toml::Value::Table(devices)) = config.get_mut("devices") else {
+        return;
+    };
+    for (_, device) in devices.iter_mut() {
+        if let toml::Value::String(ip) = device {
+            let mut table = toml::Table::new();
+            table.insert("ip".to_string(), toml::Value::String(ip.clone()));
+            *device = toml::Value::Table(table);
+        }
+    }
+}
+
+/// Where the config is kept: a file given with `--config` or `$WLD_CONFIG`, or a profile
+enum Location {
+    File(PathBuf),
+    Profile(String),
+}
+
+/// Parse a profile name, like `work`, which is used as the name of its file
+pub fn parse_profile(value: &str) -> Result<String, String> {
+    let valid = !value.is_empty()
+        && value != "config"
+        && value
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_');
+    if valid {
+        Ok(value.to_string())
+    } else {
+        Err(format!(
+            "Invalid profile name '{value}': use letters, digits, '-' and '_'"
+        ))
+    }
+}
+
+/// Names quoted and joined with `separator`, like `'bedroom', 'bed-lamp'`
+pub(crate) fn quote_list<S: AsRef<str>>(names: &[S], separator: &str) -> String {
+    names
+        .iter()
+        .map(|name| format!("'{}'", name.as_ref()))
+        .collect::<Vec<_>>()
+        .join(separator)
+}
+
+/// The user's directories, like their home directory, which the config is kept under
+fn base_dirs() -> Result<BaseDirs, WldError> {
+    BaseDirs::new().ok_or_else(|| WldError::ConfigIo {
+        action: "find",
+        path: PathBuf::from("~/.config/wld"),
+        source: io::Error::new(io::ErrorKind::NotFound, "Could not find home directory"),
+    })
+}
+
+/// A path with something added to the end of its file name, like `config.toml.lock`
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = OsString::from(path.as_os_str());
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Parse a device tag, like `outdoor` or `room=bedroom`
+pub fn parse_tag(value: &str) -> Result<String, String> {
+    if value.is_empty() || value.contains(|c: char| c.is_whitespace() || c == ',') {
+        Err(format!(
+            "Invalid tag '{value}': tags can't be empty or contain spaces or commas"
+        ))
+    } else {
+        Ok(value.to_string())
+    }
+}
+
+/// How to log in to a device that's protected by a password or PIN
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Credentials {
+    /// Username for HTTP basic auth, e.g. for a device behind a proxy
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub username: Option<String>,
+    /// Password for HTTP basic auth
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub password: Option<String>,
+    /// The device's settings PIN, which WLED asks for before changing its settings
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pin: Option<String>,
+}
+
+/// What `Config::add_device` did
+#[derive(Debug, PartialEq, Eq)]
+pub enum AddOutcome {
+    /// Saved a new device. `duplicate_of` is another device already saved with the same
+    /// address, if there is one.
+    Added { duplicate_of: Option<String> },
+    /// Replaced the `old` address of a device that was already saved
+    Replaced {
+        old: String,
+        duplicate_of: Option<String>,
+    },
+    /// The device was already saved with the same address
+    Unchanged,
+}
+
+/// What happened to one of the devices given to `Config::import_devices`
+#[derive(Debug, PartialEq, Eq)]
+pub enum Imported {
+    /// Skipped, as a device is already saved as `existing` with the same IP
+    Skipped {
+        name: String,
+        ip: String,
+        existing: String,
+    },
+    /// Saved as `name`, which may have been changed to make it unique
+    Added { name: String, ip: String },
+}
+
+/// Parse a WLED settings PIN, which is always 4 digits
+pub fn parse_pin(value: &str) -> Result<String, String> {
+    if value.len() == 4 && value.chars().all(|c| c.is_ascii_digit()) {
+        Ok(value.to_string())
+    } else {
+        Err("PIN must be 4 digits".to_string())
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Config {
     pub fn new() -> Self {
         Config {
-            devices: HashMap::new(),
+            version: VERSION,
+            devices: BTreeMap::new(),
             default_device: None,
+            groups: BTreeMap::new(),
+            scenes: BTreeMap::new(),
+            insecure: BTreeSet::new(),
+            credentials: BTreeMap::new(),
+            timeout: None,
+            retries: None,
+        }
+    }
+
+    pub fn load() -> Result<Self, WldError> {
+        if let Some(config) = KEPT.lock().unwrap().as_ref() {
+            return Ok(config.clone());
+        }
+        Self::load_from(&Self::config_path()?)
+    }
+
+    /// Lock the config file, then load it from disk, for changing it and saving it. Other
+    /// wld processes wait for the lock to be released before changing the config, so they
+    /// don't undo each other's changes. Keep the lock until the config has been saved.
+    pub fn load_locked() -> Result<(Self, ConfigLock), WldError> {
+        let lock = Self::lock()?;
+        let config = Self::load_from(&Self::config_path()?)?;
+        Ok((config, lock))
+    }
+
+    /// Lock the config file without loading it, waiting for any other wld process that
+    /// has it locked
+    pub fn lock() -> Result<ConfigLock, WldError> {
+        let path = Self::config_path()?;
+        let file = Self::lock_file(&path)?;
+        file.lock().map_err(|source| WldError::ConfigIo {
+            action: "lock",
+            path,
+            source,
+        })?;
+        Ok(ConfigLock { _file: file })
+    }
+
+    /// Lock the config file and load it, unless another wld process has it locked, in
+    /// which case `None` is returned straight away
+    pub fn try_load_locked() -> Result<Option<(Self, ConfigLock)>, WldError> {
+        let path = Self::config_path()?;
+        let file = Self::lock_file(&path)?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(fs::TryLockError::WouldBlock) => return Ok(None),
+            Err(fs::TryLockError::Error(source)) => {
+                return Err(WldError::ConfigIo {
+                    action: "lock",
+                    path,
+                    source,
+                })
+            }
+        }
+        let config = Self::load_from(&path)?;
+        Ok(Some((config, ConfigLock { _file: file })))
+    }
+
+    /// Open the file the config's lock is taken on. The config file itself is replaced
+    /// when it's saved, so the lock is taken on a file next to it.
+    fn lock_file(path: &Path) -> Result<File, WldError> {
+        let io_error = |source| WldError::ConfigIo {
+            action: "lock",
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+        File::create(with_suffix(path, ".lock")).map_err(io_error)
+    }
+
+    /// What a new config file starts as when it's opened with `wld config edit`
+    pub fn template() -> String {
+        format!(
+            r#"# wld config file. Commands like `wld add`, `wld group` and `wld tag` change it
+# for you, or you can edit it by hand. Run `wld config show` to check how wld reads it.
+
+# The version of this file's layout. Leave it as it is.
+version = {VERSION}
+
+# The device used when a command isn't given --device
+# default_device = "porch"
+
+# How long to wait for a device to respond before giving up on it
+# timeout = "5s"
+
+# How many times to ask a device again when a request doesn't reach it
+# retries = 1
+
+# Saved devices, by name. Only `ip` is needed.
+# [devices.porch]
+# ip = "192.168.1.40"
+# tags = ["outdoor"]
+# default_brightness = 128
+# default_preset = 1
+# timeout = "10s"
+# retries = 3
+
+# Groups of devices, which commands can target with --device
+# [groups]
+# downstairs = ["porch", "kitchen"]
+
+[devices]
+"#
+        )
+    }
+
+    /// Edit the config file by hand with `edit`, which is given its path, starting it from
+    /// `Config::template` if it doesn't exist. Other wld processes can't change the config
+    /// meanwhile. If it isn't valid afterwards, `edit` is called again with what's wrong,
+    /// and can return `false` to give up. If it does, or editing fails, what was there
+    /// before is put back, so a broken config is never left behind. Returns the config
+    /// file's path.
+    pub fn edit<E>(
+        mut edit: impl FnMut(&Path, Option<&str>) -> Result<bool, E>,
+    ) -> Result<PathBuf, E>
+    where
+        E: From<WldError> + std::fmt::Display,
+    {
+        let path = Self::config_path()?;
+        let _lock = Self::lock()?;
+        let io_error = |action| {
+            let path = path.clone();
+            move |source| WldError::ConfigIo {
+                action,
+                path,
+                source,
+            }
+        };
+
+        let backup = fs::read_to_string(&path).ok();
+        if backup.is_none() {
+            fs::write(&path, Self::template()).map_err(io_error("write"))?;
+        }
+        let restore = || {
+            match &backup {
+                Some(content) => fs::write(&path, content),
+                None => fs::remove_file(&path),
+            }
+            .map_err(io_error("restore"))
+        };
+
+        let mut problem = None;
+        loop {
+            match edit(&path, problem.as_deref()) {
+                Ok(true) => {}
+                Ok(false) => {
+                    restore()?;
+                    return Err(WldError::InvalidInput(format!(
+                        "Put back the config file {} as it was before editing",
+                        path.display()
+                    ))
+                    .into());
+                }
+                Err(e) => {
+                    restore()?;
+                    return Err(WldError::InvalidInput(format!(
+                        "{e}, so the config file was left as it was"
+                    ))
+                    .into());
+                }
+            }
+
+            let content = fs::read_to_string(&path).map_err(io_error("read"))?;
+            match Self::parse(&content) {
+                Ok(_) => return Ok(path),
+                Err(e) => problem = Some(e),
+            }
+        }
+    }
+
+    /// Load the config once and keep it in memory for the rest of the process. Saving
+    /// still writes it to disk, and updates the kept copy.
+    pub fn keep_loaded() -> Result<(), WldError> {
+        let config = Self::load_from(&Self::config_path()?)?;
+        *KEPT.lock().unwrap() = Some(config);
+        Ok(())
+    }
+
+    /// Load the config from a file, or an empty config if the file doesn't exist yet
+    pub fn load_from(path: &Path) -> Result<Self, WldError> {
+        if !path.exists() {
+            return Ok(Self::new());
+        }
+
+        let content = fs::read_to_string(path).map_err(|source| WldError::ConfigIo {
+            action: "read",
+            path: path.to_path_buf(),
+            source,
+        })?;
+        Self::parse(&content).map_err(|message| WldError::ConfigParse {
+            path: path.to_path_buf(),
+            message,
+        })
+    }
+
+    /// Parse a config file's contents, upgrading it to the current layout if it was
+    /// written by an older version of wld
+    pub fn parse(content: &str) -> Result<Self, String> {
+        let mut config: toml::Table = toml::from_str(content).map_err(|e| {
+            let line = e
+                .span()
+                .map(|span| content[..span.start].lines().count().max(1));
+            let location = line.map(|line| format!(" at line {line}")).unwrap_or_default();
+            format!(
+                "it isn't valid TOML{location}: {}. Fix it, or move it out of the way to start with an empty config",
+                e.message()
+            )
+        })?;
+
+        // Configs from before the layout was versioned don't say
+        let version = match config.get("version") {
+            None => 0,
+            Some(toml::Value::Integer(version)) if *version >= 0 => *version as u64,
+            Some(_) => return Err("version must be a whole number".to_string()),
+        };
+        if version > u64::from(VERSION) {
+            return Err(format!(
+                "it was written by a newer version of wld (config version {version}, but this version understands up to {VERSION}). Upgrade wld to use it"
+            ));
+        }
+
+        for migrate in &MIGRATIONS[version as usize..] {
+            migrate(&mut config);
+        }
+        config.insert("version".to_string(), toml::Value::Integer(VERSION.into()));
+
+        toml::Value::Table(config)
+            .try_into()
+            .map_err(|e: toml::de::Error| {
+                format!(
+                    "{}. Fix it, or move it out of the way to start with an empty config",
+                    e.message()
+                )
+            })
+    }
+
+    pub fn save(&self) -> Result<(), WldError> {
+        self.save_to(&Self::config_path()?)?;
+
+        if let Some(kept) = KEPT.lock().unwrap().as_mut() {
+            *kept = self.clone();
+        }
+        Ok(())
+    }
+
+    /// Save the config to a file, creating it and its directory if needed
+    pub fn save_to(&self, path: &Path) -> Result<(), WldError> {
+        let io_error = |source| WldError::ConfigIo {
+            action: "save",
+            path: path.to_path_buf(),
+            source,
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent).map_err(io_error)?;
+        }
+
+        let config = Config {
+            version: VERSION,
+            ..self.clone()
+        };
+        let content = toml::to_string_pretty(&config)
+            .map_err(io::Error::other)
+            .map_err(io_error)?;
+
+        // Write a new file and move it over the old one, so a crash part way through
+        // leaves the old config rather than half of the new one
+        let temporary = with_suffix(path, &format!(".{}.tmp", std::process::id()));
+        fs::write(&temporary, content).map_err(io_error)?;
+        fs::rename(&temporary, path)
+            .inspect_err(|_| {
+                let _ = fs::remove_file(&temporary);
+            })
+            .map_err(io_error)
+    }
+
+    /// Use a config file other than the usual one for the rest of the process, as given
+    /// with `--config`
+    pub fn use_path(path: PathBuf) {
+        *PATH.lock().unwrap() = Some(path);
+    }
+
+    /// Use a profile other than the default for the rest of the process, as given with
+    /// `--profile`
+    pub fn use_profile(name: String) {
+        *PROFILE.lock().unwrap() = Some(name);
+    }
+
+    /// Wait this long for every device to respond for the rest of the process, whatever
+    /// the config says, as given with `--timeout`
+    pub fn use_timeout(timeout: Duration) {
+        *TIMEOUT.lock().unwrap() = Some(timeout);
+    }
+
+    /// Ask every device again this many times when a request doesn't reach it for the
+    /// rest of the process, whatever the config says, as given with `--retries`
+    pub fn use_retries(retries: u32) {
+        *RETRIES.lock().unwrap() = Some(retries);
+    }
+
+    /// The config file: the one given with `--config`, or else the profile given with
+    /// `--profile`, or else `$WLD_CONFIG`, or else the profile in `$WLD_PROFILE`, or else
+    /// the default profile
+    pub fn config_path() -> Result<PathBuf, WldError> {
+        match Self::location()? {
+            Location::File(path) => Ok(path),
+            Location::Profile(name) => Self::profile_path(&name),
+        }
+    }
+
+    /// The profile in use, or `None` if a config file was given with `--config` or
+    /// `$WLD_CONFIG` instead
+    pub fn active_profile() -> Result<Option<String>, WldError> {
+        match Self::location()? {
+            Location::File(_) => Ok(None),
+            Location::Profile(name) => Ok(Some(name)),
+        }
+    }
+
+    fn location() -> Result<Location, WldError> {
+        if let Some(path) = PATH.lock().unwrap().as_ref() {
+            return Ok(Location::File(path.clone()));
+        }
+        if let Some(name) = PROFILE.lock().unwrap().as_ref() {
+            return Ok(Location::Profile(name.clone()));
+        }
+        if let Some(path) = std::env::var_os(PATH_VARIABLE).filter(|p| !p.is_empty()) {
+            return Ok(Location::File(PathBuf::from(path)));
+        }
+        match std::env::var(PROFILE_VARIABLE) {
+            Ok(name) if !name.is_empty() => parse_profile(&name)
+                .map(Location::Profile)
+                .map_err(|e| WldError::InvalidInput(format!("Invalid ${PROFILE_VARIABLE}: {e}"))),
+            _ => Ok(Location::Profile(DEFAULT_PROFILE.to_string())),
+        }
+    }
+
+    /// The directory profiles are kept in, like `~/.config/wld`
+    fn profiles_dir() -> Result<PathBuf, WldError> {
+        let base_dirs = base_dirs()?;
+        Ok(base_dirs.config_dir().join("wld"))
+    }
+
+    /// Where a profile is kept: `config.toml` for the default profile, which a config left
+    /// at the old `~/.wld.toml` is moved to first, or else `<name>.toml`, which has to have
+    /// been created with `wld profile create`
+    fn profile_path(name: &str) -> Result<PathBuf, WldError> {
+        let dir = Self::profiles_dir()?;
+        if name != DEFAULT_PROFILE {
+            let path = dir.join(format!("{name}.toml"));
+            if !path.exists() {
+                return Err(WldError::InvalidInput(format!(
+                    "Profile '{name}' doesn't exist. Create it with `wld profile create {name}`"
+                )));
+            }
+            return Ok(path);
+        }
+
+        let path = dir.join("config.toml");
+        let old_path = base_dirs()?.home_dir().join(".wld.toml");
+        if Self::migrate(&old_path, &path)? {
+            eprintln!(
+                "Moved config from {} to {}",
+                old_path.display(),
+                path.display()
+            );
+        }
+        Ok(path)
+    }
+
+    /// The names of the saved profiles, in order, always including the default profile
+    pub fn profiles() -> Result<Vec<String>, WldError> {
+        let dir = Self::profiles_dir()?;
+        let mut names = vec![DEFAULT_PROFILE.to_string()];
+        if let Ok(entries) = fs::read_dir(&dir) {
+            for entry in entries {
+                let path = entry
+                    .map_err(|source| WldError::ConfigIo {
+                        action: "list",
+                        path: dir.clone(),
+                        source,
+                    })?
+                    .path();
+                if path.extension().is_some_and(|e| e == "toml") {
+                    if let Some(name) = path.file_stem().and_then(|s| s.to_str()) {
+                        if name != "config" && parse_profile(name).is_ok() {
+                            names.push(name.to_string());
+                        }
+                    }
+                }
+            }
+        }
+        names[1..].sort();
+        Ok(names)
+    }
+
+    /// Create a new, empty profile
+    pub fn create_profile(name: &str) -> Result<(), WldError> {
+        let path = Self::profiles_dir()?.join(format!("{name}.toml"));
+        if name == DEFAULT_PROFILE || path.exists() {
+            return Err(WldError::InvalidInput(format!(
+                "Profile '{name}' already exists"
+            )));
+        }
+        Self::new().save_to(&path)
+    }
+
+    /// Delete a profile and everything saved in it
+    pub fn remove_profile(name: &str) -> Result<(), WldError> {
+        if name == DEFAULT_PROFILE {
+            return Err(WldError::InvalidInput(
+                "The default profile can't be removed".to_string(),
+            ));
+        }
+        let path = Self::profiles_dir()?.join(format!("{name}.toml"));
+        fs::remove_file(&path).map_err(|source| WldError::ConfigIo {
+            action: "remove",
+            path: path.clone(),
+            source,
+        })?;
+        let _ = fs::remove_file(with_suffix(&path, ".lock"));
+        Ok(())
+    }
+
+    /// Move a config from `old_path` to `path` if there's one at `old_path` but not at
+    /// `path`, renaming the old file to end in `.migrated`. Returns whether it was moved.
+    fn migrate(old_path: &Path, path: &Path) -> Result<bool, WldError> {
+        if path.exists() || !old_path.exists() {
+            return Ok(false);
+        }
+
+        Self::load_from(old_path)?.save_to(path)?;
+        fs::rename(old_path, with_suffix(old_path, ".migrated")).map_err(|source| {
+            WldError::ConfigIo {
+                action: "move",
+                path: old_path.to_path_buf(),
+                source,
+            }
+        })?;
+        Ok(true)
+    }
+
+    pub fn add_device(&mut self, name: String, ip: String) -> AddOutcome {
+        let duplicate_of = self.names_at(&ip).find(|other| **other != name).cloned();
+
+        // A device that's added again keeps its tags
+        let outcome = match self.devices.get_mut(&name) {
+            None => {
+                self.devices.insert(name.clone(), Device::new(ip));
+                AddOutcome::Added { duplicate_of }
+            }
+            Some(device) if device.ip == ip => AddOutcome::Unchanged,
+            Some(device) => AddOutcome::Replaced {
+                old: std::mem::replace(&mut device.ip, ip),
+                duplicate_of,
+            },
+        };
+
+        // If this is the first device, make it default
+        if self.devices.len() == 1 {
+            self.default_device = Some(name);
+        }
+
+        outcome
+    }
+
+    /// Save newly found devices, given as name and IP pairs. Devices already saved with the
+    /// same IP are skipped, and names are made unique. `choose` is asked about each device,
+    /// with the name it would be saved under, before the config is locked, so other wld
+    /// processes aren't kept waiting on the answers. `report` is told what happened to
+    /// each device as it happens.
+    pub fn import_devices<E: From<WldError>>(
+        devices: &[(String, String)],
+        mut choose: impl FnMut(&str, &str) -> Result<bool, E>,
+        mut report: impl FnMut(Imported),
+    ) -> Result<(), E> {
+        let skipped = |config: &Config, name: &str, ip: &str| {
+            config
+                .devices
+                .iter()
+                .find(|(_, saved)| saved.ip == ip)
+                .map(|(existing, _)| Imported::Skipped {
+                    name: name.to_string(),
+                    ip: ip.to_string(),
+                    existing: existing.clone(),
+                })
+        };
+
+        let mut draft = Self::load()?;
+        let mut chosen = Vec::new();
+        for (name, ip) in devices {
+            if let Some(skipped) = skipped(&draft, name, ip) {
+                report(skipped);
+                continue;
+            }
+
+            let name = discover::unique_name(name, |n| draft.devices.contains_key(n));
+            if !choose(&name, ip)? {
+                continue;
+            }
+            draft.add_device(name.clone(), ip.clone());
+            chosen.push((name, ip));
+        }
+        if chosen.is_empty() {
+            return Ok(());
+        }
+
+        // The config may have changed while asking, so check each device again against it
+        let (mut config, _lock) = Self::load_locked()?;
+        let mut added = Vec::new();
+        for (name, ip) in chosen {
+            if let Some(skipped) = skipped(&config, &name, ip) {
+                report(skipped);
+                continue;
+            }
+            let name = discover::unique_name(&name, |n| config.devices.contains_key(n));
+            config.add_device(name.clone(), ip.clone());
+            added.push(Imported::Added {
+                name,
+                ip: ip.clone(),
+            });
+        }
+
+        if !added.is_empty() {
+            config.save()?;
+        }
+        added.into_iter().for_each(report);
+
+        Ok(())
+    }
+
+    pub fn remove_device(&mut self, name: &str) -> Result<(), WldError> {
+        if !self.devices.contains_key(name) {
+            return Err(WldError::device_not_found(name));
+        }
+
+        self.devices.remove(name);
+        self.insecure.remove(name);
+        self.credentials.remove(name);
+
+        // Remove the device from any groups, dropping groups left empty
+        for members in self.groups.values_mut() {
+            members.retain(|member| member != name);
+        }
+        self.groups.retain(|_, members| !members.is_empty());
+
+        // Likewise for scenes
+        for devices in self.scenes.values_mut() {
+            devices.retain(|scene_device| scene_device.device != name);
+        }
+        self.scenes.retain(|_, devices| !devices.is_empty());
+
+        // Clear default if we removed the default device
+        if self.default_device.as_deref() == Some(name) {
+            self.default_device = None;
+
+            // If there are other devices, make the first one alphabetically the default
+            if let Some(first_name) = self.devices.keys().next() {
+                self.default_device = Some(first_name.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Set whether the TLS certificate of a device reached over HTTPS is checked, for
+    /// devices with a self-signed certificate
+    pub fn set_insecure(&mut self, name: &str, insecure: bool) {
+        if insecure {
+            self.insecure.insert(name.to_string());
+        } else {
+            self.insecure.remove(name);
+        }
+    }
+
+    /// Set how to log in to a device, or forget it if `credentials` is empty
+    pub fn set_credentials(&mut self, name: &str, credentials: Credentials) {
+        if credentials == Credentials::default() {
+            self.credentials.remove(name);
+        } else {
+            self.credentials.insert(name.to_string(), credentials);
+        }
+    }
+
+    /// The names of the saved devices at an address
+    fn names_at<'a>(&'a self, address: &'a str) -> impl Iterator<Item = &'a String> {
+        self.devices
+            .iter()
+            .filter(move |(_, device)| address::same_address(&device.ip, address))
+            .map(|(name, _)| name)
+    }
+
+    /// Whether a saved device at this address skips checking TLS certificates
+    pub fn is_insecure(&self, address: &str) -> bool {
+        self.names_at(address)
+            .any(|name| self.insecure.contains(name))
+    }
+
+    /// How to log in to the saved device at this address, if it needs it
+    pub fn credentials_for(&self, address: &str) -> Option<&Credentials> {
+        self.names_at(address)
+            .find_map(|name| self.credentials.get(name))
+    }
+
+    /// The saved device with a name, to change how it's saved
+    pub fn device_mut(&mut self, name: &str) -> Result<&mut Device, WldError> {
+        self.devices
+            .get_mut(name)
+            .ok_or_else(|| WldError::device_not_found(name))
+    }
+
+    /// How long to wait for the device at this address to respond: the timeout given with
+    /// `--timeout`, or else the device's own, or else the config's. `None` if none of them
+    /// is set, so the usual timeouts apply.
+    pub fn timeout_for(&self, address: &str) -> Option<Duration> {
+        let given = *TIMEOUT.lock().unwrap();
+        given
+            .or_else(|| self.device_at(address).and_then(|device| device.timeout))
+            .or(self.timeout)
+    }
+
+    /// How many times to ask the device at this address again when a request doesn't
+    /// reach it: the number given with `--retries`, or else the device's own, or else the
+    /// config's, or else `retry::RETRIES`
+    pub fn retries_for(&self, address: &str) -> u32 {
+        let given = *RETRIES.lock().unwrap();
+        given
+            .or_else(|| self.device_at(address).and_then(|device| device.retries))
+            .or(self.retries)
+            .unwrap_or(retry::RETRIES)
+    }
+
+    /// Record that the saved devices at an address answered a request at `at`, running
+    /// firmware `version` if it said. A device's last seen time only moves on once it's
+    /// `SEEN_INTERVAL` old, so using a device over and over doesn't keep changing the
+    /// config. Returns whether anything changed.
+    pub fn mark_seen(&mut self, address: &str, at: DateTime<Utc>, version: Option<&str>) -> bool {
+        let names: Vec<String> = self.names_at(address).cloned().collect();
+        let mut changed = false;
+        for name in &names {
+            let device = self.devices.get_mut(name).expect("names come from devices");
+            // Answers are saved in batches, so an older one mustn't replace a newer one
+            // saved by another process in the meantime
+            if device.last_seen.is_some_and(|last_seen| at < last_seen) {
+                continue;
+            }
+            let new_version = version.filter(|v| device.last_version.as_deref() != Some(*v));
+            let stale = device
+                .last_seen
+                .is_none_or(|last_seen| at - last_seen >= SEEN_INTERVAL);
+            if new_version.is_some() || stale {
+                device.last_seen = Some(at);
+                changed = true;
+            }
+            if let Some(version) = new_version {
+                device.last_version = Some(version.to_string());
+            }
+        }
+        changed
+    }
+
+    /// The saved device at an address, if there is one
+    pub fn device_at(&self, address: &str) -> Option<&Device> {
+        self.names_at(address)
+            .find_map(|name| self.devices.get(name))
+    }
+
+    /// Tag a device, e.g. with `outdoor` or `room=bedroom`
+    pub fn add_tags(&mut self, name: &str, tags: &[String]) -> Result<(), WldError> {
+        let device = self.device_mut(name)?;
+        device.tags.extend(tags.iter().cloned());
+        Ok(())
+    }
+
+    /// Remove tags from a device
+    pub fn remove_tags(&mut self, name: &str, tags: &[String]) -> Result<(), WldError> {
+        let device = self.device_mut(name)?;
+
+        if let Some(missing) = tags.iter().find(|tag| !device.tags.contains(*tag)) {
+            return Err(WldError::InvalidInput(format!(
+                "Device '{name}' isn't tagged '{missing}'"
+            )));
+        }
+        for tag in tags {
+            device.tags.remove(tag);
+        }
+        Ok(())
+    }
+
+    /// The names of the devices with every one of `tags`, in alphabetical order
+    pub fn tagged(&self, tags: &[String]) -> Vec<String> {
+        self.devices
+            .iter()
+            .filter(|(_, device)| tags.iter().all(|tag| device.tags.contains(tag)))
+            .map(|(name, _)| name.clone())
+            .collect()
+    }
+
+    /// The names of the saved devices with all of `tags`, failing if there aren't any
+    pub fn tagged_devices(&self, tags: &[String]) -> Result<Vec<String>, WldError> {
+        let names = self.tagged(tags);
+        if names.is_empty() {
+            return Err(WldError::InvalidInput(format!(
+                "No devices are tagged {}",
+                quote_list(tags, " and ")
+            )));
         }
+        Ok(names)
+    }
+
+    /// The devices a command should run against when it targets more than one: every
+    /// saved device with `all`, the devices with all of `tags`, or the members of a group
+    /// named with `device` or `$WLD_DEVICE`. Returns `None` when the command targets a
+    /// single device.
+    pub fn targets(
+        &self,
+        all: bool,
+        tags: &[String],
+        device: Option<&str>,
+    ) -> Result<Option<Vec<String>>, WldError> {
+        if all {
+            return Ok(Some(self.devices.keys().cloned().collect()));
+        }
+        if !tags.is_empty() {
+            return Ok(Some(self.tagged_devices(tags)?));
+        }
+
+        // A group can come from $WLD_DEVICE too
+        let device = device.map(str::to_string).or_else(device_from_env);
+        Ok(device.and_then(|device| self.group_members(&device).map(<[String]>::to_vec)))
+    }
+
+    pub fn set_default(&mut self, name: &str) -> Result<(), WldError> {
+        if !self.devices.contains_key(name) {
+            return Err(WldError::device_not_found(name));
+        }
+
+        self.default_device = Some(name.to_string());
+        Ok(())
+    }
+
+    /// Add devices to a group, creating the group if it doesn't exist yet
+    pub fn add_to_group(&mut self, group: &str, devices: &[String]) -> Result<(), WldError> {
+        if self.devices.contains_key(group) {
+            return Err(WldError::InvalidInput(format!(
+                "A device is already named '{group}', so it can't be used as a group name"
+            )));
+        }
+        if let Some(unknown) = devices.iter().find(|d| !self.devices.contains_key(*d)) {
+            return Err(WldError::device_not_found(unknown));
+        }
+
+        let members = self.groups.entry(group.to_string()).or_default();
+        for device in devices {
+            if !members.contains(device) {
+                members.push(device.clone());
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Remove devices from a group, or the whole group if no devices are given. Groups
+    /// left empty are removed too.
+    pub fn remove_from_group(&mut self, group: &str, devices: &[String]) -> Result<(), WldError> {
+        let members = self
+            .groups
+            .get_mut(group)
+            .ok_or_else(|| WldError::InvalidInput(format!("Group '{group}' not found")))?;
+
+        if devices.is_empty() {
+            self.groups.remove(group);
+            return Ok(());
+        }
+
+        if let Some(missing) = devices.iter().find(|d| !members.contains(d)) {
+            return Err(WldError::InvalidInput(format!(
+                "Device '{missing}' is not in group '{group}'"
+            )));
+        }
+        members.retain(|member| !devices.contains(member));
+        if members.is_empty() {
+            self.groups.remove(group);
+        }
+
+        Ok(())
+    }
+
+    /// Save a scene, replacing any scene with the same name. Devices must be saved devices,
+    /// so the scene still works if their IPs change.
+    pub fn save_scene(&mut self, scene: &str, devices: Vec<SceneDevice>) -> Result<(), WldError> {
+        if let Some(unknown) = devices
+            .iter()
+            .find(|d| !self.devices.contains_key(&d.device))
+        {
+            return Err(WldError::device_not_found(&unknown.device));
+        }
+
+        self.scenes.insert(scene.to_string(), devices);
+        Ok(())
+    }
+
+    pub fn remove_scene(&mut self, scene: &str) -> Result<(), WldError> {
+        self.scenes
+            .remove(scene)
+            .map(|_| ())
+            .ok_or_else(|| WldError::InvalidInput(format!("Scene '{scene}' not found")))
+    }
+
+    /// The devices in a group, if `name` is a group rather than a device
+    pub fn group_members(&self, name: &str) -> Option<&[String]> {
+        if self.devices.contains_key(name) {
+            return None;
+        }
+        self.groups.get(name).map(Vec::as_slice)
+    }
+
+    /// The saved device that a name that isn't saved is most likely meant to be: the only
+    /// device whose name starts with it, like `bed` for `bedroom`. If there's more than one,
+    /// or none but some with similar names, like `bdroom`, the error lists them. `None`
+    /// means the name is nothing like a saved one, so it may be a hostname, and IP
+    /// addresses are never matched.
+    fn match_device_name(&self, identifier: &str) -> Result<Option<String>, WldError> {
+        if address::is_ip_address(identifier) {
+            return Ok(None);
+        }
+        let identifier_lower = identifier.to_lowercase();
+
+        let prefixed: Vec<&str> = self
+            .devices
+            .keys()
+            .map(String::as_str)
+            .filter(|name| name.to_lowercase().starts_with(&identifier_lower))
+            .collect();
+        match prefixed.as_slice() {
+            [name] => return Ok(Some(name.to_string())),
+            [] => {}
+            names => {
+                return Err(WldError::InvalidInput(format!(
+                    "'{identifier}' could be more than one device: {}. Give more of the name",
+                    quote_list(names, ", ")
+                )))
+            }
+        }
+
+        let similar =
+            fuzzy::closest_matches(identifier, self.devices.keys().map(String::as_str), 3);
+        if similar.is_empty() {
+            return Ok(None);
+        }
+
+        Err(WldError::DeviceNotFound {
+            name: identifier.to_string(),
+            similar: similar.into_iter().map(str::to_string).collect(),
+            from_env: false,
+        })
+    }
+
+    /// The address of a device given by name or IP, or else the one in `$WLD_DEVICE`, or
+    /// else the default device
+    pub fn get_device_ip(&self, name_or_ip: Option<&str>) -> Result<String, WldError> {
+        self.pick_device_ip(name_or_ip, device_from_env().as_deref())
+    }
+
+    /// The address of the device given by name or IP with `--device`, or else from the
+    /// environment, or else the default device
+    fn pick_device_ip(
+        &self,
+        name_or_ip: Option<&str>,
+        from_env: Option<&str>,
+    ) -> Result<String, WldError> {
+        if let Some(identifier) = name_or_ip {
+            return self.resolve_device_ip(identifier);
+        }
+        if let Some(identifier) = from_env {
+            // Say where the device came from, since it wasn't typed in
+            return self.resolve_device_ip(identifier).map_err(|e| match e {
+                WldError::DeviceNotFound { name, similar, .. } => WldError::DeviceNotFound {
+                    name,
+                    similar,
+                    from_env: true,
+                },
+                e => WldError::InvalidInput(format!("{e} (from ${DEVICE_VARIABLE})")),
+            });
+        }
+
+        // Use default device
+        if let Some(default_name) = &self.default_device {
+            if let Some(device) = self.devices.get(default_name) {
+                return Ok(device.ip.clone());
+            }
+        }
+
+        Err(WldError::NoDefaultDevice)
+    }
+
+    /// The address of a device given by name or IP
+    fn resolve_device_ip(&self, identifier: &str) -> Result<String, WldError> {
+        // Check if it's a device name
+        if let Some(device) = self.devices.get(identifier) {
+            return Ok(device.ip.clone());
+        }
+        if self.group_members(identifier).is_some() {
+            return Err(WldError::InvalidInput(format!(
+                "'{identifier}' is a group, but this command only works with a single device"
+            )));
+        }
+        if let Some(name) = self.match_device_name(identifier)? {
+            return Ok(self.devices[&name].ip.clone());
+        }
+        // Otherwise treat it as an IP address or hostname
+        Ok(identifier.to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    // Helper function to create a temporary config file path
+    fn temp_config_path() -> PathBuf {
+        let temp_dir = std::env::temp_dir();
+        let unique_name = format!("wld_test_config_{}.toml", std::process::id());
+        temp_dir.join(unique_name)
+    }
+
+    // Helper function to clean up temporary config file
+    fn cleanup_config(path: &PathBuf) {
+        if path.exists() {
+            let _ = fs::remove_file(path);
+        }
+    }
+
+    #[test]
+    fn test_new_config() {
+        let config = Config::new();
+        assert!(config.devices.is_empty());
+        assert!(config.default_device.is_none());
+    }
+
+    #[test]
+    fn test_add_device() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        assert_eq!(config.devices.len(), 1);
+        assert_eq!(
+            config.devices.get("living_room").map(|d| d.ip.as_str()),
+            Some("192.168.1.100")
+        );
+        assert_eq!(config.default_device, Some("living_room".to_string()));
+    }
+
+    #[test]
+    fn test_add_device_outcome() {
+        let mut config = Config::new();
+
+        assert_eq!(
+            config.add_device("bedroom".to_string(), "192.168.1.100".to_string()),
+            AddOutcome::Added { duplicate_of: None }
+        );
+        assert_eq!(
+            config.add_device("bedroom".to_string(), "192.168.1.100".to_string()),
+            AddOutcome::Unchanged
+        );
+        assert_eq!(
+            config.add_device("bedroom".to_string(), "192.168.1.101".to_string()),
+            AddOutcome::Replaced {
+                old: "192.168.1.100".to_string(),
+                duplicate_of: None
+            }
+        );
+        assert_eq!(
+            config.add_device("lamp".to_string(), "192.168.1.101".to_string()),
+            AddOutcome::Added {
+                duplicate_of: Some("bedroom".to_string())
+            }
+        );
+        assert_eq!(config.devices["bedroom"].ip, "192.168.1.101");
+    }
+
+    #[test]
+    fn test_add_multiple_devices() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+
+        assert_eq!(config.devices.len(), 2);
+        // First device should remain default
+        assert_eq!(config.default_device, Some("living_room".to_string()));
+    }
+
+    #[test]
+    fn test_remove_device() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+
+        let result = config.remove_device("living_room");
+        assert!(result.is_ok());
+        assert_eq!(config.devices.len(), 1);
+        // bedroom should become the new default
+        assert_eq!(config.default_device, Some("bedroom".to_string()));
+    }
+
+    #[test]
+    fn test_remove_default_device_picks_first_alphabetically() {
+        let mut config = Config::new();
+        for name in ["porch", "kitchen", "attic", "office", "bedroom"] {
+            config.add_device(name.to_string(), format!("wled-{name}.local"));
+        }
+        assert_eq!(config.default_device, Some("porch".to_string()));
+
+        config.remove_device("porch").unwrap();
+        assert_eq!(config.default_device, Some("attic".to_string()));
+        config.remove_device("attic").unwrap();
+        assert_eq!(config.default_device, Some("bedroom".to_string()));
+    }
+
+    #[test]
+    fn test_serialization_is_stable() {
+        let mut config = Config::new();
+        for name in ["porch", "kitchen", "attic", "office", "bedroom"] {
+            config.add_device(name.to_string(), format!("wled-{name}.local"));
+        }
+        config
+            .add_to_group("upstairs", &["office".to_string(), "attic".to_string()])
+            .unwrap();
+        config
+            .add_to_group("outside", &["porch".to_string()])
+            .unwrap();
+
+        let content = toml::to_string_pretty(&config).unwrap();
+        let order: Vec<&str> = content
+            .lines()
+            .filter_map(|line| line.strip_prefix("[devices."))
+            .collect();
+        assert_eq!(
+            order,
+            vec!["attic]", "bedroom]", "kitchen]", "office]", "porch]"]
+        );
+        assert!(content.find("outside").unwrap() < content.find("upstairs").unwrap());
+
+        // Loading and saving again doesn't change anything
+        let reloaded = Config::parse(&content).unwrap();
+        assert_eq!(toml::to_string_pretty(&reloaded).unwrap(), content);
+    }
+
+    #[test]
+    fn test_remove_nonexistent_device() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        let error = config.remove_device("kitchen").unwrap_err();
+        assert!(matches!(&error, WldError::DeviceNotFound { name, .. } if name == "kitchen"));
+        assert_eq!(error.to_string(), "Device 'kitchen' not found");
+    }
+
+    #[test]
+    fn test_remove_last_device() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        let result = config.remove_device("living_room");
+        assert!(result.is_ok());
+        assert!(config.devices.is_empty());
+        assert!(config.default_device.is_none());
+    }
+
+    #[test]
+    fn test_set_default() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+
+        let result = config.set_default("bedroom");
+        assert!(result.is_ok());
+        assert_eq!(config.default_device, Some("bedroom".to_string()));
+    }
+
+    #[test]
+    fn test_set_default_nonexistent_device() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        let result = config.set_default("kitchen");
+        assert!(result.is_err());
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Device 'kitchen' not found"
+        );
+    }
+
+    #[test]
+    fn test_get_device_ip_by_name() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        let result = config.get_device_ip(Some("living_room"));
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "192.168.1.100");
+    }
+
+    #[test]
+    fn test_get_device_ip_by_direct_ip() {
+        let config = Config::new();
+
+        let result = config.get_device_ip(Some("192.168.1.200"));
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "192.168.1.200");
+    }
+
+    #[test]
+    fn test_get_device_ip_default() {
+        let mut config = Config::new();
+        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+
+        let result = config.get_device_ip(None);
+        assert!(result.is_ok());
+        assert_eq!(result.unwrap(), "192.168.1.100");
+    }
+
+    #[test]
+    fn test_get_device_ip_no_default() {
+        let config = Config::new();
+
+        let error = config.get_device_ip(None).unwrap_err();
+        assert!(matches!(error, WldError::NoDefaultDevice));
+        assert_eq!(
+            error.to_string(),
+            "No device specified and no default device set"
+        );
+    }
+
+    fn config_with_group() -> Config {
+        let mut config = Config::new();
+        config.add_device("kitchen".to_string(), "192.168.1.100".to_string());
+        config.add_device("lounge".to_string(), "192.168.1.101".to_string());
+        config.add_device("porch".to_string(), "192.168.1.102".to_string());
+        config
+            .add_to_group("downstairs", &["kitchen".to_string(), "lounge".to_string()])
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn test_add_to_group() {
+        let mut config = config_with_group();
+        assert_eq!(
+            config.group_members("downstairs"),
+            Some(&["kitchen".to_string(), "lounge".to_string()][..])
+        );
+
+        // Adding a device that's already a member doesn't duplicate it
+        config
+            .add_to_group("downstairs", &["lounge".to_string(), "porch".to_string()])
+            .unwrap();
+        assert_eq!(
+            config.groups["downstairs"],
+            vec!["kitchen", "lounge", "porch"]
+        );
+    }
+
+    #[test]
+    fn test_add_to_group_validates_devices() {
+        let mut config = config_with_group();
+
+        let result = config.add_to_group("outdoor", &["porch".to_string(), "shed".to_string()]);
+        assert_eq!(result.unwrap_err().to_string(), "Device 'shed' not found");
+        assert!(!config.groups.contains_key("outdoor"));
+
+        let result = config.add_to_group("porch", &["kitchen".to_string()]);
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("A device is already named 'porch'"));
+    }
+
+    #[test]
+    fn test_remove_from_group() {
+        let mut config = config_with_group();
+
+        config
+            .remove_from_group("downstairs", &["kitchen".to_string()])
+            .unwrap();
+        assert_eq!(config.groups["downstairs"], vec!["lounge"]);
+
+        let result = config.remove_from_group("downstairs", &["porch".to_string()]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Device 'porch' is not in group 'downstairs'"
+        );
+
+        // Removing the last member removes the group
+        config
+            .remove_from_group("downstairs", &["lounge".to_string()])
+            .unwrap();
+        assert!(config.groups.is_empty());
+    }
+
+    #[test]
+    fn test_remove_whole_group() {
+        let mut config = config_with_group();
+
+        config.remove_from_group("downstairs", &[]).unwrap();
+        assert!(config.groups.is_empty());
+
+        let result = config.remove_from_group("downstairs", &[]);
+        assert_eq!(
+            result.unwrap_err().to_string(),
+            "Group 'downstairs' not found"
+        );
+    }
+
+    #[test]
+    fn test_remove_device_removes_it_from_groups() {
+        let mut config = config_with_group();
+
+        config.remove_device("kitchen").unwrap();
+        assert_eq!(config.groups["downstairs"], vec!["lounge"]);
+
+        config.remove_device("lounge").unwrap();
+        assert!(config.groups.is_empty());
+    }
+
+    #[test]
+    fn test_get_device_ip_by_prefix() {
+        let mut config = Config::new();
+        config.add_device("bedroom".to_string(), "192.168.1.100".to_string());
+        config.add_device("bed".to_string(), "192.168.1.101".to_string());
+        config.add_device("kitchen".to_string(), "192.168.1.102".to_string());
+
+        assert_eq!(config.get_device_ip(Some("kit")).unwrap(), "192.168.1.102");
+        assert_eq!(config.get_device_ip(Some("KIT")).unwrap(), "192.168.1.102");
+        // Exact matches win over prefixes
+        assert_eq!(config.get_device_ip(Some("bed")).unwrap(), "192.168.1.101");
+        assert_eq!(
+            config.get_device_ip(Some("be")).unwrap_err().to_string(),
+            "'be' could be more than one device: 'bed', 'bedroom'. Give more of the name"
+        );
     }
 
-    pub fn load() -> Result<Self, Box<dyn std::error::Error>> {
-        let config_path = Self::config_path()?;
+    #[test]
+    fn test_get_device_ip_suggests_similar_names() {
+        let mut config = Config::new();
+        config.add_device("bedroom".to_string(), "192.168.1.100".to_string());
+        config.add_device("kitchen".to_string(), "192.168.1.102".to_string());
+        config.add_device("kitchen2".to_string(), "192.168.1.103".to_string());
 
-        if !config_path.exists() {
-            return Ok(Self::new());
-        }
+        assert_eq!(
+            config
+                .get_device_ip(Some("bdroom"))
+                .unwrap_err()
+                .to_string(),
+            "Device 'bdroom' not found, did you mean 'bedroom'?"
+        );
+        assert_eq!(
+            config
+                .get_device_ip(Some("kitchn"))
+                .unwrap_err()
+                .to_string(),
+            "Device 'kitchn' not found, did you mean 'kitchen' or 'kitchen2'?"
+        );
 
-        let content = fs::read_to_string(&config_path)?;
-        let config: Config = toml::from_str(&content)?;
-        Ok(config)
+        // Anything else is taken as an address
+        assert_eq!(
+            config.get_device_ip(Some("wled-porch.local")).unwrap(),
+            "wled-porch.local"
+        );
+        assert_eq!(
+            config.get_device_ip(Some("192.168.1.10")).unwrap(),
+            "192.168.1.10"
+        );
     }
 
-    pub fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let config_path = Self::config_path()?;
-
-        // Ensure parent directory exists
-        if let Some(parent) = config_path.parent() {
-            fs::create_dir_all(parent)?;
-        }
+    #[test]
+    fn test_ip_addresses_are_never_matched() {
+        let mut config = Config::new();
+        config.add_device("192.168.1.100".to_string(), "192.168.1.100".to_string());
 
-        let content = toml::to_string_pretty(&self)?;
-        fs::write(&config_path, content)?;
-        Ok(())
+        // Similar to the name, and a prefix of it, but an address
+        assert_eq!(
+            config.get_device_ip(Some("192.168.1.10")).unwrap(),
+            "192.168.1.10"
+        );
     }
 
-    pub fn config_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
-        let base_dirs = BaseDirs::new().ok_or("Could not find home directory")?;
-        Ok(base_dirs.home_dir().join(".wld.toml"))
+    #[test]
+    fn test_pick_device_ip_from_env() {
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.add_device("office".to_string(), "192.168.1.41".to_string());
+
+        // --device, then the environment, then the default device
+        assert_eq!(
+            config
+                .pick_device_ip(Some("porch"), Some("office"))
+                .unwrap(),
+            "192.168.1.40"
+        );
+        assert_eq!(
+            config.pick_device_ip(None, Some("office")).unwrap(),
+            "192.168.1.41"
+        );
+        assert_eq!(
+            config.pick_device_ip(None, Some("192.168.1.99")).unwrap(),
+            "192.168.1.99"
+        );
+        assert_eq!(config.pick_device_ip(None, None).unwrap(), "192.168.1.40");
+
+        let error = config.pick_device_ip(None, Some("ofice")).unwrap_err();
+        assert!(matches!(
+            error,
+            WldError::DeviceNotFound { from_env: true, .. }
+        ));
+        assert_eq!(
+            error.to_string(),
+            "Device 'ofice' not found, did you mean 'office'? (from $WLD_DEVICE)"
+        );
     }
 
-    pub fn add_device(&mut self, name: String, ip: String) {
-        self.devices.insert(name.clone(), ip);
+    #[test]
+    fn test_get_device_ip_rejects_group() {
+        let config = config_with_group();
 
-        // If this is the first device, make it default
-        if self.devices.len() == 1 {
-            self.default_device = Some(name);
-        }
+        let result = config.get_device_ip(Some("downstairs"));
+        assert!(result
+            .unwrap_err()
+            .to_string()
+            .contains("'downstairs' is a group"));
     }
 
-    pub fn remove_device(&mut self, name: &str) -> Result<(), String> {
-        if !self.devices.contains_key(name) {
-            return Err(format!("Device '{name}' not found"));
+    fn scene_device(device: &str) -> SceneDevice {
+        SceneDevice {
+            device: device.to_string(),
+            on: Some(true),
+            brightness: Some(51),
+            color: Some("#FFB464".to_string()),
+            effect: None,
+            palette: None,
         }
+    }
 
-        self.devices.remove(name);
+    #[test]
+    fn test_save_and_remove_scene() {
+        let mut config = config_with_group();
+        config
+            .save_scene("movie night", vec![scene_device("kitchen")])
+            .unwrap();
+        assert_eq!(config.scenes["movie night"], vec![scene_device("kitchen")]);
 
-        // Clear default if we removed the default device
-        if self.default_device.as_deref() == Some(name) {
-            self.default_device = None;
+        assert_eq!(
+            config
+                .save_scene("party", vec![scene_device("garage")])
+                .unwrap_err()
+                .to_string(),
+            "Device 'garage' not found"
+        );
 
-            // If there's another device, make it the default
-            if let Some(first_name) = self.devices.keys().next() {
-                self.default_device = Some(first_name.clone());
-            }
-        }
+        config.remove_scene("movie night").unwrap();
+        assert!(config.scenes.is_empty());
+        assert_eq!(
+            config.remove_scene("movie night").unwrap_err().to_string(),
+            "Scene 'movie night' not found"
+        );
+    }
 
-        Ok(())
+    #[test]
+    fn test_remove_device_removes_it_from_scenes() {
+        let mut config = config_with_group();
+        config
+            .save_scene("movie night", vec![scene_device("kitchen")])
+            .unwrap();
+
+        config.remove_device("kitchen").unwrap();
+        assert!(config.scenes.is_empty());
     }
 
-    pub fn set_default(&mut self, name: &str) -> Result<(), String> {
-        if !self.devices.contains_key(name) {
-            return Err(format!("Device '{name}' not found"));
-        }
+    #[test]
+    fn test_scenes_round_trip_through_toml() {
+        let mut config = config_with_group();
+        config
+            .save_scene("movie night", vec![scene_device("kitchen")])
+            .unwrap();
 
-        self.default_device = Some(name.to_string());
-        Ok(())
+        let loaded: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(loaded.scenes, config.scenes);
     }
 
-    pub fn get_device_ip(&self, name_or_ip: Option<&str>) -> Result<String, String> {
-        // If specific name/IP provided, use it
-        if let Some(identifier) = name_or_ip {
-            // Check if it's a device name
-            if let Some(ip) = self.devices.get(identifier) {
-                return Ok(ip.clone());
-            }
-            // Otherwise treat it as an IP address
-            return Ok(identifier.to_string());
-        }
+    #[test]
+    fn test_insecure_devices() {
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "https://wled.example.com".to_string());
+        config.add_device("kitchen".to_string(), "192.168.1.100".to_string());
+        config.set_insecure("porch", true);
 
-        // Use default device
-        if let Some(default_name) = &self.default_device {
-            if let Some(ip) = self.devices.get(default_name) {
-                return Ok(ip.clone());
-            }
-        }
+        assert!(config.is_insecure("https://wled.example.com"));
+        assert!(!config.is_insecure("192.168.1.100"));
 
-        Err("No device specified and no default device set".to_string())
+        config.remove_device("porch").unwrap();
+        assert!(config.insecure.is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
-    use std::path::PathBuf;
+    #[test]
+    fn test_credentials() {
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        let credentials = Credentials {
+            username: Some("admin".to_string()),
+            password: Some("hunter2".to_string()),
+            pin: Some("1234".to_string()),
+        };
+        config.set_credentials("porch", credentials.clone());
 
-    // Helper function to create a temporary config file path
-    fn temp_config_path() -> PathBuf {
-        let temp_dir = std::env::temp_dir();
-        let unique_name = format!("wld_test_config_{}.toml", std::process::id());
-        temp_dir.join(unique_name)
+        assert_eq!(config.credentials_for("192.168.1.40"), Some(&credentials));
+        assert_eq!(config.credentials_for("192.168.1.41"), None);
+
+        // Adding the device again without credentials forgets them
+        config.set_credentials("porch", Credentials::default());
+        assert!(config.credentials.is_empty());
+
+        config.set_credentials("porch", credentials);
+        config.remove_device("porch").unwrap();
+        assert!(config.credentials.is_empty());
     }
 
-    // Helper function to clean up temporary config file
-    fn cleanup_config(path: &PathBuf) {
-        if path.exists() {
-            let _ = fs::remove_file(path);
+    #[test]
+    fn test_parse_pin() {
+        assert_eq!(parse_pin("0420"), Ok("0420".to_string()));
+        for value in ["", "123", "12345", "12a4"] {
+            assert!(parse_pin(value).is_err(), "{value}");
         }
     }
 
     #[test]
-    fn test_new_config() {
-        let config = Config::new();
-        assert!(config.devices.is_empty());
-        assert!(config.default_device.is_none());
+    fn test_tags() {
+        let mut config = config_with_group();
+        config
+            .add_tags(
+                "kitchen",
+                &["room=kitchen".to_string(), "indoor".to_string()],
+            )
+            .unwrap();
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
+        config.add_tags("lounge", &["indoor".to_string()]).unwrap();
+
+        assert_eq!(
+            config.tagged(&["indoor".to_string()]),
+            vec!["kitchen", "lounge"]
+        );
+        // Several tags pick out the devices with all of them
+        assert_eq!(
+            config.tagged(&["indoor".to_string(), "room=kitchen".to_string()]),
+            vec!["kitchen"]
+        );
+        assert_eq!(config.tagged(&[]), vec!["kitchen", "lounge", "porch"]);
+
+        config
+            .remove_tags("kitchen", &["indoor".to_string()])
+            .unwrap();
+        assert_eq!(config.tagged(&["indoor".to_string()]), vec!["lounge"]);
+        assert_eq!(
+            config
+                .remove_tags("kitchen", &["outdoor".to_string()])
+                .unwrap_err()
+                .to_string(),
+            "Device 'kitchen' isn't tagged 'outdoor'"
+        );
+        assert_eq!(
+            config
+                .add_tags("shed", &["outdoor".to_string()])
+                .unwrap_err()
+                .to_string(),
+            "Device 'shed' not found"
+        );
     }
 
     #[test]
-    fn test_add_device() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+    fn test_adding_device_again_keeps_tags() {
+        let mut config = config_with_group();
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
+        config.add_device("porch".to_string(), "192.168.1.200".to_string());
+
+        assert_eq!(config.tagged(&["outdoor".to_string()]), vec!["porch"]);
+    }
+
+    #[test]
+    fn test_tagged_devices_round_trip_through_toml() {
+        let mut config = config_with_group();
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
+
+        let content = toml::to_string_pretty(&config).unwrap();
+
+        let loaded: Config = toml::from_str(&content).unwrap();
+        assert_eq!(loaded.devices, config.devices);
+    }
+
+    #[test]
+    fn test_targets() {
+        let mut config = config_with_group();
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
+        let names = |names: &[&str]| Some(names.iter().map(|n| n.to_string()).collect());
 
-        assert_eq!(config.devices.len(), 1);
         assert_eq!(
-            config.devices.get("living_room"),
-            Some(&"192.168.1.100".to_string())
+            config.targets(true, &[], None).unwrap(),
+            names(&["kitchen", "lounge", "porch"])
+        );
+        assert_eq!(
+            config
+                .targets(false, &["outdoor".to_string()], None)
+                .unwrap(),
+            names(&["porch"])
+        );
+        assert_eq!(
+            config.targets(false, &[], Some("downstairs")).unwrap(),
+            names(&["kitchen", "lounge"])
+        );
+        assert_eq!(config.targets(false, &[], Some("porch")).unwrap(), None);
+        assert_eq!(
+            config
+                .targets(false, &["indoor".to_string()], None)
+                .unwrap_err()
+                .to_string(),
+            "No devices are tagged 'indoor'"
         );
-        assert_eq!(config.default_device, Some("living_room".to_string()));
     }
 
     #[test]
-    fn test_add_multiple_devices() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
-        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+    fn test_device_defaults_round_trip_through_toml() {
+        let mut config = config_with_group();
+        config.device_mut("porch").unwrap().default_preset = Some(2);
+        config.device_mut("kitchen").unwrap().default_brightness = Some(200);
 
-        assert_eq!(config.devices.len(), 2);
-        // First device should remain default
-        assert_eq!(config.default_device, Some("living_room".to_string()));
+        let loaded: Config = toml::from_str(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(loaded.devices, config.devices);
+        assert_eq!(
+            loaded
+                .device_at("192.168.1.100")
+                .unwrap()
+                .default_brightness,
+            Some(200)
+        );
     }
 
     #[test]
-    fn test_remove_device() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
-        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+    fn test_parse_tag() {
+        assert_eq!(parse_tag("room=bedroom"), Ok("room=bedroom".to_string()));
+        for value in ["", "front porch", "a,b"] {
+            assert!(parse_tag(value).is_err(), "{value}");
+        }
+    }
 
-        let result = config.remove_device("living_room");
-        assert!(result.is_ok());
-        assert_eq!(config.devices.len(), 1);
-        // bedroom should become the new default
-        assert_eq!(config.default_device, Some("bedroom".to_string()));
+    #[test]
+    fn test_config_without_groups_loads() {
+        let config = Config::parse(
+            "default_device = \"kitchen\"\n\n[devices]\nkitchen = \"192.168.1.100\"\n",
+        )
+        .unwrap();
+        assert!(config.groups.is_empty());
     }
 
     #[test]
-    fn test_remove_nonexistent_device() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+    fn test_parse_unversioned_config_with_addresses() {
+        // Before configs had a version, devices were saved as just their address
+        let config = Config::parse(
+            "default_device = \"kitchen\"\n\n[devices]\nkitchen = \"192.168.1.100\"\nporch = \"wled-porch.local:8080\"\n",
+        )
+        .unwrap();
 
-        let result = config.remove_device("kitchen");
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Device 'kitchen' not found");
+        assert_eq!(config.version, VERSION);
+        assert_eq!(
+            config.devices.get("porch"),
+            Some(&Device::new("wled-porch.local:8080".to_string()))
+        );
+
+        // and are saved as tables from then on
+        let content = toml::to_string_pretty(&config).unwrap();
+        assert!(content.starts_with(&format!("version = {VERSION}\n")));
+        assert!(content.contains("[devices.kitchen]\nip = \"192.168.1.100\""));
+        let loaded = Config::parse(&content).unwrap();
+        assert_eq!(loaded.devices, config.devices);
+        assert_eq!(loaded.default_device, config.default_device);
     }
 
     #[test]
-    fn test_remove_last_device() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+    fn test_parse_unversioned_config_with_tables() {
+        // Devices with tags or defaults were already tables, mixed with bare addresses
+        let config = Config::parse(
+            "[devices]\nkitchen = \"192.168.1.100\"\n\n[devices.porch]\nip = \"192.168.1.40\"\ntags = [\"outdoor\"]\ndefault_brightness = 200\n",
+        )
+        .unwrap();
 
-        let result = config.remove_device("living_room");
-        assert!(result.is_ok());
-        assert!(config.devices.is_empty());
-        assert!(config.default_device.is_none());
+        assert_eq!(config.tagged(&["outdoor".to_string()]), vec!["porch"]);
+        assert_eq!(
+            config.devices.get("porch").unwrap().default_brightness,
+            Some(200)
+        );
+        assert_eq!(
+            config.devices.get("kitchen"),
+            Some(&Device::new("192.168.1.100".to_string()))
+        );
+
+        let loaded = Config::parse(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(loaded.devices, config.devices);
     }
 
     #[test]
-    fn test_set_default() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
-        config.add_device("bedroom".to_string(), "192.168.1.101".to_string());
+    fn test_parse_current_config() {
+        let mut config = config_with_group();
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
 
-        let result = config.set_default("bedroom");
-        assert!(result.is_ok());
-        assert_eq!(config.default_device, Some("bedroom".to_string()));
+        let loaded = Config::parse(&toml::to_string_pretty(&config).unwrap()).unwrap();
+        assert_eq!(loaded.devices, config.devices);
+        assert_eq!(loaded.groups, config.groups);
     }
 
     #[test]
-    fn test_set_default_nonexistent_device() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+    fn test_parse_config_from_newer_version() {
+        let error =
+            Config::parse(&format!("version = {}\n\n[devices]\n", VERSION + 1)).unwrap_err();
+        assert!(
+            error.contains("written by a newer version of wld"),
+            "{error}"
+        );
+        assert!(error.contains("Upgrade wld"), "{error}");
 
-        let result = config.set_default("kitchen");
-        assert!(result.is_err());
-        assert_eq!(result.unwrap_err(), "Device 'kitchen' not found");
+        assert!(Config::parse("version = \"one\"\n\n[devices]\n").is_err());
     }
 
     #[test]
-    fn test_get_device_ip_by_name() {
-        let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+    fn test_parse_invalid_config() {
+        let error = Config::parse("[devices]\nporch = \"192.168.1.40\"\nkitchen =\n").unwrap_err();
+        assert!(
+            error.starts_with("it isn't valid TOML at line 3: "),
+            "{error}"
+        );
+        assert!(error.ends_with("move it out of the way to start with an empty config"));
 
-        let result = config.get_device_ip(Some("living_room"));
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "192.168.1.100");
+        let error = Config::parse("[devices]\nporch = 40\n").unwrap_err();
+        assert!(error.ends_with("move it out of the way to start with an empty config"));
     }
 
     #[test]
-    fn test_get_device_ip_by_direct_ip() {
-        let config = Config::new();
+    fn test_timeouts() {
+        let config = Config::parse(
+            "version = 1\ntimeout = \"2s\"\n\n[devices.porch]\nip = \"192.168.1.40\"\ntimeout = 10\n\n[devices.kitchen]\nip = \"192.168.1.100\"\n",
+        )
+        .unwrap();
+        assert_eq!(
+            config.timeout_for("192.168.1.40"),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            config.timeout_for("192.168.1.100"),
+            Some(Duration::from_secs(2))
+        );
+        assert_eq!(Config::new().timeout_for("192.168.1.40"), None);
 
-        let result = config.get_device_ip(Some("192.168.1.200"));
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "192.168.1.200");
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(saved.contains("timeout = \"10s\""), "{saved}");
+        assert_eq!(Config::parse(&saved).unwrap().devices, config.devices);
+
+        let error = Config::parse("version = 1\ntimeout = \"0s\"\n\n[devices]\n").unwrap_err();
+        assert!(error.contains("Timeout must be at least 1s"), "{error}");
     }
 
     #[test]
-    fn test_get_device_ip_default() {
+    fn test_retries() {
+        let config = Config::parse(
+            "version = 1\nretries = 2\n\n[devices.porch]\nip = \"192.168.1.40\"\nretries = 0\n\n[devices.kitchen]\nip = \"192.168.1.100\"\n",
+        )
+        .unwrap();
+        assert_eq!(config.retries_for("192.168.1.40"), 0);
+        assert_eq!(config.retries_for("192.168.1.100"), 2);
+        assert_eq!(Config::new().retries_for("192.168.1.40"), retry::RETRIES);
+    }
+
+    #[test]
+    fn test_mark_seen() {
         let mut config = Config::new();
-        config.add_device("living_room".to_string(), "192.168.1.100".to_string());
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.add_device("kitchen".to_string(), "192.168.1.100".to_string());
+        let at = |time: &str| time.parse::<DateTime<Utc>>().unwrap();
 
-        let result = config.get_device_ip(None);
-        assert!(result.is_ok());
-        assert_eq!(result.unwrap(), "192.168.1.100");
+        assert!(config.mark_seen("192.168.1.40", at("2026-10-15T08:00:00Z"), Some("0.15.0")));
+        assert!(config.mark_seen("192.168.1.40", at("2026-10-15T09:00:00Z"), None));
+        let porch = &config.devices["porch"];
+        assert_eq!(porch.last_seen, Some(at("2026-10-15T09:00:00Z")));
+        assert_eq!(porch.last_version.as_deref(), Some("0.15.0"));
+        assert_eq!(config.devices["kitchen"].last_seen, None);
+
+        // Answers soon after the last one saved change nothing, unless the firmware has
+        assert!(!config.mark_seen("192.168.1.40", at("2026-10-15T09:30:00Z"), None));
+        assert!(!config.mark_seen("192.168.1.40", at("2026-10-15T09:30:00Z"), Some("0.15.0")));
+        assert_eq!(
+            config.devices["porch"].last_seen,
+            Some(at("2026-10-15T09:00:00Z"))
+        );
+        assert!(config.mark_seen("192.168.1.40", at("2026-10-15T09:30:00Z"), Some("0.15.1")));
+
+        // An answer from before the last one saved is ignored
+        assert!(!config.mark_seen("192.168.1.40", at("2026-10-15T07:00:00Z"), Some("0.14.0")));
+        assert_eq!(
+            config.devices["porch"].last_seen,
+            Some(at("2026-10-15T09:30:00Z"))
+        );
+        assert_eq!(
+            config.devices["porch"].last_version.as_deref(),
+            Some("0.15.1")
+        );
+
+        // Devices that aren't saved aren't tracked
+        assert!(!config.mark_seen("10.0.0.2", at("2026-10-15T09:00:00Z"), None));
+
+        let saved = toml::to_string_pretty(&config).unwrap();
+        assert!(
+            saved.contains("last_seen = \"2026-10-15T09:30:00Z\""),
+            "{saved}"
+        );
+        assert_eq!(Config::parse(&saved).unwrap().devices, config.devices);
     }
 
     #[test]
-    fn test_get_device_ip_no_default() {
-        let config = Config::new();
+    fn test_save_to_replaces_file() {
+        let dir = std::env::temp_dir().join(format!("wld_test_save_to_{}", std::process::id()));
+        let path = dir.join("config.toml");
+        let _ = fs::remove_dir_all(&dir);
 
-        let result = config.get_device_ip(None);
-        assert!(result.is_err());
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.save_to(&path).unwrap();
+        config.add_device("kitchen".to_string(), "192.168.1.100".to_string());
+        config.save_to(&path).unwrap();
+
+        assert_eq!(Config::load_from(&path).unwrap().devices, config.devices);
+        // Nothing is left behind from writing it
+        let files: Vec<_> = fs::read_dir(&dir).unwrap().collect();
+        assert_eq!(files.len(), 1);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_parse_profile() {
+        assert_eq!(parse_profile("work"), Ok("work".to_string()));
         assert_eq!(
-            result.unwrap_err(),
-            "No device specified and no default device set"
+            parse_profile("client_2-demo"),
+            Ok("client_2-demo".to_string())
         );
+        for value in ["", "config", "../work", "my work", "work.toml"] {
+            assert!(parse_profile(value).is_err(), "{value}");
+        }
+    }
+
+    #[test]
+    fn test_template_is_valid() {
+        let config = Config::parse(&Config::template()).unwrap();
+        assert!(config.devices.is_empty());
+        assert!(config.default_device.is_none());
+    }
+
+    #[test]
+    fn test_save_writes_current_version() {
+        let path = std::env::temp_dir().join(format!(
+            "wld_test_config_version_{}.toml",
+            std::process::id()
+        ));
+        let mut config = Config::new();
+        config.version = 0;
+        config.save_to(&path).unwrap();
+
+        let content = fs::read_to_string(&path).unwrap();
+        assert!(content.starts_with(&format!("version = {VERSION}\n")));
+
+        let _ = fs::remove_file(&path);
     }
 
     #[test]
@@ -274,12 +2110,15 @@ mod tests {
 
         assert_eq!(loaded_config.devices.len(), 2);
         assert_eq!(
-            loaded_config.devices.get("living_room"),
-            Some(&"192.168.1.100".to_string())
+            loaded_config
+                .devices
+                .get("living_room")
+                .map(|d| d.ip.as_str()),
+            Some("192.168.1.100")
         );
         assert_eq!(
-            loaded_config.devices.get("bedroom"),
-            Some(&"192.168.1.101".to_string())
+            loaded_config.devices.get("bedroom").map(|d| d.ip.as_str()),
+            Some("192.168.1.101")
         );
         assert_eq!(
             loaded_config.default_device,
@@ -289,6 +2128,60 @@ mod tests {
         cleanup_config(&config_path);
     }
 
+    #[test]
+    fn test_load_from_and_save_to_missing_file() {
+        let dir = std::env::temp_dir().join(format!("wld_test_config_dir_{}", std::process::id()));
+        let path = dir.join("nested").join("wld.toml");
+        let _ = fs::remove_dir_all(&dir);
+
+        // A file that doesn't exist yet reads as an empty config, and saving creates it
+        let mut config = Config::load_from(&path).unwrap();
+        assert!(config.devices.is_empty());
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.save_to(&path).unwrap();
+
+        let loaded = Config::load_from(&path).unwrap();
+        assert_eq!(loaded.devices, config.devices);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_migrate() {
+        let dir = std::env::temp_dir().join(format!("wld_test_migrate_{}", std::process::id()));
+        let old_path = dir.join(".wld.toml");
+        let path = dir.join(".config").join("wld").join("config.toml");
+        let migrated = dir.join(".wld.toml.migrated");
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+
+        // A fresh install has nothing to move
+        assert!(!Config::migrate(&old_path, &path).unwrap());
+        assert!(!path.exists());
+
+        // An old config is moved, and the old file renamed
+        let mut old = Config::new();
+        old.add_device("porch".to_string(), "192.168.1.40".to_string());
+        old.save_to(&old_path).unwrap();
+        assert!(Config::migrate(&old_path, &path).unwrap());
+        assert!(!old_path.exists());
+        assert!(migrated.exists());
+        assert_eq!(Config::load_from(&path).unwrap().devices, old.devices);
+
+        // Once moved, there's nothing more to do
+        assert!(!Config::migrate(&old_path, &path).unwrap());
+
+        // When both exist, the new config wins and the old one is left alone
+        let mut stale = Config::new();
+        stale.add_device("tv".to_string(), "192.168.1.50".to_string());
+        stale.save_to(&old_path).unwrap();
+        assert!(!Config::migrate(&old_path, &path).unwrap());
+        assert!(old_path.exists());
+        assert_eq!(Config::load_from(&path).unwrap().devices, old.devices);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
     #[test]
     fn test_config_serialization() {
         let mut config = Config::new();
@@ -0,0 +1,1695 @@
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
+
+use wled_json_api_library::structures::state::{Nl, Seg, State, Udpn};
+use wled_json_api_library::wled::Wled;
+
+use crate::brightness::BrightnessValue;
+use crate::catalog::{self, Selector};
+use crate::color::Rgb;
+use crate::config::Config;
+use crate::error::{AtDevice, WldError};
+use crate::scene::SceneDevice;
+use crate::segment::{self, Orientation, SegmentChange};
+use crate::{
+    address, api, backup, clone, duration, fade, identify, interrupt, nightlight, random, retry,
+    sunrise,
+};
+
+/// The settings for requests to the device at an address, from how it was saved
+pub fn client_options(config: &Config, ip: &str) -> api::ClientOptions {
+    let credentials = config.credentials_for(ip);
+
+    api::ClientOptions {
+        insecure: config.is_insecure(ip),
+        timeout: config.timeout_for(ip),
+        login: credentials
+            .and_then(|c| c.username.clone())
+            .map(|username| {
+                let password = credentials.and_then(|c| c.password.clone());
+                (username, password.unwrap_or_default())
+            }),
+    }
+}
+
+/// Check the device at an address answers `json/info` like a WLED device, using the
+/// settings saved for it in `config`. The error says what's wrong, following "device at
+/// {ip}".
+pub fn verify_device(config: &Config, ip: &str) -> Result<api::Info, String> {
+    let options = client_options(config, ip);
+    let info = api::probe_info(ip, options.timeout(), &options)
+        .map_err(|e| format!("didn't respond like a WLED device: {e}"))?;
+
+    match info.brand.as_deref() {
+        Some("WLED") => Ok(info),
+        _ => Err("responded, but doesn't look like a WLED device".to_string()),
+    }
+}
+
+/// Fail with a clear error if the device at an IP address turned down a request's login
+fn check_authorized(ip: &str, response: &reqwest::blocking::Response) -> Result<(), WldError> {
+    if response.status() == reqwest::StatusCode::UNAUTHORIZED {
+        return Err(WldError::status(ip, response.status()));
+    }
+    Ok(())
+}
+
+/// Connect to the device at an IP address, checking it responds like
+/// `Wled::try_from_url` does but with the shared HTTP client
+pub fn connect(ip: &str) -> Result<Wled, WldError> {
+    let wled = open(ip)?;
+    let response = retry::retrying(ip, || {
+        api::send(&wled.client, wled.client.get(wled.url.clone())).at(ip)
+    })?;
+    if response.status() != reqwest::StatusCode::OK {
+        return Err(WldError::status(ip, response.status()));
+    }
+
+    Ok(wled)
+}
+
+/// Set up a client for the device at an IP address without checking it responds, for
+/// commands whose first request to the device reports if it can't be reached
+fn open(ip: &str) -> Result<Wled, WldError> {
+    let mut url = address::base_url(ip)?;
+    url.set_path("json/cfg");
+    address::resolve(&url)?;
+
+    let config = Config::load()?;
+    let client = client_options(&config, ip).client(ip)?;
+
+    Ok(Wled {
+        effects: None,
+        palettes: None,
+        state: None,
+        info: None,
+        cfg: None,
+        live: None,
+        nodes: None,
+        net: None,
+        client,
+        url,
+    })
+}
+
+/// Send the device at `ip` its settings PIN, if one is saved for it. WLED unlocks its
+/// settings for a while once it's sent the right PIN, so this is only needed before
+/// changing them, like saving a preset, rebooting or restoring a backup.
+pub fn unlock_settings(wled: &Wled, ip: &str) -> Result<(), WldError> {
+    let config = Config::load()?;
+    let Some(pin) = config.credentials_for(ip).and_then(|c| c.pin.as_ref()) else {
+        return Ok(());
+    };
+
+    let mut url = wled.url.clone();
+    url.set_path("json/state");
+    let response = retry::retrying(ip, || {
+        let request = wled
+            .client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(serde_json::json!({ "pin": pin }).to_string());
+        api::send(&wled.client, request).at(ip)
+    })?;
+    check_authorized(ip, &response)
+}
+
+/// Resolve a device name or IP (falling back to the default device) and connect to it
+pub fn connect_to_device(device: Option<&str>) -> Result<(String, Wled), WldError> {
+    let config = Config::load()?;
+    let ip = config.get_device_ip(device)?;
+    let wled = connect(&ip)?;
+
+    Ok((ip, wled))
+}
+
+/// Like `connect_to_device`, but without checking the device responds first
+fn open_device(device: Option<&str>) -> Result<(String, Wled), WldError> {
+    let config = Config::load()?;
+    let ip = config.get_device_ip(device)?;
+    let wled = open(&ip)?;
+
+    Ok((ip, wled))
+}
+
+/// Fetch the state of the device at `ip` into `wled.state`, asking again if the request
+/// doesn't reach it
+pub(crate) fn get_state(wled: &mut Wled, ip: &str) -> Result<(), WldError> {
+    let state = api::get_text(wled, "json/state")?;
+    wled.state = Some(State::try_from(state.as_str()).at(ip)?);
+    Ok(())
+}
+
+/// Send `wled.state` to the device at `ip`, sending it again if it doesn't reach the
+/// device, and return the response body. The state is sent as it is, so it has to be
+/// safe to apply twice.
+fn flush_state(wled: &Wled, ip: &str) -> Result<String, WldError> {
+    let state = serde_json::to_string(&wled.state.clone().unwrap_or_default())
+        .map_err(|e| format!("Failed to write state for device at {ip}: {e}"))?;
+    let mut url = wled.url.clone();
+    url.set_path("json/state");
+
+    retry::retrying(ip, || {
+        let request = wled
+            .client
+            .post(url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(state.clone());
+        let response = api::send(&wled.client, request).at(ip)?;
+        check_authorized(ip, &response)?;
+        response.error_for_status().and_then(api::read_text).at(ip)
+    })
+}
+
+/// Replace any group names with the devices in them, without repeating a device
+pub fn expand_groups(config: &Config, names: &[String]) -> Vec<String> {
+    let mut devices: Vec<String> = Vec::new();
+    for name in names {
+        let members = match config.group_members(name) {
+            Some(members) => members.to_vec(),
+            None => vec![name.clone()],
+        };
+        for member in members {
+            if !devices.contains(&member) {
+                devices.push(member);
+            }
+        }
+    }
+    devices
+}
+
+/// Capture how each of the named devices looks right now, for a scene. Group names are
+/// expanded to their devices.
+pub fn capture_scene(names: &[String]) -> Result<Vec<SceneDevice>, WldError> {
+    let config = Config::load()?;
+
+    let devices = expand_groups(&config, names);
+    if let Some(unknown) = devices.iter().find(|d| !config.devices.contains_key(*d)) {
+        return Err(format!("Device or group '{unknown}' not found").into());
+    }
+
+    devices
+        .iter()
+        .map(|name| {
+            let (ip, mut wled) = connect_to_device(Some(name))?;
+            get_state(&mut wled, &ip)?;
+            let state = wled.state.unwrap_or_default();
+            Ok(SceneDevice::capture(name, &state))
+        })
+        .collect()
+}
+
+/// Make a device look as it did in a scene, returning the device IP
+fn apply_scene_device(scene_device: &SceneDevice) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(Some(&scene_device.device))?;
+
+    get_state(&mut wled, &ip)?;
+    let main_segment = wled.state.as_ref().and_then(|s| s.mainseg).unwrap_or(0);
+
+    wled.state = Some(scene_device.to_state(main_segment)?);
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// A device's full state and LED count, to copy to other devices
+pub struct CloneSource {
+    pub ip: String,
+    pub state: serde_json::Value,
+    pub led_count: Option<u16>,
+}
+
+/// What copying a device's state to another device would send
+pub struct ClonePlan {
+    pub ip: String,
+    pub update: serde_json::Value,
+    /// The target's LED count, if it's different from the source's
+    pub led_count_mismatch: Option<u16>,
+}
+
+/// Fetch the state of the device to copy from
+pub fn get_clone_source(device: &str) -> Result<CloneSource, WldError> {
+    let (ip, wled) = connect_to_device(Some(device))?;
+
+    Ok(CloneSource {
+        state: api::get_json(&wled, "json/state")?,
+        led_count: api::get_info(&wled)?.leds.and_then(|leds| leds.count),
+        ip,
+    })
+}
+
+/// Work out the update that makes a device look like the source, and send it unless
+/// `dry_run` is set
+pub fn clone_to_device(
+    source: &CloneSource,
+    device: &str,
+    dry_run: bool,
+) -> Result<ClonePlan, WldError> {
+    let (ip, wled) = connect_to_device(Some(device))?;
+
+    let target: serde_json::Value = api::get_json(&wled, "json/state")?;
+    let led_count = api::get_info(&wled)?.leds.and_then(|leds| leds.count);
+    let update = clone::prepare(&source.state, &clone::segment_ids(&target));
+
+    if !dry_run {
+        api::post_state(&wled, &update)?;
+    }
+
+    Ok(ClonePlan {
+        ip,
+        update,
+        led_count_mismatch: led_count.filter(|count| Some(*count) != source.led_count),
+    })
+}
+
+/// How applying a scene to one of its devices went
+pub struct SceneOutcome {
+    pub device: String,
+    /// The device IP, or why applying the scene failed
+    pub result: Result<String, String>,
+}
+
+/// Apply a scene to all of its devices at once, so they change together
+pub fn apply_scene(name: &str) -> Result<Vec<SceneOutcome>, WldError> {
+    let config = Config::load()?;
+    let devices = config
+        .scenes
+        .get(name)
+        .ok_or_else(|| format!("Scene '{name}' not found"))?;
+
+    let results = std::thread::scope(|scope| {
+        let handles: Vec<_> = devices
+            .iter()
+            .map(|scene_device| {
+                scope.spawn(move || apply_scene_device(scene_device).map_err(|e| e.to_string()))
+            })
+            .collect();
+
+        devices
+            .iter()
+            .zip(handles)
+            .map(|(scene_device, handle)| {
+                let result = handle
+                    .join()
+                    .unwrap_or_else(|_| Err("Failed unexpectedly".to_string()));
+                SceneOutcome {
+                    device: scene_device.device.clone(),
+                    result,
+                }
+            })
+            .collect()
+    });
+
+    Ok(results)
+}
+
+/// Set the brightness of a device, fading over `transition` (in 100ms units) if given.
+/// Returns the device IP and its new brightness, along with its previous brightness if
+/// it was read to make a relative change.
+///
+/// A device left on at brightness 0 shows nothing, though it still says it's on, so a
+/// brightness of 0 turns the device off instead, keeping the brightness it turns back on
+/// at. Any other brightness turns the device on too, so it shows. With `raw`, the
+/// brightness is sent as it is, and nothing else.
+pub fn set_device_brightness(
+    device: Option<&str>,
+    value: BrightnessValue,
+    transition: Option<u8>,
+    raw: bool,
+) -> Result<(String, Option<u8>, u8), WldError> {
+    let (ip, mut wled) = open_device(device)?;
+
+    // WLED applies partial updates, so the state is only read for a relative change
+    let previous = match value {
+        BrightnessValue::Absolute(_) => None,
+        BrightnessValue::Relative(_) => {
+            get_state(&mut wled, &ip)?;
+            let state = wled.state.as_ref();
+            let previous = state.and_then(|s| s.bri).ok_or_else(|| {
+                WldError::rejected(&ip, format!("Device at {ip} did not report its brightness"))
+            })?;
+            // An off device reports the brightness it turns back on at, but shows nothing,
+            // so turning it up starts from 0
+            match state.and_then(|s| s.on) {
+                Some(false) if !raw => Some(0),
+                _ => Some(previous),
+            }
+        }
+    };
+    let brightness = value.apply(previous.unwrap_or_default());
+
+    wled.state = Some(match (raw, brightness) {
+        (false, 0) => State {
+            on: Some(false),
+            tt: transition,
+            ..Default::default()
+        },
+        (false, _) => State {
+            on: Some(true),
+            bri: Some(brightness),
+            tt: transition,
+            ..Default::default()
+        },
+        (true, _) => State {
+            bri: Some(brightness),
+            tt: transition,
+            ..Default::default()
+        },
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok((ip, previous, brightness))
+}
+
+/// Turn a device on or off, fading over `transition` (in 100ms units) if given
+pub fn set_device_power(
+    device: Option<&str>,
+    power_state: bool,
+    transition: Option<u8>,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = open_device(device)?;
+
+    // WLED applies partial updates, so there's no need to read the state first
+    wled.state = Some(State {
+        on: Some(power_state),
+        tt: transition,
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// What `turn_on_device` set as it turned a device on
+pub struct TurnedOn {
+    pub ip: String,
+    pub brightness: Option<u8>,
+    pub preset: Option<u8>,
+}
+
+/// Turn a device on, setting its brightness and main color in the same request if given.
+/// A saved device's default brightness and preset (see `wld set`) are used unless a
+/// brightness or color is given.
+pub fn turn_on_device(
+    device: Option<&str>,
+    brightness: Option<BrightnessValue>,
+    color: Option<Rgb>,
+    transition: Option<u8>,
+) -> Result<TurnedOn, WldError> {
+    let (ip, mut wled) = open_device(device)?;
+    let saved = Config::load()?.device_at(&ip).cloned();
+
+    // WLED applies partial updates, so the state is only read for a relative brightness
+    // or for a color, which goes to the main segment
+    let current = if matches!(brightness, Some(BrightnessValue::Relative(_))) || color.is_some() {
+        get_state(&mut wled, &ip)?;
+        wled.state.take().unwrap_or_default()
+    } else {
+        State::default()
+    };
+
+    let brightness = match brightness {
+        Some(BrightnessValue::Relative(_)) if current.bri.is_none() => {
+            return Err(WldError::rejected(
+                &ip,
+                format!("Device at {ip} did not report its brightness"),
+            ));
+        }
+        // A brightness of 0 would turn the device straight back off
+        Some(value) => Some(value.apply(current.bri.unwrap_or_default()).max(1)),
+        None => saved.as_ref().and_then(|d| d.default_brightness),
+    };
+    let preset = match color {
+        Some(_) => None,
+        None => saved.as_ref().and_then(|d| d.default_preset),
+    };
+
+    // Compose everything into one update, so the device changes all at once
+    let mut state = State {
+        on: Some(true),
+        tt: transition,
+        bri: brightness,
+        ps: preset.map(i32::from),
+        ..Default::default()
+    };
+    if let Some(color) = color {
+        let main_segment = current.mainseg.unwrap_or(0);
+        state.seg = segment::change(main_segment, SegmentChange::Color(color)).seg;
+    }
+    wled.state = Some(state);
+
+    // Send updated state
+    flush_state(&wled, &ip)?;
+
+    Ok(TurnedOn {
+        ip,
+        brightness,
+        preset,
+    })
+}
+
+/// Arm a device's nightlight to turn it off after `minutes`, returning the device IP. The
+/// device keeps time itself, so nothing needs to keep running.
+pub fn set_device_off_timer(device: Option<&str>, minutes: u8) -> Result<String, WldError> {
+    set_device_nightlight(device, minutes, nightlight::Mode::Instant, 0)
+}
+
+/// Arm a device's nightlight to bring it to `target` brightness over `minutes`, as `mode`
+/// does, returning the device IP. A sunrise starts by turning the device on at its lowest
+/// brightness, so there's something to brighten.
+pub fn set_device_nightlight(
+    device: Option<&str>,
+    minutes: u8,
+    mode: nightlight::Mode,
+    target: u8,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    let sunrise = mode == nightlight::Mode::Sunrise;
+
+    wled.state = Some(State {
+        on: sunrise.then_some(true),
+        bri: sunrise.then_some(1),
+        nl: Some(Nl {
+            on: Some(true),
+            dur: Some(minutes),
+            mode: Some(mode as u8),
+            tbri: Some(target),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Stop a device's nightlight, leaving its brightness where it is, returning the device IP
+pub fn cancel_device_nightlight(device: Option<&str>) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    wled.state = Some(State {
+        nl: Some(Nl {
+            on: Some(false),
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// How a fade ended
+#[derive(Debug)]
+pub struct FadeOutcome {
+    pub ip: String,
+    pub start: u8,
+    /// The last brightness sent to the device
+    pub reached: u8,
+    /// False if the fade was stopped with Ctrl-C
+    pub completed: bool,
+}
+
+/// Fade the brightness of a device to `target` over `over`, sending periodic updates.
+/// Pressing Ctrl-C stops the fade, leaving the device at the last brightness sent.
+pub fn fade_device_brightness(
+    device: Option<&str>,
+    target: BrightnessValue,
+    over: Duration,
+) -> Result<FadeOutcome, WldError> {
+    interrupt::catch_ctrl_c()?;
+
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    get_state(&mut wled, &ip)?;
+    let start = wled.state.as_ref().and_then(|s| s.bri).ok_or_else(|| {
+        WldError::rejected(&ip, format!("Device at {ip} did not report its brightness"))
+    })?;
+    let plan = fade::plan(start, target.apply(start), over);
+
+    let started = Instant::now();
+    let mut reached = start;
+
+    for step in &plan.steps {
+        if !interrupt::sleep(step.at.saturating_sub(started.elapsed())) {
+            return Ok(FadeOutcome {
+                ip,
+                start,
+                reached,
+                completed: false,
+            });
+        }
+
+        let update = serde_json::json!({ "bri": step.brightness, "tt": plan.transition });
+
+        // Give a dropped request one more chance before giving up on the whole fade
+        if api::post_state(&wled, &update).is_err() {
+            let retried =
+                interrupt::sleep(Duration::from_secs(1)).then(|| api::post_state(&wled, &update));
+            match retried {
+                Some(Ok(_)) => {}
+                Some(Err(e)) => {
+                    return Err(WldError::rejected(
+                        &ip,
+                        format!("Fade aborted at brightness {reached}: {e}"),
+                    ))
+                }
+                None => {
+                    return Ok(FadeOutcome {
+                        ip,
+                        start,
+                        reached,
+                        completed: false,
+                    })
+                }
+            }
+        }
+
+        reached = step.brightness;
+    }
+
+    // Wait for the last step's transition to finish
+    let completed = interrupt::sleep(over.saturating_sub(started.elapsed()));
+
+    Ok(FadeOutcome {
+        ip,
+        start,
+        reached,
+        completed,
+    })
+}
+
+/// Turn a device on at its lowest brightness and arm the nightlight's sunrise mode to
+/// brighten it to `target` over `minutes`, returning the device IP
+pub fn start_device_sunrise(
+    device: Option<&str>,
+    minutes: u8,
+    target: u8,
+) -> Result<String, WldError> {
+    set_device_nightlight(device, minutes, nightlight::Mode::Sunrise, target)
+}
+
+/// Play a 30 second sunrise on a device by sending updates from here, since the
+/// nightlight can't run for less than a minute. Returns the device IP and whether the
+/// sunrise finished, rather than being stopped with Ctrl-C.
+pub fn preview_device_sunrise(
+    device: Option<&str>,
+    target: u8,
+) -> Result<(String, bool), WldError> {
+    interrupt::catch_ctrl_c()?;
+
+    let (ip, mut wled) = connect_to_device(device)?;
+    get_state(&mut wled, &ip)?;
+    let main_segment = wled.state.as_ref().and_then(|s| s.mainseg).unwrap_or(0);
+
+    let steps = sunrise::PREVIEW_LENGTH.as_millis() / sunrise::PREVIEW_INTERVAL.as_millis();
+    let transition = (sunrise::PREVIEW_INTERVAL.as_millis() / 100) as u8;
+    let started = Instant::now();
+
+    for i in 0..=steps {
+        let at = sunrise::PREVIEW_INTERVAL * i as u32;
+        if !interrupt::sleep(at.saturating_sub(started.elapsed())) {
+            return Ok((ip, false));
+        }
+
+        let (brightness, color) = sunrise::step(i as f64 / steps as f64, target);
+        let update = serde_json::json!({
+            "on": true,
+            "bri": brightness,
+            "tt": if i == 0 { 0 } else { transition },
+            "seg": [{ "id": main_segment, "fx": 0, "col": [[color.r, color.g, color.b]] }],
+        });
+        api::post_state(&wled, &update)?;
+    }
+
+    Ok((ip, true))
+}
+
+/// Blink a device white and off `times` times, then restore the state it had before, even
+/// if Ctrl-C is pressed or a blink fails. Returns the device IP and whether every blink
+/// was shown.
+pub fn identify_device(device: Option<&str>, times: u32) -> Result<(String, bool), WldError> {
+    interrupt::catch_ctrl_c()?;
+
+    let (ip, wled) = connect_to_device(device)?;
+    let original: serde_json::Value = api::get_json(&wled, "json/state")?;
+
+    let blink = || -> Result<bool, WldError> {
+        for _ in 0..times {
+            api::post_state(&wled, &identify::white(&original))?;
+            if !interrupt::sleep(identify::BLINK_INTERVAL) {
+                return Ok(false);
+            }
+            api::post_state(&wled, &identify::off())?;
+            if !interrupt::sleep(identify::BLINK_INTERVAL) {
+                return Ok(false);
+            }
+        }
+        Ok(true)
+    };
+    let blinked = blink();
+
+    api::post_state(&wled, &identify::restore(&original)).map_err(|e| {
+        WldError::rejected(
+            &ip,
+            format!("Failed to restore device at {ip} after blinking: {e}"),
+        )
+    })?;
+
+    Ok((ip, blinked?))
+}
+
+/// Toggle the power state of a device, returning the device IP and its new power state
+pub fn toggle_device_power(device: Option<&str>) -> Result<(String, bool), WldError> {
+    let (ip, wled) = open_device(device)?;
+
+    // WLED flips the power state itself when sent "t", and "v" asks it to
+    // respond with the resulting state, so this only takes a single request
+    let response = api::post_state_once(&wled, &serde_json::json!({"on": "t", "v": true}))?;
+
+    let state = State::try_from(response.as_str()).at(&ip)?;
+    let power_state = state.on.ok_or_else(|| {
+        WldError::rejected(
+            &ip,
+            format!("Device at {ip} did not report its power state"),
+        )
+    })?;
+
+    Ok((ip, power_state))
+}
+
+/// Set the primary color of the first segment, fading over `transition` (in 100ms units)
+/// if given, and return the device IP
+pub fn set_device_color(
+    device: Option<&str>,
+    color: Rgb,
+    transition: Option<u8>,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    get_state(&mut wled, &ip)?;
+    let main_segment = wled.state.take().and_then(|s| s.mainseg).unwrap_or(0);
+
+    // Only send the color for the main segment, so other segment settings are left alone
+    wled.state = Some(State {
+        tt: transition,
+        ..segment::change(main_segment, SegmentChange::Color(color))
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Apply a segment update to the device's main segment
+fn update_main_segment(wled: &mut Wled, segment: Seg) -> Result<(), WldError> {
+    let ip = address::address_of(&wled.url);
+    get_state(wled, &ip)?;
+    let main_segment = wled.state.as_ref().and_then(|s| s.mainseg).unwrap_or(0);
+
+    wled.state = Some(segment::update(i16::from(main_segment), segment));
+    flush_state(wled, &ip)?;
+
+    Ok(())
+}
+
+/// Fetch the LED capabilities a device reports. Failing to read them isn't fatal, since
+/// they're only used for warnings.
+fn get_led_capabilities(wled: &Wled) -> api::Leds {
+    api::get_info(wled)
+        .ok()
+        .and_then(|info| info.leds)
+        .unwrap_or_default()
+}
+
+/// Set the white channel of the main segment, keeping its RGB color. Returns the device
+/// IP and whether the device reports having a white channel (if it says).
+pub fn set_device_white(
+    device: Option<&str>,
+    white: u8,
+) -> Result<(String, Option<bool>), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    let has_white = get_led_capabilities(&wled).has_white();
+
+    // WLED sets the white channel as the fourth element of a color, so the RGB part has
+    // to be sent along with it
+    get_state(&mut wled, &ip)?;
+    let state = wled.state.as_ref();
+    let main_segment = state.and_then(|s| s.mainseg).unwrap_or(0);
+    let rgb = state
+        .and_then(|s| s.seg.as_ref())
+        .and_then(|segments| {
+            segments
+                .iter()
+                .find(|s| s.id == Some(i16::from(main_segment)))
+        })
+        .and_then(|s| s.col.as_ref())
+        .and_then(|colors| colors.first())
+        .map(|color| color.iter().take(3).copied().collect::<Vec<u8>>())
+        .filter(|rgb| rgb.len() == 3)
+        .unwrap_or_else(|| vec![0, 0, 0]);
+
+    wled.state = Some(segment::update(
+        i16::from(main_segment),
+        Seg {
+            col: Some(vec![[rgb, vec![white]].concat()]),
+            ..Default::default()
+        },
+    ));
+    flush_state(&wled, &ip)?;
+
+    Ok((ip, has_white))
+}
+
+/// Set the color temperature of the main segment. Returns the device IP and whether the
+/// device reports supporting color temperature (if it says).
+pub fn set_device_cct(device: Option<&str>, cct: u8) -> Result<(String, Option<bool>), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    let has_cct = get_led_capabilities(&wled).has_cct();
+
+    update_main_segment(
+        &mut wled,
+        Seg {
+            cct: Some(u16::from(cct)),
+            ..Default::default()
+        },
+    )?;
+
+    Ok((ip, has_cct))
+}
+
+/// Set the effect on the main segment, and its speed and intensity if given, returning the
+/// device IP and the resolved effect
+pub fn set_device_effect(
+    device: Option<&str>,
+    effect: &Selector,
+    speed: Option<u8>,
+    intensity: Option<u8>,
+) -> Result<(String, u16, String), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    let (names, count) = effect_choices(&wled, effect)?;
+    let (id, name) = catalog::resolve(effect, &names, count, "effect")?;
+    set_effect(&mut wled, id, speed, intensity)?;
+
+    Ok((ip, id, name))
+}
+
+/// The effects on a device to pick `effect` from: their names, and how many there are.
+/// Devices can have more effects than names when picking by ID.
+pub(crate) fn effect_choices(
+    wled: &Wled,
+    effect: &Selector,
+) -> Result<(Vec<String>, usize), WldError> {
+    let names = api::get_effect_names(wled)?;
+    let count = match effect {
+        Selector::Id(_) => api::get_info(wled)?
+            .fxcount
+            .map_or(names.len(), usize::from),
+        Selector::Name(_) => names.len(),
+    };
+    Ok((names, count))
+}
+
+/// Set the effect on the main segment by ID, and its speed and intensity if given
+pub(crate) fn set_effect(
+    wled: &mut Wled,
+    id: u16,
+    speed: Option<u8>,
+    intensity: Option<u8>,
+) -> Result<(), WldError> {
+    update_main_segment(
+        wled,
+        Seg {
+            fx: Some(id),
+            sx: speed,
+            ix: intensity,
+            ..Default::default()
+        },
+    )
+}
+
+/// Set the palette on the main segment, returning the device IP and the resolved palette
+pub fn set_device_palette(
+    device: Option<&str>,
+    palette: &Selector,
+) -> Result<(String, u16, String), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    let names = api::get_palette_names(&wled)?;
+    let count = match palette {
+        Selector::Id(_) => api::get_info(&wled)?
+            .palcount
+            .map_or(names.len(), usize::from),
+        Selector::Name(_) => names.len(),
+    };
+    let (id, name) = catalog::resolve(palette, &names, count, "palette")?;
+
+    update_main_segment(
+        &mut wled,
+        Seg {
+            pal: Some(id),
+            ..Default::default()
+        },
+    )?;
+
+    Ok((ip, id, name))
+}
+
+/// Fetch the names of the effects on a device, indexed by effect ID
+pub fn get_device_effects(device: Option<&str>) -> Result<Vec<String>, WldError> {
+    let (_, wled) = connect_to_device(device)?;
+    api::get_effect_names(&wled)
+}
+
+/// Fetch the names of the palettes on a device, indexed by palette ID
+pub fn get_device_palettes(device: Option<&str>) -> Result<Vec<String>, WldError> {
+    let (_, wled) = connect_to_device(device)?;
+    api::get_palette_names(&wled)
+}
+
+/// What `wld random` picked for a device
+#[derive(Debug)]
+pub struct RandomLook {
+    pub ip: String,
+    pub effect: Option<(u16, String)>,
+    pub palette: Option<(u16, String)>,
+    pub color: Option<Rgb>,
+}
+
+/// Apply a random effect, palette and/or primary color to the main segment in a single
+/// update. Effects and palettes are picked from the ones the device reports having.
+pub fn randomize_device(
+    device: Option<&str>,
+    effect: bool,
+    palette: bool,
+    color: bool,
+) -> Result<RandomLook, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    let (fxcount, palcount) = if effect || palette {
+        let info = api::get_info(&wled)?;
+        (info.fxcount, info.palcount)
+    } else {
+        (None, None)
+    };
+
+    let pick = |names: Vec<String>, count: Option<u16>, kind: &str| {
+        let count = count.map_or(names.len(), usize::from);
+        random::pick_id(&mut rand::thread_rng(), &names, count)
+            .map(|id| {
+                let name = names.get(usize::from(id)).cloned().unwrap_or_default();
+                (id, name)
+            })
+            .ok_or_else(|| {
+                WldError::rejected(&ip, format!("Device at {ip} has no {kind}s to choose from"))
+            })
+    };
+
+    let effect = if effect {
+        Some(pick(api::get_effect_names(&wled)?, fxcount, "effect")?)
+    } else {
+        None
+    };
+    let palette = if palette {
+        Some(pick(api::get_palette_names(&wled)?, palcount, "palette")?)
+    } else {
+        None
+    };
+    let color = color.then(|| random::pick_color(&mut rand::thread_rng()));
+
+    update_main_segment(
+        &mut wled,
+        Seg {
+            fx: effect.as_ref().map(|(id, _)| *id),
+            pal: palette.as_ref().map(|(id, _)| *id),
+            col: color.map(|color| vec![color.to_wled()]),
+            ..Default::default()
+        },
+    )?;
+
+    Ok(RandomLook {
+        ip,
+        effect,
+        palette,
+        color,
+    })
+}
+
+/// Apply a preset, returning the device IP and the preset ID the device reports as
+/// active afterwards (-1 if none, which usually means the preset doesn't exist)
+pub fn apply_device_preset(
+    device: Option<&str>,
+    preset: u8,
+) -> Result<(String, Option<i32>), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    // "v" asks the device to respond with its resulting state
+    wled.state = Some(State {
+        ps: Some(i32::from(preset)),
+        v: Some(true),
+        ..Default::default()
+    });
+    let response = flush_state(&wled, &ip)?;
+    let state = State::try_from(response.as_str()).at(&ip)?;
+
+    Ok((ip, state.ps))
+}
+
+/// Save the current state of a device into a preset slot, returning the device IP and
+/// the saved preset as read back from the device
+pub fn save_device_preset(
+    device: Option<&str>,
+    slot: u8,
+    name: Option<&str>,
+) -> Result<(String, api::Preset), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    unlock_settings(&wled, &ip)?;
+
+    let mut update = serde_json::json!({ "psave": slot });
+    if let Some(name) = name {
+        update["n"] = serde_json::Value::from(name);
+    }
+    api::post_state_once(&wled, &update)?;
+
+    // WLED writes presets to flash in the background, so give it a moment to show up
+    for _ in 0..10 {
+        let saved = api::get_presets(&wled)?
+            .into_iter()
+            .find(|(id, _)| *id == u16::from(slot))
+            .map(|(_, preset)| preset)
+            .filter(|preset| name.is_none() || preset.name.as_deref() == name);
+
+        if let Some(preset) = saved {
+            return Ok((ip, preset));
+        }
+
+        std::thread::sleep(std::time::Duration::from_millis(200));
+    }
+
+    Err(WldError::rejected(
+        &ip,
+        format!("Device at {ip} did not confirm that preset {slot} was saved"),
+    ))
+}
+
+/// Fetch the presets saved on a device, sorted by ID
+pub fn get_device_presets(device: Option<&str>) -> Result<Vec<(u16, api::Preset)>, WldError> {
+    let (_, wled) = connect_to_device(device)?;
+    api::get_presets(&wled)
+}
+
+/// Start a playlist, returning the device IP and the playlist's name
+pub fn start_device_playlist(
+    device: Option<&str>,
+    playlist: u8,
+) -> Result<(String, Option<String>), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+
+    // Playlists are stored as presets, so check the preset exists and is a playlist
+    let preset = api::get_presets(&wled)?
+        .into_iter()
+        .find(|(id, _)| *id == u16::from(playlist))
+        .map(|(_, preset)| preset)
+        .ok_or_else(|| format!("Playlist {playlist} not found on device at {ip}"))?;
+    if preset.playlist.is_none() {
+        return Err(format!("Preset {playlist} on device at {ip} is not a playlist").into());
+    }
+
+    // Applying a playlist's preset starts the playlist
+    api::post_state(&wled, &serde_json::json!({ "ps": playlist }))?;
+
+    Ok((ip, preset.name))
+}
+
+/// Stop the running playlist, returning the device IP
+pub fn stop_device_playlist(device: Option<&str>) -> Result<String, WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    api::post_state(&wled, &serde_json::json!({ "pl": -1 }))?;
+    Ok(ip)
+}
+
+/// A playlist running on a device
+#[derive(Debug)]
+pub struct RunningPlaylist {
+    pub id: u16,
+    pub name: Option<String>,
+}
+
+/// Find the running playlist, returning the device IP and the playlist (if any is running)
+pub fn get_device_playlist(
+    device: Option<&str>,
+) -> Result<(String, Option<RunningPlaylist>), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+
+    // The library models "pl" as an i8, which can't hold every preset ID
+    let state: serde_json::Value = api::get_json(&wled, "json/state")?;
+    let running = state["pl"].as_i64().and_then(|pl| u16::try_from(pl).ok());
+
+    let Some(playlist) = running else {
+        return Ok((ip, None));
+    };
+
+    // Resolving the name is a nicety, so don't fail if presets can't be read
+    let name = api::get_presets(&wled)
+        .ok()
+        .and_then(|presets| presets.into_iter().find(|(id, _)| *id == playlist))
+        .and_then(|(_, preset)| preset.name);
+
+    Ok((ip, Some(RunningPlaylist { id: playlist, name })))
+}
+
+/// The segments configured on a device
+pub struct DeviceSegments {
+    pub segments: Vec<Seg>,
+    /// The ID of the main segment, if the device reports it
+    pub main: Option<u8>,
+}
+
+/// Fetch the segments configured on a device, returning the device IP and the segments
+pub fn get_device_segments(device: Option<&str>) -> Result<(String, DeviceSegments), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    get_state(&mut wled, &ip)?;
+    let state = wled.state.unwrap_or_default();
+
+    Ok((
+        ip,
+        DeviceSegments {
+            segments: state.seg.unwrap_or_default(),
+            main: state.mainseg,
+        },
+    ))
+}
+
+/// Make a segment the main one, which commands like `wld effect` act on, returning the
+/// device IP
+pub fn set_device_main_segment(device: Option<&str>, id: u8) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    find_segment(&mut wled, &ip, id)?;
+
+    wled.state = Some(State {
+        mainseg: Some(id),
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Fetch a segment's current state, failing if the device doesn't have it. WLED silently
+/// ignores updates for segments that don't exist, so this is checked before changing one.
+fn find_segment(wled: &mut Wled, ip: &str, id: u8) -> Result<Seg, WldError> {
+    get_state(wled, ip)?;
+    let segments = wled
+        .state
+        .as_ref()
+        .and_then(|s| s.seg.clone())
+        .unwrap_or_default();
+
+    match segments.iter().find(|s| s.id == Some(i16::from(id))) {
+        Some(segment) => Ok(segment.clone()),
+        None => {
+            let ids: Vec<String> = segments
+                .iter()
+                .filter_map(|s| s.id)
+                .map(|id| id.to_string())
+                .collect();
+            Err(format!(
+                "Segment {id} not found on device at {ip} (segments: {})",
+                ids.join(", ")
+            )
+            .into())
+        }
+    }
+}
+
+/// Apply a change to a single segment, returning the device IP
+pub fn update_device_segment(
+    device: Option<&str>,
+    id: u8,
+    change: SegmentChange,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    find_segment(&mut wled, &ip, id)?;
+
+    wled.state = Some(segment::change(id, change));
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Set whether a segment is reversed or mirrored, toggling it if `value` is `None`.
+/// Returns the device IP and the new setting.
+pub fn set_device_segment_orientation(
+    device: Option<&str>,
+    id: u8,
+    orientation: Orientation,
+    value: Option<bool>,
+) -> Result<(String, bool), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+    let segment = find_segment(&mut wled, &ip, id)?;
+
+    let value = match value {
+        Some(value) => value,
+        None => !orientation.get(&segment).ok_or_else(|| {
+            WldError::rejected(
+                &ip,
+                format!("Device at {ip} did not report the direction of segment {id}"),
+            )
+        })?,
+    };
+
+    wled.state = Some(segment::change(id, orientation.change(value)));
+    flush_state(&wled, &ip)?;
+
+    Ok((ip, value))
+}
+
+/// Set the LEDs a segment covers, from `start` up to but not including `stop`, creating
+/// the segment if needed, and return the device IP. `grouping` and `spacing` are left as
+/// they are if `None`.
+pub fn set_device_segment_bounds(
+    device: Option<&str>,
+    id: u8,
+    start: u16,
+    stop: u16,
+    grouping: Option<u8>,
+    spacing: Option<u8>,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    let led_count = api::get_info(&wled)?.leds.and_then(|leds| leds.count);
+    segment::validate_bounds(start, stop, led_count)?;
+
+    let bounds = SegmentChange::Bounds {
+        start,
+        stop,
+        grouping,
+        spacing,
+    };
+    wled.state = Some(segment::change(id, bounds));
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Turn sending and/or receiving UDP sync packets on or off, returning the device IP.
+/// Flags that are `None` are left as they are.
+pub fn set_device_sync(
+    device: Option<&str>,
+    send: Option<bool>,
+    recv: Option<bool>,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    // Only send the udpn object, so the rest of the state is left alone
+    wled.state = Some(State {
+        udpn: Some(Udpn {
+            send,
+            recv,
+            ..Default::default()
+        }),
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Fetch the UDP sync settings of a device, returning the device IP and the settings
+pub fn get_device_sync(device: Option<&str>) -> Result<(String, Udpn), WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    get_state(&mut wled, &ip)?;
+    let udpn = wled.state.and_then(|s| s.udpn).ok_or_else(|| {
+        WldError::rejected(
+            &ip,
+            format!("Device at {ip} did not report its sync settings"),
+        )
+    })?;
+
+    Ok((ip, udpn))
+}
+
+/// Set the live override (`lor`) of a device: 0 lets realtime data through, 1 ignores it
+/// until the live data stream ends and 2 ignores it until the device reboots. Returns the
+/// device IP.
+pub fn set_device_live_override(
+    device: Option<&str>,
+    live_override: u8,
+) -> Result<String, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    wled.state = Some(State {
+        lor: Some(live_override),
+        ..Default::default()
+    });
+    flush_state(&wled, &ip)?;
+
+    Ok(ip)
+}
+
+/// Realtime data status of a device
+#[derive(Debug)]
+pub struct LiveStatus {
+    pub ip: String,
+    pub receiving: Option<bool>,
+    /// Name of the realtime data source, e.g. "E1.31"
+    pub source: Option<String>,
+    /// IP address the realtime data is coming from
+    pub source_ip: Option<String>,
+    pub live_override: Option<u8>,
+}
+
+/// Fetch whether a device is receiving realtime data, and its live override setting
+pub fn get_device_live_status(device: Option<&str>) -> Result<LiveStatus, WldError> {
+    let (ip, mut wled) = connect_to_device(device)?;
+
+    let info = api::get_info(&wled)?;
+    get_state(&mut wled, &ip)?;
+    let live_override = wled.state.and_then(|s| s.lor);
+
+    Ok(LiveStatus {
+        ip,
+        receiving: info.live,
+        source: info.lm.filter(|lm| !lm.is_empty()),
+        source_ip: info.lip.filter(|lip| !lip.is_empty()),
+        live_override,
+    })
+}
+
+/// Fetch the full JSON state of a device, including fields wld doesn't model
+pub fn get_device_state_json(
+    device: Option<&str>,
+) -> Result<(String, serde_json::Value), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    let state = api::get_json(&wled, "json/state")?;
+    Ok((ip, state))
+}
+
+/// Send a JSON state update to a device as-is. Returns the device IP and its response.
+pub fn send_device_state_json(
+    device: Option<&str>,
+    state: &serde_json::Value,
+) -> Result<(String, serde_json::Value), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    let response = api::post_state_once(&wled, state)?;
+    let response = serde_json::from_str(&response).unwrap_or(serde_json::Value::Null);
+    Ok((ip, response))
+}
+
+/// Fetch the other WLED devices a device has discovered, sorted by name
+pub fn get_device_nodes(device: Option<&str>) -> Result<(String, Vec<api::Node>), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    let mut nodes = api::get_nodes(&wled)?;
+    nodes.sort_by(|a, b| (&a.name, &a.ip).cmp(&(&b.name, &b.ip)));
+    Ok((ip, nodes))
+}
+
+/// Tell the device at an IP address to join a Wi-Fi network, and restart it so it does.
+/// Used to set up a device in setup mode, which can't be reached at the same address
+/// once it has joined.
+pub fn join_wifi(ip: &str, ssid: &str, password: &str) -> Result<(), WldError> {
+    let wled = connect(ip)?;
+    unlock_settings(&wled, ip)?;
+    let network = serde_json::json!({ "nw": { "ins": [{ "ssid": ssid, "psk": password }] } });
+    api::post_config(&wled, &network)?;
+    api::post_state_once(&wled, &serde_json::json!({ "rb": true }))?;
+    Ok(())
+}
+
+/// Reboot a device and wait up to `timeout` for it to come back, returning the device IP
+/// and how long the reboot took
+pub fn reboot_device(
+    device: Option<&str>,
+    timeout: Duration,
+) -> Result<(String, Duration), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    unlock_settings(&wled, &ip)?;
+
+    let uptime_before = api::get_info(&wled)?.uptime;
+    api::post_state_once(&wled, &serde_json::json!({ "rb": true }))?;
+    let took = wait_for_restart(&ip, uptime_before, timeout)?;
+
+    Ok((ip, took))
+}
+
+/// Wait up to `timeout` for a device that has been told to restart to come back, returning
+/// how long it took. `uptime_before` is the device's uptime before it was told to restart.
+pub fn wait_for_restart(
+    ip: &str,
+    uptime_before: Option<u64>,
+    timeout: Duration,
+) -> Result<Duration, WldError> {
+    let options = client_options(&Config::load()?, ip);
+    let started = Instant::now();
+
+    // The device may still answer for a moment before it restarts, so it only counts as
+    // back once it has stopped responding or its uptime has gone down
+    let mut went_down = false;
+    while started.elapsed() < timeout {
+        std::thread::sleep(Duration::from_secs(1));
+
+        match api::probe_info(ip, Duration::from_secs(2), &options) {
+            Ok(info) => {
+                let uptime_reset = matches!(
+                    (uptime_before, info.uptime),
+                    (Some(before), Some(after)) if after < before
+                );
+                if went_down || uptime_reset {
+                    return Ok(started.elapsed());
+                }
+            }
+            Err(_) => went_down = true,
+        }
+    }
+
+    Err(WldError::rejected(
+        ip,
+        format!(
+            "Device at {ip} did not come back within {}",
+            duration::format_duration(timeout)
+        ),
+    ))
+}
+
+/// Download a device's configuration and presets into `dir`, named with `timestamp`,
+/// returning the device IP and the files written
+pub fn backup_device(
+    device: Option<&str>,
+    dir: &Path,
+    timestamp: &str,
+) -> Result<(String, Vec<PathBuf>), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    unlock_settings(&wled, &ip)?;
+
+    let mut contents = Vec::new();
+    for file in backup::BackupFile::ALL {
+        let text = match file {
+            // Older firmware doesn't serve cfg.json directly, but has the same document at
+            // json/cfg
+            backup::BackupFile::Config => api::get_text(&wled, file.device_name())
+                .or_else(|_| api::get_text(&wled, "json/cfg"))?,
+            backup::BackupFile::Presets => api::get_text(&wled, file.device_name())?,
+        };
+        serde_json::from_str::<serde_json::Value>(&text).map_err(|e| {
+            WldError::rejected(
+                &ip,
+                format!(
+                    "Device at {ip} returned an invalid {}: {e}",
+                    file.device_name()
+                ),
+            )
+        })?;
+        contents.push((file, text));
+    }
+
+    std::fs::create_dir_all(dir).map_err(|source| WldError::Io {
+        action: "create",
+        path: dir.to_path_buf(),
+        source,
+    })?;
+    let mut paths = Vec::new();
+    for (file, text) in contents {
+        let path = dir.join(file.backup_name(timestamp));
+        std::fs::write(&path, text).map_err(|source| WldError::Io {
+            action: "write",
+            path: path.clone(),
+            source,
+        })?;
+        paths.push(path);
+    }
+
+    Ok((ip, paths))
+}
+
+/// How `restore_device` is getting on, so it can be shown as it happens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RestoreStep {
+    /// A backed up file was uploaded
+    Restored(backup::BackupFile),
+    /// The device at `ip` is restarting to apply its restored configuration
+    Restarting { ip: String },
+}
+
+/// Upload backed up files to a device, then check the device responds again, waiting up
+/// to `timeout` if it restarts. Every file is checked before any is uploaded, so a bad
+/// backup can't leave the device half restored. `confirm` is asked first, with the device
+/// IP, and `progress` is told about each step. Returns the device IP, or `None` if
+/// `confirm` said no.
+pub fn restore_device<E: From<WldError>>(
+    device: Option<&str>,
+    backups: &[(backup::BackupFile, PathBuf)],
+    timeout: Duration,
+    confirm: impl FnOnce(&str) -> Result<bool, E>,
+    mut progress: impl FnMut(RestoreStep),
+) -> Result<Option<String>, E> {
+    let mut contents = Vec::new();
+    for (file, path) in backups {
+        let text = std::fs::read_to_string(path).map_err(|source| WldError::Io {
+            action: "read",
+            path: path.clone(),
+            source,
+        })?;
+        serde_json::from_str::<serde_json::Value>(&text).map_err(|e| {
+            WldError::InvalidInput(format!("'{}' isn't valid JSON: {e}", path.display()))
+        })?;
+        contents.push((*file, text));
+    }
+
+    let (ip, wled) = connect_to_device(device)?;
+    if !confirm(&ip)? {
+        return Ok(None);
+    }
+
+    unlock_settings(&wled, &ip)?;
+    let uptime_before = api::get_info(&wled)?.uptime;
+    let mut restarts = false;
+    for (file, text) in contents {
+        api::upload_file(&wled, file.device_name(), text)?;
+        progress(RestoreStep::Restored(file));
+        restarts |= file == backup::BackupFile::Config;
+    }
+
+    if restarts {
+        progress(RestoreStep::Restarting { ip: ip.clone() });
+        wait_for_restart(&ip, uptime_before, timeout)?;
+    } else {
+        let options = client_options(&Config::load()?, &ip);
+        api::probe_info(&ip, Duration::from_secs(5), &options).map_err(|e| {
+            WldError::rejected(
+                &ip,
+                format!("Device at {ip} isn't responding after the restore: {e}"),
+            )
+        })?;
+    }
+
+    Ok(Some(ip))
+}
+
+/// Fetch firmware and hardware details of a device, returning the device IP and details
+pub fn get_device_info(device: Option<&str>) -> Result<(String, api::Info), WldError> {
+    let (ip, wled) = connect_to_device(device)?;
+    let info = api::get_info(&wled)?;
+    Ok((ip, info))
+}
+
+/// Fetch the Wi-Fi details of the device at an IP address
+pub fn get_device_wifi(ip: &str) -> Result<api::Wifi, WldError> {
+    let wled = connect(ip)?;
+    Ok(api::get_info(&wled)?.wifi.unwrap_or_default())
+}
+
+/// Fetch the estimated power draw and power limit of the device at an IP address
+pub fn get_device_power(ip: &str) -> Result<api::Leds, WldError> {
+    let wled = connect(ip)?;
+    Ok(api::get_info(&wled)?.leds.unwrap_or_default())
+}
+
+/// Whether a device is on or off, or couldn't be reached
+#[derive(Debug)]
+pub enum DeviceStatus {
+    On,
+    Off,
+    Unreachable,
+}
+
+/// Check whether a device is on, along with its brightness if it reports it
+pub fn get_device_status_and_brightness(ip: &str) -> (DeviceStatus, Option<u8>) {
+    let mut wled = match connect(ip) {
+        Ok(w) => w,
+        Err(_) => return (DeviceStatus::Unreachable, None),
+    };
+
+    // Try to get current state from device
+    match get_state(&mut wled, ip) {
+        Ok(_) => {
+            let brightness = wled.state.as_ref().and_then(|s| s.bri);
+
+            // Check if device is on or off
+            if let Some(state) = &wled.state {
+                if let Some(on) = state.on {
+                    if on {
+                        return (DeviceStatus::On, brightness);
+                    } else {
+                        return (DeviceStatus::Off, brightness);
+                    }
+                }
+            }
+            // If we can reach the device but can't determine state, assume it's on
+            (DeviceStatus::On, brightness)
+        }
+        Err(_) => (DeviceStatus::Unreachable, None),
+    }
+}
+
+/// What a reachable device is showing, beyond whether it's on
+#[derive(Debug, Default)]
+pub struct DeviceDetails {
+    pub brightness: Option<u8>,
+    /// The id of the effect on the main segment, with its name if the device lists its
+    /// effects
+    pub effect: Option<(u16, Option<String>)>,
+    /// The active preset, if any is
+    pub preset: Option<i32>,
+}
+
+/// Check whether a device is on, along with its brightness, effect and preset. Unlike
+/// `get_device_status_and_brightness`, this also fetches the device's effect names, to
+/// name its effect.
+pub fn get_device_details(ip: &str) -> (DeviceStatus, DeviceDetails) {
+    let unreachable = (DeviceStatus::Unreachable, DeviceDetails::default());
+    let Ok(mut wled) = connect(ip) else {
+        return unreachable;
+    };
+    if get_state(&mut wled, ip).is_err() {
+        return unreachable;
+    }
+    let Some(state) = wled.state.take() else {
+        return unreachable;
+    };
+
+    // A device that doesn't say whether it's on is treated as on
+    let status = if state.on == Some(false) {
+        DeviceStatus::Off
+    } else {
+        DeviceStatus::On
+    };
+
+    let main_segment = i16::from(state.mainseg.unwrap_or(0));
+    let effect = state.seg.as_ref().and_then(|segments| {
+        segments
+            .iter()
+            .find(|segment| segment.id == Some(main_segment))
+            .or(segments.first())
+            .and_then(|segment| segment.fx)
+    });
+    let details = DeviceDetails {
+        brightness: state.bri,
+        effect: effect.map(|id| {
+            let names = api::get_effect_names(&wled).ok();
+            (
+                id,
+                names.and_then(|names| names.get(usize::from(id)).cloned()),
+            )
+        }),
+        preset: state.ps.filter(|preset| *preset > 0),
+    };
+    (status, details)
+}
+
+/// How many devices `get_device_statuses` checks at the same time, unless it's told
+/// otherwise
+pub const STATUS_CHECKS: usize = 8;
+
+/// Check the status and brightness of saved devices, up to `at_once` at the same time, so
+/// a slow or unreachable device doesn't hold up the others. The statuses are in the same
+/// order as `names`, whatever order they're checked in.
+pub fn get_device_statuses(
+    config: &Config,
+    names: &[String],
+    at_once: usize,
+) -> Vec<(DeviceStatus, Option<u8>)> {
+    get_device_statuses_with(config, names, at_once, |_, _| {})
+}
+
+/// Like `get_device_statuses`, calling `on_checked` with each device's index in `names`
+/// and its status as soon as it's known, e.g. to report progress
+pub fn get_device_statuses_with<F>(
+    config: &Config,
+    names: &[String],
+    at_once: usize,
+    on_checked: F,
+) -> Vec<(DeviceStatus, Option<u8>)>
+where
+    F: Fn(usize, &(DeviceStatus, Option<u8>)) + Sync,
+{
+    check_devices(
+        config,
+        names,
+        at_once,
+        get_device_status_and_brightness,
+        on_checked,
+    )
+    .into_iter()
+    .map(|status| status.unwrap_or((DeviceStatus::Unreachable, None)))
+    .collect()
+}
+
+/// Like `get_device_statuses`, with the brightness, effect and preset of each device
+/// that's reachable
+pub fn get_device_statuses_in_detail(
+    config: &Config,
+    names: &[String],
+    at_once: usize,
+) -> Vec<(DeviceStatus, DeviceDetails)> {
+    check_devices(config, names, at_once, get_device_details, |_, _| {})
+        .into_iter()
+        .map(|status| status.unwrap_or((DeviceStatus::Unreachable, DeviceDetails::default())))
+        .collect()
+}
+
+/// Run `check` on the address of each device in `names`, up to `at_once` at the same
+/// time, returning the results in the same order as `names`. A result is missing if the
+/// check panicked.
+fn check_devices<T, C, F>(
+    config: &Config,
+    names: &[String],
+    at_once: usize,
+    check: C,
+    on_checked: F,
+) -> Vec<Option<T>>
+where
+    T: Send,
+    C: Fn(&str) -> T + Sync,
+    F: Fn(usize, &T) + Sync,
+{
+    let next = std::sync::atomic::AtomicUsize::new(0);
+    let mut statuses: Vec<Option<T>> = std::iter::repeat_with(|| None).take(names.len()).collect();
+
+    std::thread::scope(|scope| {
+        let workers: Vec<_> = (0..names.len().min(at_once.max(1)))
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut checked = Vec::new();
+                    loop {
+                        let index = next.fetch_add(1, std::sync::atomic::Ordering::Relaxed);
+                        let Some(name) = names.get(index) else {
+                            return checked;
+                        };
+                        let status = check(&config.devices[name].ip);
+                        on_checked(index, &status);
+                        checked.push((index, status));
+                    }
+                })
+            })
+            .collect();
+
+        for worker in workers {
+            for (index, status) in worker.join().unwrap_or_default() {
+                statuses[index] = Some(status);
+            }
+        }
+    });
+
+    statuses
+}
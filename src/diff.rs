@@ -0,0 +1,248 @@
+use serde_json::Value;
+
+use crate::color::Rgb;
+
+/// A field that's different on two devices, with how each device shows it
+#[derive(Debug, PartialEq)]
+pub struct Difference {
+    pub field: String,
+    pub a: String,
+    pub b: String,
+}
+
+/// Top-level state fields compared by `summary`, with the names they're shown with
+const STATE_FIELDS: [(&str, &str); 2] = [("on", "on"), ("bri", "brightness")];
+
+/// Segment fields compared by `summary`, with the names they're shown with
+const SEGMENT_FIELDS: [(&str, &str); 9] = [
+    ("on", "on"),
+    ("bri", "brightness"),
+    ("col", "color"),
+    ("fx", "effect"),
+    ("pal", "palette"),
+    ("sx", "speed"),
+    ("ix", "intensity"),
+    ("start", "start"),
+    ("stop", "stop"),
+];
+
+/// How a field is shown: colors by their primary color, switches as on or off, and
+/// fields the device didn't report as `-`
+fn describe(key: &str, value: Option<&Value>) -> String {
+    match (key, value) {
+        (_, None | Some(Value::Null)) => "-".to_string(),
+        (_, Some(Value::Bool(on))) => if *on { "on" } else { "off" }.to_string(),
+        ("col", Some(value)) => match value.as_array() {
+            Some(c) if c.len() >= 3 => {
+                let channel = |i: usize| c[i].as_u64().unwrap_or(0).min(255) as u8;
+                Rgb::new(channel(0), channel(1), channel(2)).to_string()
+            }
+            _ => value.to_string(),
+        },
+        (_, Some(value)) => value.to_string(),
+    }
+}
+
+/// Compare a field of two objects, returning how they differ if they do. Only the primary
+/// color is compared, since that's the one shown.
+fn compare(field: String, key: &str, a: &Value, b: &Value) -> Option<Difference> {
+    let value = |object: &Value| match (key, object.get(key)) {
+        ("col", Some(colors)) => colors.get(0).cloned(),
+        (_, value) => value.cloned(),
+    };
+    let (x, y) = (value(a), value(b));
+
+    (x != y).then(|| Difference {
+        field,
+        a: describe(key, x.as_ref()),
+        b: describe(key, y.as_ref()),
+    })
+}
+
+/// The segment with the given ID in a device's state
+fn segment(state: &Value, id: u64) -> Option<&Value> {
+    state["seg"]
+        .as_array()?
+        .iter()
+        .find(|s| s["id"].as_u64() == Some(id))
+}
+
+/// Compare the fields that decide how two devices look: power and brightness, then the
+/// power, brightness, color, effect, palette, speed, intensity and bounds of each segment
+pub fn summary(a: &Value, b: &Value) -> Vec<Difference> {
+    let mut differences: Vec<Difference> = STATE_FIELDS
+        .iter()
+        .filter_map(|(key, name)| compare(name.to_string(), key, a, b))
+        .collect();
+
+    let mut ids: Vec<u64> = [a, b]
+        .iter()
+        .filter_map(|state| state["seg"].as_array())
+        .flatten()
+        .filter_map(|s| s["id"].as_u64())
+        .collect();
+    ids.sort_unstable();
+    ids.dedup();
+
+    for id in ids {
+        match (segment(a, id), segment(b, id)) {
+            (Some(x), Some(y)) => differences.extend(
+                SEGMENT_FIELDS
+                    .iter()
+                    .filter_map(|(key, name)| compare(format!("segment {id} {name}"), key, x, y)),
+            ),
+            (x, y) => differences.push(Difference {
+                field: format!("segment {id}"),
+                a: if x.is_some() { "present" } else { "missing" }.to_string(),
+                b: if y.is_some() { "present" } else { "missing" }.to_string(),
+            }),
+        }
+    }
+
+    differences
+}
+
+/// Compare two JSON documents structurally, returning the path of each value that's
+/// different (like `seg[0].col[0][1]`) with the value on each side, or `None` if one side
+/// doesn't have it
+pub fn structural(a: &Value, b: &Value) -> Vec<(String, Option<Value>, Option<Value>)> {
+    let mut differences = Vec::new();
+    walk(String::new(), Some(a), Some(b), &mut differences);
+    differences
+}
+
+fn walk(
+    path: String,
+    a: Option<&Value>,
+    b: Option<&Value>,
+    differences: &mut Vec<(String, Option<Value>, Option<Value>)>,
+) {
+    match (a, b) {
+        (Some(Value::Object(x)), Some(Value::Object(y))) => {
+            let mut keys: Vec<&String> = x.keys().chain(y.keys()).collect();
+            keys.sort();
+            keys.dedup();
+            for key in keys {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{path}.{key}")
+                };
+                walk(path, x.get(key), y.get(key), differences);
+            }
+        }
+        (Some(Value::Array(x)), Some(Value::Array(y))) => {
+            for index in 0..x.len().max(y.len()) {
+                walk(
+                    format!("{path}[{index}]"),
+                    x.get(index),
+                    y.get(index),
+                    differences,
+                );
+            }
+        }
+        (x, y) if x != y => differences.push((path, x.cloned(), y.cloned())),
+        _ => {}
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state() -> Value {
+        json!({
+            "on": true, "bri": 128, "transition": 7,
+            "seg": [{"id": 0, "start": 0, "stop": 30, "on": true, "bri": 255,
+                     "col": [[255, 0, 0], [0, 0, 0]], "fx": 0, "pal": 0, "sx": 128, "ix": 128}]
+        })
+    }
+
+    #[test]
+    fn test_summary_identical() {
+        assert!(summary(&state(), &state()).is_empty());
+    }
+
+    #[test]
+    fn test_summary_only_lists_differences() {
+        let mut other = state();
+        other["bri"] = json!(200);
+        other["transition"] = json!(0);
+        other["seg"][0]["col"][0] = json!([0, 0, 255]);
+        other["seg"][0]["sx"] = json!(40);
+        // Only the primary color is compared
+        other["seg"][0]["col"][1] = json!([9, 9, 9]);
+
+        assert_eq!(
+            summary(&state(), &other),
+            vec![
+                Difference {
+                    field: "brightness".to_string(),
+                    a: "128".to_string(),
+                    b: "200".to_string()
+                },
+                Difference {
+                    field: "segment 0 color".to_string(),
+                    a: "RGB(255, 0, 0)".to_string(),
+                    b: "RGB(0, 0, 255)".to_string()
+                },
+                Difference {
+                    field: "segment 0 speed".to_string(),
+                    a: "128".to_string(),
+                    b: "40".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_summary_missing_segments() {
+        let mut other = state();
+        other["on"] = json!(false);
+        other["seg"]
+            .as_array_mut()
+            .unwrap()
+            .push(json!({"id": 1, "start": 30, "stop": 60}));
+
+        assert_eq!(
+            summary(&state(), &other),
+            vec![
+                Difference {
+                    field: "on".to_string(),
+                    a: "on".to_string(),
+                    b: "off".to_string()
+                },
+                Difference {
+                    field: "segment 1".to_string(),
+                    a: "missing".to_string(),
+                    b: "present".to_string()
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_structural() {
+        let mut other = state();
+        other["transition"] = json!(0);
+        other["seg"][0]["col"][0][2] = json!(9);
+        other["seg"][0]["n"] = json!("left");
+        other.as_object_mut().unwrap().remove("bri");
+
+        assert_eq!(
+            structural(&state(), &other),
+            vec![
+                ("bri".to_string(), Some(json!(128)), None),
+                (
+                    "seg[0].col[0][2]".to_string(),
+                    Some(json!(0)),
+                    Some(json!(9))
+                ),
+                ("seg[0].n".to_string(), None, Some(json!("left"))),
+                ("transition".to_string(), Some(json!(7)), Some(json!(0))),
+            ]
+        );
+        assert!(structural(&state(), &state()).is_empty());
+    }
+}
@@ -0,0 +1,124 @@
+use std::collections::BTreeSet;
+use std::net::Ipv4Addr;
+use std::time::{Duration, Instant};
+
+use mdns_sd::{ServiceDaemon, ServiceEvent};
+
+/// mDNS service types WLED devices advertise themselves under
+const SERVICE_TYPES: &[&str] = &["_wled._tcp.local.", "_http._tcp.local."];
+
+/// Browse the local network with mDNS for up to `timeout`, returning the addresses of
+/// services that could be WLED devices. These still need confirming, e.g. by fetching
+/// `json/info`, since `_http._tcp` is advertised by all sorts of things.
+pub fn browse(timeout: Duration) -> Result<Vec<String>, Box<dyn std::error::Error>> {
+    let daemon = ServiceDaemon::new()?;
+    let receivers = SERVICE_TYPES
+        .iter()
+        .map(|service_type| Ok((*service_type, daemon.browse(service_type)?)))
+        .collect::<Result<Vec<_>, mdns_sd::Error>>()?;
+
+    let mut addresses = BTreeSet::new();
+    let deadline = Instant::now() + timeout;
+
+    while Instant::now() < deadline {
+        for (service_type, receiver) in &receivers {
+            while let Ok(event) = receiver.try_recv() {
+                let ServiceEvent::ServiceResolved(info) = event else {
+                    continue;
+                };
+                if !is_candidate(service_type, info.get_fullname(), info.get_hostname()) {
+                    continue;
+                }
+                for ip in info.get_addresses_v4() {
+                    addresses.insert(address(*ip, info.get_port()));
+                }
+            }
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    }
+
+    // Shutting down can fail if the daemon has already stopped, which is fine here
+    let _ = daemon.shutdown();
+
+    Ok(addresses.into_iter().collect())
+}
+
+/// Whether a service found with mDNS could be a WLED device: anything advertising
+/// `_wled._tcp`, or an `_http._tcp` service with "wled" in its name
+fn is_candidate(service_type: &str, fullname: &str, hostname: &str) -> bool {
+    if service_type.starts_with("_wled.") {
+        return true;
+    }
+
+    [fullname, hostname]
+        .iter()
+        .any(|name| name.to_lowercase().contains("wled"))
+}
+
+/// The address to save a device under, leaving off the port when it's the default
+fn address(ip: Ipv4Addr, port: u16) -> String {
+    if port == 80 || port == 0 {
+        ip.to_string()
+    } else {
+        format!("{ip}:{port}")
+    }
+}
+
+/// A name for a new device that isn't `taken` yet, adding a number to the end if needed
+/// (e.g. "WLED-2"), since devices that haven't been renamed are all called "WLED"
+pub fn unique_name(name: &str, taken: impl Fn(&str) -> bool) -> String {
+    if !taken(name) {
+        return name.to_string();
+    }
+
+    (2..)
+        .map(|n| format!("{name}-{n}"))
+        .find(|candidate| !taken(candidate))
+        .expect("There is always an unused name")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_candidate() {
+        assert!(is_candidate(
+            "_wled._tcp.local.",
+            "desk._wled._tcp.local.",
+            "desk.local."
+        ));
+        assert!(is_candidate(
+            "_http._tcp.local.",
+            "WLED-Kitchen._http._tcp.local.",
+            "kitchen.local."
+        ));
+        assert!(is_candidate(
+            "_http._tcp.local.",
+            "Kitchen._http._tcp.local.",
+            "wled-a1b2c3.local."
+        ));
+        assert!(!is_candidate(
+            "_http._tcp.local.",
+            "Printer._http._tcp.local.",
+            "printer.local."
+        ));
+    }
+
+    #[test]
+    fn test_address() {
+        let ip = Ipv4Addr::new(192, 168, 1, 50);
+        assert_eq!(address(ip, 80), "192.168.1.50");
+        assert_eq!(address(ip, 8080), "192.168.1.50:8080");
+    }
+
+    #[test]
+    fn test_unique_name() {
+        let taken = ["WLED", "WLED-2", "desk"];
+        let is_taken = |name: &str| taken.contains(&name);
+
+        assert_eq!(unique_name("kitchen", is_taken), "kitchen");
+        assert_eq!(unique_name("desk", is_taken), "desk-2");
+        assert_eq!(unique_name("WLED", is_taken), "WLED-3");
+    }
+}
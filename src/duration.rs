@@ -0,0 +1,155 @@
+use std::time::Duration;
+
+/// Parse a duration like `90s`, `15m`, `1h30m` or `2h`. A bare number is taken as seconds.
+pub fn parse_duration(value: &str) -> Result<Duration, String> {
+    let value = value.trim();
+    let invalid = || format!("Invalid duration '{value}': expected e.g. 90s, 15m or 1h30m");
+
+    if value.is_empty() {
+        return Err(invalid());
+    }
+
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Ok(Duration::from_secs(seconds));
+    }
+
+    let mut total: u64 = 0;
+    let mut number = String::new();
+    let mut last_unit = None;
+
+    for c in value.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+
+        let (rank, seconds_per_unit) = match c {
+            'h' => (0, 3600),
+            'm' => (1, 60),
+            's' => (2, 1),
+            _ => return Err(invalid()),
+        };
+
+        // Units must come in order (h, m, s) and each needs a number in front of it
+        if number.is_empty() || last_unit.is_some_and(|last| rank <= last) {
+            return Err(invalid());
+        }
+
+        let amount: u64 = number.parse().map_err(|_| invalid())?;
+        total = amount
+            .checked_mul(seconds_per_unit)
+            .and_then(|seconds| total.checked_add(seconds))
+            .ok_or_else(invalid)?;
+        number.clear();
+        last_unit = Some(rank);
+    }
+
+    if !number.is_empty() {
+        return Err(invalid());
+    }
+
+    Ok(Duration::from_secs(total))
+}
+
+/// Parse how long to wait for a device to respond, like `5s`. A timeout of nothing would
+/// give up on every device straight away, so it has to be at least a second.
+pub fn parse_timeout(value: &str) -> Result<Duration, String> {
+    let timeout = parse_duration(value)?;
+    if timeout.is_zero() {
+        return Err("Timeout must be at least 1s".to_string());
+    }
+    Ok(timeout)
+}
+
+/// Format a duration in the same style `parse_duration` accepts, e.g. `1h30m`
+pub fn format_duration(duration: Duration) -> String {
+    let total = duration.as_secs();
+    let (hours, minutes, seconds) = (total / 3600, total % 3600 / 60, total % 60);
+
+    let mut formatted = String::new();
+    if hours > 0 {
+        formatted.push_str(&format!("{hours}h"));
+    }
+    if minutes > 0 {
+        formatted.push_str(&format!("{minutes}m"));
+    }
+    if seconds > 0 || formatted.is_empty() {
+        formatted.push_str(&format!("{seconds}s"));
+    }
+    formatted
+}
+
+/// Format how long ago something happened, roughly, like `2h ago`
+pub fn format_ago(elapsed: Duration) -> String {
+    let seconds = elapsed.as_secs();
+    match seconds {
+        0..60 => "just now".to_string(),
+        60..3600 => format!("{}m ago", seconds / 60),
+        3600..86400 => format!("{}h ago", seconds / 3600),
+        _ => format!("{}d ago", seconds / 86400),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_single_units() {
+        assert_eq!(parse_duration("90s"), Ok(Duration::from_secs(90)));
+        assert_eq!(parse_duration("15m"), Ok(Duration::from_secs(900)));
+        assert_eq!(parse_duration("2h"), Ok(Duration::from_secs(7200)));
+    }
+
+    #[test]
+    fn test_parse_combined_units() {
+        assert_eq!(parse_duration("1h30m"), Ok(Duration::from_secs(5400)));
+        assert_eq!(parse_duration("1h0m5s"), Ok(Duration::from_secs(3605)));
+        assert_eq!(parse_duration("2m30s"), Ok(Duration::from_secs(150)));
+    }
+
+    #[test]
+    fn test_parse_bare_seconds() {
+        assert_eq!(parse_duration("45"), Ok(Duration::from_secs(45)));
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid() {
+        for value in ["", "m", "10x", "1m1h", "5m5m", "1h30", "-5s", "1.5m"] {
+            assert!(parse_duration(value).is_err(), "{value}");
+        }
+        assert_eq!(
+            parse_duration("soon"),
+            Err("Invalid duration 'soon': expected e.g. 90s, 15m or 1h30m".to_string())
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(Duration::from_secs(5400)), "1h30m");
+        assert_eq!(format_duration(Duration::from_secs(90)), "1m30s");
+        assert_eq!(format_duration(Duration::from_secs(3605)), "1h5s");
+        assert_eq!(format_duration(Duration::ZERO), "0s");
+    }
+
+    #[test]
+    fn test_format_ago() {
+        assert_eq!(format_ago(Duration::from_secs(5)), "just now");
+        assert_eq!(format_ago(Duration::from_secs(150)), "2m ago");
+        assert_eq!(
+            format_ago(Duration::from_secs(2 * 3600 + 59 * 60)),
+            "2h ago"
+        );
+        assert_eq!(format_ago(Duration::from_secs(3 * 86400)), "3d ago");
+    }
+
+    #[test]
+    fn test_parse_timeout() {
+        assert_eq!(parse_timeout("3s"), Ok(Duration::from_secs(3)));
+        assert_eq!(
+            parse_timeout("0s"),
+            Err("Timeout must be at least 1s".to_string())
+        );
+        assert!(parse_timeout("soon").is_err());
+    }
+}
@@ -0,0 +1,250 @@
+use std::io;
+use std::path::PathBuf;
+
+use wled_json_api_library::errors::WledJsonApiError;
+
+use crate::config;
+
+/// Something that went wrong, by what caused it, so callers can tell "device not found"
+/// from "device unreachable" without reading the message
+#[derive(Debug, thiserror::Error)]
+pub enum WldError {
+    /// A device name isn't saved, but is close to names that are
+    #[error("Device '{name}' not found{}{}", did_you_mean(similar), from_env_note(*from_env))]
+    DeviceNotFound {
+        name: String,
+        similar: Vec<String>,
+        /// Whether the name came from `$WLD_DEVICE`, rather than being typed in
+        from_env: bool,
+    },
+    /// No device was given, and there's no default device to fall back to
+    #[error("No device specified and no default device set")]
+    NoDefaultDevice,
+    /// Something asked for doesn't make sense, like a group that doesn't exist, an effect
+    /// the device doesn't have, or a segment that would go past the end of the LEDs
+    #[error("{0}")]
+    InvalidInput(String),
+    /// The config file couldn't be read or written
+    #[error("Failed to {action} config file {}: {source}", path.display())]
+    ConfigIo {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The config file isn't valid
+    #[error("Failed to read config file {}: {message}", path.display())]
+    ConfigParse { path: PathBuf, message: String },
+    /// A device couldn't be reached, or didn't answer in time
+    #[error("{}{}", describe_http(ip, source), attempts_note(*attempts))]
+    Http {
+        ip: String,
+        source: reqwest::Error,
+        /// How many times the request was made before giving up
+        attempts: u32,
+    },
+    /// A device answered, but turned the request down or didn't answer as a WLED device
+    /// would
+    #[error("{message}")]
+    DeviceRejected { ip: String, message: String },
+    /// A file other than the config, like a backup, couldn't be read or written
+    #[error("Failed to {action} '{}': {source}", path.display())]
+    Io {
+        action: &'static str,
+        path: PathBuf,
+        source: io::Error,
+    },
+    /// The MCP server couldn't start, or stopped with an error
+    #[error("MCP server failed: {0}")]
+    Server(String),
+}
+
+impl WldError {
+    /// A device name that isn't saved, with nothing similar to suggest
+    pub fn device_not_found(name: &str) -> Self {
+        WldError::DeviceNotFound {
+            name: name.to_string(),
+            similar: Vec::new(),
+            from_env: false,
+        }
+    }
+
+    /// An error from a request to the device at `ip`. An error status means the device
+    /// answered, so it's a rejection rather than the device being unreachable.
+    pub fn request(ip: &str, error: reqwest::Error) -> Self {
+        match error.status() {
+            Some(status) => WldError::status(ip, status),
+            None => WldError::Http {
+                ip: ip.to_string(),
+                source: error,
+                attempts: 1,
+            },
+        }
+    }
+
+    /// A device at `ip` that answered with an error status, saying so plainly when it
+    /// turned down the login
+    pub fn status(ip: &str, status: reqwest::StatusCode) -> Self {
+        WldError::DeviceRejected {
+            ip: ip.to_string(),
+            message: describe_status(ip, status),
+        }
+    }
+
+    /// A device at `ip` that answered with something that doesn't make sense
+    pub fn rejected(ip: &str, message: impl Into<String>) -> Self {
+        WldError::DeviceRejected {
+            ip: ip.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+impl From<String> for WldError {
+    fn from(message: String) -> Self {
+        WldError::InvalidInput(message)
+    }
+}
+
+impl From<&str> for WldError {
+    fn from(message: &str) -> Self {
+        WldError::InvalidInput(message.to_string())
+    }
+}
+
+/// Attach the address of the device a request went to to its error
+pub(crate) trait AtDevice<T> {
+    fn at(self, ip: &str) -> Result<T, WldError>;
+}
+
+impl<T> AtDevice<T> for Result<T, reqwest::Error> {
+    fn at(self, ip: &str) -> Result<T, WldError> {
+        self.map_err(|e| WldError::request(ip, e))
+    }
+}
+
+impl<T> AtDevice<T> for Result<T, WledJsonApiError> {
+    fn at(self, ip: &str) -> Result<T, WldError> {
+        self.map_err(|e| match e {
+            WledJsonApiError::ReqwestError(e) => WldError::request(ip, e),
+            WledJsonApiError::HttpError(status) => WldError::status(ip, status),
+            WledJsonApiError::SerdeError(e) => WldError::rejected(
+                ip,
+                format!("Device at {ip} sent a response that couldn't be read: {e}"),
+            ),
+            e => WldError::rejected(ip, format!("Device at {ip}: {e}")),
+        })
+    }
+}
+
+impl<T> AtDevice<T> for Result<T, serde_json::Error> {
+    fn at(self, ip: &str) -> Result<T, WldError> {
+        self.map_err(|e| {
+            WldError::rejected(
+                ip,
+                format!("Device at {ip} sent a response that couldn't be read: {e}"),
+            )
+        })
+    }
+}
+
+/// Whether a request failed because nothing is listening at the device's address
+pub(crate) fn is_connection_refused(error: &reqwest::Error) -> bool {
+    let mut source: Option<&(dyn std::error::Error + 'static)> = Some(error);
+    while let Some(e) = source {
+        if let Some(io) = e.downcast_ref::<io::Error>() {
+            return io.kind() == io::ErrorKind::ConnectionRefused;
+        }
+        source = e.source();
+    }
+    false
+}
+
+fn describe_http(ip: &str, error: &reqwest::Error) -> String {
+    if is_connection_refused(error) {
+        format!("Connection refused by device at {ip}")
+    } else if error.is_timeout() {
+        format!("Device at {ip} didn't respond in time")
+    } else {
+        format!("Failed to reach device at {ip}: {error}")
+    }
+}
+
+fn describe_status(ip: &str, status: reqwest::StatusCode) -> String {
+    if status == reqwest::StatusCode::UNAUTHORIZED {
+        format!("Authentication failed for device at {ip}: check the username, password or PIN saved with `wld add`")
+    } else {
+        format!("Device at {ip} responded with {status}")
+    }
+}
+
+fn attempts_note(attempts: u32) -> String {
+    if attempts > 1 {
+        format!(" (tried {attempts} times)")
+    } else {
+        String::new()
+    }
+}
+
+fn did_you_mean(similar: &[String]) -> String {
+    if similar.is_empty() {
+        return String::new();
+    }
+    format!(", did you mean {}?", config::quote_list(similar, " or "))
+}
+
+fn from_env_note(from_env: bool) -> String {
+    if from_env {
+        format!(" (from ${})", config::DEVICE_VARIABLE)
+    } else {
+        String::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_device_not_found_message() {
+        let error = WldError::DeviceNotFound {
+            name: "ofice".to_string(),
+            similar: vec!["office".to_string(), "offices".to_string()],
+            from_env: false,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Device 'ofice' not found, did you mean 'office' or 'offices'?"
+        );
+
+        let error = WldError::DeviceNotFound {
+            name: "attic".to_string(),
+            similar: Vec::new(),
+            from_env: true,
+        };
+        assert_eq!(
+            error.to_string(),
+            "Device 'attic' not found (from $WLD_DEVICE)"
+        );
+    }
+
+    #[test]
+    fn test_device_errors() {
+        let error = Err::<(), _>(WledJsonApiError::HttpError(
+            reqwest::StatusCode::INTERNAL_SERVER_ERROR,
+        ))
+        .at("192.168.1.40")
+        .unwrap_err();
+        assert!(matches!(&error, WldError::DeviceRejected { ip, .. } if ip == "192.168.1.40"));
+        assert_eq!(
+            error.to_string(),
+            "Device at 192.168.1.40 responded with 500 Internal Server Error"
+        );
+
+        let error = serde_json::from_str::<u8>("{")
+            .at("192.168.1.40")
+            .unwrap_err();
+        assert!(error
+            .to_string()
+            .starts_with("Device at 192.168.1.40 sent a response that couldn't be read"));
+    }
+}
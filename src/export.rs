@@ -0,0 +1,628 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use serde::{Deserialize, Serialize};
+
+use crate::address;
+use crate::config::{self, Config};
+use crate::error::WldError;
+
+/// The version of the device list format that this version of wld writes and reads
+pub const VERSION: u32 = 1;
+
+/// The saved devices, default device and groups, as written by `wld export`. Every field
+/// is always written, so the layout doesn't change with what's saved.
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceList {
+    pub version: u32,
+    #[serde(default)]
+    pub default_device: Option<String>,
+    #[serde(default)]
+    pub devices: Vec<ListedDevice>,
+    #[serde(default)]
+    pub groups: Vec<ListedGroup>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListedDevice {
+    pub name: String,
+    pub ip: String,
+    #[serde(default)]
+    pub tags: BTreeSet<String>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListedGroup {
+    pub name: String,
+    pub devices: Vec<String>,
+}
+
+/// What to do with devices that are already saved when importing a list
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Add the devices in the list, keeping saved devices and their addresses
+    Merge,
+    /// Make the saved devices, default and groups exactly those in the list
+    Replace,
+}
+
+/// A change made to the config by importing a list, other than adding tags and groups
+#[derive(Debug, PartialEq, Eq)]
+pub enum Change {
+    Added {
+        name: String,
+        ip: String,
+    },
+    /// A saved device with a different address in the list, which kept its address
+    Kept {
+        name: String,
+        ip: String,
+        ignored: String,
+    },
+    /// A saved device with a different address in the list, which took the list's
+    Moved {
+        name: String,
+        old: String,
+        new: String,
+    },
+    Removed {
+        name: String,
+    },
+    Default {
+        name: String,
+    },
+}
+
+impl fmt::Display for Change {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Change::Added { name, ip } => write!(f, "Added device '{name}' ({ip})"),
+            Change::Kept { name, ip, ignored } => write!(
+                f,
+                "Kept device '{name}' at {ip}, not {ignored} from the list (use --replace to take the list's address)"
+            ),
+            Change::Moved { name, old, new } => {
+                write!(f, "Changed device '{name}' from {old} to {new}")
+            }
+            Change::Removed { name } => write!(f, "Removed device '{name}'"),
+            Change::Default { name } => write!(f, "Set default device to '{name}'"),
+        }
+    }
+}
+
+impl DeviceList {
+    pub fn from_config(config: &Config) -> Self {
+        DeviceList {
+            version: VERSION,
+            default_device: config.default_device.clone(),
+            devices: config
+                .devices
+                .iter()
+                .map(|(name, device)| ListedDevice {
+                    name: name.clone(),
+                    ip: device.ip.clone(),
+                    tags: device.tags.clone(),
+                })
+                .collect(),
+            groups: config
+                .groups
+                .iter()
+                .map(|(name, devices)| ListedGroup {
+                    name: name.clone(),
+                    devices: devices.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("a device list can always be written as JSON")
+    }
+
+    /// Just the names and addresses of the devices, with a `name,ip` header
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("name,ip\n");
+        for device in &self.devices {
+            csv.push_str(&format!(
+                "{},{}\n",
+                csv_field(&device.name),
+                csv_field(&device.ip)
+            ));
+        }
+        csv
+    }
+
+    /// Read a list written by `to_json`, checking it all before anything is imported
+    pub fn from_json(text: &str) -> Result<Self, String> {
+        let mut list: DeviceList =
+            serde_json::from_str(text).map_err(|e| format!("Invalid device list: {e}"))?;
+        if list.version > VERSION {
+            return Err(format!(
+                "Invalid device list: it was written by a newer version of wld (list version {}, but this version understands up to {VERSION}). Upgrade wld to import it",
+                list.version
+            ));
+        }
+        if list.version == 0 {
+            return Err("Invalid device list: version 0 isn't a version of the list".to_string());
+        }
+        list.validate()?;
+        Ok(list)
+    }
+
+    /// Read `name,ip` pairs, like a spreadsheet saved as CSV. The header line is optional.
+    pub fn from_csv(text: &str) -> Result<Self, String> {
+        let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+        let mut devices = Vec::new();
+        let mut errors = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let line_number = index + 1;
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            let fields = match split_csv_line(line) {
+                Ok(fields) => fields,
+                Err(e) => {
+                    errors.push(format!("line {line_number}: {e}"));
+                    continue;
+                }
+            };
+            let is_header = fields.len() == 2
+                && fields[0].eq_ignore_ascii_case("name")
+                && fields[1].eq_ignore_ascii_case("ip");
+            if line_number == 1 && is_header {
+                continue;
+            }
+
+            match <[String; 2]>::try_from(fields) {
+                Ok([name, ip]) => devices.push(ListedDevice {
+                    name,
+                    ip,
+                    tags: BTreeSet::new(),
+                }),
+                Err(fields) => errors.push(format!(
+                    "line {line_number}: expected a name and an IP, but found {} fields",
+                    fields.len()
+                )),
+            }
+        }
+
+        if !errors.is_empty() {
+            return Err(format!("Invalid device list:\n  {}", errors.join("\n  ")));
+        }
+
+        let mut list = DeviceList {
+            version: VERSION,
+            default_device: None,
+            devices,
+            groups: Vec::new(),
+        };
+        list.validate()?;
+        Ok(list)
+    }
+
+    /// Check the list makes sense on its own, tidying addresses like `wld add` does.
+    /// Every problem is reported at once.
+    fn validate(&mut self) -> Result<(), String> {
+        let mut errors = Vec::new();
+        let mut names = BTreeSet::new();
+
+        for device in &mut self.devices {
+            let name = device.name.trim().to_string();
+            if name.is_empty() {
+                errors.push(format!("a device at '{}' has no name", device.ip));
+                continue;
+            }
+            if !names.insert(name.clone()) {
+                errors.push(format!("device '{name}' is listed more than once"));
+            }
+            match address::parse_address(&device.ip) {
+                Ok(ip) => device.ip = ip,
+                Err(e) => errors.push(format!("device '{name}': {e}")),
+            }
+            for tag in &device.tags {
+                if let Err(e) = config::parse_tag(tag) {
+                    errors.push(format!("device '{name}': {e}"));
+                }
+            }
+            device.name = name;
+        }
+
+        if let Some(name) = &self.default_device {
+            if !names.contains(name) {
+                errors.push(format!("default device '{name}' isn't in the list"));
+            }
+        }
+
+        let mut groups = BTreeSet::new();
+        for group in &self.groups {
+            let name = &group.name;
+            if !groups.insert(name) {
+                errors.push(format!("group '{name}' is listed more than once"));
+            }
+            if names.contains(name) {
+                errors.push(format!("group '{name}' has the same name as a device"));
+            }
+            if group.devices.is_empty() {
+                errors.push(format!("group '{name}' has no devices"));
+            }
+            for member in group.devices.iter().filter(|m| !names.contains(*m)) {
+                errors.push(format!(
+                    "group '{name}': device '{member}' isn't in the list"
+                ));
+            }
+        }
+
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(format!("Invalid device list:\n  {}", errors.join("\n  ")))
+        }
+    }
+
+    /// Import the list into `config`, returning what changed. The config is left as it
+    /// was if the list can't be imported, e.g. because a group has the same name as a
+    /// saved device.
+    pub fn import(&self, config: &mut Config, mode: Mode) -> Result<Vec<Change>, WldError> {
+        let mut updated = config.clone();
+        let changes = self.apply(&mut updated, mode)?;
+        *config = updated;
+        Ok(changes)
+    }
+
+    fn apply(&self, config: &mut Config, mode: Mode) -> Result<Vec<Change>, WldError> {
+        let mut changes = Vec::new();
+        let previous_default = config.default_device.clone();
+
+        if mode == Mode::Replace {
+            let listed: BTreeSet<&str> = self.devices.iter().map(|d| d.name.as_str()).collect();
+            let unlisted: Vec<String> = config
+                .devices
+                .keys()
+                .filter(|name| !listed.contains(name.as_str()))
+                .cloned()
+                .collect();
+            for name in unlisted {
+                config.remove_device(&name)?;
+                changes.push(Change::Removed { name });
+            }
+            config.groups.clear();
+        }
+
+        for device in &self.devices {
+            let name = device.name.clone();
+            match config.devices.get(&name).map(|saved| saved.ip.clone()) {
+                None => {
+                    config.add_device(name.clone(), device.ip.clone());
+                    changes.push(Change::Added {
+                        name: name.clone(),
+                        ip: device.ip.clone(),
+                    });
+                }
+                Some(ip) if ip == device.ip => {}
+                Some(ip) if mode == Mode::Merge => changes.push(Change::Kept {
+                    name: name.clone(),
+                    ip,
+                    ignored: device.ip.clone(),
+                }),
+                Some(old) => {
+                    config.add_device(name.clone(), device.ip.clone());
+                    changes.push(Change::Moved {
+                        name: name.clone(),
+                        old,
+                        new: device.ip.clone(),
+                    });
+                }
+            }
+
+            let saved = config.device_mut(&name)?;
+            if mode == Mode::Replace {
+                saved.tags.clear();
+            }
+            saved.tags.extend(device.tags.iter().cloned());
+        }
+
+        for group in &self.groups {
+            config.add_to_group(&group.name, &group.devices)?;
+        }
+
+        // Merging keeps the saved default, unless there wasn't one
+        if let Some(name) = &self.default_device {
+            if mode == Mode::Replace || previous_default.is_none() {
+                config.set_default(name)?;
+            }
+        }
+        if config.default_device != previous_default {
+            if let Some(name) = &config.default_device {
+                changes.push(Change::Default { name: name.clone() });
+            }
+        }
+
+        Ok(changes)
+    }
+}
+
+/// Quote a CSV field if it needs it
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// Split a line of CSV into fields, which may be quoted, with `""` for a quote in one
+fn split_csv_line(line: &str) -> Result<Vec<String>, String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut in_quotes = false;
+    let mut was_quoted = false;
+    let mut chars = line.trim_end_matches('\r').chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if in_quotes => {
+                if chars.peek() == Some(&'"') {
+                    chars.next();
+                    field.push('"');
+                } else {
+                    in_quotes = false;
+                }
+            }
+            '"' if !was_quoted && field.trim().is_empty() => {
+                field.clear();
+                in_quotes = true;
+                was_quoted = true;
+            }
+            ',' if !in_quotes => {
+                fields.push(csv_value(std::mem::take(&mut field), was_quoted));
+                was_quoted = false;
+            }
+            _ if was_quoted && !in_quotes => {
+                if !c.is_whitespace() {
+                    return Err("a quoted field is followed by more text".to_string());
+                }
+            }
+            _ => field.push(c),
+        }
+    }
+
+    if in_quotes {
+        return Err("a quoted field isn't closed".to_string());
+    }
+    fields.push(csv_value(field, was_quoted));
+    Ok(fields)
+}
+
+/// Spaces around a field are dropped, unless they're inside quotes
+fn csv_value(field: String, was_quoted: bool) -> String {
+    if was_quoted {
+        field
+    } else {
+        field.trim().to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> Config {
+        let mut config = Config::new();
+        config.add_device("porch".to_string(), "192.168.1.40".to_string());
+        config.add_device("kitchen".to_string(), "192.168.1.41".to_string());
+        config.add_tags("porch", &["outdoor".to_string()]).unwrap();
+        config
+            .add_to_group("downstairs", &["kitchen".to_string()])
+            .unwrap();
+        config
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let list = DeviceList::from_config(&config());
+        let json = list.to_json();
+        assert_eq!(DeviceList::from_json(&json).unwrap(), list);
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            value,
+            serde_json::json!({
+                "version": 1,
+                "default_device": "porch",
+                "devices": [
+                    {"name": "kitchen", "ip": "192.168.1.41", "tags": []},
+                    {"name": "porch", "ip": "192.168.1.40", "tags": ["outdoor"]},
+                ],
+                "groups": [{"name": "downstairs", "devices": ["kitchen"]}],
+            })
+        );
+    }
+
+    #[test]
+    fn test_invalid_json() {
+        let error =
+            DeviceList::from_json(r#"{"version": 1, "devices": [], "extra": 1}"#).unwrap_err();
+        assert!(error.contains("unknown field `extra`"), "{error}");
+
+        let error = DeviceList::from_json(r#"{"version": 2}"#).unwrap_err();
+        assert!(error.contains("newer version of wld"), "{error}");
+
+        let error = DeviceList::from_json(
+            r#"{
+                "version": 1,
+                "default_device": "attic",
+                "devices": [
+                    {"name": "porch", "ip": "192.168.1.400"},
+                    {"name": "porch", "ip": "192.168.1.40", "tags": ["front porch"]}
+                ],
+                "groups": [{"name": "outside", "devices": ["garden"]}]
+            }"#,
+        )
+        .unwrap_err();
+        assert_eq!(
+            error.lines().skip(1).map(str::trim).collect::<Vec<_>>(),
+            vec![
+                "device 'porch': Invalid device address '192.168.1.400': expected an IP address or hostname, optionally with a port, e.g. 192.168.1.40:8080",
+                "device 'porch' is listed more than once",
+                "device 'porch': Invalid tag 'front porch': tags can't be empty or contain spaces or commas",
+                "default device 'attic' isn't in the list",
+                "group 'outside': device 'garden' isn't in the list",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_csv() {
+        let csv = DeviceList::from_config(&config()).to_csv();
+        assert_eq!(csv, "name,ip\nkitchen,192.168.1.41\nporch,192.168.1.40\n");
+
+        let list = DeviceList::from_csv(&csv).unwrap();
+        assert_eq!(list.devices.len(), 2);
+        assert_eq!(list.devices[1].name, "porch");
+
+        // Without a header, quoted, with Windows line endings and a byte order mark
+        let list = DeviceList::from_csv(
+            "\u{feff}\"Living room, left\",192.168.1.50\r\n\"say \"\"hi\"\"\" , http://192.168.1.51:80/\r\n",
+        )
+        .unwrap();
+        let pairs: Vec<(&str, &str)> = list
+            .devices
+            .iter()
+            .map(|d| (d.name.as_str(), d.ip.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("Living room, left", "192.168.1.50"),
+                ("say \"hi\"", "192.168.1.51")
+            ]
+        );
+        assert_eq!(csv_field("say \"hi\""), "\"say \"\"hi\"\"\"");
+
+        let error = DeviceList::from_csv("name,ip\nporch\ntv,1,2\n\"open,1").unwrap_err();
+        assert_eq!(
+            error,
+            "Invalid device list:\n  line 2: expected a name and an IP, but found 1 fields\n  line 3: expected a name and an IP, but found 3 fields\n  line 4: a quoted field isn't closed"
+        );
+    }
+
+    fn list(json: &str) -> DeviceList {
+        DeviceList::from_json(json).unwrap()
+    }
+
+    #[test]
+    fn test_merge() {
+        let mut config = config();
+        let list = list(
+            r#"{
+                "version": 1,
+                "default_device": "tv",
+                "devices": [
+                    {"name": "porch", "ip": "192.168.1.99", "tags": ["front"]},
+                    {"name": "tv", "ip": "192.168.1.42"}
+                ],
+                "groups": [{"name": "downstairs", "devices": ["tv"]}]
+            }"#,
+        );
+
+        let changes = list.import(&mut config, Mode::Merge).unwrap();
+        assert_eq!(
+            changes,
+            vec![
+                Change::Kept {
+                    name: "porch".to_string(),
+                    ip: "192.168.1.40".to_string(),
+                    ignored: "192.168.1.99".to_string(),
+                },
+                Change::Added {
+                    name: "tv".to_string(),
+                    ip: "192.168.1.42".to_string(),
+                },
+            ]
+        );
+        assert_eq!(config.devices["porch"].ip, "192.168.1.40");
+        assert_eq!(
+            config.devices["porch"].tags,
+            BTreeSet::from(["front".to_string(), "outdoor".to_string()])
+        );
+        assert_eq!(config.groups["downstairs"], vec!["kitchen", "tv"]);
+        assert_eq!(config.default_device.as_deref(), Some("porch"));
+    }
+
+    #[test]
+    fn test_merge_into_empty_config_takes_default() {
+        let mut config = Config::new();
+        let changes = DeviceList::from_config(&self::config())
+            .import(&mut config, Mode::Merge)
+            .unwrap();
+        assert_eq!(
+            changes.last(),
+            Some(&Change::Default {
+                name: "porch".to_string()
+            })
+        );
+        assert_eq!(config.default_device.as_deref(), Some("porch"));
+        assert_eq!(config.devices.len(), 2);
+    }
+
+    #[test]
+    fn test_replace() {
+        let mut config = config();
+        let list = list(
+            r#"{
+                "version": 1,
+                "default_device": "tv",
+                "devices": [
+                    {"name": "porch", "ip": "192.168.1.99", "tags": ["front"]},
+                    {"name": "tv", "ip": "192.168.1.42"}
+                ]
+            }"#,
+        );
+
+        let changes = list.import(&mut config, Mode::Replace).unwrap();
+        let messages: Vec<String> = changes.iter().map(|c| c.to_string()).collect();
+        assert_eq!(
+            messages,
+            vec![
+                "Removed device 'kitchen'",
+                "Changed device 'porch' from 192.168.1.40 to 192.168.1.99",
+                "Added device 'tv' (192.168.1.42)",
+                "Set default device to 'tv'",
+            ]
+        );
+        assert_eq!(
+            config.devices["porch"].tags,
+            BTreeSet::from(["front".to_string()])
+        );
+        assert!(config.groups.is_empty());
+        assert_eq!(config.default_device.as_deref(), Some("tv"));
+    }
+
+    #[test]
+    fn test_failed_import_changes_nothing() {
+        let mut config = config();
+        config.add_device("outside".to_string(), "192.168.1.43".to_string());
+        let before = toml::to_string(&config).unwrap();
+
+        // The group can't be saved, because a device already has its name
+        let list = list(
+            r#"{
+                "version": 1,
+                "devices": [{"name": "garden", "ip": "192.168.1.44"}],
+                "groups": [{"name": "outside", "devices": ["garden"]}]
+            }"#,
+        );
+        let error = list.import(&mut config, Mode::Merge).unwrap_err();
+        assert!(matches!(error, WldError::InvalidInput(_)), "{error}");
+        assert!(
+            error.to_string().contains("already named 'outside'"),
+            "{error}"
+        );
+        assert_eq!(toml::to_string(&config).unwrap(), before);
+    }
+}
@@ -0,0 +1,103 @@
+use std::time::Duration;
+
+/// How often to send a brightness update during a fade
+const STEP_INTERVAL: Duration = Duration::from_secs(2);
+
+/// A brightness update to send partway through a fade
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Step {
+    /// When to send the update, relative to the start of the fade
+    pub at: Duration,
+    pub brightness: u8,
+}
+
+/// The updates that make up a fade
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plan {
+    pub steps: Vec<Step>,
+    /// WLED transition time (in 100ms units) to send with each step, so the device
+    /// fades smoothly from one step to the next
+    pub transition: u8,
+}
+
+/// Plan a fade from `start` to `target` brightness lasting `over`.
+///
+/// Each step is sent at the start of its interval with a transition covering the
+/// interval, so the device reaches `target` when `over` has passed. There are never more
+/// steps than brightness levels to pass through.
+pub fn plan(start: u8, target: u8, over: Duration) -> Plan {
+    let delta = i32::from(target) - i32::from(start);
+    if delta == 0 {
+        return Plan {
+            steps: Vec::new(),
+            transition: 0,
+        };
+    }
+
+    let intervals = over.as_millis().div_ceil(STEP_INTERVAL.as_millis()).max(1);
+    let count = intervals.min(delta.unsigned_abs() as u128) as u32;
+    let interval = over / count;
+
+    let steps = (1..=count)
+        .map(|i| {
+            let offset = (delta * i as i32) as f64 / f64::from(count);
+            Step {
+                at: interval * (i - 1),
+                brightness: (f64::from(start) + offset).round() as u8,
+            }
+        })
+        .collect();
+
+    let transition = (interval.as_millis() / 100).min(u128::from(u8::MAX)) as u8;
+
+    Plan { steps, transition }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_plan_interpolates_to_target() {
+        let plan = plan(128, 28, Duration::from_secs(8));
+        let brightness: Vec<u8> = plan.steps.iter().map(|s| s.brightness).collect();
+        assert_eq!(brightness, vec![103, 78, 53, 28]);
+        assert_eq!(plan.transition, 20);
+    }
+
+    #[test]
+    fn test_plan_spaces_steps_evenly() {
+        let plan = plan(0, 255, Duration::from_secs(3));
+        let times: Vec<Duration> = plan.steps.iter().map(|s| s.at).collect();
+        assert_eq!(times, vec![Duration::ZERO, Duration::from_millis(1500)]);
+        assert_eq!(plan.transition, 15);
+    }
+
+    #[test]
+    fn test_plan_never_has_more_steps_than_levels() {
+        let plan = plan(10, 13, Duration::from_secs(15 * 60));
+        let brightness: Vec<u8> = plan.steps.iter().map(|s| s.brightness).collect();
+        assert_eq!(brightness, vec![11, 12, 13]);
+        assert_eq!(plan.steps[1].at, Duration::from_secs(300));
+        // Transitions are capped at WLED's maximum
+        assert_eq!(plan.transition, 255);
+    }
+
+    #[test]
+    fn test_plan_zero_duration_is_one_step() {
+        let plan = plan(200, 50, Duration::ZERO);
+        assert_eq!(
+            plan.steps,
+            vec![Step {
+                at: Duration::ZERO,
+                brightness: 50
+            }]
+        );
+        assert_eq!(plan.transition, 0);
+    }
+
+    #[test]
+    fn test_plan_nothing_to_do() {
+        assert!(plan(80, 80, Duration::from_secs(60)).steps.is_empty());
+    }
+}
@@ -0,0 +1,85 @@
+/// Number of single-character edits needed to turn `a` into `b`
+pub fn levenshtein(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1; b.len() + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(ca != *cb);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        previous = current;
+    }
+
+    previous[b.len()]
+}
+
+/// Candidates that look like plausible misspellings or abbreviations of `target`,
+/// closest first. Comparison is case-insensitive.
+pub fn closest_matches<'a, I>(target: &str, candidates: I, limit: usize) -> Vec<&'a str>
+where
+    I: IntoIterator<Item = &'a str>,
+{
+    let target = target.to_lowercase();
+    let max_distance = (target.chars().count() / 3).max(2);
+
+    let mut scored: Vec<(usize, &str)> = candidates
+        .into_iter()
+        .filter_map(|candidate| {
+            let lower = candidate.to_lowercase();
+            let distance = if lower.starts_with(&target) {
+                0
+            } else {
+                levenshtein(&target, &lower)
+            };
+            (distance <= max_distance).then_some((distance, candidate))
+        })
+        .collect();
+
+    scored.sort();
+    scored.dedup_by(|a, b| a.1 == b.1);
+    scored
+        .into_iter()
+        .take(limit)
+        .map(|(_, candidate)| candidate)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_levenshtein() {
+        assert_eq!(levenshtein("", ""), 0);
+        assert_eq!(levenshtein("red", "red"), 0);
+        assert_eq!(levenshtein("red", "reed"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "blue"), 4);
+    }
+
+    #[test]
+    fn test_closest_matches_orders_by_distance() {
+        let candidates = ["green", "red", "reed", "blue"];
+        assert_eq!(closest_matches("rde", candidates, 3), vec!["red", "reed"]);
+    }
+
+    #[test]
+    fn test_closest_matches_includes_prefixes() {
+        let candidates = ["bedroom", "bathroom", "office"];
+        assert_eq!(closest_matches("bed", candidates, 3), vec!["bedroom"]);
+    }
+
+    #[test]
+    fn test_closest_matches_respects_limit() {
+        let candidates = ["aa", "ab", "ac", "ad"];
+        assert_eq!(closest_matches("a", candidates, 2).len(), 2);
+    }
+
+    #[test]
+    fn test_closest_matches_no_matches() {
+        let candidates = ["living_room", "kitchen"];
+        assert!(closest_matches("zzz", candidates, 3).is_empty());
+    }
+}
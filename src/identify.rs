@@ -0,0 +1,114 @@
+use std::time::Duration;
+
+use serde_json::{json, Map, Value};
+
+/// How long the strip stays white, then off, in each blink
+pub const BLINK_INTERVAL: Duration = Duration::from_millis(400);
+
+/// Top-level state fields that `restore` puts back
+const STATE_FIELDS: [&str; 3] = ["on", "bri", "mainseg"];
+
+/// Segment fields that `restore` puts back: everything that decides what a segment looks
+/// like, but not its bounds, which blinking doesn't touch
+const SEGMENT_FIELDS: [&str; 14] = [
+    "id", "on", "bri", "col", "fx", "sx", "ix", "pal", "c1", "c2", "c3", "cct", "rev", "mi",
+];
+
+/// The segment IDs in a device's state
+fn segment_ids(state: &Value) -> Vec<Value> {
+    state["seg"]
+        .as_array()
+        .map(|segments| segments.iter().map(|s| s["id"].clone()).collect())
+        .unwrap_or_default()
+}
+
+/// A state update that turns every segment of the device solid white at full brightness
+pub fn white(state: &Value) -> Value {
+    let segments: Vec<Value> = segment_ids(state)
+        .into_iter()
+        .map(|id| json!({"id": id, "on": true, "bri": 255, "fx": 0, "col": [[255, 255, 255]]}))
+        .collect();
+
+    json!({"on": true, "bri": 255, "tt": 0, "seg": segments})
+}
+
+/// A state update that turns the device off straight away
+pub fn off() -> Value {
+    json!({"on": false, "tt": 0})
+}
+
+/// A state update that puts back the state a device reported before blinking, copying
+/// only the fields blinking changes so nothing else is disturbed
+pub fn restore(state: &Value) -> Value {
+    let pick = |from: &Value, fields: &[&str]| -> Map<String, Value> {
+        fields
+            .iter()
+            .filter_map(|&field| Some((field.to_string(), from.get(field)?.clone())))
+            .collect()
+    };
+
+    let mut update = pick(state, &STATE_FIELDS);
+    update.insert("tt".to_string(), json!(0));
+    if let Some(segments) = state["seg"].as_array() {
+        let segments: Vec<Value> = segments
+            .iter()
+            .map(|segment| Value::Object(pick(segment, &SEGMENT_FIELDS)))
+            .collect();
+        update.insert("seg".to_string(), Value::Array(segments));
+    }
+
+    Value::Object(update)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state() -> Value {
+        json!({
+            "on": true, "bri": 90, "transition": 7, "ps": 3, "mainseg": 1,
+            "seg": [
+                {"id": 0, "start": 0, "stop": 10, "on": true, "bri": 255, "fx": 9, "sx": 40,
+                 "col": [[255, 0, 0], [0, 0, 0], [0, 0, 0]], "pal": 2, "n": "left"},
+                {"id": 1, "start": 10, "stop": 30, "on": false, "bri": 128, "fx": 0,
+                 "col": [[0, 0, 255]]}
+            ]
+        })
+    }
+
+    #[test]
+    fn test_white_covers_every_segment() {
+        let update = white(&state());
+
+        assert_eq!(update["on"], true);
+        assert_eq!(update["tt"], 0);
+        let segments = update["seg"].as_array().unwrap();
+        assert_eq!(segments.len(), 2);
+        assert_eq!(segments[1]["id"], 1);
+        assert_eq!(segments[1]["on"], true);
+        assert_eq!(segments[1]["col"], json!([[255, 255, 255]]));
+    }
+
+    #[test]
+    fn test_restore() {
+        assert_eq!(
+            restore(&state()),
+            json!({
+                "on": true, "bri": 90, "mainseg": 1, "tt": 0,
+                "seg": [
+                    {"id": 0, "on": true, "bri": 255, "fx": 9, "sx": 40,
+                     "col": [[255, 0, 0], [0, 0, 0], [0, 0, 0]], "pal": 2},
+                    {"id": 1, "on": false, "bri": 128, "fx": 0, "col": [[0, 0, 255]]}
+                ]
+            })
+        );
+    }
+
+    #[test]
+    fn test_restore_without_segments() {
+        assert_eq!(
+            restore(&json!({"on": false, "bri": 10})),
+            json!({"on": false, "bri": 10, "tt": 0})
+        );
+    }
+}
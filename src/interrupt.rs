@@ -0,0 +1,42 @@
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::OnceLock;
+use std::time::{Duration, Instant};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+static HANDLER: OnceLock<Result<(), String>> = OnceLock::new();
+
+/// Catch Ctrl-C so long-running commands can stop cleanly instead of being killed.
+/// Safe to call more than once.
+pub fn catch_ctrl_c() -> Result<(), String> {
+    HANDLER
+        .get_or_init(|| {
+            ctrlc::set_handler(|| INTERRUPTED.store(true, Ordering::SeqCst))
+                .map_err(|e| format!("Failed to set Ctrl-C handler: {e}"))
+        })
+        .clone()
+}
+
+/// Whether Ctrl-C has been pressed since `catch_ctrl_c` was called
+pub fn interrupted() -> bool {
+    INTERRUPTED.load(Ordering::SeqCst)
+}
+
+/// Forget an earlier Ctrl-C, so the next command in a REPL session isn't stopped by it
+pub fn reset() {
+    INTERRUPTED.store(false, Ordering::SeqCst);
+}
+
+/// Sleep for `duration`, waking early if Ctrl-C is pressed. Returns false if interrupted.
+pub fn sleep(duration: Duration) -> bool {
+    let deadline = Instant::now() + duration;
+
+    while !interrupted() {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        if remaining.is_zero() {
+            return true;
+        }
+        std::thread::sleep(remaining.min(Duration::from_millis(100)));
+    }
+
+    false
+}
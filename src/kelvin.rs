@@ -0,0 +1,69 @@
+/// The warmest color temperature WLED maps onto its CCT scale
+pub const MIN_KELVIN: u16 = 1900;
+/// The coolest color temperature WLED maps onto its CCT scale
+pub const MAX_KELVIN: u16 = 10091;
+
+/// Convert a color temperature in Kelvin to WLED's 0-255 CCT scale, the same way WLED
+/// does when it's given Kelvin: each CCT step covers 32K, starting from 1900K
+pub fn kelvin_to_cct(kelvin: u16) -> Result<u8, String> {
+    if !(MIN_KELVIN..=MAX_KELVIN).contains(&kelvin) {
+        return Err(format!(
+            "Color temperature {kelvin}K is out of range: must be between {MIN_KELVIN}K and {MAX_KELVIN}K"
+        ));
+    }
+
+    Ok(((kelvin - MIN_KELVIN) >> 5) as u8)
+}
+
+/// Parse a color temperature in Kelvin, with or without a trailing `K` (e.g. `4500K`),
+/// into WLED's CCT scale
+pub fn parse_kelvin(value: &str) -> Result<u8, String> {
+    let value = value.trim();
+    let number = value
+        .strip_suffix(['K', 'k'])
+        .unwrap_or(value)
+        .parse::<u16>()
+        .map_err(|_| format!("Invalid color temperature '{value}': expected Kelvin, e.g. 4500K"))?;
+
+    kelvin_to_cct(number)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_boundaries() {
+        assert_eq!(kelvin_to_cct(1900), Ok(0));
+        assert_eq!(kelvin_to_cct(6500), Ok(143));
+        assert_eq!(kelvin_to_cct(10091), Ok(255));
+    }
+
+    #[test]
+    fn test_midpoints() {
+        assert_eq!(kelvin_to_cct(2700), Ok(25));
+        assert_eq!(kelvin_to_cct(4500), Ok(81));
+        assert_eq!(kelvin_to_cct(5995), Ok(127));
+    }
+
+    #[test]
+    fn test_out_of_range() {
+        assert_eq!(
+            kelvin_to_cct(1899),
+            Err(
+                "Color temperature 1899K is out of range: must be between 1900K and 10091K"
+                    .to_string()
+            )
+        );
+        assert!(kelvin_to_cct(10092).is_err());
+    }
+
+    #[test]
+    fn test_parse_kelvin() {
+        assert_eq!(parse_kelvin("4500K"), Ok(81));
+        assert_eq!(parse_kelvin("4500k"), Ok(81));
+        assert_eq!(parse_kelvin("4500"), Ok(81));
+        assert!(parse_kelvin("warm").is_err());
+        assert!(parse_kelvin("99999K").is_err());
+    }
+}
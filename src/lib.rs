@@ -0,0 +1,64 @@
+//! Control WLED lights from Rust, with the same code as the `wld` command line tool.
+//!
+//! Devices are saved in wld's config file, which [`config::Config`] loads and changes. The
+//! functions in [`device`] control a device given by saved name or address, falling back
+//! to the default device like `wld --device` does, and return what they did rather than
+//! printing it. Errors are a [`WldError`], which says what went wrong:
+//!
+//! ```no_run
+//! use wld::brightness::BrightnessValue;
+//! use wld::device;
+//!
+//! # fn main() -> Result<(), Box<dyn std::error::Error>> {
+//! let ip = device::set_device_power(Some("porch"), true, None)?;
+//! let (_, _, brightness) =
+//!     device::set_device_brightness(Some("porch"), BrightnessValue::Absolute(128), None, false)?;
+//! println!("Turned on device at {ip} at brightness {brightness}");
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod address;
+pub mod api;
+pub mod backup;
+pub mod brightness;
+pub mod catalog;
+pub mod cct;
+pub mod check;
+pub mod clone;
+pub mod color;
+pub mod color_names;
+pub mod config;
+/// Controlling devices: turning them on and off, setting colors, effects and presets,
+/// and reading their status
+pub mod device;
+pub mod diff;
+pub mod discover;
+pub mod duration;
+pub mod error;
+pub mod export;
+pub mod fade;
+pub mod fuzzy;
+pub mod identify;
+pub mod interrupt;
+pub mod kelvin;
+pub mod nightlight;
+pub mod output;
+pub mod ping;
+pub mod random;
+pub mod retry;
+pub mod scene;
+pub mod schedule;
+pub mod segment;
+pub mod setup;
+pub mod sunrise;
+/// Keeping track of when saved devices were last seen, and their firmware versions
+pub mod track;
+pub mod transition;
+pub mod watch;
+
+/// The MCP server behind `wld mcp`
+#[cfg(feature = "mcp")]
+pub mod mcp;
+
+pub use error::WldError;
@@ -1,19 +1,93 @@
-mod config;
+mod completions;
+mod repl;
+mod script;
+mod table;
 
-#[cfg(feature = "mcp")]
-mod mcp;
+use clap::{CommandFactory, Parser, Subcommand, ValueEnum};
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Duration;
+use table::{ColorChoice, Style, Table};
+use wld::brightness::{self, BrightnessArg, BrightnessValue};
+use wld::catalog::Selector;
+use wld::color::Rgb;
+use wld::config::{AddOutcome, Config};
+use wld::device::{
+    apply_device_preset, apply_scene, backup_device, capture_scene, client_options,
+    clone_to_device, connect_to_device, expand_groups, fade_device_brightness, get_clone_source,
+    get_device_details, get_device_effects, get_device_info, get_device_live_status,
+    get_device_nodes, get_device_palettes, get_device_playlist, get_device_power,
+    get_device_presets, get_device_segments, get_device_state_json,
+    get_device_status_and_brightness, get_device_statuses, get_device_statuses_in_detail,
+    get_device_sync, get_device_wifi, identify_device, join_wifi, preview_device_sunrise,
+    randomize_device, reboot_device, restore_device, save_device_preset, send_device_state_json,
+    set_device_brightness, set_device_cct, set_device_color, set_device_effect,
+    set_device_live_override, set_device_main_segment, set_device_off_timer, set_device_palette,
+    set_device_power, set_device_segment_bounds, set_device_segment_orientation, set_device_sync,
+    set_device_white, start_device_playlist, start_device_sunrise, stop_device_playlist,
+    toggle_device_power, turn_on_device, update_device_segment, verify_device, DeviceDetails,
+    DeviceSegments, DeviceStatus, RestoreStep, RunningPlaylist, SceneOutcome, TurnedOn,
+    STATUS_CHECKS,
+};
+use wld::segment::{Orientation, SegmentChange};
+use wld::WldError;
+use wld::{
+    address, api, backup, catalog, cct, check, color, config, diff, discover, duration, export,
+    interrupt, kelvin, nightlight, output, ping, schedule, setup, sunrise, track, transition,
+    watch,
+};
 
-use clap::{Parser, Subcommand};
-use config::Config;
-use wled_json_api_library::structures::state::State;
-use wled_json_api_library::wled::Wled;
+/// What each exit status means, shown at the end of `wld --help`. Some commands, like
+/// `wld status -d` and `wld diff`, have their own statuses, described in their help.
+const EXIT_STATUS_HELP: &str = "\
+Exit status:
+  0  Success
+  1  A mistake in the command or the config, like a device that isn't saved
+  2  A device couldn't be reached, or didn't respond in time
+  3  A device responded, but turned the request down
+  4  The config file couldn't be read or written, or isn't valid
+  5  Some of the devices a command ran against failed or couldn't be reached";
 
 #[derive(Parser)]
 #[command(name = "wld")]
 #[command(about = "Control WLED lights from your terminal", long_about = None)]
+#[command(after_help = EXIT_STATUS_HELP)]
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+    /// Config file to use instead of ~/.config/wld/config.toml. Can also be set with
+    /// $WLD_CONFIG.
+    #[arg(long, global = true, value_name = "PATH")]
+    config: Option<PathBuf>,
+    /// Profile to use, like `work`, instead of the default one. Can also be set with
+    /// $WLD_PROFILE.
+    #[arg(
+        long,
+        global = true,
+        value_name = "NAME",
+        value_parser = config::parse_profile,
+        conflicts_with = "config"
+    )]
+    profile: Option<String>,
+    /// How long to wait for a device to respond before giving up on it, e.g. 2s. Can
+    /// also be set with `timeout` in the config, for every device or just one. [default:
+    /// 5s]
+    #[arg(long, global = true, value_name = "DURATION", value_parser = duration::parse_timeout)]
+    timeout: Option<Duration>,
+    /// How many times to ask a device again when a request doesn't reach it, waiting a
+    /// little longer each time. Can also be set with `retries` in the config, for every
+    /// device or just one. [default: 1]
+    #[arg(long, global = true, value_name = "N")]
+    retries: Option<u32>,
+    /// Log each request to a device to stderr, with its status and how long it took. Give
+    /// it twice (-vv) to log what was sent and received too, leaving out PINs and passwords.
+    #[arg(short, long, global = true, action = clap::ArgAction::Count)]
+    verbose: u8,
+    /// Don't save when each device was last seen, or its firmware version, in the config.
+    /// They're shown by `wld ls --long`.
+    #[arg(long, global = true)]
+    no_track: bool,
 }
 
 #[derive(Subcommand)]
@@ -22,8 +96,30 @@ enum Commands {
     Add {
         /// Name for the device
         name: String,
-        /// IP address of the device
+        /// IP address or hostname of the device, with a port if it isn't on 80
+        /// (e.g. 192.168.1.40 or 192.168.1.40:8080), or an https:// URL for a device
+        /// behind an HTTPS proxy
+        #[arg(value_parser = address::parse_address)]
         ip: String,
+        /// Don't check the device's TLS certificate, e.g. if it's self-signed
+        #[arg(long)]
+        insecure: bool,
+        /// Username to log in with, for a device behind a proxy that asks for one
+        #[arg(long)]
+        username: Option<String>,
+        /// Password to log in with
+        #[arg(long, requires = "username")]
+        password: Option<String>,
+        /// The device's settings PIN, if it has one
+        #[arg(long, value_parser = config::parse_pin)]
+        pin: Option<String>,
+        /// Check the device answers like a WLED device before saving it
+        #[arg(long)]
+        verify: bool,
+        /// Replace a device that's already saved with this name without asking, and with
+        /// --verify, save the device even if it doesn't answer like a WLED device
+        #[arg(long)]
+        force: bool,
     },
     /// Delete a saved device
     Delete {
@@ -31,222 +127,3607 @@ enum Commands {
         name: String,
     },
     /// List all saved devices
-    Ls,
+    Ls {
+        /// Print the devices as JSON
+        #[arg(long)]
+        json: bool,
+        /// Show more about each device, like what it turns on with, and when it was last
+        /// seen and which firmware it was running, without asking it
+        #[arg(short, long, conflicts_with = "json")]
+        long: bool,
+        /// Only list devices with this tag. Give it more than once for devices with all
+        /// of the tags
+        #[arg(long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+        /// Check whether each device is reachable, and whether it's on, all at once
+        #[arg(long)]
+        check: bool,
+        /// Exit with a non-zero status if any device is unreachable (with --check)
+        #[arg(long, requires = "check")]
+        fail_unreachable: bool,
+        /// When to color the list
+        #[arg(
+            long,
+            value_name = "WHEN",
+            default_value = "auto",
+            conflicts_with = "json"
+        )]
+        color: ColorChoice,
+    },
     /// Set the default device
     SetDefault {
         /// Name of the device to set as default
         name: String,
     },
-    /// Turn device on
-    On {
+    /// Set what `wld on` does for a saved device when it isn't told otherwise
+    Set {
+        /// Name of the device
+        name: String,
+        /// Brightness to turn on at, unless `wld on` is given --brightness (1-255)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..))]
+        default_brightness: Option<u8>,
+        /// Preset to apply when turning on, unless `wld on` is given --color
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=250))]
+        default_preset: Option<u8>,
+        /// Forget the device's default brightness and preset
+        #[arg(long, conflicts_with_all = ["default_brightness", "default_preset"])]
+        clear: bool,
+    },
+    /// Find WLED devices on the local network with mDNS
+    Discover {
+        /// How long to search for, e.g. 5s
+        #[arg(long, default_value = "5s", value_parser = duration::parse_duration)]
+        wait: Duration,
+        /// Offer to save the devices found, using their reported names
+        #[arg(long)]
+        add: bool,
+        /// Save the devices found without asking (with --add)
+        #[arg(short, long, requires = "add")]
+        yes: bool,
+    },
+    /// Set up a new device: find it in setup mode, save it, and connect it to your Wi-Fi
+    ///
+    /// A device that hasn't joined a Wi-Fi network yet opens its own, called WLED-AP,
+    /// where it's at 4.3.2.1. Connect to that network first. Once the device has been
+    /// given your network's details, it restarts to join it, and is looked for there so
+    /// its new address can be saved.
+    Setup {
+        /// Name to save the device as (defaults to the name it reports)
+        name: Option<String>,
+        /// Wi-Fi network for the device to join (asks if not given)
+        #[arg(long)]
+        ssid: Option<String>,
+        /// Password for the Wi-Fi network (asks if not given)
+        #[arg(long, requires = "ssid")]
+        wifi_password: Option<String>,
+        /// Where the device in setup mode is
+        #[arg(long, default_value = setup::AP_ADDRESS)]
+        address: String,
+        /// How long to look for the device once it has joined your network, e.g. 2m
+        #[arg(long, default_value = "2m", value_parser = duration::parse_duration)]
+        wait: Duration,
+        /// Don't ask anything, leaving the device off your network unless --ssid is given
+        #[arg(short, long)]
+        yes: bool,
+    },
+    /// List the other WLED devices a device has discovered on the network
+    Nodes {
         /// Device name or IP (uses default if not specified)
         #[arg(short, long)]
         device: Option<String>,
+        /// Offer to save the devices listed, using their reported names
+        #[arg(long)]
+        add: bool,
+        /// Save the devices listed without asking (with --add)
+        #[arg(short, long, requires = "add")]
+        yes: bool,
+    },
+    /// Manage named groups of devices, which on, off and brightness can target with -d
+    Group {
+        #[command(subcommand)]
+        action: GroupAction,
+    },
+    /// Tag devices, e.g. with `outdoor` or `room=bedroom`, so ls, status, on, off and
+    /// brightness can pick them out with --tag
+    Tag {
+        #[command(subcommand)]
+        action: TagAction,
+    },
+    /// Save and apply scenes: how several devices should look, applied together
+    Scene {
+        #[command(subcommand)]
+        action: SceneAction,
+    },
+    /// Show, find or edit the config file
+    Config {
+        #[command(subcommand)]
+        action: ConfigAction,
+    },
+    /// Manage profiles: separate sets of devices, groups and scenes, like `home` and
+    /// `work`, picked with --profile
+    Profile {
+        #[command(subcommand)]
+        action: ProfileAction,
+    },
+    /// Print the saved devices, default device, groups and tags, to import elsewhere
+    Export {
+        /// Write JSON with everything, or CSV with just names and IPs
+        #[arg(long, value_enum, default_value = "json")]
+        format: ListFormat,
+    },
+    /// Save the devices in a list written by `wld export`, or a CSV file of name,ip pairs
+    Import {
+        /// File to import, or - to read it from stdin
+        file: String,
+        /// Keep devices that are already saved, and their IPs (the default)
+        #[arg(long, conflicts_with = "replace")]
+        merge: bool,
+        /// Remove saved devices that aren't in the list, and take the list's IPs, default
+        /// device and groups
+        #[arg(long)]
+        replace: bool,
+        /// Format of the file
+        #[arg(long, value_enum, default_value = "json")]
+        format: ListFormat,
+    },
+    /// Make devices look like another device right now, by copying its state
+    Clone {
+        /// Device name or IP to copy from
+        #[arg(long)]
+        from: String,
+        /// Device name, group or IP to copy to (can be given more than once)
+        #[arg(long, required = true)]
+        to: Vec<String>,
+        /// Print the JSON that would be sent to each device, without sending it
+        #[arg(long)]
+        dry_run: bool,
+    },
+    /// Turn device on
+    On {
+        /// Device name, group or IP (uses default if not specified)
+        #[arg(short, long, conflicts_with = "all")]
+        device: Option<String>,
+        /// Apply to every configured device
+        #[arg(long)]
+        all: bool,
+        /// Apply to every configured device with this tag. Give it more than once for
+        /// devices with all of the tags
+        #[arg(long = "tag", value_name = "TAG", conflicts_with_all = ["device", "all"])]
+        tags: Vec<String>,
+        /// Fade to the new state over this many seconds (e.g. 1.5)
+        #[arg(short, long, value_parser = transition::parse_transition)]
+        transition: Option<u8>,
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+        /// Brightness to turn on at (1-255, a percentage like 50%, or a change like +20),
+        /// sent along with turning on so the old brightness doesn't flash first
+        #[arg(short, long, allow_hyphen_values = true)]
+        brightness: Option<BrightnessArg>,
+        /// Color to turn on with, as #RRGGBB, RRGGBB, r,g,b or a CSS color name
+        #[arg(short, long, value_parser = color::parse_color)]
+        color: Option<Rgb>,
+        /// Wait this long before turning the device on, e.g. 20m or 1h30m
+        #[arg(long, value_parser = duration::parse_duration, conflicts_with_all = ["all", "tags", "transition", "json", "brightness", "color"])]
+        after: Option<Duration>,
+        /// With --after, wait here rather than using the device's nightlight timer. The
+        /// nightlight can only turn devices off, so `on` always waits here.
+        #[arg(long, requires = "after")]
+        local: bool,
     },
     /// Turn device off
     Off {
+        /// Device name, group or IP (uses default if not specified)
+        #[arg(short, long, conflicts_with = "all")]
+        device: Option<String>,
+        /// Apply to every configured device
+        #[arg(long)]
+        all: bool,
+        /// Apply to every configured device with this tag. Give it more than once for
+        /// devices with all of the tags
+        #[arg(long = "tag", value_name = "TAG", conflicts_with_all = ["device", "all"])]
+        tags: Vec<String>,
+        /// Fade to the new state over this many seconds (e.g. 1.5)
+        #[arg(short, long, value_parser = transition::parse_transition)]
+        transition: Option<u8>,
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+        /// Wait this long before turning the device off, e.g. 20m or 1h30m. Uses the
+        /// device's nightlight timer when the delay is a whole number of minutes, up to 255.
+        #[arg(long, value_parser = duration::parse_duration, conflicts_with_all = ["all", "tags", "transition", "json"])]
+        after: Option<Duration>,
+        /// With --after, wait here rather than using the device's nightlight timer
+        #[arg(long, requires = "after")]
+        local: bool,
+    },
+    /// Toggle device power
+    Toggle {
         /// Device name or IP (uses default if not specified)
         #[arg(short, long)]
         device: Option<String>,
     },
+    /// Run wld commands from a file, one per line, e.g. `on -d porch`
+    Run {
+        /// Script to run, or - to read it from stdin. Blank lines and lines starting with #
+        /// are skipped, and `sleep <duration>` pauses between commands.
+        file: String,
+        /// Carry on past lines that fail, instead of stopping at the first one
+        #[arg(long)]
+        keep_going: bool,
+    },
+    /// Run a command at a time of day, e.g. `wld at 22:30 off -d bedroom`
+    At {
+        /// Local time to run the command, e.g. 22:30. If it has already passed today, the
+        /// command runs tomorrow.
+        #[arg(value_parser = schedule::parse_time)]
+        time: chrono::NaiveTime,
+        /// Wait here rather than using the device's nightlight timer
+        #[arg(long)]
+        local: bool,
+        /// The command to run, without the leading `wld`
+        #[arg(trailing_var_arg = true, allow_hyphen_values = true, required = true)]
+        command: Vec<String>,
+    },
+    /// Start an interactive session for running several commands, e.g. `on -d porch`
+    Repl,
+    /// Print a shell completion script, e.g. `wld completions bash > ~/.wld-completion.bash`
+    Completions {
+        /// Shell to complete commands in
+        shell: clap_complete::Shell,
+    },
+    /// List values for shell completion scripts to offer
+    #[command(name = "_complete", hide = true)]
+    Complete { list: CompletionList },
     /// Start a MCP (Model Context Protocol) server for controlling WLED devices
     #[cfg(feature = "mcp")]
-    Mcp,
-    /// Set device brightness (0-255)
+    Mcp {
+        /// Serve over streamable HTTP at this address and port, e.g. 0.0.0.0:8080, instead
+        /// of stdio. Clients connect to /mcp.
+        #[arg(long, value_name = "ADDRESS:PORT")]
+        http: Option<std::net::SocketAddr>,
+        /// Bearer token HTTP clients must send. Defaults to $WLD_MCP_TOKEN.
+        #[arg(long, requires = "http")]
+        token: Option<String>,
+    },
+    /// Set device brightness (0-255), or change it with +N/-N
     Brightness {
-        /// Brightness level (0-255, or 0-100 if --percentage is used)
+        /// Brightness level (0-255, or 0-100 if --percentage is used), or a change to the
+        /// current level like +20 or -20. Add a % to give a percentage, e.g. 50% or -10%
+        #[arg(allow_hyphen_values = true, required_unless_present = "percent")]
+        value: Option<BrightnessArg>,
+        /// Device name, group or IP (uses default if not specified)
+        #[arg(short, long, conflicts_with = "all")]
+        device: Option<String>,
+        /// Apply to every configured device
+        #[arg(long)]
+        all: bool,
+        /// Apply to every configured device with this tag. Give it more than once for
+        /// devices with all of the tags
+        #[arg(long = "tag", value_name = "TAG", conflicts_with_all = ["device", "all"])]
+        tags: Vec<String>,
+        /// Interpret value as a percentage (0-100) instead of 0-255
+        #[arg(short, long)]
+        percentage: bool,
+        /// Brightness as a percentage (0-100), instead of giving a value
+        #[arg(
+            long,
+            allow_negative_numbers = true,
+            conflicts_with_all = ["value", "percentage"]
+        )]
+        percent: Option<BrightnessValue>,
+        /// Fade to the new state over this many seconds (e.g. 1.5)
+        #[arg(short, long, value_parser = transition::parse_transition)]
+        transition: Option<u8>,
+        /// Send the brightness as it is. Otherwise 0 turns the device off, and any other
+        /// brightness turns it on
+        #[arg(long)]
+        raw: bool,
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Gradually fade the brightness to a target over a duration
+    Fade {
+        /// Target brightness (0-255, or a percentage like 10%)
+        target: BrightnessArg,
+        /// How long the fade should take, e.g. 90s, 15m or 1h30m
+        #[arg(long, value_parser = duration::parse_duration)]
+        over: Duration,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Fake a dawn: turn the device on dimly, then brighten it with the nightlight's
+    /// sunrise mode
+    Sunrise {
+        /// How long the sunrise takes, from 1m to 4h15m, rounded to whole minutes
+        #[arg(long, default_value = "30m", value_parser = nightlight::parse_minutes)]
+        duration: u8,
+        /// Brightness to finish at (1-255)
+        #[arg(long, default_value_t = 255, value_parser = clap::value_parser!(u8).range(1..))]
+        target_brightness: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Run a 30 second sunrise from here to see what it looks like
+        #[arg(long, conflicts_with = "duration")]
+        preview: bool,
+    },
+    /// Set the primary color of the device
+    Color {
+        /// Color as #RRGGBB, RRGGBB, r,g,b or a CSS color name (e.g. "red")
+        #[arg(value_parser = color::parse_color)]
+        value: Rgb,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Fade to the new state over this many seconds (e.g. 1.5)
+        #[arg(short, long, value_parser = transition::parse_transition)]
+        transition: Option<u8>,
+        /// Print the result as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Set the white channel of the main segment (for RGBW strips)
+    White {
+        /// White level (0-255)
         value: u8,
         /// Device name or IP (uses default if not specified)
         #[arg(short, long)]
         device: Option<String>,
-        /// Interpret value as a percentage (0-100) instead of 0-255
+    },
+    /// Set the color temperature of the main segment (for CCT strips)
+    Cct {
+        /// Color temperature (0-255, from warmest to coolest), in Kelvin (e.g. 4500K), or
+        /// warm, neutral or cool
+        #[arg(value_parser = cct::parse_cct, required_unless_present = "kelvin")]
+        value: Option<u8>,
+        /// Color temperature in Kelvin (1900-10091), instead of giving a value
+        #[arg(long, value_parser = kelvin::parse_kelvin, conflicts_with = "value")]
+        kelvin: Option<u8>,
+        /// Device name or IP (uses default if not specified)
         #[arg(short, long)]
-        percentage: bool,
+        device: Option<String>,
+    },
+    /// Set the active effect on the main segment
+    Effect {
+        /// Effect ID or name (e.g. 9 or "Rainbow")
+        effect: Selector,
+        /// Effect speed (0-255)
+        #[arg(long)]
+        speed: Option<u8>,
+        /// Effect intensity (0-255)
+        #[arg(long)]
+        intensity: Option<u8>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// List the effects available on the device
+    Effects {
+        /// Only show effects whose name contains this text
+        #[arg(short, long)]
+        search: Option<String>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Set the active palette on the main segment
+    Palette {
+        /// Palette ID or name (e.g. 6 or "Party")
+        palette: Selector,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// List the palettes available on the device
+    Palettes {
+        /// Only show palettes whose name contains this text
+        #[arg(short, long)]
+        search: Option<String>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Apply a random effect, palette and/or color. With no flags, all three are randomized.
+    Random {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Pick a random effect
+        #[arg(long)]
+        effect: bool,
+        /// Pick a random palette
+        #[arg(long)]
+        palette: bool,
+        /// Pick a random primary color
+        #[arg(long)]
+        color: bool,
+    },
+    /// Apply a preset saved on the device, or list the saved presets
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Preset {
+        #[command(subcommand)]
+        action: Option<PresetAction>,
+        /// Preset ID to apply (1-250)
+        #[arg(
+            required_unless_present = "list",
+            value_parser = clap::value_parser!(u8).range(1..=250)
+        )]
+        id: Option<u8>,
+        /// List the presets saved on the device instead of applying one
+        #[arg(short, long, conflicts_with = "id")]
+        list: bool,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Start a playlist saved on the device, or stop or check the running playlist
+    #[command(args_conflicts_with_subcommands = true, subcommand_negates_reqs = true)]
+    Playlist {
+        #[command(subcommand)]
+        action: Option<PlaylistAction>,
+        /// Preset ID of the playlist to start (1-250)
+        #[arg(required = true, value_parser = clap::value_parser!(u8).range(1..=250))]
+        id: Option<u8>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// List or control the segments of a device
+    Segment {
+        #[command(subcommand)]
+        action: SegmentAction,
+    },
+    /// Turn UDP sync with other WLED devices on or off, or show its status
+    Sync {
+        #[command(subcommand)]
+        action: SyncAction,
+    },
+    /// Control whether realtime data (e.g. E1.31) overrides the device, or show its status
+    Live {
+        #[command(subcommand)]
+        action: LiveAction,
+    },
+    /// Reboot a device and wait for it to come back
+    Reboot {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// How long to wait for the device to come back, e.g. 90s or 2m
+        #[arg(long, default_value = "60s", value_parser = duration::parse_duration)]
+        wait: Duration,
+    },
+    /// Save a device's configuration and presets to timestamped files
+    Backup {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long, conflicts_with = "all")]
+        device: Option<String>,
+        /// Back up every saved device, each into its own subdirectory
+        #[arg(long)]
+        all: bool,
+        /// Directory to save the backup in
+        #[arg(long, default_value = ".")]
+        out: PathBuf,
+    },
+    /// Upload a backup of a device's configuration and presets back to it
+    Restore {
+        /// A backup file, or a directory to restore the newest backups in
+        path: PathBuf,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+        /// How long to wait for the device to come back after restoring its configuration
+        #[arg(long, default_value = "60s", value_parser = duration::parse_duration)]
+        wait: Duration,
+    },
+    /// Print or send a device's raw JSON state, for fields wld doesn't otherwise support
+    State {
+        #[command(subcommand)]
+        action: StateAction,
+    },
+    /// Repeatedly show a device's power, brightness, effect and preset, highlighting changes
+    Watch {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// How often to check the device, e.g. 2s or 1m
+        #[arg(long, default_value = "2s", value_parser = duration::parse_duration)]
+        interval: Duration,
+        /// Print one JSON object per check (JSON Lines) instead of a summary
+        #[arg(long)]
+        json: bool,
+    },
+    /// Compare how two devices look, listing only what's different. Exits with status 0
+    /// if they're the same, 1 if they differ and 2 if either can't be reached.
+    Diff {
+        /// First device name or IP
+        a: String,
+        /// Second device name or IP
+        b: String,
+        /// Compare every field of the devices' state, rather than just how they look
+        #[arg(long)]
+        full: bool,
+    },
+    /// Measure how quickly a device responds to HTTP requests
+    Ping {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Number of requests to send
+        #[arg(short, long, default_value_t = 5, value_parser = clap::value_parser!(u32).range(1..))]
+        count: u32,
+    },
+    /// Blink a device white and off a few times to find which one it is, then put it back
+    /// as it was
+    Identify {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Number of blinks
+        #[arg(long, default_value_t = 3, value_parser = clap::value_parser!(u32).range(1..=20))]
+        times: u32,
+    },
+    /// Show firmware and hardware details of a device
+    Info {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Print the details as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Check status of all configured devices, or of one device
+    ///
+    /// Exits with status 2 if no device could be reached, or 5 if only some could. With
+    /// --device, exits with status 0 if the device is on, 1 if it's off and 2 if it's
+    /// unreachable.
+    Status {
+        /// Only check this device name or IP
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Print the status as JSON
+        #[arg(long)]
+        json: bool,
+        /// Only check devices with this tag. Give it more than once for devices with all
+        /// of the tags
+        #[arg(long = "tag", value_name = "TAG", conflicts_with = "device")]
+        tags: Vec<String>,
+        /// How many devices to check at the same time
+        #[arg(long, value_name = "N", default_value_t = STATUS_CHECKS, value_parser = clap::builder::RangedU64ValueParser::<usize>::new().range(1..), conflicts_with = "device")]
+        parallel: usize,
+        /// Show the brightness, effect and preset of each device that's reachable
+        #[arg(short, long, conflicts_with = "json")]
+        long: bool,
+        /// When to color the statuses
+        #[arg(
+            long,
+            value_name = "WHEN",
+            default_value = "auto",
+            conflicts_with = "json"
+        )]
+        color: ColorChoice,
+    },
+    /// Show the Wi-Fi signal of all configured devices, weakest first
+    Wifi,
+    /// Show the estimated power draw of a device, or of all configured devices
+    Power {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long, conflicts_with = "all")]
+        device: Option<String>,
+        /// Show every configured device, and the total across them
+        #[arg(long)]
+        all: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum PresetAction {
+    /// Save the current state of the device as a preset
+    Save {
+        /// Preset slot to save to (1-250)
+        #[arg(value_parser = clap::value_parser!(u8).range(1..=250))]
+        slot: u8,
+        /// Name for the preset
+        #[arg(short, long)]
+        name: Option<String>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum PlaylistAction {
+    /// Stop the running playlist
+    Stop {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Show which playlist is running
+    Status {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum SegmentAction {
+    /// List the segments of the device
+    Ls {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Turn a segment on
+    On {
+        /// Segment ID
+        id: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Turn a segment off
+    Off {
+        /// Segment ID
+        id: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Set a segment's brightness (0-255)
+    Brightness {
+        /// Segment ID
+        id: u8,
+        /// Brightness level (0-255)
+        value: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Set a segment's primary color
+    Color {
+        /// Segment ID
+        id: u8,
+        /// Color as #RRGGBB, RRGGBB, r,g,b or a CSS color name (e.g. "red")
+        #[arg(value_parser = color::parse_color)]
+        value: Rgb,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Set the LEDs a segment covers, creating the segment if it doesn't exist
+    Set {
+        /// Segment ID
+        id: u8,
+        /// First LED of the segment
+        #[arg(long)]
+        start: u16,
+        /// LED after the last LED of the segment
+        #[arg(long)]
+        stop: u16,
+        /// Number of LEDs that act as one
+        #[arg(long)]
+        grouping: Option<u8>,
+        /// Number of LEDs to skip between groups
+        #[arg(long)]
+        spacing: Option<u8>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Delete a segment
+    Delete {
+        /// Segment ID
+        id: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Make a segment the main one, which commands like `effect` act on
+    Main {
+        /// Segment ID
+        id: u8,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Run effects on a segment from its other end, for strips mounted the other way round
+    Reverse {
+        /// Segment ID
+        id: u8,
+        /// Turn reversing on or off (toggles if not specified)
+        state: Option<Switch>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Mirror effects on a segment around its middle
+    Mirror {
+        /// Segment ID
+        id: u8,
+        /// Turn mirroring on or off (toggles if not specified)
+        state: Option<Switch>,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
     },
-    /// Check status of all configured devices
-    Status,
 }
 
-fn main() {
-    if let Err(e) = run() {
-        eprintln!("Error: {e}");
-        std::process::exit(1);
+/// An on or off setting given as a word on the command line
+#[derive(Clone, Copy, ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+impl Switch {
+    fn is_on(self) -> bool {
+        matches!(self, Switch::On)
     }
 }
 
-pub fn set_device_brightness(
-    device: Option<&str>,
-    brightness: u8,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::load()?;
-    let ip = config.get_device_ip(device)?;
+/// The format of a list of devices from `wld export`
+#[derive(Clone, Copy, ValueEnum)]
+enum ListFormat {
+    Json,
+    Csv,
+}
+
+#[derive(Subcommand)]
+enum SyncAction {
+    /// Turn on sending and/or receiving sync packets (both if neither is given)
+    On {
+        /// Only change whether state changes are sent to other devices
+        #[arg(long)]
+        send: bool,
+        /// Only change whether state changes are received from other devices
+        #[arg(long)]
+        recv: bool,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Turn off sending and/or receiving sync packets (both if neither is given)
+    Off {
+        /// Only change whether state changes are sent to other devices
+        #[arg(long)]
+        send: bool,
+        /// Only change whether state changes are received from other devices
+        #[arg(long)]
+        recv: bool,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Show whether the device sends and receives sync packets
+    Status {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum GroupAction {
+    /// Add devices to a group, creating it if needed
+    Add {
+        /// Name of the group
+        group: String,
+        /// Names of the devices to add
+        #[arg(required = true)]
+        devices: Vec<String>,
+    },
+    /// Remove devices from a group, or delete the group if no devices are given
+    Rm {
+        /// Name of the group
+        group: String,
+        /// Names of the devices to remove
+        devices: Vec<String>,
+    },
+    /// List groups and their devices
+    Ls,
+}
+
+#[derive(Subcommand)]
+enum StateAction {
+    /// Print the device's full JSON state
+    Get {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+        /// Print compact JSON rather than pretty-printing it
+        #[arg(long)]
+        raw: bool,
+    },
+    /// Send a JSON state update to the device as-is
+    Set {
+        /// JSON state update, e.g. '{"on":true,"bri":128}', or - to read it from stdin
+        json: String,
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum LiveAction {
+    /// Let realtime data take over the device again
+    Off {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Ignore realtime data until the live data stream ends
+    Override {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Ignore realtime data until the device reboots
+    UntilReboot {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+    /// Show whether the device is receiving realtime data
+    Status {
+        /// Device name or IP (uses default if not specified)
+        #[arg(short, long)]
+        device: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum TagAction {
+    /// Add tags to a device
+    Add {
+        /// Name of the device
+        device: String,
+        /// Tags to add, like `outdoor` or `room=bedroom`
+        #[arg(required = true, value_parser = config::parse_tag)]
+        tags: Vec<String>,
+    },
+    /// Remove tags from a device
+    Rm {
+        /// Name of the device
+        device: String,
+        /// Tags to remove
+        #[arg(required = true)]
+        tags: Vec<String>,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigAction {
+    /// Print where the config file is
+    Path,
+    /// Open the config file in $VISUAL or $EDITOR, checking it afterwards
+    Edit,
+    /// Print the config as wld reads it, with passwords and PINs hidden
+    Show,
+    /// Check the config for problems, like groups with devices that aren't saved. Exits
+    /// with a non-zero status if there are any errors.
+    Check {
+        /// Also check that every device answers like a WLED device
+        #[arg(long)]
+        online: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ProfileAction {
+    /// List profiles, marking the one in use
+    Ls,
+    /// Create an empty profile
+    Create {
+        /// Name of the profile, e.g. `work`
+        #[arg(value_parser = config::parse_profile)]
+        name: String,
+    },
+    /// Delete a profile and everything saved in it
+    Rm {
+        /// Name of the profile
+        name: String,
+        /// Delete the profile without asking
+        #[arg(short, long)]
+        yes: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SceneAction {
+    /// Save how some devices look right now as a scene, replacing any scene with the name
+    Save {
+        /// Name of the scene
+        name: String,
+        /// Saved devices or groups to include, separated by commas
+        #[arg(long, required = true, value_delimiter = ',')]
+        devices: Vec<String>,
+    },
+    /// Make each device in a scene look as it did when the scene was saved
+    Apply {
+        /// Name of the scene
+        name: String,
+    },
+    /// List scenes and their devices
+    Ls,
+    /// Delete a scene
+    Rm {
+        /// Name of the scene
+        name: String,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum CompletionList {
+    /// Saved device and group names, for `-d`/`--device`
+    Devices,
+}
+
+fn main() -> ExitCode {
+    let cli = match Cli::try_parse() {
+        Ok(cli) => cli,
+        Err(e) => {
+            let _ = e.print();
+            // --help and --version are reported as errors too, but aren't failures
+            return if e.use_stderr() {
+                Exit::USAGE.into()
+            } else {
+                ExitCode::SUCCESS
+            };
+        }
+    };
+    if let Some(path) = &cli.config {
+        Config::use_path(path.clone());
+    }
+    if let Some(profile) = &cli.profile {
+        Config::use_profile(profile.clone());
+    }
+    if let Some(timeout) = cli.timeout {
+        Config::use_timeout(timeout);
+    }
+    if let Some(retries) = cli.retries {
+        Config::use_retries(retries);
+    }
+    if cli.no_track {
+        track::disable();
+    }
+    log_requests(cli.verbose);
+
+    let result = run(cli);
+    track::save();
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => match e.downcast_ref::<Exit>() {
+            Some(exit) => (*exit).into(),
+            None => {
+                eprintln!("Error: {e}");
+                Exit::for_error(e.as_ref()).into()
+            }
+        },
+    }
+}
+
+/// Read commands from the terminal and run them until Ctrl-D, keeping the config loaded
+/// and reusing HTTP connections between commands. Commands are parsed with the same
+/// definitions as the command line, and errors are printed rather than ending the session.
+fn run_repl() -> Result<(), Box<dyn std::error::Error>> {
+    use rustyline::error::ReadlineError;
+
+    Config::keep_loaded()?;
+    // Ctrl-C stops the command that's running, like `wld watch`, rather than the session
+    interrupt::catch_ctrl_c()?;
+
+    let mut editor = rustyline::DefaultEditor::new()?;
+    let history = repl::history_path()?;
+    // There's no history file until the first session ends
+    let _ = editor.load_history(&history);
+
+    println!("Enter commands like `on -d porch` or `brightness 80`, `help` to list them, or press Ctrl-D to exit");
+
+    loop {
+        let line = match editor.readline("wld> ") {
+            Ok(line) => line,
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => return Err(e.into()),
+        };
+
+        let args = match repl::split_args(&line) {
+            Ok(args) if args.is_empty() => continue,
+            Ok(args) => args,
+            Err(e) => {
+                eprintln!("Error: {e}");
+                continue;
+            }
+        };
+        let _ = editor.add_history_entry(line.as_str());
+
+        if matches!(args[0].as_str(), "exit" | "quit") {
+            break;
+        }
+
+        let cli = match Cli::try_parse_from(std::iter::once("wld".to_string()).chain(args)) {
+            Ok(cli) => cli,
+            Err(e) => {
+                // Covers --help too, which clap reports as an error
+                let _ = e.print();
+                continue;
+            }
+        };
+
+        if let Some(e) = misplaced_config_flag(&cli) {
+            eprintln!("Error: {e}");
+            continue;
+        }
+
+        match cli.command {
+            Commands::Repl => eprintln!("Error: Already in the REPL"),
+            #[cfg(feature = "mcp")]
+            Commands::Mcp { .. } => {
+                eprintln!("Error: The MCP server can't be started from the REPL")
+            }
+            _ => {
+                interrupt::reset();
+                let result = run(cli);
+                track::save();
+                match result {
+                    // The command has already reported its outcome
+                    Err(e) if e.is::<Exit>() => {}
+                    Err(e) => eprintln!("Error: {e}"),
+                    Ok(()) => {}
+                }
+            }
+        }
+    }
+
+    let _ = editor.save_history(&history);
+    Ok(())
+}
+
+/// A line of a script for `wld run`, checked and ready to run
+enum ScriptStep {
+    Sleep(Duration),
+    Command(Cli),
+}
+
+/// Run the commands in a script in order, stopping at the first line that fails unless
+/// `keep_going`. The whole script is checked before anything runs, so a typo near the
+/// end doesn't leave a scene half applied.
+fn run_script(file: &str, keep_going: bool) -> Result<(), Box<dyn std::error::Error>> {
+    let text = if file == "-" {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+        input
+    } else {
+        std::fs::read_to_string(file).map_err(|e| format!("Failed to read '{file}': {e}"))?
+    };
+
+    let steps: Vec<(usize, Result<ScriptStep, String>)> = script::parse(&text)
+        .into_iter()
+        .map(|(line, step)| {
+            let step = step.and_then(|step| match step {
+                script::Step::Sleep(duration) => Ok(ScriptStep::Sleep(duration)),
+                script::Step::Command(args) => parse_script_command(args).map(ScriptStep::Command),
+            });
+            (line, step)
+        })
+        .collect();
+
+    if !keep_going {
+        if let Some((line, Err(e))) = steps.iter().find(|(_, step)| step.is_err()) {
+            return Err(format!("Line {line}: {e}").into());
+        }
+    }
+
+    Config::keep_loaded()?;
+
+    let total = steps.len();
+    let mut failed = 0;
+    for (line, step) in steps {
+        let result = match step {
+            Ok(ScriptStep::Sleep(duration)) => {
+                std::thread::sleep(duration);
+                Ok(())
+            }
+            Ok(ScriptStep::Command(cli)) => run(cli).map_err(|e| match e.downcast_ref::<Exit>() {
+                // The command has already reported its outcome
+                Some(Exit(code)) => format!("Exited with status {code}"),
+                None => e.to_string(),
+            }),
+            Err(e) => Err(e),
+        };
+
+        if let Err(e) = result {
+            if !keep_going {
+                return Err(format!("Line {line}: {e}").into());
+            }
+            eprintln!("Error: Line {line}: {e}");
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        eprintln!("{failed} of {total} lines failed");
+        return Err(Exit::USAGE.into());
+    }
+    Ok(())
+}
+
+/// Parse a wld command given as arguments without the leading `wld`, e.g. a line of a
+/// script, with a one-line message if it's invalid
+fn parse_command_line(args: Vec<String>) -> Result<Cli, String> {
+    let cli = Cli::try_parse_from(std::iter::once("wld".to_string()).chain(args)).map_err(|e| {
+        // Only keep the first line of clap's message, leaving out the usage
+        let message = e.to_string();
+        let first_line = message.lines().next().unwrap_or_default();
+        first_line.trim_start_matches("error: ").to_string()
+    })?;
+
+    if let Some(e) = misplaced_config_flag(&cli) {
+        return Err(e);
+    }
+    Ok(cli)
+}
+
+/// Log requests to devices to stderr, as asked for with `-v`, or with what was sent and
+/// received too with `-vv`
+fn log_requests(verbose: u8) {
+    let level = match verbose {
+        0 => return,
+        1 => "debug",
+        _ => "trace",
+    };
+    tracing_subscriber::fmt()
+        .with_env_filter(tracing_subscriber::EnvFilter::new(format!("wld={level}")))
+        .with_writer(std::io::stderr)
+        .with_ansi(std::io::stderr().is_terminal())
+        .with_target(false)
+        .without_time()
+        .init();
+}
+
+/// The error for a `--config` or `--profile` given to a command that's run by another one,
+/// which can't switch config files part way through
+fn misplaced_config_flag(cli: &Cli) -> Option<String> {
+    let flag = if cli.config.is_some() {
+        "--config"
+    } else if cli.profile.is_some() {
+        "--profile"
+    } else if cli.timeout.is_some() {
+        "--timeout"
+    } else if cli.retries.is_some() {
+        "--retries"
+    } else if cli.verbose > 0 {
+        "--verbose"
+    } else if cli.no_track {
+        "--no-track"
+    } else {
+        return None;
+    };
+    Some(format!("{flag} can only be given when starting wld"))
+}
+
+/// Parse a line of a script as a wld command, rejecting commands that can't run in one
+fn parse_script_command(args: Vec<String>) -> Result<Cli, String> {
+    let cli = parse_command_line(args)?;
+
+    match cli.command {
+        Commands::Run { .. } | Commands::Repl => {
+            Err("Scripts can't run other scripts or the REPL".to_string())
+        }
+        #[cfg(feature = "mcp")]
+        Commands::Mcp { .. } => Err("Scripts can't start the MCP server".to_string()),
+        _ => Ok(cli),
+    }
+}
+
+/// Run a command at the next occurrence of a wall-clock time. An `off` command is handed to
+/// the device's nightlight timer when it's due within the timer's limit (unless `local`),
+/// and otherwise this waits until it's time, until Ctrl-C.
+fn run_at(
+    time: chrono::NaiveTime,
+    local: bool,
+    args: Vec<String>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let display = args.join(" ");
+    // Check the command before anything else, so mistakes show up now rather than later
+    let cli = parse_command_line(args)?;
+    match cli.command {
+        Commands::Repl => return Err(format!("`{display}` can't be scheduled").into()),
+        #[cfg(feature = "mcp")]
+        Commands::Mcp { .. } => return Err(format!("`{display}` can't be scheduled").into()),
+        _ => {}
+    }
+
+    let now = chrono::Local::now();
+    let target = schedule::next_occurrence(&now, time);
+    let day = if target.date_naive() == now.date_naive() {
+        "today"
+    } else {
+        "tomorrow"
+    };
+    let when = format!("{} {day}", target.format("%H:%M"));
+    let delay = (target - now).to_std().unwrap_or_default();
+
+    if !local {
+        // The nightlight counts whole minutes from when it's armed, so this is accurate to
+        // within half a minute
+        let minutes =
+            nightlight::whole_minutes(Duration::from_secs((delay.as_secs() + 30) / 60 * 60));
+
+        match (&cli.command, minutes) {
+            (
+                Commands::Off {
+                    device,
+                    all: false,
+                    tags,
+                    transition: None,
+                    json: false,
+                    after: None,
+                    ..
+                },
+                Some(minutes),
+            ) if tags.is_empty() => {
+                let ip = set_device_off_timer(device.as_deref(), minutes)?;
+                println!("Device at {ip} will turn off at {when}, using its nightlight timer");
+                return Ok(());
+            }
+            _ => println!(
+                "Devices can only do this themselves for `off` within the next {}, so waiting here instead",
+                duration::format_duration(Duration::from_secs(u64::from(nightlight::MAX_MINUTES) * 60))
+            ),
+        }
+    }
+
+    interrupt::catch_ctrl_c()?;
+    println!(
+        "Waiting until {when} (in {}) to run `{display}`. Keep this running, or press Ctrl-C to cancel.",
+        duration::format_duration(delay)
+    );
+
+    // Check the clock rather than sleeping for the whole delay, so time spent with the
+    // computer asleep counts
+    while chrono::Local::now() < target {
+        if !interrupt::sleep(Duration::from_secs(1)) {
+            println!("Cancelled, `{display}` wasn't run");
+            return Ok(());
+        }
+    }
+
+    run(cli)
+}
+
+/// Returned by a command that has already printed its outcome but needs to exit with
+/// `code`, like `wld status` for a device that's off. Used instead of exiting directly so
+/// the REPL can carry on.
+#[derive(Debug, Clone, Copy)]
+struct Exit(u8);
+
+impl Exit {
+    /// A mistake in the command or the config, like a device that isn't saved, or
+    /// anything else that doesn't have a status of its own
+    const USAGE: Exit = Exit(1);
+    /// A device couldn't be reached, or didn't respond in time
+    const UNREACHABLE: Exit = Exit(2);
+    /// A device responded, but turned the request down
+    const REJECTED: Exit = Exit(3);
+    /// The config file couldn't be read or written, or isn't valid
+    const CONFIG: Exit = Exit(4);
+    /// Some of the devices a command ran against failed or couldn't be reached
+    const PARTIAL: Exit = Exit(5);
+
+    /// How to exit after `error`, so scripts can tell what went wrong without reading
+    /// the message
+    fn for_error(error: &(dyn std::error::Error + 'static)) -> Exit {
+        match error.downcast_ref::<WldError>() {
+            Some(WldError::Http { .. }) => Exit::UNREACHABLE,
+            Some(WldError::DeviceRejected { .. }) => Exit::REJECTED,
+            Some(WldError::ConfigIo { .. } | WldError::ConfigParse { .. }) => Exit::CONFIG,
+            _ => Exit::USAGE,
+        }
+    }
+
+    /// How to exit after checking devices, `unreachable` of `total` of which couldn't be
+    /// reached
+    fn for_unreachable(unreachable: usize, total: usize) -> Result<(), Exit> {
+        match unreachable {
+            0 => Ok(()),
+            _ if unreachable == total => Err(Exit::UNREACHABLE),
+            _ => Err(Exit::PARTIAL),
+        }
+    }
+}
+
+impl From<Exit> for ExitCode {
+    fn from(exit: Exit) -> Self {
+        ExitCode::from(exit.0)
+    }
+}
+
+impl std::fmt::Display for Exit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "exit code {}", self.0)
+    }
+}
+
+impl std::error::Error for Exit {}
+
+/// What a device turns on with by default, like `brightness 200 and preset 2`, if it has
+/// any defaults
+fn describe_defaults(device: &config::Device) -> Option<String> {
+    let details: Vec<String> = device
+        .default_brightness
+        .map(|b| format!("brightness {b}"))
+        .into_iter()
+        .chain(device.default_preset.map(|p| format!("preset {p}")))
+        .collect();
+    (!details.is_empty()).then(|| details.join(" and "))
+}
+
+/// How long ago a device was last seen, like `2h ago`
+fn describe_last_seen(at: chrono::DateTime<chrono::Utc>) -> String {
+    // A clock that's gone back makes it look like the future
+    let elapsed = (chrono::Utc::now() - at).to_std().unwrap_or_default();
+    duration::format_ago(elapsed)
+}
+
+/// The cell marking the default device in a table of devices
+fn default_marker(config: &Config, name: &str) -> (String, Style) {
+    let marker = if config.default_device.as_deref() == Some(name) {
+        "*"
+    } else {
+        ""
+    };
+    (marker.to_string(), Style::Plain)
+}
+
+/// The brightness, effect and preset of a device, as shown by `wld status --long`, each
+/// empty if the device didn't report it
+fn describe_details(details: &DeviceDetails) -> [String; 3] {
+    let brightness = details
+        .brightness
+        .map(|brightness| format!("{brightness} ({}%)", brightness::percentage(brightness)));
+    let effect = details
+        .effect
+        .as_ref()
+        .map(|(id, name)| name.clone().unwrap_or_else(|| id.to_string()));
+    [
+        brightness.unwrap_or_default(),
+        effect.unwrap_or_default(),
+        details
+            .preset
+            .map(|preset| preset.to_string())
+            .unwrap_or_default(),
+    ]
+}
+
+/// A device's status as printed by `wld status --json`
+fn status_entry(
+    config: &Config,
+    name: String,
+    ip: String,
+    status: &DeviceStatus,
+    details: DeviceDetails,
+) -> output::StatusEntry {
+    output::StatusEntry {
+        default: config.default_device.as_ref() == Some(&name),
+        name,
+        ip,
+        status: status.into(),
+        brightness: details.brightness,
+        effect: details
+            .effect
+            .map(|(id, name)| output::EffectEntry { id, name }),
+        preset: details.preset,
+    }
+}
+
+/// The cell for a device's status in a table of devices, colored by what it is
+fn describe_status(status: &DeviceStatus) -> (String, Style) {
+    match status {
+        DeviceStatus::On => ("ON".to_string(), Style::Green),
+        DeviceStatus::Off => ("OFF".to_string(), Style::Grey),
+        DeviceStatus::Unreachable => ("UNREACHABLE".to_string(), Style::Red),
+    }
+}
+
+/// Tags listed for a message, like `'outdoor' and 'room=porch'`
+fn describe_tags(tags: &[String]) -> String {
+    let quoted: Vec<String> = tags.iter().map(|tag| format!("'{tag}'")).collect();
+    quoted.join(" and ")
+}
+
+/// What a command that changes a device did, for printing as prose or JSON
+struct ActionOutcome {
+    ip: String,
+    /// The value set, if the action takes one
+    value: Option<serde_json::Value>,
+    message: String,
+}
+
+/// The saved name of a device given by name or IP, falling back to the one in
+/// `$WLD_DEVICE`, then the default device
+fn saved_device_name(config: &Config, device: Option<&str>, ip: &str) -> Option<String> {
+    let device = device.map(str::to_string).or_else(config::device_from_env);
+    match device.as_deref() {
+        Some(device) if config.devices.contains_key(device) => Some(device.to_string()),
+        Some(_) => config
+            .devices
+            .iter()
+            .find(|(_, saved)| saved.ip == ip)
+            .map(|(name, _)| name.clone()),
+        None => config.default_device.clone(),
+    }
+}
+
+/// Print the outcome of a command that changed a single device: its message, or with
+/// `json` an `output::ActionResult`. In JSON mode, failures are printed to stdout too
+/// before exiting with a non-zero status.
+fn report_action(
+    action: &str,
+    device: Option<&str>,
+    json: bool,
+    result: Result<ActionOutcome, WldError>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    if !json {
+        println!("{}", result?.message);
+        return Ok(());
+    }
+
+    match result {
+        Ok(outcome) => {
+            let config = Config::load()?;
+            let entry = output::ActionResult {
+                device: saved_device_name(&config, device, &outcome.ip),
+                ip: Some(outcome.ip),
+                action: Some(action.to_string()),
+                value: outcome.value,
+                success: true,
+                error: None,
+            };
+            println!("{}", serde_json::to_string(&entry)?);
+            Ok(())
+        }
+        Err(e) => {
+            let entry = output::ActionResult {
+                error: Some(e.to_string()),
+                ..Default::default()
+            };
+            println!("{}", serde_json::to_string(&entry)?);
+            Err(Exit::for_error(&e).into())
+        }
+    }
+}
+
+/// Apply a change to each of the named devices, printing the outcome for each (or with
+/// `json`, an array of `output::ActionResult`s). Carries on past devices that fail, then
+/// exits with a non-zero status if any did.
+fn for_each_device(
+    names: &[String],
+    action: &str,
+    json: bool,
+    apply: impl Fn(&str) -> Result<ActionOutcome, WldError>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let config = Config::load()?;
+
+    if json {
+        let mut results = Vec::new();
+        for name in names {
+            let ip = config.get_device_ip(Some(name))?;
+            let result = match apply(&ip) {
+                Ok(outcome) => output::ActionResult {
+                    action: Some(action.to_string()),
+                    value: outcome.value,
+                    success: true,
+                    ..Default::default()
+                },
+                Err(e) => output::ActionResult {
+                    error: Some(e.to_string()),
+                    ..Default::default()
+                },
+            };
+            results.push(output::ActionResult {
+                device: Some(name.clone()),
+                ip: Some(ip),
+                ..result
+            });
+        }
+
+        println!("{}", serde_json::to_string_pretty(&results)?);
+        if results.iter().any(|r| !r.success) {
+            return Err(Exit::PARTIAL.into());
+        }
+        return Ok(());
+    }
+
+    if names.is_empty() {
+        println!("No devices saved");
+        return Ok(());
+    }
+
+    let mut failed = 0;
+    for name in names {
+        let default_marker = if config.default_device.as_ref() == Some(name) {
+            " (default)"
+        } else {
+            ""
+        };
+        let ip = config.get_device_ip(Some(name))?;
+
+        print!("  {name} ({ip}){default_marker}: ");
+
+        match apply(&ip) {
+            Ok(outcome) => println!("{}", outcome.message),
+            Err(e) => {
+                println!("FAILED ({e})");
+                failed += 1;
+            }
+        }
+    }
+
+    println!();
+    if failed == 0 {
+        println!("Updated all {} devices", names.len());
+    } else {
+        println!(
+            "Updated {} of {} devices, {failed} failed",
+            names.len() - failed,
+            names.len()
+        );
+        return Err(Exit::PARTIAL.into());
+    }
+
+    Ok(())
+}
+
+/// Turn a device on or off after `after`, with the device's nightlight timer where it can
+/// do the job (unless `local`), or otherwise by waiting here until Ctrl-C
+fn set_device_power_after(
+    device: Option<&str>,
+    power_state: bool,
+    after: Duration,
+    local: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let action = if power_state { "on" } else { "off" };
+
+    if !local {
+        match (power_state, nightlight::whole_minutes(after)) {
+            (false, Some(minutes)) => {
+                let ip = set_device_off_timer(device, minutes)?;
+                println!(
+                    "Device at {ip} will turn off in {}, using its nightlight timer",
+                    duration::format_duration(after)
+                );
+                return Ok(());
+            }
+            (false, None) => println!(
+                "The nightlight timer only counts whole minutes up to {}, so waiting here instead",
+                nightlight::MAX_MINUTES
+            ),
+            (true, _) => {
+                println!("The nightlight timer can only turn devices off, so waiting here instead")
+            }
+        }
+    }
+
+    // Connect first, so a device that can't be reached fails now rather than at the end
+    let (ip, _) = connect_to_device(device)?;
+    interrupt::catch_ctrl_c()?;
+
+    println!(
+        "Turning {action} device at {ip} in {}. Keep this running, or press Ctrl-C to cancel.",
+        duration::format_duration(after)
+    );
+    if !interrupt::sleep(after) {
+        println!("Cancelled, device at {ip} left unchanged");
+        return Ok(());
+    }
+
+    set_device_power(Some(&ip), power_state, None)?;
+    println!("Turned {action} device at {ip}");
+
+    Ok(())
+}
+
+/// Print a list of effects or palettes, one `ID  name` entry per line
+fn print_catalog(names: &[String], search: Option<&str>, kind: &str) {
+    let entries = catalog::list(names, search);
+
+    if entries.is_empty() {
+        println!("No {kind}s found");
+        return;
+    }
+
+    for (id, name) in entries {
+        println!("{id:>3}  {name}");
+    }
+}
+
+/// Set or toggle a segment's direction, and print how it ends up
+fn print_segment_orientation(
+    device: Option<&str>,
+    id: u8,
+    orientation: Orientation,
+    state: Option<Switch>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let (ip, value) =
+        set_device_segment_orientation(device, id, orientation, state.map(Switch::is_on))?;
+    println!(
+        "Segment {id} on device at {ip} is now {}",
+        orientation.describe(value)
+    );
+
+    Ok(())
+}
+
+/// Print segments as a table, marking the main segment
+fn print_segments(DeviceSegments { segments, main }: &DeviceSegments) {
+    if segments.is_empty() {
+        println!("No segments found");
+        return;
+    }
+
+    println!(" ID  START   STOP  ON   BRI  COLOR");
+    for segment in segments {
+        let number = |value: Option<u16>| value.map_or("-".to_string(), |v| v.to_string());
+        let on = match segment.on {
+            Some(true) => "on",
+            Some(false) => "off",
+            None => "-",
+        };
+        let color = segment
+            .col
+            .as_ref()
+            .and_then(|colors| colors.first())
+            .filter(|c| c.len() >= 3)
+            .map_or("-".to_string(), |c| Rgb::new(c[0], c[1], c[2]).to_string());
+
+        let marker = match (segment.id, main) {
+            (Some(id), Some(main)) if id == i16::from(*main) => "  (main)",
+            _ => "",
+        };
+
+        println!(
+            "{:>3}  {:>5}  {:>5}  {on:<3}  {:>3}  {color}{marker}",
+            segment.id.map_or("-".to_string(), |id| id.to_string()),
+            number(segment.start),
+            number(segment.stop),
+            segment.bri.map_or("-".to_string(), |bri| bri.to_string()),
+        );
+    }
+}
+
+/// The sync flags to set for `wld sync on|off`: just the ones asked for, or both
+fn sync_flags(enabled: bool, send: bool, recv: bool) -> (Option<bool>, Option<bool>) {
+    if !send && !recv {
+        (Some(enabled), Some(enabled))
+    } else {
+        (send.then_some(enabled), recv.then_some(enabled))
+    }
+}
+
+/// Parse a JSON state update given on the command line, where `-` means read it from stdin
+fn read_state_json(json: &str) -> Result<serde_json::Value, Box<dyn std::error::Error>> {
+    let json = if json == "-" {
+        let mut input = String::new();
+        std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+        input
+    } else {
+        json.to_string()
+    };
+
+    let state: serde_json::Value =
+        serde_json::from_str(&json).map_err(|e| format!("Invalid JSON state: {e}"))?;
+    if !state.is_object() {
+        return Err("Invalid JSON state: expected an object, e.g. '{\"on\":true}'".into());
+    }
+
+    Ok(state)
+}
+
+/// Seconds since the Unix epoch
+fn unix_time() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+/// Save devices found by `wld discover --add` or `wld nodes --add`, asking about each
+/// one unless `yes` is set
+fn add_found_devices(
+    devices: &[(String, String)],
+    yes: bool,
+) -> Result<(), Box<dyn std::error::Error>> {
+    Config::import_devices(
+        devices,
+        |name, ip| Ok(yes || confirm(&format!("Add '{name}' ({ip})?"))?),
+        |imported| match imported {
+            config::Imported::Skipped { name, ip, existing } => {
+                println!("Skipping {name} ({ip}): already saved as '{existing}'")
+            }
+            config::Imported::Added { name, ip } => {
+                println!("Added device '{name}' with IP {ip}")
+            }
+        },
+    )
+}
+
+/// Open a file in the editor from `$VISUAL` or `$EDITOR`, or `vi` if neither is set, and
+/// wait for it to close
+fn run_editor(path: &Path) -> Result<(), Box<dyn std::error::Error>> {
+    let editor = ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|variable| std::env::var(variable).ok())
+        .find(|editor| !editor.trim().is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    // The editor can come with arguments, like `code --wait`
+    let args = repl::split_args(&editor)?;
+    let (program, args) = args.split_first().ok_or("No editor set")?;
+
+    let status = std::process::Command::new(program)
+        .args(args)
+        .arg(path)
+        .status()
+        .map_err(|e| format!("Failed to start editor '{program}': {e}"))?;
+    if !status.success() {
+        return Err(format!("Editor '{program}' exited with {status}").into());
+    }
+    Ok(())
+}
+
+/// Ask the user for a line of text, failing if they don't give any
+fn prompt(prompt: &str) -> Result<String, Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    print!("{prompt}");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    match answer.trim() {
+        "" => Err("Nothing was entered".into()),
+        answer => Ok(answer.to_string()),
+    }
+}
+
+/// Ask the user to confirm an action, treating anything but "y" or "yes" as no
+fn confirm(prompt: &str) -> Result<bool, Box<dyn std::error::Error>> {
+    use std::io::Write;
+
+    print!("{prompt} [y/N] ");
+    std::io::stdout().flush()?;
+
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer)?;
+
+    Ok(matches!(answer.trim().to_lowercase().as_str(), "y" | "yes"))
+}
+
+/// Label and value pairs describing a device, with "n/a" for anything it doesn't report
+fn info_rows(info: &api::Info) -> Vec<(&'static str, String)> {
+    fn or_na<T: ToString>(value: Option<T>) -> String {
+        value.map_or("n/a".to_string(), |v| v.to_string())
+    }
+
+    let leds = info.leds.as_ref();
+    let wifi = info.wifi.as_ref();
+
+    let max_power = leds.and_then(|l| l.maxpwr).map(|maxpwr| match maxpwr {
+        0 => "no limit".to_string(),
+        milliamps => format!("{milliamps} mA"),
+    });
+    let rssi = wifi
+        .and_then(|w| w.rssi)
+        .map(|rssi| match wifi.and_then(|w| w.signal) {
+            Some(signal) => format!("{rssi} dBm ({signal}%)"),
+            None => format!("{rssi} dBm"),
+        });
+
+    vec![
+        ("Name", or_na(info.name.as_ref())),
+        ("Version", or_na(info.ver.as_ref())),
+        ("Build", or_na(info.vid)),
+        ("Architecture", or_na(info.arch.as_ref())),
+        ("LEDs", or_na(leds.and_then(|l| l.count))),
+        ("Max power", or_na(max_power)),
+        (
+            "Free heap",
+            or_na(info.freeheap.map(|b| format!("{b} bytes"))),
+        ),
+        (
+            "Uptime",
+            or_na(
+                info.uptime
+                    .map(|secs| duration::format_duration(Duration::from_secs(secs))),
+            ),
+        ),
+        ("Wi-Fi signal", or_na(rssi)),
+        ("Wi-Fi channel", or_na(wifi.and_then(|w| w.channel))),
+        ("IP", or_na(info.ip.as_ref())),
+        ("MAC", or_na(info.mac.as_deref().map(format_mac))),
+    ]
+}
+
+/// Add colons to a MAC address, which WLED reports without separators
+fn format_mac(mac: &str) -> String {
+    if mac.len() == 12 && mac.chars().all(|c| c.is_ascii_hexdigit()) {
+        (0..12)
+            .step_by(2)
+            .map(|i| &mac[i..i + 2])
+            .collect::<Vec<_>>()
+            .join(":")
+    } else {
+        mac.to_string()
+    }
+}
+
+/// Print rows under left-aligned headers, with columns padded to fit their contents
+fn print_table<const N: usize>(headers: &[&str; N], rows: &[[String; N]]) {
+    let widths: Vec<usize> = (0..N)
+        .map(|i| {
+            rows.iter()
+                .map(|row| row[i].chars().count())
+                .chain([headers[i].len()])
+                .max()
+                .unwrap_or(0)
+        })
+        .collect();
+
+    let print_row = |cells: Vec<&str>| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(&widths)
+            .map(|(cell, width)| format!("{cell:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(headers.to_vec());
+    for row in rows {
+        print_row(row.iter().map(String::as_str).collect());
+    }
+}
+
+/// Describe a power draw in milliamps, with the share of the limit in use if there is one
+fn format_power(milliamps: u32, limit: Option<u32>) -> String {
+    match limit {
+        Some(limit) if limit > 0 => {
+            let percent = (f64::from(milliamps) / f64::from(limit) * 100.0).round();
+            format!("{milliamps} mA ({percent}% of {limit} mA limit)")
+        }
+        _ => format!("{milliamps} mA (no power limit set)"),
+    }
+}
+
+/// Order Wi-Fi results so the devices most likely to need attention come first:
+/// unreachable devices, then those not reporting a signal, then weakest signal first
+fn sort_wifi_results(results: &mut [(String, String, Option<api::Wifi>)]) {
+    results.sort_by_key(|(name, _, wifi)| {
+        let rank = match wifi {
+            None => (0, 0),
+            Some(api::Wifi { rssi: None, .. }) => (1, 0),
+            Some(api::Wifi {
+                rssi: Some(rssi), ..
+            }) => (2, *rssi),
+        };
+        (rank, name.clone())
+    });
+}
+fn run(cli: Cli) -> Result<(), Box<dyn std::error::Error>> {
+    match cli.command {
+        Commands::Add {
+            name,
+            ip,
+            insecure,
+            username,
+            password,
+            pin,
+            verify,
+            force,
+        } => {
+            if insecure && !address::is_https(&ip) {
+                return Err("--insecure can only be used with an https:// address".into());
+            }
+
+            let save_device = |config: &mut Config| {
+                let outcome = config.add_device(name.clone(), ip.clone());
+                config.set_insecure(&name, insecure);
+                config.set_credentials(
+                    &name,
+                    config::Credentials {
+                        username: username.clone(),
+                        password: password.clone(),
+                        pin: pin.clone(),
+                    },
+                );
+                outcome
+            };
+
+            // Ask first and check the device before locking the config, so other wld
+            // processes aren't kept waiting on the answer
+            let mut draft = Config::load()?;
+            let planned = save_device(&mut draft);
+            match &planned {
+                AddOutcome::Replaced { old, .. } if !force => {
+                    if !std::io::stdin().is_terminal() {
+                        return Err(format!(
+                            "Device '{name}' is already saved with IP {old}. Use --force to replace it with {ip}"
+                        )
+                        .into());
+                    }
+                    println!("Device '{name}' is already saved with IP {old}");
+                    if !confirm(&format!("Replace it with {ip}?"))? {
+                        println!("Kept device '{name}' with IP {old}");
+                        return Ok(());
+                    }
+                }
+                _ => {}
+            }
+            if let AddOutcome::Added {
+                duplicate_of: Some(other),
+            }
+            | AddOutcome::Replaced {
+                duplicate_of: Some(other),
+                ..
+            } = &planned
+            {
+                eprintln!("Warning: device '{other}' is already saved with IP {ip}");
+            }
+
+            if verify {
+                match verify_device(&draft, &ip) {
+                    Ok(info) => println!(
+                        "Found WLED {} with {} LEDs at {ip}",
+                        info.ver.as_deref().unwrap_or("(unknown version)"),
+                        info.leds
+                            .and_then(|leds| leds.count)
+                            .map_or("an unknown number of".to_string(), |c| c.to_string())
+                    ),
+                    Err(e) if force => {
+                        eprintln!("Warning: device at {ip} {e}, but saving it anyway");
+                    }
+                    Err(e) => {
+                        return Err(
+                            format!("Device at {ip} {e}. Use --force to save it anyway").into()
+                        )
+                    }
+                }
+            }
+
+            let (mut config, _lock) = Config::load_locked()?;
+            let outcome = save_device(&mut config);
+            // Another wld process may have saved the name meanwhile, with an address
+            // nobody agreed to replace
+            if let AddOutcome::Replaced { old, .. } = &outcome {
+                let agreed =
+                    matches!(&planned, AddOutcome::Replaced { old: asked, .. } if asked == old);
+                if !agreed && !force {
+                    return Err(format!(
+                        "Device '{name}' was saved with IP {old} while adding it. Run the command again to replace it"
+                    )
+                    .into());
+                }
+            }
+
+            config.save()?;
+            match outcome {
+                AddOutcome::Added { .. } => {
+                    println!("Added device '{name}' with IP {ip}");
+                    if config.devices.len() == 1 {
+                        println!("Set '{name}' as the default device");
+                    }
+                }
+                AddOutcome::Replaced { old, .. } => {
+                    println!("Changed the IP of device '{name}' from {old} to {ip}");
+                }
+                AddOutcome::Unchanged => {
+                    println!("Updated device '{name}' with IP {ip}");
+                }
+            }
+        }
+        Commands::Delete { name } => {
+            let (mut config, _lock) = Config::load_locked()?;
+            config.remove_device(&name)?;
+            config.save()?;
+            println!("Deleted device '{name}'");
+        }
+        Commands::Ls {
+            json: true,
+            tags,
+            check,
+            fail_unreachable,
+            ..
+        } => {
+            let config = Config::load()?;
+            let names = config.tagged(&tags);
+            let statuses = check.then(|| get_device_statuses(&config, &names, STATUS_CHECKS));
+
+            let devices: Vec<output::DeviceEntry> = names
+                .into_iter()
+                .enumerate()
+                .map(|(index, name)| {
+                    let device = &config.devices[&name];
+                    output::DeviceEntry {
+                        ip: device.ip.clone(),
+                        default: config.default_device.as_ref() == Some(&name),
+                        tags: device.tags.iter().cloned().collect(),
+                        default_brightness: device.default_brightness,
+                        default_preset: device.default_preset,
+                        last_seen: device.last_seen,
+                        last_version: device.last_version.clone(),
+                        status: statuses
+                            .as_ref()
+                            .map(|statuses| (&statuses[index].0).into()),
+                        name,
+                    }
+                })
+                .collect();
+
+            println!("{}", serde_json::to_string_pretty(&devices)?);
+
+            if fail_unreachable {
+                let unreachable = devices
+                    .iter()
+                    .filter(|d| d.status == Some(output::StatusValue::Unreachable))
+                    .count();
+                Exit::for_unreachable(unreachable, devices.len())?;
+            }
+        }
+        Commands::Ls {
+            json: false,
+            tags,
+            long,
+            check,
+            fail_unreachable,
+            color,
+        } => {
+            let config = Config::load()?;
+
+            if config.devices.is_empty() {
+                println!("No devices saved");
+                return Ok(());
+            }
+            let names = config.tagged(&tags);
+            if names.is_empty() {
+                println!("No devices are tagged {}", describe_tags(&tags));
+                return Ok(());
+            }
+
+            let statuses = check.then(|| get_device_statuses(&config, &names, STATUS_CHECKS));
+
+            let mut table = Table::new(&[
+                "NAME",
+                "ADDRESS",
+                "DEFAULT",
+                "TAGS",
+                "ACCESS",
+                "TURNS ON WITH",
+                "LAST SEEN",
+                "FIRMWARE",
+                "STATE",
+            ]);
+            for (index, name) in names.iter().enumerate() {
+                let device = &config.devices[name];
+                let mut access = Vec::new();
+                if config.insecure.contains(name) {
+                    access.push("insecure".to_string());
+                }
+                // Only the username is shown, so passwords and PINs don't end up on screen
+                if let Some(credentials) = config.credentials.get(name) {
+                    if let Some(username) = &credentials.username {
+                        access.push(format!("user {username}, password ****"));
+                    }
+                    if credentials.pin.is_some() {
+                        access.push("PIN ****".to_string());
+                    }
+                }
+                let tags: Vec<&str> = device.tags.iter().map(String::as_str).collect();
+                let defaults = long.then(|| describe_defaults(device)).flatten();
+                let last_seen = device.last_seen.filter(|_| long).map(describe_last_seen);
+                let firmware = device.last_version.clone().filter(|_| long);
+                let status = match statuses.as_ref().map(|statuses| &statuses[index].0) {
+                    Some(status) => describe_status(status),
+                    None => (String::new(), Style::Plain),
+                };
+
+                table.row(vec![
+                    (name.clone(), Style::Plain),
+                    (device.ip.clone(), Style::Plain),
+                    default_marker(&config, name),
+                    (tags.join(", "), Style::Plain),
+                    (access.join(", "), Style::Plain),
+                    (defaults.unwrap_or_default(), Style::Plain),
+                    (last_seen.unwrap_or_default(), Style::Plain),
+                    (firmware.unwrap_or_default(), Style::Plain),
+                    status,
+                ]);
+            }
+            print!("{}", table.render(color.enabled()));
+
+            if let (true, Some(statuses)) = (fail_unreachable, &statuses) {
+                let unreachable = statuses
+                    .iter()
+                    .filter(|(status, _)| matches!(status, DeviceStatus::Unreachable))
+                    .count();
+                Exit::for_unreachable(unreachable, statuses.len())?;
+            }
+        }
+        Commands::SetDefault { name } => {
+            let (mut config, _lock) = Config::load_locked()?;
+            config.set_default(&name)?;
+            config.save()?;
+            println!("Set '{name}' as the default device");
+        }
+        Commands::Set {
+            name,
+            default_brightness,
+            default_preset,
+            clear,
+        } => {
+            if !clear && default_brightness.is_none() && default_preset.is_none() {
+                return Err(
+                    "Nothing to set: give --default-brightness, --default-preset or --clear".into(),
+                );
+            }
+
+            let (mut config, _lock) = Config::load_locked()?;
+            let device = config.device_mut(&name)?;
+            if clear {
+                device.default_brightness = None;
+                device.default_preset = None;
+            }
+            device.default_brightness = default_brightness.or(device.default_brightness);
+            device.default_preset = default_preset.or(device.default_preset);
+            let defaults = describe_defaults(device);
+            config.save()?;
+
+            match defaults {
+                Some(defaults) => println!("Device '{name}' now turns on with {defaults}"),
+                None => println!("Cleared the defaults for device '{name}'"),
+            }
+        }
+        Commands::Discover { wait, add, yes } => {
+            if wait.is_zero() {
+                return Err("--wait must be at least 1 second".into());
+            }
+
+            println!(
+                "Searching for WLED devices for {}...",
+                duration::format_duration(wait)
+            );
+
+            // Anything can advertise itself over mDNS, so only keep devices that answer
+            // like WLED does
+            let mut found: Vec<(String, api::Info)> = discover::browse(wait)?
+                .into_iter()
+                .filter_map(|address| {
+                    let info = api::probe_info(
+                        &address,
+                        Duration::from_secs(2),
+                        &api::ClientOptions::default(),
+                    )
+                    .ok()?;
+                    (info.brand.as_deref() == Some("WLED")).then_some((address, info))
+                })
+                .collect();
+
+            // A device in setup mode is only there while this computer is on its Wi-Fi
+            // network, so isn't worth saving, and may not advertise itself at all
+            let in_setup = found
+                .iter()
+                .any(|(address, _)| setup::is_ap_address(address))
+                || api::probe_info(
+                    setup::AP_ADDRESS,
+                    Duration::from_secs(1),
+                    &api::ClientOptions::default(),
+                )
+                .is_ok_and(|info| info.brand.as_deref() == Some("WLED"));
+            found.retain(|(address, _)| !setup::is_ap_address(address));
+            let setup_note = || {
+                if in_setup {
+                    println!(
+                        "Found a WLED device in setup mode at {}. Run `wld setup` to connect it to your Wi-Fi network",
+                        setup::AP_ADDRESS
+                    );
+                }
+            };
+
+            if found.is_empty() {
+                println!("No WLED devices found");
+                setup_note();
+                return Ok(());
+            }
+
+            found.sort_by(|(a, a_info), (b, b_info)| (&a_info.name, a).cmp(&(&b_info.name, b)));
+
+            let rows: Vec<[String; 4]> = found
+                .iter()
+                .map(|(address, info)| {
+                    [
+                        info.name.clone().unwrap_or_else(|| "n/a".to_string()),
+                        address.clone(),
+                        info.mac.as_deref().map_or("n/a".to_string(), format_mac),
+                        info.ver.clone().unwrap_or_else(|| "n/a".to_string()),
+                    ]
+                })
+                .collect();
+            print_table(&["NAME", "IP", "MAC", "VERSION"], &rows);
+            setup_note();
+
+            if add {
+                println!();
+                let devices: Vec<(String, String)> = found
+                    .into_iter()
+                    .map(|(address, info)| {
+                        (info.name.unwrap_or_else(|| "WLED".to_string()), address)
+                    })
+                    .collect();
+                add_found_devices(&devices, yes)?;
+            }
+        }
+        Commands::Setup {
+            name,
+            ssid,
+            wifi_password,
+            address,
+            wait,
+            yes,
+        } => {
+            interrupt::catch_ctrl_c()?;
+
+            println!("Looking for a WLED device in setup mode at {address}...");
+            let info = match api::probe_info(
+                &address,
+                setup::AP_TIMEOUT,
+                &api::ClientOptions::default(),
+            ) {
+                Ok(info) if info.brand.as_deref() == Some("WLED") => info,
+                Ok(_) => {
+                    return Err(
+                        format!("The device at {address} doesn't look like a WLED device").into(),
+                    )
+                }
+                Err(_) => {
+                    return Err(format!(
+                        "No WLED device answered at {address}. Connect this computer to the device's own Wi-Fi network first: it's called {}, with the password {}, unless they've been changed. If the device is already on your network, use `wld discover --add` instead.",
+                        setup::AP_SSID,
+                        setup::AP_PASSWORD
+                    )
+                    .into())
+                }
+            };
+            println!(
+                "Found WLED {} with {} LEDs",
+                info.ver.as_deref().unwrap_or("(unknown version)"),
+                info.leds
+                    .as_ref()
+                    .and_then(|leds| leds.count)
+                    .map_or("an unknown number of".to_string(), |c| c.to_string())
+            );
+
+            // Save it where it is now, so it can be used straight away even if it never
+            // joins another network
+            let (mut config, lock) = Config::load_locked()?;
+            // A device saved by an earlier setup that didn't finish is saved again
+            let earlier = config
+                .devices
+                .iter()
+                .find(|(_, saved)| address::same_address(&saved.ip, &address))
+                .map(|(name, _)| name.clone());
+            let name = match name.or(earlier) {
+                Some(name) => name,
+                None => discover::unique_name(info.name.as_deref().unwrap_or("WLED"), |n| {
+                    config.devices.contains_key(n)
+                }),
+            };
+            if let Some(saved) = config.devices.get(&name) {
+                if !address::same_address(&saved.ip, &address) {
+                    return Err(format!(
+                        "Device '{name}' is already saved with IP {}. Give the new device another name",
+                        saved.ip
+                    )
+                    .into());
+                }
+            }
+            config.add_device(name.clone(), address.clone());
+            config.save()?;
+            drop(lock);
+            println!("Saved it as '{name}'");
+
+            let ssid = match ssid {
+                Some(ssid) => Some(ssid),
+                None if !yes
+                    && std::io::stdin().is_terminal()
+                    && confirm("Connect it to your Wi-Fi network?")? =>
+                {
+                    Some(prompt("Wi-Fi network name: ")?)
+                }
+                None => None,
+            };
+            let Some(ssid) = ssid else {
+                println!(
+                    "Device '{name}' can only be reached while this computer is connected to its Wi-Fi network. Run `wld setup {name} --ssid <network>` to connect it to yours."
+                );
+                return Ok(());
+            };
+            let password = match wifi_password {
+                Some(password) => password,
+                None => rpassword::prompt_password(format!("Password for '{ssid}': "))?,
+            };
+
+            println!("Sending the details of '{ssid}' to the device...");
+            join_wifi(&address, &ssid, &password)?;
+            println!(
+                "The device is restarting to join '{ssid}'. Connect this computer to '{ssid}' again if it doesn't on its own."
+            );
+
+            let not_found = format!(
+                "Find it with `wld discover`, then save its address with `wld add {name} <ip> --force`. If it couldn't join '{ssid}', for example because the password was wrong, it opens its own network again after a minute or so, for you to run `wld setup` again."
+            );
+            let Some(mac) = info.mac else {
+                return Err(format!(
+                    "The device didn't say what its MAC address is, so it can't be looked for on '{ssid}'. {not_found}"
+                )
+                .into());
+            };
+            println!(
+                "Looking for it on '{ssid}' for up to {}...",
+                duration::format_duration(wait)
+            );
+            let Some(found) = setup::locate(&Config::load()?, &mac, wait) else {
+                return Err(format!(
+                    "Couldn't find the device on '{ssid}' within {}. {not_found}",
+                    duration::format_duration(wait)
+                )
+                .into());
+            };
+
+            let (mut config, _lock) = Config::load_locked()?;
+            config.add_device(name.clone(), found.clone());
+            config.save()?;
+            println!(
+                "Found it at {found}, and saved device '{name}' there. Try `wld on -d {name}`"
+            );
+        }
+        Commands::Nodes { device, add, yes } => {
+            let (ip, nodes) = get_device_nodes(device.as_deref())?;
+
+            if nodes.is_empty() {
+                println!("Device at {ip} hasn't found any other WLED devices");
+                return Ok(());
+            }
+
+            let rows: Vec<[String; 3]> = nodes
+                .iter()
+                .map(|node| {
+                    let or_na = |value: Option<&str>| value.unwrap_or("n/a").to_string();
+                    [
+                        or_na(node.name.as_deref()),
+                        or_na(node.ip.as_deref()),
+                        or_na(node.chip()),
+                    ]
+                })
+                .collect();
+            print_table(&["NAME", "IP", "TYPE"], &rows);
+
+            if add {
+                println!();
+                let devices: Vec<(String, String)> = nodes
+                    .into_iter()
+                    .filter_map(|node| {
+                        let name = node.name.unwrap_or_else(|| "WLED".to_string());
+                        Some((name, node.ip?))
+                    })
+                    .collect();
+                add_found_devices(&devices, yes)?;
+            }
+        }
+        Commands::Group { action } => match action {
+            GroupAction::Add { group, devices } => {
+                let (mut config, _lock) = Config::load_locked()?;
+                config.add_to_group(&group, &devices)?;
+                config.save()?;
+                println!("Added {} to group '{group}'", devices.join(", "));
+            }
+            GroupAction::Rm { group, devices } => {
+                let (mut config, _lock) = Config::load_locked()?;
+                config.remove_from_group(&group, &devices)?;
+                config.save()?;
+                if devices.is_empty() {
+                    println!("Deleted group '{group}'");
+                } else {
+                    println!("Removed {} from group '{group}'", devices.join(", "));
+                }
+            }
+            GroupAction::Ls => {
+                let config = Config::load()?;
+
+                if config.groups.is_empty() {
+                    println!("No groups saved");
+                    return Ok(());
+                }
+
+                println!("Saved groups:");
+                for (group, devices) in &config.groups {
+                    println!("  {group} - {}", devices.join(", "));
+                }
+            }
+        },
+        Commands::Tag { action } => match action {
+            TagAction::Add { device, tags } => {
+                let (mut config, _lock) = Config::load_locked()?;
+                config.add_tags(&device, &tags)?;
+                config.save()?;
+                println!("Tagged device '{device}' with {}", tags.join(", "));
+            }
+            TagAction::Rm { device, tags } => {
+                let (mut config, _lock) = Config::load_locked()?;
+                config.remove_tags(&device, &tags)?;
+                config.save()?;
+                println!("Removed {} from device '{device}'", tags.join(", "));
+            }
+        },
+        Commands::Profile { action } => match action {
+            ProfileAction::Ls => {
+                let active = Config::active_profile()?;
+                println!("Profiles:");
+                for name in Config::profiles()? {
+                    let marker = if active.as_ref() == Some(&name) {
+                        "*"
+                    } else {
+                        " "
+                    };
+                    println!("{marker} {name}");
+                }
+            }
+            ProfileAction::Create { name } => {
+                Config::create_profile(&name)?;
+                println!("Created profile '{name}'. Use it with `wld --profile {name}`");
+            }
+            ProfileAction::Rm { name, yes } => {
+                if name == config::DEFAULT_PROFILE {
+                    return Err("The default profile can't be removed".into());
+                }
+                if !Config::profiles()?.contains(&name) {
+                    return Err(format!("Profile '{name}' doesn't exist").into());
+                }
+                if !yes && !confirm(&format!("Delete profile '{name}' and its devices?"))? {
+                    println!("Kept profile '{name}'");
+                    return Ok(());
+                }
+                Config::remove_profile(&name)?;
+                println!("Deleted profile '{name}'");
+            }
+        },
+        Commands::Export { format } => {
+            let list = export::DeviceList::from_config(&Config::load()?);
+            match format {
+                ListFormat::Json => println!("{}", list.to_json()),
+                ListFormat::Csv => print!("{}", list.to_csv()),
+            }
+        }
+        Commands::Import {
+            file,
+            merge: _,
+            replace,
+            format,
+        } => {
+            let text = if file == "-" {
+                let mut input = String::new();
+                std::io::Read::read_to_string(&mut std::io::stdin(), &mut input)?;
+                input
+            } else {
+                std::fs::read_to_string(&file)
+                    .map_err(|e| format!("Failed to read '{file}': {e}"))?
+            };
+            let list = match format {
+                ListFormat::Json => export::DeviceList::from_json(&text)?,
+                ListFormat::Csv => export::DeviceList::from_csv(&text)?,
+            };
+            let mode = if replace {
+                export::Mode::Replace
+            } else {
+                export::Mode::Merge
+            };
+
+            let (mut config, _lock) = Config::load_locked()?;
+            let changes = list
+                .import(&mut config, mode)
+                .map_err(|e| format!("Nothing was imported: {e}"))?;
+            config.save()?;
+
+            for change in &changes {
+                println!("{change}");
+            }
+            println!(
+                "Imported {} device(s) and {} group(s)",
+                list.devices.len(),
+                list.groups.len()
+            );
+        }
+        Commands::Config { action } => match action {
+            ConfigAction::Path => println!("{}", Config::config_path()?.display()),
+            ConfigAction::Edit => {
+                let path = Config::edit(|path, problem| {
+                    if let Some(problem) = problem {
+                        eprintln!("Error: config file {} {problem}", path.display());
+                        if !std::io::stdin().is_terminal() || !confirm("Edit it again?")? {
+                            return Ok(false);
+                        }
+                    }
+                    run_editor(path)?;
+                    Ok::<_, Box<dyn std::error::Error>>(true)
+                })?;
+                println!("Saved config file {}", path.display());
+            }
+            ConfigAction::Check { online } => {
+                let path = Config::config_path()?;
+                let config = Config::load_from(&path)?;
+
+                let mut problems = check::check(&config);
+                if online {
+                    for (name, device) in &config.devices {
+                        if let Err(e) = verify_device(&config, &device.ip) {
+                            problems.push(check::Problem::error(
+                                format!("devices.{name}"),
+                                format!("device at {} {e}", device.ip),
+                            ));
+                        }
+                    }
+                }
+
+                if problems.is_empty() {
+                    println!("No problems found in {}", path.display());
+                    return Ok(());
+                }
+                for problem in &problems {
+                    println!("{problem}");
+                }
+                let errors = problems
+                    .iter()
+                    .filter(|p| p.severity == check::Severity::Error)
+                    .count();
+                println!(
+                    "\nFound {errors} error(s) and {} warning(s) in {}",
+                    problems.len() - errors,
+                    path.display()
+                );
+                if errors > 0 {
+                    return Err(Exit::USAGE.into());
+                }
+            }
+            ConfigAction::Show => {
+                let mut config = Config::load()?;
+                config.version = config::VERSION;
+                for credentials in config.credentials.values_mut() {
+                    for secret in [&mut credentials.password, &mut credentials.pin] {
+                        if secret.is_some() {
+                            *secret = Some("****".to_string());
+                        }
+                    }
+                }
+                print!("{}", toml::to_string_pretty(&config)?);
+            }
+        },
+        Commands::Scene { action } => match action {
+            SceneAction::Save { name, devices } => {
+                let captured = capture_scene(&devices)?;
+                let names: Vec<&str> = captured.iter().map(|d| d.device.as_str()).collect();
+                let names = names.join(", ");
+
+                let (mut config, _lock) = Config::load_locked()?;
+                config.save_scene(&name, captured)?;
+                config.save()?;
+                println!("Saved scene '{name}' with {names}");
+            }
+            SceneAction::Apply { name } => {
+                let results = apply_scene(&name)?;
+
+                println!("Applying scene '{name}':");
+                let mut failed = 0;
+                for SceneOutcome { device, result } in &results {
+                    match result {
+                        Ok(ip) => println!("  {device} ({ip}): OK"),
+                        Err(e) => {
+                            println!("  {device}: FAILED ({e})");
+                            failed += 1;
+                        }
+                    }
+                }
+
+                println!();
+                if failed == 0 {
+                    println!("Applied scene '{name}' to all {} devices", results.len());
+                } else {
+                    println!(
+                        "Applied scene '{name}' to {} of {} devices, {failed} failed",
+                        results.len() - failed,
+                        results.len()
+                    );
+                    return Err(Exit::PARTIAL.into());
+                }
+            }
+            SceneAction::Ls => {
+                let config = Config::load()?;
+
+                if config.scenes.is_empty() {
+                    println!("No scenes saved");
+                    return Ok(());
+                }
+
+                println!("Saved scenes:");
+                for (scene, devices) in &config.scenes {
+                    let names: Vec<&str> = devices.iter().map(|d| d.device.as_str()).collect();
+                    println!("  {scene} - {}", names.join(", "));
+                }
+            }
+            SceneAction::Rm { name } => {
+                let (mut config, _lock) = Config::load_locked()?;
+                config.remove_scene(&name)?;
+                config.save()?;
+                println!("Deleted scene '{name}'");
+            }
+        },
+        Commands::Diff { a, b, full } => {
+            // Failing to fetch either device has its own exit status, so scripts can tell
+            // it apart from the devices being different
+            let fetch = || -> Result<_, Box<dyn std::error::Error>> {
+                Ok((
+                    get_device_state_json(Some(&a))?,
+                    get_device_state_json(Some(&b))?,
+                ))
+            };
+            let ((ip_a, state_a), (ip_b, state_b)) = match fetch() {
+                Ok(states) => states,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return Err(Exit::UNREACHABLE.into());
+                }
+            };
+
+            let rows: Vec<[String; 3]> = if full {
+                let describe = |value: Option<serde_json::Value>| {
+                    value.map_or("(missing)".to_string(), |v| v.to_string())
+                };
+                diff::structural(&state_a, &state_b)
+                    .into_iter()
+                    .map(|(path, x, y)| [path, describe(x), describe(y)])
+                    .collect()
+            } else {
+                diff::summary(&state_a, &state_b)
+                    .into_iter()
+                    .map(|d| [d.field, d.a, d.b])
+                    .collect()
+            };
+
+            if rows.is_empty() {
+                println!("Devices {a} ({ip_a}) and {b} ({ip_b}) are the same");
+                return Ok(());
+            }
+
+            println!("Devices {a} ({ip_a}) and {b} ({ip_b}) differ:");
+            print_table(&["FIELD", a.as_str(), b.as_str()], &rows);
+            return Err(Exit(1).into());
+        }
+        Commands::Clone { from, to, dry_run } => {
+            let source = get_clone_source(&from)?;
+            let targets = expand_groups(&Config::load()?, &to);
+
+            let mut failed = 0;
+            for target in &targets {
+                let plan = match clone_to_device(&source, target, dry_run) {
+                    Ok(plan) => plan,
+                    Err(e) => {
+                        eprintln!("Error: Failed to copy to {target}: {e}");
+                        failed += 1;
+                        continue;
+                    }
+                };
+
+                if let Some(count) = plan.led_count_mismatch {
+                    eprintln!(
+                        "Warning: device at {} has {count} LEDs but device at {} has {}, so the copy may not line up",
+                        plan.ip,
+                        source.ip,
+                        source
+                            .led_count
+                            .map_or("an unknown number of".to_string(), |c| c.to_string())
+                    );
+                }
+                if dry_run {
+                    println!("Would send to device at {}:", plan.ip);
+                    println!("{}", serde_json::to_string_pretty(&plan.update)?);
+                } else {
+                    println!("Copied device at {} to device at {}", source.ip, plan.ip);
+                }
+            }
+
+            if failed > 0 {
+                println!(
+                    "Copied to {} of {} devices, {failed} failed",
+                    targets.len() - failed,
+                    targets.len()
+                );
+                return Err(Exit::PARTIAL.into());
+            }
+        }
+        Commands::On {
+            device,
+            after: Some(after),
+            local,
+            ..
+        } => set_device_power_after(device.as_deref(), true, after, local)?,
+        Commands::Off {
+            device,
+            after: Some(after),
+            local,
+            ..
+        } => set_device_power_after(device.as_deref(), false, after, local)?,
+        Commands::On {
+            device,
+            all,
+            tags,
+            transition,
+            json,
+            brightness,
+            color,
+            ..
+        } => {
+            let brightness = brightness.map(|b| b.resolve(false)).transpose()?;
+            let power_on = |device: Option<&str>| {
+                let TurnedOn {
+                    ip,
+                    brightness,
+                    preset,
+                } = turn_on_device(device, brightness, color, transition)?;
+                let details: Vec<String> = brightness
+                    .map(|b| format!("brightness {b}"))
+                    .into_iter()
+                    .chain(color.map(|c| format!("color {c}")))
+                    .chain(preset.map(|p| format!("preset {p}")))
+                    .collect();
+                if details.is_empty() {
+                    return Ok(ActionOutcome {
+                        message: format!("Turned on device at {ip}"),
+                        ip,
+                        value: None,
+                    });
+                }
+                Ok(ActionOutcome {
+                    message: format!("Turned on device at {ip} with {}", details.join(" and ")),
+                    ip,
+                    value: Some(serde_json::json!({
+                        "brightness": brightness,
+                        "color": color.map(|c| c.to_hex()),
+                        "preset": preset,
+                    })),
+                })
+            };
+
+            if let Some(names) = Config::load()?.targets(all, &tags, device.as_deref())? {
+                return for_each_device(&names, "on", json, |ip| {
+                    Ok(ActionOutcome {
+                        message: "ON".to_string(),
+                        ..power_on(Some(ip))?
+                    })
+                });
+            }
+
+            report_action("on", device.as_deref(), json, power_on(device.as_deref()))?;
+        }
+        Commands::Off {
+            device,
+            all,
+            tags,
+            transition,
+            json,
+            ..
+        } => {
+            let power_off = |device: Option<&str>| {
+                let ip = set_device_power(device, false, transition)?;
+                Ok(ActionOutcome {
+                    message: format!("Turned off device at {ip}"),
+                    ip,
+                    value: None,
+                })
+            };
+
+            if let Some(names) = Config::load()?.targets(all, &tags, device.as_deref())? {
+                return for_each_device(&names, "off", json, |ip| {
+                    Ok(ActionOutcome {
+                        message: "OFF".to_string(),
+                        ..power_off(Some(ip))?
+                    })
+                });
+            }
+
+            report_action("off", device.as_deref(), json, power_off(device.as_deref()))?;
+        }
+        Commands::Toggle { device } => {
+            let (ip, power_state) = toggle_device_power(device.as_deref())?;
+            let state = if power_state { "ON" } else { "OFF" };
+            println!("Toggled device at {ip}: now {state}");
+        }
+        Commands::Run { file, keep_going } => run_script(&file, keep_going)?,
+        Commands::At {
+            time,
+            local,
+            command,
+        } => run_at(time, local, command)?,
+        Commands::Repl => run_repl()?,
+        Commands::Completions { shell } => {
+            print!("{}", completions::script(shell, &mut Cli::command()));
+        }
+        Commands::Complete {
+            list: CompletionList::Devices,
+        } => {
+            // Completion scripts run this on every tab press, so it stays quiet on errors
+            let Ok(config) = Config::load() else {
+                return Ok(());
+            };
+            let mut names: Vec<&String> =
+                config.devices.keys().chain(config.groups.keys()).collect();
+            names.sort();
+            for name in names {
+                println!("{name}");
+            }
+        }
+        #[cfg(feature = "mcp")]
+        Commands::Mcp { http, token } => {
+            wld::mcp::handle_mcp_command(http, token)?;
+        }
+        Commands::Brightness {
+            value,
+            device,
+            all,
+            tags,
+            percentage,
+            percent,
+            transition,
+            raw,
+            json,
+        } => {
+            let value = match (value, percent) {
+                (Some(value), _) => value.resolve(percentage),
+                (None, Some(percent)) => percent.percentage_to_scale(),
+                (None, None) => Err("No brightness specified".to_string()),
+            };
+            let set_brightness = |device: Option<&str>, short: bool| {
+                let value = value.clone()?;
+                let (ip, previous, brightness) =
+                    set_device_brightness(device, value, transition, raw)?;
+                let turned_off = brightness == 0 && !raw;
+                // The previous brightness is only read for a relative change
+                let message = match (previous, short) {
+                    (None, false) if turned_off => format!(
+                        "Turned off device at {ip}, since it shows nothing at brightness 0 (use --raw to set 0 anyway)"
+                    ),
+                    (Some(previous), false) if turned_off => format!(
+                        "Brightness {previous} → 0, so turned off device at {ip} (use --raw to set 0 anyway)"
+                    ),
+                    (None, true) if turned_off => "off, at brightness 0".to_string(),
+                    (Some(previous), true) if turned_off => {
+                        format!("brightness {previous} → 0, so off")
+                    }
+                    (None, false) => format!("Set brightness to {brightness} for device at {ip}"),
+                    (Some(previous), false) => {
+                        format!("Brightness {previous} → {brightness} for device at {ip}")
+                    }
+                    (None, true) => format!("brightness {brightness}"),
+                    (Some(previous), true) => format!("brightness {previous} → {brightness}"),
+                };
+                Ok(ActionOutcome {
+                    ip,
+                    value: Some(serde_json::json!(brightness)),
+                    message,
+                })
+            };
+
+            if let Some(names) = Config::load()?.targets(all, &tags, device.as_deref())? {
+                // An invalid value fails the same way on every device, so report it once
+                value.clone()?;
+                return for_each_device(&names, "brightness", json, |ip| {
+                    set_brightness(Some(ip), true)
+                });
+            }
 
-    let url = reqwest::Url::parse(&format!("http://{ip}"))?;
-    let mut wled = Wled::try_from_url(&url)?;
+            report_action(
+                "brightness",
+                device.as_deref(),
+                json,
+                set_brightness(device.as_deref(), false),
+            )?;
+        }
+        Commands::Fade {
+            target,
+            over,
+            device,
+        } => {
+            let target = target.resolve(false)?;
+            println!(
+                "Fading over {} (press Ctrl-C to stop)...",
+                duration::format_duration(over)
+            );
 
-    // Get current state
-    wled.get_state_from_wled()?;
+            let outcome = fade_device_brightness(device.as_deref(), target, over)?;
+            if outcome.completed {
+                println!(
+                    "Faded device at {} from {} to {}",
+                    outcome.ip, outcome.start, outcome.reached
+                );
+            } else {
+                println!(
+                    "Stopped fading device at {}, leaving brightness at {}",
+                    outcome.ip, outcome.reached
+                );
+            }
+        }
+        Commands::Sunrise {
+            target_brightness,
+            device,
+            preview: true,
+            ..
+        } => {
+            println!(
+                "Previewing a {} sunrise (press Ctrl-C to stop)...",
+                duration::format_duration(sunrise::PREVIEW_LENGTH)
+            );
+            match preview_device_sunrise(device.as_deref(), target_brightness)? {
+                (ip, true) => println!("Sunrise preview finished on device at {ip}"),
+                (ip, false) => println!("Stopped the sunrise preview on device at {ip}"),
+            }
+        }
+        Commands::Sunrise {
+            duration: minutes,
+            target_brightness,
+            device,
+            preview: false,
+        } => {
+            let ip = start_device_sunrise(device.as_deref(), minutes, target_brightness)?;
+            let length = Duration::from_secs(u64::from(minutes) * 60);
+            let finish = chrono::Local::now() + chrono::Duration::minutes(i64::from(minutes));
+            println!(
+                "Started a {} sunrise on device at {ip}, which will reach brightness {target_brightness} at {}",
+                duration::format_duration(length),
+                finish.format("%H:%M")
+            );
+        }
+        Commands::Color {
+            value,
+            device,
+            transition,
+            json,
+        } => {
+            let result =
+                set_device_color(device.as_deref(), value, transition).map(|ip| ActionOutcome {
+                    message: format!("Set color to {value} for device at {ip}"),
+                    ip,
+                    value: Some(serde_json::json!(value.to_hex())),
+                });
+            report_action("color", device.as_deref(), json, result)?;
+        }
+        Commands::White { value, device } => {
+            let (ip, has_white) = set_device_white(device.as_deref(), value)?;
+            println!("Set white to {value} for device at {ip}");
+            if has_white == Some(false) {
+                eprintln!(
+                    "Warning: device at {ip} reports no white channel, so this may have no effect"
+                );
+            }
+        }
+        Commands::Cct {
+            value,
+            kelvin,
+            device,
+        } => {
+            let value = value.or(kelvin).ok_or("No color temperature specified")?;
+            let (ip, has_cct) = set_device_cct(device.as_deref(), value)?;
+            println!("Set color temperature to {value} for device at {ip}");
+            if has_cct == Some(false) {
+                eprintln!("Warning: device at {ip} reports no color temperature support, so this may have no effect");
+            }
+        }
+        Commands::Effect {
+            effect,
+            speed,
+            intensity,
+            device,
+        } => {
+            let (ip, id, name) = set_device_effect(device.as_deref(), &effect, speed, intensity)?;
+            println!("Set effect {id} ({name}) for device at {ip}");
+        }
+        Commands::Effects { search, device } => {
+            let names = get_device_effects(device.as_deref())?;
+            print_catalog(&names, search.as_deref(), "effect");
+        }
+        Commands::Palette { palette, device } => {
+            let (ip, id, name) = set_device_palette(device.as_deref(), &palette)?;
+            println!("Set palette {id} ({name}) for device at {ip}");
+        }
+        Commands::Random {
+            device,
+            effect,
+            palette,
+            color,
+        } => {
+            // With no flags, randomize everything
+            let all = !effect && !palette && !color;
+            let look = randomize_device(
+                device.as_deref(),
+                effect || all,
+                palette || all,
+                color || all,
+            )?;
 
-    // Update state
-    if let Some(state) = &mut wled.state {
-        state.bri = Some(brightness);
-    } else {
-        wled.state = Some(State {
-            bri: Some(brightness),
-            ..Default::default()
-        });
-    }
+            println!("Applied a random look to device at {}:", look.ip);
+            if let Some((id, name)) = look.effect {
+                println!("  Effect: {id} ({name})");
+            }
+            if let Some((id, name)) = look.palette {
+                println!("  Palette: {id} ({name})");
+            }
+            if let Some(color) = look.color {
+                println!("  Color: {}", color.to_hex());
+            }
+        }
+        Commands::Palettes { search, device } => {
+            let names = get_device_palettes(device.as_deref())?;
+            print_catalog(&names, search.as_deref(), "palette");
+        }
+        Commands::Preset {
+            action: Some(PresetAction::Save { slot, name, device }),
+            ..
+        } => {
+            let (ip, preset) = save_device_preset(device.as_deref(), slot, name.as_deref())?;
+            let name = preset.name.as_deref().unwrap_or("(unnamed)");
+            println!("Saved preset {slot} ({name}) on device at {ip}");
+        }
+        Commands::Preset {
+            list: true, device, ..
+        } => {
+            let presets = get_device_presets(device.as_deref())?;
 
-    // Send updated state
-    wled.flush_state()?;
+            if presets.is_empty() {
+                println!("No presets saved");
+                return Ok(());
+            }
 
-    println!("Set brightness to {brightness} for device at {ip}");
+            for (id, preset) in presets {
+                let name = preset.name.as_deref().unwrap_or("(unnamed)");
+                let playlist_marker = if preset.playlist.is_some() {
+                    " (playlist)"
+                } else {
+                    ""
+                };
+                println!("{id:>3}  {name}{playlist_marker}");
+            }
+        }
+        Commands::Preset { id, device, .. } => {
+            let id = id.ok_or("No preset specified")?;
+            let (ip, active) = apply_device_preset(device.as_deref(), id)?;
+            println!("Applied preset {id} to device at {ip}");
 
-    Ok(())
-}
+            match active {
+                Some(-1) => eprintln!(
+                    "Warning: device reports no active preset, so preset {id} may not exist"
+                ),
+                Some(active) if active != i32::from(id) => eprintln!(
+                    "Warning: device reports preset {active} as active, so preset {id} may not exist"
+                ),
+                _ => {}
+            }
+        }
+        Commands::Playlist {
+            action: Some(PlaylistAction::Stop { device }),
+            ..
+        } => {
+            let ip = stop_device_playlist(device.as_deref())?;
+            println!("Stopped playlist on device at {ip}");
+        }
+        Commands::Playlist {
+            action: Some(PlaylistAction::Status { device }),
+            ..
+        } => match get_device_playlist(device.as_deref())? {
+            (
+                ip,
+                Some(RunningPlaylist {
+                    id,
+                    name: Some(name),
+                }),
+            ) => {
+                println!("Playlist {id} ({name}) is running on device at {ip}")
+            }
+            (ip, Some(RunningPlaylist { id, name: None })) => {
+                println!("Playlist {id} is running on device at {ip}")
+            }
+            (ip, None) => println!("No playlist is running on device at {ip}"),
+        },
+        Commands::Playlist { id, device, .. } => {
+            let id = id.ok_or("No playlist specified")?;
+            let (ip, name) = start_device_playlist(device.as_deref(), id)?;
+            match name {
+                Some(name) => println!("Started playlist {id} ({name}) on device at {ip}"),
+                None => println!("Started playlist {id} on device at {ip}"),
+            }
+        }
+        Commands::Segment { action } => match action {
+            SegmentAction::Ls { device } => {
+                let (_, segments) = get_device_segments(device.as_deref())?;
+                print_segments(&segments);
+            }
+            SegmentAction::On { id, device } => {
+                let ip = update_device_segment(device.as_deref(), id, SegmentChange::Power(true))?;
+                println!("Turned on segment {id} on device at {ip}");
+            }
+            SegmentAction::Off { id, device } => {
+                let ip = update_device_segment(device.as_deref(), id, SegmentChange::Power(false))?;
+                println!("Turned off segment {id} on device at {ip}");
+            }
+            SegmentAction::Brightness { id, value, device } => {
+                let ip =
+                    update_device_segment(device.as_deref(), id, SegmentChange::Brightness(value))?;
+                println!("Set brightness of segment {id} to {value} on device at {ip}");
+            }
+            SegmentAction::Color { id, value, device } => {
+                let ip = update_device_segment(device.as_deref(), id, SegmentChange::Color(value))?;
+                println!("Set color of segment {id} to {value} on device at {ip}");
+            }
+            SegmentAction::Set {
+                id,
+                start,
+                stop,
+                grouping,
+                spacing,
+                device,
+            } => {
+                let ip = set_device_segment_bounds(
+                    device.as_deref(),
+                    id,
+                    start,
+                    stop,
+                    grouping,
+                    spacing,
+                )?;
+                println!("Set segment {id} to LEDs {start}-{stop} on device at {ip}");
+            }
+            SegmentAction::Delete { id, device } => {
+                let ip = update_device_segment(device.as_deref(), id, SegmentChange::Delete)?;
+                println!("Deleted segment {id} on device at {ip}");
+            }
+            SegmentAction::Main { id, device } => {
+                let ip = set_device_main_segment(device.as_deref(), id)?;
+                println!("Segment {id} is now the main segment on device at {ip}");
+            }
+            SegmentAction::Reverse { id, state, device } => {
+                print_segment_orientation(device.as_deref(), id, Orientation::Reverse, state)?;
+            }
+            SegmentAction::Mirror { id, state, device } => {
+                print_segment_orientation(device.as_deref(), id, Orientation::Mirror, state)?;
+            }
+        },
+        Commands::Sync { action } => match action {
+            SyncAction::On { send, recv, device } => {
+                let (send, recv) = sync_flags(true, send, recv);
+                let ip = set_device_sync(device.as_deref(), send, recv)?;
+                println!("Turned on sync for device at {ip}");
+            }
+            SyncAction::Off { send, recv, device } => {
+                let (send, recv) = sync_flags(false, send, recv);
+                let ip = set_device_sync(device.as_deref(), send, recv)?;
+                println!("Turned off sync for device at {ip}");
+            }
+            SyncAction::Status { device } => {
+                let (ip, udpn) = get_device_sync(device.as_deref())?;
+                let describe = |flag: Option<bool>| match flag {
+                    Some(true) => "on",
+                    Some(false) => "off",
+                    None => "n/a",
+                };
+                println!("Sync for device at {ip}:");
+                println!("  Send: {}", describe(udpn.send));
+                println!("  Receive: {}", describe(udpn.recv));
+            }
+        },
+        Commands::Live { action } => {
+            let (device, live_override, description) = match action {
+                LiveAction::Off { device } => (device, 0, "Realtime data can take over"),
+                LiveAction::Override { device } => {
+                    (device, 1, "Ignoring realtime data until the stream ends on")
+                }
+                LiveAction::UntilReboot { device } => {
+                    (device, 2, "Ignoring realtime data until the next reboot of")
+                }
+                LiveAction::Status { device } => {
+                    let status = get_device_live_status(device.as_deref())?;
 
-pub fn set_device_power(
-    device: Option<&str>,
-    power_state: bool,
-) -> Result<(), Box<dyn std::error::Error>> {
-    let config = Config::load()?;
-    let ip = config.get_device_ip(device)?;
+                    let receiving = match status.receiving {
+                        Some(true) => {
+                            let source = status.source.as_deref().unwrap_or("unknown source");
+                            match &status.source_ip {
+                                Some(source_ip) => format!("yes ({source} from {source_ip})"),
+                                None => format!("yes ({source})"),
+                            }
+                        }
+                        Some(false) => "no".to_string(),
+                        None => "n/a".to_string(),
+                    };
+                    let live_override = match status.live_override {
+                        Some(0) => "off",
+                        Some(1) => "until the stream ends",
+                        Some(2) => "until reboot",
+                        _ => "n/a",
+                    };
 
-    let url = reqwest::Url::parse(&format!("http://{ip}"))?;
-    let mut wled = Wled::try_from_url(&url)?;
+                    println!("Live status of device at {}:", status.ip);
+                    println!("  Receiving realtime data: {receiving}");
+                    println!("  Live override: {live_override}");
+                    return Ok(());
+                }
+            };
 
-    // Get current state
-    wled.get_state_from_wled()?;
+            let ip = set_device_live_override(device.as_deref(), live_override)?;
+            println!("{description} device at {ip}");
+        }
+        Commands::Reboot { device, yes, wait } => {
+            let ip = Config::load()?.get_device_ip(device.as_deref())?;
 
-    // Update state
-    if let Some(state) = &mut wled.state {
-        state.on = Some(power_state);
-    } else {
-        wled.state = Some(State {
-            on: Some(power_state),
-            ..Default::default()
-        });
-    }
+            if !yes && !confirm(&format!("Reboot device at {ip}?"))? {
+                println!("Reboot cancelled");
+                return Ok(());
+            }
 
-    // Send updated state
-    wled.flush_state()?;
+            println!("Rebooting device at {ip}...");
+            let (ip, took) = reboot_device(Some(&ip), wait)?;
+            println!(
+                "Device at {ip} is back after {}",
+                duration::format_duration(took)
+            );
+        }
+        Commands::Backup { device, all, out } => {
+            let timestamp = backup::timestamp(unix_time());
 
-    let action = if power_state { "on" } else { "off" };
-    println!("Turned {action} device at {ip}");
+            if !all {
+                let (ip, paths) = backup_device(device.as_deref(), &out, &timestamp)?;
+                println!("Backed up device at {ip} to:");
+                for path in paths {
+                    println!("  {}", path.display());
+                }
+                return Ok(());
+            }
 
-    Ok(())
-}
+            let config = Config::load()?;
+            let names: Vec<&String> = config.devices.keys().collect();
 
-#[derive(Debug)]
-pub enum DeviceStatus {
-    On,
-    Off,
-    Unreachable,
-}
+            if names.is_empty() {
+                println!("No devices saved");
+                return Ok(());
+            }
 
-pub fn get_device_status(ip: &str) -> DeviceStatus {
-    let url = match reqwest::Url::parse(&format!("http://{ip}")) {
-        Ok(u) => u,
-        Err(_) => return DeviceStatus::Unreachable,
-    };
+            let mut failed = 0;
+            for name in &names {
+                let dir = out.join(backup::directory_name(name));
+                print!("  {name}: ");
+                match backup_device(Some(name), &dir, &timestamp) {
+                    Ok(_) => println!("saved to {}", dir.display()),
+                    Err(e) => {
+                        println!("FAILED ({e})");
+                        failed += 1;
+                    }
+                }
+            }
 
-    let mut wled = match Wled::try_from_url(&url) {
-        Ok(w) => w,
-        Err(_) => return DeviceStatus::Unreachable,
-    };
+            println!();
+            if failed == 0 {
+                println!("Backed up all {} devices", names.len());
+            } else {
+                println!(
+                    "Backed up {} of {} devices, {failed} failed",
+                    names.len() - failed,
+                    names.len()
+                );
+                return Err(Exit::PARTIAL.into());
+            }
+        }
+        Commands::Restore {
+            path,
+            device,
+            yes,
+            wait,
+        } => {
+            let backups = backup::find_backups(&path)?;
 
-    // Try to get current state from device
-    match wled.get_state_from_wled() {
-        Ok(_) => {
-            // Check if device is on or off
-            if let Some(state) = &wled.state {
-                if let Some(on) = state.on {
-                    if on {
-                        return DeviceStatus::On;
-                    } else {
-                        return DeviceStatus::Off;
-                    }
+            let confirm_restore = |ip: &str| {
+                if yes {
+                    return Ok(true);
+                }
+                println!("This will overwrite on device at {ip}:");
+                for (file, path) in &backups {
+                    println!("  {} with {}", file.device_name(), path.display());
                 }
+                confirm("Continue?")
+            };
+            let progress = |step| match step {
+                RestoreStep::Restored(file) => println!("Restored {}", file.device_name()),
+                RestoreStep::Restarting { ip } => {
+                    println!("Waiting for device at {ip} to restart...")
+                }
+            };
+            match restore_device(device.as_deref(), &backups, wait, confirm_restore, progress)? {
+                Some(ip) => println!("Device at {ip} is responding after the restore"),
+                None => println!("Restore cancelled"),
             }
-            // If we can reach the device but can't determine state, assume it's on
-            DeviceStatus::On
         }
-        Err(_) => DeviceStatus::Unreachable,
-    }
-}
+        Commands::State { action } => match action {
+            StateAction::Get { device, raw } => {
+                let (_, state) = get_device_state_json(device.as_deref())?;
+                if raw {
+                    println!("{state}");
+                } else {
+                    println!("{}", serde_json::to_string_pretty(&state)?);
+                }
+            }
+            StateAction::Set { json, device } => {
+                let state = read_state_json(&json)?;
+                let (ip, response) = send_device_state_json(device.as_deref(), &state)?;
 
-fn run() -> Result<(), Box<dyn std::error::Error>> {
-    let cli = Cli::parse();
+                // With "v": true the device responds with its full state, which is worth showing
+                if response == serde_json::json!({"success": true}) {
+                    println!("Sent state to device at {ip}");
+                } else {
+                    println!("{}", serde_json::to_string_pretty(&response)?);
+                }
+            }
+        },
+        Commands::Watch {
+            device,
+            interval,
+            json,
+        } => {
+            if interval.is_zero() {
+                return Err("Interval must be at least 1 second".into());
+            }
+            let watcher = watch::Watch::start(device.as_deref())?;
+            let redraw = !json && std::io::stdout().is_terminal();
+            if !json {
+                println!(
+                    "Watching device at {} every {} (press Ctrl-C to stop)...",
+                    watcher.ip,
+                    duration::format_duration(interval)
+                );
+            }
 
-    match cli.command {
-        Commands::Add { name, ip } => {
-            let mut config = Config::load()?;
-            config.add_device(name.clone(), ip.clone());
-            config.save()?;
-            println!("Added device '{name}' with IP {ip}");
+            let mut previous: Option<watch::Snapshot> = None;
+            watcher.run(interval, |snapshot| {
+                let line = match snapshot {
+                    Ok(snapshot) => {
+                        let line = if json {
+                            snapshot
+                                .to_json(&watcher.effect_names, unix_time())
+                                .to_string()
+                        } else {
+                            snapshot.render(previous.as_ref(), &watcher.effect_names, redraw)
+                        };
+                        previous = Some(snapshot);
+                        line
+                    }
+                    Err(e) if json => {
+                        serde_json::json!({ "time": unix_time(), "error": e.to_string() })
+                            .to_string()
+                    }
+                    Err(e) => format!("UNREACHABLE ({e})"),
+                };
 
-            if config.devices.len() == 1 {
-                println!("Set '{name}' as the default device");
+                if redraw {
+                    // Clear the line and draw the summary over it
+                    print!("\r\x1b[2K{line}");
+                    std::io::Write::flush(&mut std::io::stdout())
+                } else {
+                    println!("{line}");
+                    Ok(())
+                }
+            })?;
+            if redraw {
+                println!();
             }
         }
-        Commands::Delete { name } => {
-            let mut config = Config::load()?;
-            config.remove_device(&name)?;
-            config.save()?;
-            println!("Deleted device '{name}'");
+        Commands::Identify { device, times } => {
+            println!("Blinking device {times} times (press Ctrl-C to stop)...");
+            match identify_device(device.as_deref(), times)? {
+                (ip, true) => println!("Blinked device at {ip} and restored its state"),
+                (ip, false) => println!("Stopped blinking device at {ip} and restored its state"),
+            }
         }
-        Commands::Ls => {
+        Commands::Ping { device, count } => {
+            interrupt::catch_ctrl_c()?;
+
             let config = Config::load()?;
+            let ip = config.get_device_ip(device.as_deref())?;
+            let options = client_options(&config, &ip);
+            let client = options
+                .builder()?
+                .timeout(options.timeout.unwrap_or(ping::TIMEOUT))
+                .build()?;
+            let url = address::base_url(&ip)?;
 
-            if config.devices.is_empty() {
-                println!("No devices saved");
-                return Ok(());
+            println!("Pinging device at {ip} with GET /json/info...");
+
+            let mut results = Vec::new();
+            for seq in 1..=count {
+                if seq > 1 && !interrupt::sleep(ping::INTERVAL) {
+                    break;
+                }
+
+                match api::ping(&client, &url) {
+                    Ok(elapsed) => {
+                        println!(
+                            "Reply from {ip}: seq={seq} time={} ms",
+                            ping::format_ms(elapsed)
+                        );
+                        results.push(Some(elapsed));
+                    }
+                    Err(e) if e.is_timeout() => {
+                        println!("Request seq={seq} timed out");
+                        results.push(None);
+                    }
+                    Err(e) => {
+                        println!("Request seq={seq} failed: {e}");
+                        results.push(None);
+                    }
+                }
             }
 
-            println!("Saved devices:");
-            for (name, ip) in &config.devices {
-                let default_marker = if config.default_device.as_ref() == Some(name) {
-                    " (default)"
-                } else {
-                    ""
-                };
-                println!("  {name} - {ip}{default_marker}");
+            let stats = ping::Stats::new(&results);
+            println!("\n--- {ip} ping statistics ---");
+            println!(
+                "{} requests sent, {} replies received, {}% lost",
+                stats.sent,
+                stats.received,
+                stats.loss_percent()
+            );
+            if let Some((min, avg, max)) = stats.round_trip {
+                println!(
+                    "round-trip min/avg/max = {}/{}/{} ms",
+                    ping::format_ms(min),
+                    ping::format_ms(avg),
+                    ping::format_ms(max)
+                );
+            }
+
+            if stats.received == 0 {
+                return Err(Exit::UNREACHABLE.into());
             }
         }
-        Commands::SetDefault { name } => {
-            let mut config = Config::load()?;
-            config.set_default(&name)?;
-            config.save()?;
-            println!("Set '{name}' as the default device");
-        }
-        Commands::On { device } => {
-            set_device_power(device.as_deref(), true)?;
-        }
-        Commands::Off { device } => {
-            set_device_power(device.as_deref(), false)?;
-        }
-        #[cfg(feature = "mcp")]
-        Commands::Mcp => {
-            mcp::handle_mcp_command()?;
+        Commands::Info { device, json } => {
+            let (_, info) = get_device_info(device.as_deref())?;
+
+            if json {
+                println!("{}", serde_json::to_string_pretty(&info)?);
+                return Ok(());
+            }
+
+            let rows = info_rows(&info);
+            let width = rows.iter().map(|(label, _)| label.len()).max().unwrap_or(0);
+            for (label, value) in rows {
+                println!("{label:<width$}  {value}");
+            }
         }
-        Commands::Brightness {
-            value,
-            device,
-            percentage,
+        Commands::Status {
+            device: Some(device),
+            json,
+            long,
+            ..
         } => {
-            let brightness = if percentage {
-                // Validate percentage is 0-100
-                if value > 100 {
-                    return Err(format!("Percentage must be between 0 and 100, got {value}").into());
+            // Scripts branch on the exit code, so failing to resolve the device has to
+            // count as unreachable rather than exiting with 1, which means off
+            let ip = match Config::load().and_then(|config| config.get_device_ip(Some(&device))) {
+                Ok(ip) => ip,
+                Err(e) => {
+                    eprintln!("Error: {e}");
+                    return Err(Exit::UNREACHABLE.into());
+                }
+            };
+
+            // Naming the effect takes another request, so it's only done when it's shown
+            let (status, details) = if json || long {
+                get_device_details(&ip)
+            } else {
+                let (status, brightness) = get_device_status_and_brightness(&ip);
+                let details = DeviceDetails {
+                    brightness,
+                    ..Default::default()
+                };
+                (status, details)
+            };
+
+            if json {
+                let config = Config::load()?;
+                // Report the saved name, even if the device was given by IP
+                let name = config
+                    .devices
+                    .iter()
+                    .find(|(name, saved)| **name == device || saved.ip == device)
+                    .map_or(device.clone(), |(name, _)| name.clone());
+                let entry = status_entry(&config, name, ip, &status, details);
+                println!("{}", serde_json::to_string_pretty(&entry)?);
+            } else {
+                let label = match status {
+                    DeviceStatus::On => "ON",
+                    DeviceStatus::Off => "OFF",
+                    DeviceStatus::Unreachable => "UNREACHABLE",
+                };
+                let details: Vec<String> = ["brightness", "effect", "preset"]
+                    .into_iter()
+                    .zip(describe_details(&details))
+                    .filter(|(_, value)| long && !value.is_empty())
+                    .map(|(label, value)| format!("{label} {value}"))
+                    .collect();
+                if details.is_empty() {
+                    println!("Device at {ip}: {label}");
+                } else {
+                    println!("Device at {ip}: {label}, {}", details.join(", "));
                 }
-                // Convert percentage to 0-255 range
-                ((value as u16 * 255) / 100) as u8
+            }
+
+            match status {
+                DeviceStatus::On => {}
+                DeviceStatus::Off => return Err(Exit(1).into()),
+                DeviceStatus::Unreachable => return Err(Exit::UNREACHABLE.into()),
+            }
+        }
+        Commands::Status {
+            device: None,
+            json: true,
+            tags,
+            parallel,
+            ..
+        } => {
+            let config = Config::load()?;
+
+            let names = if tags.is_empty() {
+                config.tagged(&tags)
             } else {
-                value
+                config.tagged_devices(&tags)?
             };
-            set_device_brightness(device.as_deref(), brightness)?;
+
+            let statuses = get_device_statuses_in_detail(&config, &names, parallel);
+            let entries: Vec<output::StatusEntry> = names
+                .into_iter()
+                .zip(statuses)
+                .map(|(name, (status, details))| {
+                    let ip = config.devices[&name].ip.clone();
+                    status_entry(&config, name, ip, &status, details)
+                })
+                .collect();
+
+            println!("{}", serde_json::to_string_pretty(&entries)?);
+
+            let unreachable = entries
+                .iter()
+                .filter(|e| e.status == output::StatusValue::Unreachable)
+                .count();
+            Exit::for_unreachable(unreachable, entries.len())?;
         }
-        Commands::Status => {
+        Commands::Status {
+            device: None,
+            json: false,
+            tags,
+            parallel,
+            long,
+            color,
+        } => {
             let config = Config::load()?;
 
             if config.devices.is_empty() {
@@ -254,11 +3735,85 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
                 return Ok(());
             }
 
-            println!("Checking status of all devices...\n");
+            let names = if tags.is_empty() {
+                println!("Checking status of all devices...\n");
+                config.tagged(&tags)
+            } else {
+                let names = config.tagged_devices(&tags)?;
+                println!(
+                    "Checking status of devices tagged {}...\n",
+                    describe_tags(&tags)
+                );
+                names
+            };
+
+            let statuses = if long {
+                get_device_statuses_in_detail(&config, &names, parallel)
+            } else {
+                get_device_statuses(&config, &names, parallel)
+                    .into_iter()
+                    .map(|(status, _)| (status, DeviceDetails::default()))
+                    .collect()
+            };
+            let unreachable = statuses
+                .iter()
+                .filter(|(status, _)| matches!(status, DeviceStatus::Unreachable))
+                .count();
+
+            let mut table = Table::new(&[
+                "NAME",
+                "ADDRESS",
+                "DEFAULT",
+                "STATE",
+                "BRIGHTNESS",
+                "EFFECT",
+                "PRESET",
+            ]);
+            for (name, (status, details)) in names.iter().zip(&statuses) {
+                let mut row = vec![
+                    (name.clone(), Style::Plain),
+                    (config.devices[name].ip.clone(), Style::Plain),
+                    default_marker(&config, name),
+                    describe_status(status),
+                ];
+                row.extend(
+                    describe_details(details)
+                        .into_iter()
+                        .map(|detail| (detail, Style::Plain)),
+                );
+                table.row(row);
+            }
+            print!("{}", table.render(color.enabled()));
+
+            Exit::for_unreachable(unreachable, names.len())?;
+        }
+        Commands::Power { device, all: false } => {
+            let config = Config::load()?;
+            let ip = config.get_device_ip(device.as_deref())?;
+            let leds = get_device_power(&ip)?;
+
+            let milliamps = leds
+                .pwr
+                .ok_or_else(|| format!("Device at {ip} did not report its power draw"))?;
+            println!(
+                "Estimated power draw of device at {ip}: {}",
+                format_power(milliamps, leds.maxpwr)
+            );
+        }
+        Commands::Power { all: true, .. } => {
+            let config = Config::load()?;
+
+            if config.devices.is_empty() {
+                println!("No devices saved");
+                return Ok(());
+            }
 
-            let mut all_reachable = true;
+            let mut total = 0;
+            let mut total_limit = Some(0);
+            let mut unreachable = 0;
 
-            for (name, ip) in &config.devices {
+            for (name, device) in &config.devices {
+                let ip = &device.ip;
                 let default_marker = if config.default_device.as_ref() == Some(name) {
                     " (default)"
                 } else {
@@ -267,23 +3822,95 @@ fn run() -> Result<(), Box<dyn std::error::Error>> {
 
                 print!("  {name} ({ip}){default_marker}: ");
 
-                match get_device_status(ip) {
-                    DeviceStatus::On => {
-                        println!("ON");
+                match get_device_power(ip) {
+                    Ok(api::Leds {
+                        pwr: Some(milliamps),
+                        maxpwr,
+                        ..
+                    }) => {
+                        println!("{}", format_power(milliamps, maxpwr));
+                        total += milliamps;
+                        // The combined limit only means something if every device has one
+                        total_limit = match (total_limit, maxpwr) {
+                            (Some(sum), Some(limit)) if limit > 0 => Some(sum + limit),
+                            _ => None,
+                        };
                     }
-                    DeviceStatus::Off => {
-                        println!("OFF");
+                    Ok(_) => {
+                        println!("n/a");
+                        total_limit = None;
                     }
-                    DeviceStatus::Unreachable => {
+                    Err(_) => {
                         println!("UNREACHABLE");
-                        all_reachable = false;
+                        unreachable += 1;
+                        total_limit = None;
                     }
                 }
             }
 
-            if !all_reachable {
-                std::process::exit(1);
+            println!();
+            match total_limit {
+                Some(limit) if limit > 0 => {
+                    let percent = (f64::from(total) / f64::from(limit) * 100.0).round();
+                    println!("Total: {total} mA ({percent}% of {limit} mA combined limit)");
+                }
+                _ => println!("Total: {total} mA"),
+            }
+
+            if unreachable > 0 {
+                eprintln!("Warning: the total doesn't include unreachable devices");
+            }
+            Exit::for_unreachable(unreachable, config.devices.len())?;
+        }
+        Commands::Wifi => {
+            let config = Config::load()?;
+
+            if config.devices.is_empty() {
+                println!("No devices saved");
+                return Ok(());
+            }
+
+            println!("Checking Wi-Fi signal of all devices...\n");
+
+            let mut results: Vec<(String, String, Option<api::Wifi>)> = config
+                .devices
+                .iter()
+                .map(|(name, device)| {
+                    let ip = &device.ip;
+                    (name.clone(), ip.clone(), get_device_wifi(ip).ok())
+                })
+                .collect();
+            sort_wifi_results(&mut results);
+
+            let mut unreachable = 0;
+
+            for (name, ip, wifi) in &results {
+                let default_marker = if config.default_device.as_ref() == Some(name) {
+                    " (default)"
+                } else {
+                    ""
+                };
+
+                print!("  {name} ({ip}){default_marker}: ");
+
+                let Some(wifi) = wifi else {
+                    println!("UNREACHABLE");
+                    unreachable += 1;
+                    continue;
+                };
+
+                let signal = match (wifi.rssi, wifi.signal) {
+                    (Some(rssi), Some(signal)) => format!("{rssi} dBm ({signal}%)"),
+                    (Some(rssi), None) => format!("{rssi} dBm"),
+                    (None, _) => "signal n/a".to_string(),
+                };
+                let channel = wifi
+                    .channel
+                    .map_or("n/a".to_string(), |channel| channel.to_string());
+                println!("{signal}, channel {channel}");
             }
+
+            Exit::for_unreachable(unreachable, results.len())?;
         }
     }
 
@@ -1,13 +1,44 @@
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use axum::{
+    extract::{Request, State},
+    http::{header, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+};
 use rmcp::{
     handler::server::{router::tool::ToolRouter, wrapper::Parameters},
     model::*,
-    schemars, tool, tool_handler, tool_router,
-    transport::stdio,
-    ErrorData as McpError, ServerHandler, ServiceExt,
+    schemars,
+    service::RequestContext,
+    tool, tool_handler, tool_router,
+    transport::{
+        stdio,
+        streamable_http_server::{session::local::LocalSessionManager, StreamableHttpService},
+    },
+    ErrorData as McpError, RoleServer, ServerHandler, ServiceExt,
 };
 
-use crate::config::Config;
-use crate::{get_device_status, set_device_brightness, set_device_power, DeviceStatus};
+use crate::address;
+use crate::api;
+use crate::brightness::BrightnessValue;
+use crate::catalog::{self, Selector};
+use crate::color;
+use crate::config::{AddOutcome, Config};
+use crate::device::{
+    apply_device_preset, cancel_device_nightlight, connect, effect_choices, get_device_details,
+    get_device_presets, get_device_statuses_with, set_device_brightness, set_device_color,
+    set_device_nightlight, set_device_power, set_effect, toggle_device_power, DeviceStatus,
+    STATUS_CHECKS,
+};
+use crate::duration;
+use crate::error::WldError;
+use crate::nightlight;
+use crate::output::StatusValue;
+use crate::track;
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct EmptyParams {}
@@ -20,178 +51,1092 @@ pub struct WledDeviceParams {
 
 #[derive(serde::Deserialize, schemars::JsonSchema)]
 pub struct WledBrightnessParams {
-    /// Brightness level (0-255)
-    pub value: u8,
+    /// Brightness level (0-255). Give either this or percent.
+    pub value: Option<u8>,
+    /// Brightness as a percentage (0-100). Give either this or value.
+    pub percent: Option<u8>,
     /// Device name or IP address (optional - if not specified, the default device is used)
     pub device: Option<String>,
 }
 
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledColorParams {
+    /// Color as #RRGGBB, RRGGBB, r,g,b (each 0-255) or a CSS color name like "red" or
+    /// "rebeccapurple"
+    pub color: String,
+    /// Device name or IP address (optional - if not specified, the default device is used)
+    pub device: Option<String>,
+}
+
+/// An effect given by ID or by name, which models send either way
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum EffectParam {
+    Id(u16),
+    Name(String),
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledSetEffectParams {
+    /// Effect ID from wled_effects_list (e.g. 9), or effect name (e.g. "Rainbow")
+    pub effect: EffectParam,
+    /// Effect speed (0-255, optional)
+    pub speed: Option<u8>,
+    /// Effect intensity (0-255, optional)
+    pub intensity: Option<u8>,
+    /// Device name or IP address (optional - if not specified, the default device is used)
+    pub device: Option<String>,
+}
+
+/// A preset given by ID or by name
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+#[serde(untagged)]
+pub enum PresetParam {
+    Id(u8),
+    Name(String),
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledApplyPresetParams {
+    /// Preset ID from wled_presets_list (e.g. 3), or preset name (e.g. "Movie"), matched
+    /// case-insensitively
+    pub preset: PresetParam,
+    /// Device name or IP address (optional - if not specified, the default device is used)
+    pub device: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledNightlightParams {
+    /// How long the nightlight runs for, in whole minutes (1-255). Needed unless cancel is
+    /// true.
+    pub duration_minutes: Option<i64>,
+    /// Brightness to reach when the nightlight ends (0-255, optional - defaults to 0,
+    /// which turns the device off)
+    pub target_brightness: Option<u8>,
+    /// How to get there: "instant" waits then jumps to the target, "fade" fades the
+    /// brightness, "color_fade" fades the brightness and color, and "sunrise" brightens
+    /// with a sunrise effect (optional - defaults to "instant")
+    pub mode: Option<String>,
+    /// Stop a nightlight that's running instead, leaving the brightness where it is
+    /// (optional - defaults to false)
+    pub cancel: Option<bool>,
+    /// Device name or IP address (optional - if not specified, the default device is used)
+    pub device: Option<String>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledSetStateParams {
+    /// Fields of the WLED JSON API state object to change, e.g. {"seg": [{"id": 0, "grp": 2}]}
+    #[schemars(with = "serde_json::Map<String, serde_json::Value>")]
+    pub state: serde_json::Value,
+    /// Device name or IP address (optional - if not specified, the default device is used)
+    pub device: Option<String>,
+}
+
+/// The largest state update `wled_set_state` sends, in bytes of JSON
+const MAX_STATE_BYTES: usize = 16 * 1024;
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledAddDeviceParams {
+    /// Name to save the device under, e.g. "bedroom"
+    pub name: String,
+    /// IP address or hostname of the device, with a port if it isn't on 80 (e.g.
+    /// 192.168.1.40 or 192.168.1.40:8080)
+    pub address: String,
+    /// Replace the address of a device that's already saved with this name (optional -
+    /// defaults to false)
+    pub replace: Option<bool>,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledNameParams {
+    /// Name of a saved device
+    pub name: String,
+}
+
+#[derive(serde::Deserialize, schemars::JsonSchema)]
+pub struct WledStatusParams {
+    /// Device name or IP address to report on in detail, as JSON (optional - if not
+    /// specified, every saved device is listed)
+    pub device: Option<String>,
+}
+
+/// The status of one device, as returned by `wled_status` when it's given a device
+#[derive(Debug, serde::Serialize)]
+pub struct DeviceReport {
+    /// The saved name of the device, or null if it was given by an address that isn't saved
+    pub name: Option<String>,
+    pub ip: String,
+    pub default: bool,
+    pub status: StatusValue,
+    /// The rest are only included when the device is reachable and reports them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<EffectReport>,
+    /// The active preset, left out if none is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<i32>,
+}
+
+/// The effect on a device's main segment
+#[derive(Debug, serde::Serialize)]
+pub struct EffectReport {
+    pub id: u16,
+    /// Left out if the device doesn't list its effects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
 #[derive(Clone)]
 pub struct WledMcpServer {
     tool_router: ToolRouter<WledMcpServer>,
+    /// The least severe log messages the client wants, set with `logging/setLevel`
+    log_level: Arc<Mutex<LoggingLevel>>,
+    device_locks: DeviceLocks,
+}
+
+/// A lock for each device, by address, so calls that change the same device take turns
+/// rather than overwriting each other's changes. Shared by every client of the server.
+#[derive(Clone, Default)]
+struct DeviceLocks(Arc<Mutex<HashMap<String, Arc<tokio::sync::Mutex<()>>>>>);
+
+impl DeviceLocks {
+    fn get(&self, ip: &str) -> Arc<tokio::sync::Mutex<()>> {
+        Arc::clone(self.0.lock().unwrap().entry(ip.to_string()).or_default())
+    }
+}
+
+impl Default for WledMcpServer {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[tool_router]
 impl WledMcpServer {
     pub fn new() -> Self {
+        Self::with_device_locks(DeviceLocks::default())
+    }
+
+    /// A server sharing `device_locks` with others, so that calls from all of their clients
+    /// take turns with each device
+    fn with_device_locks(device_locks: DeviceLocks) -> Self {
         Self {
             tool_router: Self::tool_router(),
+            log_level: Arc::new(Mutex::new(LoggingLevel::Info)),
+            device_locks,
         }
     }
 
-    #[tool(description = "List saved WLED devices from configuration")]
+    #[tool(
+        description = "List saved WLED devices from configuration",
+        annotations(read_only_hint = true)
+    )]
     async fn wled_devices(
         &self,
         Parameters(_params): Parameters<EmptyParams>,
     ) -> Result<CallToolResult, McpError> {
-        match Config::load() {
-            Ok(config) => {
-                if config.devices.is_empty() {
-                    return Ok(CallToolResult::success(vec![Content::text(
-                        "No devices saved",
-                    )]));
-                }
+        text_result(load_config().map(|config| describe_devices(&config)))
+    }
 
-                let mut output = String::from("Saved devices:\n");
-                for (name, ip) in &config.devices {
-                    let default_marker = if config.default_device.as_ref() == Some(name) {
-                        " (default)"
-                    } else {
-                        ""
-                    };
-                    output.push_str(&format!("  {name} - {ip}{default_marker}\n"));
-                }
-                Ok(CallToolResult::success(vec![Content::text(output)]))
-            }
-            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
-                "Failed to load configuration: {e}"
-            ))])),
-        }
+    #[tool(
+        description = "Save a WLED device under a name, so it can be controlled by name. The address is an IP address or hostname, with a port if it isn't on 80 (e.g. 192.168.1.40 or 192.168.1.40:8080). The first device saved becomes the default. If the name is already saved with a different address, this fails unless replace is true. Returns the saved devices.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_add_device(
+        &self,
+        Parameters(params): Parameters<WledAddDeviceParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let WledAddDeviceParams {
+            name,
+            address,
+            replace,
+        } = params;
+        let address = match address::parse_address(&address) {
+            Ok(address) => address,
+            Err(e) => return ToolError::InvalidParams(e).into_result(),
+        };
+
+        text_result(
+            change_config(move |config| {
+                let message = match config.add_device(name.clone(), address.clone()) {
+                    AddOutcome::Replaced { old, .. } if !replace.unwrap_or(false) => {
+                        return Err(ToolError::InvalidParams(format!(
+                            "Device '{name}' is already saved with IP {old}. Set replace to true to change it to {address}"
+                        )));
+                    }
+                    AddOutcome::Replaced { old, .. } => {
+                        format!("Changed the IP of device '{name}' from {old} to {address}")
+                    }
+                    AddOutcome::Added { .. } if config.devices.len() == 1 => format!(
+                        "Added device '{name}' with IP {address}, and set it as the default device"
+                    ),
+                    AddOutcome::Added { .. } => format!("Added device '{name}' with IP {address}"),
+                    AddOutcome::Unchanged => {
+                        format!("Device '{name}' is already saved with IP {address}")
+                    }
+                };
+                Ok(message)
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Delete a saved WLED device by name, removing it from groups and scenes too. Returns the saved devices.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_delete_device(
+        &self,
+        Parameters(params): Parameters<WledNameParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let name = params.name;
+        text_result(
+            change_config(move |config| {
+                config.remove_device(&name)?;
+                Ok(format!("Deleted device '{name}'"))
+            })
+            .await,
+        )
     }
 
     #[tool(
-        description = "Turn WLED device on. By default, the default device is used, but you can optionally specify a device name or IP address."
+        description = "Set which saved WLED device is used when a tool isn't given a device. Returns the saved devices.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_set_default(
+        &self,
+        Parameters(params): Parameters<WledNameParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let name = params.name;
+        text_result(
+            change_config(move |config| {
+                config.set_default(&name)?;
+                Ok(format!("Set '{name}' as the default device"))
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Turn WLED device on. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
     )]
     async fn wled_on(
         &self,
         Parameters(params): Parameters<WledDeviceParams>,
     ) -> Result<CallToolResult, McpError> {
-        let device = params.device.clone();
-        match tokio::task::spawn_blocking(move || {
-            set_device_power(device.as_deref(), true).map_err(|e| e.to_string())
-        })
-        .await
-        {
-            Ok(Ok(())) => Ok(CallToolResult::success(vec![Content::text(
-                "Device turned on successfully",
-            )])),
-            Ok(Err(e)) => Ok(CallToolResult::error(vec![Content::text(e)])),
-            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
-                "Task error: {e}"
-            ))])),
-        }
+        text_result(
+            self.with_device(params.device, move |ip| {
+                set_device_power(Some(&ip), true, None)?;
+                Ok("Device turned on successfully".to_string())
+            })
+            .await,
+        )
     }
 
     #[tool(
-        description = "Turn WLED device off. By default, the default device is used, but you can optionally specify a device name or IP address."
+        description = "Turn WLED device off. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
     )]
     async fn wled_off(
         &self,
         Parameters(params): Parameters<WledDeviceParams>,
     ) -> Result<CallToolResult, McpError> {
-        let device = params.device.clone();
-        match tokio::task::spawn_blocking(move || {
-            set_device_power(device.as_deref(), false).map_err(|e| e.to_string())
-        })
-        .await
-        {
-            Ok(Ok(())) => Ok(CallToolResult::success(vec![Content::text(
-                "Device turned off successfully",
-            )])),
-            Ok(Err(e)) => Ok(CallToolResult::error(vec![Content::text(e)])),
-            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
-                "Task error: {e}"
-            ))])),
-        }
+        text_result(
+            self.with_device(params.device, move |ip| {
+                set_device_power(Some(&ip), false, None)?;
+                Ok("Device turned off successfully".to_string())
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Toggle WLED device power: turn it off if it's on, or on if it's off, in one step. The result says whether the device is now ON or OFF. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = false
+        )
+    )]
+    async fn wled_toggle(
+        &self,
+        Parameters(params): Parameters<WledDeviceParams>,
+    ) -> Result<CallToolResult, McpError> {
+        text_result(
+            self.with_device(params.device, move |ip| {
+                let (ip, power_state) = toggle_device_power(Some(&ip))?;
+                let state = if power_state { "ON" } else { "OFF" };
+                Ok(format!("Device at {ip} is now {state}"))
+            })
+            .await,
+        )
     }
 
     #[tool(
-        description = "Set WLED device brightness (0-255). By default, the default device is used, but you can optionally specify a device name or IP address."
+        description = "Set WLED device brightness, either as a level (0-255) with value or as a percentage (0-100) with percent. Setting a brightness also turns the device on, except for 0, which turns it off. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
     )]
     async fn wled_brightness(
         &self,
         Parameters(params): Parameters<WledBrightnessParams>,
     ) -> Result<CallToolResult, McpError> {
-        let device = params.device.clone();
-        let value = params.value;
-        match tokio::task::spawn_blocking(move || {
-            set_device_brightness(device.as_deref(), value).map_err(|e| e.to_string())
-        })
-        .await
-        {
-            Ok(Ok(())) => Ok(CallToolResult::success(vec![Content::text(format!(
-                "Device brightness set to {value} successfully"
-            ))])),
-            Ok(Err(e)) => Ok(CallToolResult::error(vec![Content::text(e)])),
-            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
-                "Task error: {e}"
-            ))])),
-        }
+        let value = match (params.value, params.percent) {
+            (Some(value), None) => Ok(BrightnessValue::Absolute(value)),
+            (None, Some(percent)) => BrightnessValue::Absolute(percent).percentage_to_scale(),
+            (Some(_), Some(_)) => Err("Give either value or percent, not both".to_string()),
+            (None, None) => Err("Give either value or percent".to_string()),
+        };
+        let value = match value {
+            Ok(value) => value,
+            Err(e) => return ToolError::InvalidParams(e).into_result(),
+        };
+
+        text_result(
+            self.with_device(params.device, move |ip| {
+                let (_, _, brightness) = set_device_brightness(Some(&ip), value, None, false)?;
+                if brightness == 0 {
+                    return Ok(
+                        "Device turned off, since it shows nothing at brightness 0".to_string()
+                    );
+                }
+                Ok(format!(
+                    "Device brightness set to {brightness} successfully"
+                ))
+            })
+            .await,
+        )
     }
 
-    #[tool(description = "Check status of all configured WLED devices")]
-    async fn wled_status(
+    #[tool(
+        description = "Set the color of a WLED device's main segment. The color can be hex (#FF8000 or FF8000), an r,g,b triple with each value 0-255 (255,128,0), or a CSS color name (orange, skyblue, rebeccapurple). By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_color(
         &self,
-        Parameters(_params): Parameters<EmptyParams>,
+        Parameters(params): Parameters<WledColorParams>,
     ) -> Result<CallToolResult, McpError> {
-        match tokio::task::spawn_blocking(|| -> Result<String, String> {
-            let config = Config::load().map_err(|e| e.to_string())?;
+        let color = match color::parse_color(&params.color) {
+            Ok(color) => color,
+            Err(e) => return ToolError::InvalidParams(format!("Invalid color: {e}")).into_result(),
+        };
 
-            if config.devices.is_empty() {
-                return Ok("No devices saved".to_string());
-            }
+        text_result(
+            self.with_device(params.device, move |ip| {
+                set_device_color(Some(&ip), color, None)?;
+                Ok(format!(
+                    "Set color to {} ({color}) for device at {ip}",
+                    color.to_hex()
+                ))
+            })
+            .await,
+        )
+    }
 
-            let mut output = String::from("Checking status of all devices:\n\n");
-            let mut all_reachable = true;
+    #[tool(
+        description = "List the effects available on a WLED device, one per line as `ID: name`. Use an ID with wled_set_effect. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(read_only_hint = true)
+    )]
+    async fn wled_effects_list(
+        &self,
+        Parameters(params): Parameters<WledDeviceParams>,
+    ) -> Result<CallToolResult, McpError> {
+        text_result(
+            blocking(move || {
+                let ip = resolve_device(params.device.as_deref())?;
+                let wled = connect(&ip)?;
+                let names = api::get_effect_names(&wled)?;
 
-            for (name, ip) in &config.devices {
-                let default_marker = if config.default_device.as_ref() == Some(name) {
-                    " (default)"
-                } else {
-                    ""
-                };
+                let mut output = format!("Effects on device at {ip}:\n");
+                for (id, name) in catalog::list(&names, None) {
+                    output.push_str(&format!("{id}: {name}\n"));
+                }
+                Ok(output)
+            })
+            .await,
+        )
+    }
 
-                output.push_str(&format!("  {name} ({ip}){default_marker}: "));
+    #[tool(
+        description = "Set the effect on a WLED device's main segment, by ID (from wled_effects_list) or by name. Names match case-insensitively, and an unambiguous prefix is enough. Optionally set the effect's speed and intensity (0-255). By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_set_effect(
+        &self,
+        Parameters(params): Parameters<WledSetEffectParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let effect = match params.effect {
+            EffectParam::Id(id) => Selector::Id(id),
+            EffectParam::Name(name) => match name.parse() {
+                Ok(effect) => effect,
+                Err(e) => return ToolError::InvalidParams(e).into_result(),
+            },
+        };
 
-                match get_device_status(ip) {
-                    DeviceStatus::On => {
-                        output.push_str("ON\n");
-                    }
-                    DeviceStatus::Off => {
-                        output.push_str("OFF\n");
-                    }
-                    DeviceStatus::Unreachable => {
-                        output.push_str("UNREACHABLE\n");
-                        all_reachable = false;
+        let WledSetEffectParams {
+            speed,
+            intensity,
+            device,
+            ..
+        } = params;
+        text_result(
+            self.with_device(device, move |ip| {
+                let mut wled = connect(&ip)?;
+                let (names, count) = effect_choices(&wled, &effect)?;
+                // An effect the device doesn't have is a mistake in the call, not a failure
+                let (id, name) = catalog::resolve(&effect, &names, count, "effect")
+                    .map_err(|e| ToolError::InvalidParams(e.to_string()))?;
+                set_effect(&mut wled, id, speed, intensity)?;
+                Ok(format!("Set effect {id} ({name}) for device at {ip}"))
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "List the presets saved on a WLED device, one per line as `ID: name`. Presets are saved looks, often named after what they're for, like \"Movie\" or \"Reading\". Apply one with wled_apply_preset. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(read_only_hint = true)
+    )]
+    async fn wled_presets_list(
+        &self,
+        Parameters(params): Parameters<WledDeviceParams>,
+    ) -> Result<CallToolResult, McpError> {
+        text_result(
+            blocking(move || {
+                let ip = resolve_device(params.device.as_deref())?;
+                let presets = get_device_presets(Some(&ip))?;
+
+                if presets.is_empty() {
+                    return Ok(format!("No presets saved on device at {ip}"));
+                }
+                let mut output = format!("Presets on device at {ip}:\n");
+                for (id, preset) in presets {
+                    let name = preset.name.as_deref().unwrap_or("(unnamed)");
+                    let playlist_marker = if preset.playlist.is_some() {
+                        " (playlist)"
+                    } else {
+                        ""
+                    };
+                    output.push_str(&format!("{id}: {name}{playlist_marker}\n"));
+                }
+                Ok(output)
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Apply a preset saved on a WLED device, by ID (from wled_presets_list) or by name, matched case-insensitively. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_apply_preset(
+        &self,
+        Parameters(params): Parameters<WledApplyPresetParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let WledApplyPresetParams { preset, device } = params;
+        text_result(
+            self.with_device(device, move |ip| {
+                let id = match preset {
+                    PresetParam::Id(id) => id,
+                    PresetParam::Name(name) => {
+                        let presets = get_device_presets(Some(&ip))?;
+                        let id =
+                            api::find_preset(&presets, &name).map_err(ToolError::InvalidParams)?;
+                        u8::try_from(id).map_err(|_| {
+                            ToolError::InvalidParams(format!("Preset {id} can't be applied"))
+                        })?
                     }
+                };
+
+                let (ip, active) = apply_device_preset(Some(&ip), id)?;
+                if active.is_some_and(|active| active != i32::from(id)) {
+                    return Err(ToolError::InvalidParams(format!(
+                        "Device at {ip} didn't apply preset {id}, so it may not exist"
+                    )));
                 }
-            }
+                Ok(format!("Applied preset {id} to device at {ip}"))
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Set a timer on a WLED device with its nightlight, like \"turn off in 15 minutes\": over duration_minutes, the device goes to target_brightness (0, off, by default) in the given mode. The device keeps time itself, so the timer runs even if this server stops. Set cancel to true to stop a nightlight that's running. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = false,
+            idempotent_hint = true
+        )
+    )]
+    async fn wled_nightlight(
+        &self,
+        Parameters(params): Parameters<WledNightlightParams>,
+    ) -> Result<CallToolResult, McpError> {
+        if params.cancel.unwrap_or(false) {
+            return text_result(
+                self.with_device(params.device, |ip| {
+                    let ip = cancel_device_nightlight(Some(&ip))?;
+                    Ok(format!("Stopped the nightlight on device at {ip}"))
+                })
+                .await,
+            );
+        }
 
-            if !all_reachable {
-                output.push_str("\nWarning: Some devices are unreachable");
+        let minutes = match params.duration_minutes {
+            Some(minutes) => u8::try_from(minutes)
+                .ok()
+                .filter(|minutes| (1..=nightlight::MAX_MINUTES).contains(minutes))
+                .ok_or_else(|| {
+                    format!(
+                        "duration_minutes is {minutes}, but must be between 1 and {}",
+                        nightlight::MAX_MINUTES
+                    )
+                }),
+            None => Err("Give duration_minutes, or set cancel to true".to_string()),
+        };
+        let mode = params
+            .mode
+            .as_deref()
+            .map_or(Ok(nightlight::Mode::Instant), nightlight::Mode::parse);
+        let (minutes, mode) = match (minutes, mode) {
+            (Ok(minutes), Ok(mode)) => (minutes, mode),
+            (Err(e), _) | (_, Err(e)) => return ToolError::InvalidParams(e).into_result(),
+        };
+        let target = params.target_brightness.unwrap_or(0);
+
+        text_result(
+            self.with_device(params.device, move |ip| {
+                let ip = set_device_nightlight(Some(&ip), minutes, mode, target)?;
+                let when = chrono::Local::now() + chrono::Duration::minutes(i64::from(minutes));
+                let goal = if target == 0 {
+                    "turn off".to_string()
+                } else {
+                    format!("reach brightness {target}")
+                };
+                Ok(format!(
+                    "Device at {ip} will {goal} at {} (in {}), using its nightlight",
+                    when.format("%H:%M"),
+                    duration::format_duration(Duration::from_secs(u64::from(minutes) * 60))
+                ))
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Advanced: send a raw state update to a WLED device, for settings no other tool covers, like segment grouping or effect sliders. The state is an object with fields of the WLED JSON API's /json/state, e.g. {\"seg\": [{\"id\": 0, \"grp\": 2}]}, and only the fields given are changed. The device's response is returned. Prefer the dedicated tools where they fit: this is an escape hatch, it isn't checked beyond being an object of up to 16 KiB, and a wrong field can leave the device in an odd state. By default, the default device is used, but you can optionally specify a device name or IP address.",
+        annotations(
+            read_only_hint = false,
+            destructive_hint = true,
+            idempotent_hint = false
+        )
+    )]
+    async fn wled_set_state(
+        &self,
+        Parameters(params): Parameters<WledSetStateParams>,
+    ) -> Result<CallToolResult, McpError> {
+        let WledSetStateParams { state, device } = params;
+        if !state.is_object() {
+            return ToolError::InvalidParams(
+                "state must be a JSON object, like {\"on\": true}".to_string(),
+            )
+            .into_result();
+        }
+        let size = state.to_string().len();
+        if size > MAX_STATE_BYTES {
+            return ToolError::InvalidParams(format!(
+                "state is {size} bytes, but can be at most {MAX_STATE_BYTES}"
+            ))
+            .into_result();
+        }
+
+        text_result(
+            self.with_device(device, move |ip| {
+                let wled = connect(&ip)?;
+                Ok(api::post_state_once(&wled, &state)?)
+            })
+            .await,
+        )
+    }
+
+    #[tool(
+        description = "Check the status of WLED devices. Without a device, lists whether each saved device is ON, OFF or UNREACHABLE, as text. With a device name or IP address, returns that device's status as a JSON object: {\"name\": saved name or null, \"ip\": string, \"default\": boolean, \"status\": \"on\" | \"off\" | \"unreachable\", \"brightness\": 0-255, \"effect\": {\"id\": number, \"name\": string}, \"preset\": number}. brightness, effect and preset are left out when the device is unreachable, and preset is left out when no preset is active.",
+        annotations(read_only_hint = true)
+    )]
+    async fn wled_status(
+        &self,
+        Parameters(params): Parameters<WledStatusParams>,
+        context: RequestContext<RoleServer>,
+    ) -> Result<CallToolResult, McpError> {
+        if let Some(device) = params.device {
+            let report = blocking(move || {
+                serde_json::to_value(device_report(&device)?)
+                    .map_err(|e| ToolError::Internal(e.to_string()))
+            })
+            .await;
+            return match report {
+                Ok(report) => Ok(CallToolResult::structured(report)),
+                Err(e) => e.into_result(),
+            };
+        }
+
+        let config = match load_config() {
+            Ok(config) => config,
+            Err(e) => return e.into_result(),
+        };
+        if config.devices.is_empty() {
+            return text_result(Ok("No devices saved".to_string()));
+        }
+        let names: Vec<String> = config.devices.keys().cloned().collect();
+
+        // Each device is reported to the client as soon as it's checked, while the rest
+        // carry on being checked
+        let (checked, mut updates) = tokio::sync::mpsc::unbounded_channel();
+        let check = blocking({
+            let config = config.clone();
+            let names = names.clone();
+            move || {
+                Ok(get_device_statuses_with(
+                    &config,
+                    &names,
+                    STATUS_CHECKS,
+                    |index, (status, _)| {
+                        // Only fails if the call has been abandoned
+                        let _ = checked.send((index, StatusValue::from(status)));
+                    },
+                ))
+            }
+        });
+        let report = async {
+            let mut done = 0;
+            while let Some((index, status)) = updates.recv().await {
+                done += 1;
+                let name = &names[index];
+                let (level, state) = match status {
+                    StatusValue::On => (LoggingLevel::Info, "ON"),
+                    StatusValue::Off => (LoggingLevel::Info, "OFF"),
+                    StatusValue::Unreachable => (LoggingLevel::Warning, "UNREACHABLE"),
+                };
+                self.log(
+                    &context,
+                    level,
+                    format!("{name} ({}): {state}", config.devices[name].ip),
+                )
+                .await;
+                report_progress(
+                    &context,
+                    done,
+                    names.len(),
+                    format!("Checked {done} of {} devices", names.len()),
+                )
+                .await;
             }
+        };
+        let (statuses, ()) = tokio::join!(check, report);
+
+        text_result(statuses.map(|statuses| describe_statuses(&config, &names, statuses)))
+    }
+}
 
-            Ok(output)
+impl WledMcpServer {
+    /// Run `work`, which changes the device given by name or address (or the default
+    /// device), with its address, once no other call is changing the same device. Calls to
+    /// different devices still run at the same time.
+    async fn with_device<T, F>(&self, device: Option<String>, work: F) -> Result<T, ToolError>
+    where
+        F: FnOnce(String) -> Result<T, ToolError> + Send + 'static,
+        T: Send + 'static,
+    {
+        let ip = resolve_device(device.as_deref())?;
+        // The lock is held until the work is done, even if the call is abandoned first
+        let guard = self.device_locks.get(&ip).lock_owned().await;
+        blocking(move || {
+            let _guard = guard;
+            work(ip)
         })
         .await
-        {
-            Ok(Ok(output)) => Ok(CallToolResult::success(vec![Content::text(output)])),
-            Ok(Err(e)) => Ok(CallToolResult::error(vec![Content::text(e)])),
-            Err(e) => Ok(CallToolResult::error(vec![Content::text(format!(
-                "Task error: {e}"
-            ))])),
+    }
+
+    /// Send the client a log message, unless it's asked for only more severe ones
+    async fn log(
+        &self,
+        context: &RequestContext<RoleServer>,
+        level: LoggingLevel,
+        message: String,
+    ) {
+        let wanted = *self.log_level.lock().unwrap();
+        if severity(level) < severity(wanted) {
+            return;
+        }
+        // Log messages are best effort, so a client that's gone doesn't fail the call
+        let _ = context
+            .peer
+            .notify_logging_message(LoggingMessageNotificationParam {
+                level,
+                logger: Some("wld".to_string()),
+                data: serde_json::Value::String(message),
+            })
+            .await;
+    }
+}
+
+/// How severe a log level is, to compare it with the level the client asked for
+fn severity(level: LoggingLevel) -> u8 {
+    match level {
+        LoggingLevel::Debug => 0,
+        LoggingLevel::Info => 1,
+        LoggingLevel::Notice => 2,
+        LoggingLevel::Warning => 3,
+        LoggingLevel::Error => 4,
+        LoggingLevel::Critical => 5,
+        LoggingLevel::Alert => 6,
+        LoggingLevel::Emergency => 7,
+    }
+}
+
+/// Tell the client how far through `total` steps a call is, if it asked to be told by
+/// sending a progress token
+async fn report_progress(
+    context: &RequestContext<RoleServer>,
+    done: usize,
+    total: usize,
+    message: String,
+) {
+    let Some(progress_token) = context.meta.get_progress_token() else {
+        return;
+    };
+    let _ = context
+        .peer
+        .notify_progress(ProgressNotificationParam {
+            progress_token,
+            progress: done as f64,
+            total: Some(total as f64),
+            message: Some(message),
+        })
+        .await;
+}
+
+/// Whether each of the devices in `names` is on, off or unreachable
+fn describe_statuses(
+    config: &Config,
+    names: &[String],
+    statuses: Vec<(DeviceStatus, Option<u8>)>,
+) -> String {
+    let mut output = String::from("Checking status of all devices:\n\n");
+    let mut all_reachable = true;
+
+    for (name, (status, _)) in names.iter().zip(statuses) {
+        let ip = &config.devices[name].ip;
+        let default_marker = if config.default_device.as_ref() == Some(name) {
+            " (default)"
+        } else {
+            ""
+        };
+
+        output.push_str(&format!("  {name} ({ip}){default_marker}: "));
+
+        match status {
+            DeviceStatus::On => {
+                output.push_str("ON\n");
+            }
+            DeviceStatus::Off => {
+                output.push_str("OFF\n");
+            }
+            DeviceStatus::Unreachable => {
+                output.push_str("UNREACHABLE\n");
+                all_reachable = false;
+            }
         }
     }
+
+    if !all_reachable {
+        output.push_str("\nWarning: Some devices are unreachable");
+    }
+
+    output
+}
+
+/// Why a tool call failed, which decides how the client hears about it. A call that
+/// doesn't make sense is answered with a JSON-RPC error, so clients can tell it apart from
+/// one that failed while being carried out. Those are tool results with `isError` set,
+/// and `structuredContent` of `{"category", "message"}`.
+#[derive(Debug)]
+enum ToolError {
+    /// A parameter isn't valid, like a malformed color, or names a device, preset or effect
+    /// that doesn't exist. Sent as an "invalid params" JSON-RPC error.
+    InvalidParams(String),
+    /// The config couldn't be loaded or saved. Category `config`.
+    Config(String),
+    /// The device couldn't be reached, or didn't make the change. Category `device`.
+    Device(String),
+    /// The server itself went wrong. Sent as an "internal error" JSON-RPC error.
+    Internal(String),
+}
+
+impl From<WldError> for ToolError {
+    fn from(e: WldError) -> Self {
+        match e {
+            WldError::DeviceNotFound { .. }
+            | WldError::NoDefaultDevice
+            | WldError::InvalidInput(_) => ToolError::InvalidParams(e.to_string()),
+            WldError::ConfigIo { .. } | WldError::ConfigParse { .. } => {
+                ToolError::Config(e.to_string())
+            }
+            WldError::Http { .. } | WldError::DeviceRejected { .. } | WldError::Io { .. } => {
+                ToolError::Device(e.to_string())
+            }
+            WldError::Server(_) => ToolError::Internal(e.to_string()),
+        }
+    }
+}
+
+impl ToolError {
+    /// Report the failure to the client
+    fn into_result(self) -> Result<CallToolResult, McpError> {
+        let (category, message) = match self {
+            ToolError::InvalidParams(message) => {
+                return Err(McpError::invalid_params(message, None))
+            }
+            ToolError::Internal(message) => return Err(McpError::internal_error(message, None)),
+            ToolError::Config(message) => ("config", message),
+            ToolError::Device(message) => ("device", message),
+        };
+
+        let mut result = CallToolResult::error(vec![Content::text(message.clone())]);
+        result.structured_content =
+            Some(serde_json::json!({ "category": category, "message": message }));
+        Ok(result)
+    }
+}
+
+/// The result of a tool that replies with text
+fn text_result(result: Result<String, ToolError>) -> Result<CallToolResult, McpError> {
+    match result {
+        Ok(text) => Ok(CallToolResult::success(vec![Content::text(text)])),
+        Err(e) => e.into_result(),
+    }
+}
+
+/// Run `work`, like talking to a device, off the async runtime
+async fn blocking<T, F>(work: F) -> Result<T, ToolError>
+where
+    F: FnOnce() -> Result<T, ToolError> + Send + 'static,
+    T: Send + 'static,
+{
+    tokio::task::spawn_blocking(|| {
+        let result = work();
+        // The server runs for a long time, so devices are noted as seen after each call
+        track::save();
+        result
+    })
+    .await
+    .map_err(|e| ToolError::Internal(format!("Task error: {e}")))?
+}
+
+fn load_config() -> Result<Config, ToolError> {
+    Config::load().map_err(|e| ToolError::Config(format!("Failed to load configuration: {e}")))
+}
+
+/// The address of a device given by name or address, or of the default device. A device
+/// that can't be found is a mistake in the call, not a failure to reach it.
+fn resolve_device(device: Option<&str>) -> Result<String, ToolError> {
+    Ok(load_config()?.get_device_ip(device)?)
+}
+
+/// The saved devices, marking the default
+fn describe_devices(config: &Config) -> String {
+    if config.devices.is_empty() {
+        return "No devices saved".to_string();
+    }
+
+    let mut output = String::from("Saved devices:\n");
+    for (name, device) in &config.devices {
+        let ip = &device.ip;
+        let default_marker = if config.default_device.as_ref() == Some(name) {
+            " (default)"
+        } else {
+            ""
+        };
+        output.push_str(&format!("  {name} - {ip}{default_marker}\n"));
+    }
+    output
+}
+
+/// Find out how a device is, by name or address
+fn device_report(device: &str) -> Result<DeviceReport, ToolError> {
+    let config = load_config()?;
+    let ip = config.get_device_ip(Some(device))?;
+    let name = config
+        .devices
+        .iter()
+        .find(|(_, saved)| saved.ip == ip)
+        .map(|(name, _)| name.clone());
+
+    let (status, details) = get_device_details(&ip);
+    Ok(DeviceReport {
+        default: name.is_some() && config.default_device == name,
+        name,
+        ip,
+        status: (&status).into(),
+        brightness: details.brightness,
+        effect: details.effect.map(|(id, name)| EffectReport { id, name }),
+        preset: details.preset,
+    })
+}
+
+/// The fields of `json/info` included when a device is read as a resource, alongside
+/// its state
+const RESOURCE_INFO_FIELDS: &[&str] = &["name", "ver", "arch", "leds", "wifi", "uptime", "mac"];
+
+/// The URI a saved device is listed under as a resource, like `wled://bedroom`. Anything
+/// in the name that can't appear in a URI is percent-encoded.
+fn resource_uri(name: &str) -> String {
+    let mut uri = String::from("wled://");
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || b"-._~".contains(&byte) {
+            uri.push(char::from(byte));
+        } else {
+            uri.push_str(&format!("%{byte:02X}"));
+        }
+    }
+    uri
+}
+
+/// Fetch a device's state, with some of its info, as the JSON contents of its resource
+fn read_device(ip: &str) -> Result<String, String> {
+    let unreachable =
+        |e: WldError| format!("Device unreachable: couldn't read the state of {ip}: {e}");
+    let wled = connect(ip).map_err(unreachable)?;
+    let state: serde_json::Value = api::get_json(&wled, "json/state").map_err(unreachable)?;
+    let info: serde_json::Map<String, serde_json::Value> =
+        api::get_json(&wled, "json/info").map_err(unreachable)?;
+    let info: serde_json::Map<String, serde_json::Value> = info
+        .into_iter()
+        .filter(|(field, _)| RESOURCE_INFO_FIELDS.contains(&field.as_str()))
+        .collect();
+
+    serde_json::to_string_pretty(&serde_json::json!({ "state": state, "info": info }))
+        .map_err(|e| e.to_string())
+}
+
+/// Guidance for the model using the server: what devices there are, how to refer to
+/// them, and an example call for each tool
+fn instructions(config: Option<&Config>) -> String {
+    let mut output = String::from(
+        "Controls WLED lights. Tools with a `device` parameter take the name of a saved device or an IP address or hostname (with :port if it isn't 80). Leave `device` out to use the default device.\n",
+    );
+
+    let devices: Vec<String> = config
+        .map(|config| {
+            config
+                .devices
+                .keys()
+                .map(|name| {
+                    if config.default_device.as_ref() == Some(name) {
+                        format!("{name} (default)")
+                    } else {
+                        name.clone()
+                    }
+                })
+                .collect()
+        })
+        .unwrap_or_default();
+    if devices.is_empty() {
+        output.push_str("No devices are saved yet. Save one with wled_add_device.\n");
+    } else {
+        output.push_str(&format!("Saved devices: {}.\n", devices.join(", ")));
+    }
+    output.push_str(
+        "Brightness is 0-255 with `value`, or 0-100 with `percent`. Colors are hex, r,g,b or CSS names.\n",
+    );
+
+    // Examples use a real device where there is one
+    let device = config
+        .and_then(|config| {
+            config
+                .default_device
+                .clone()
+                .or_else(|| config.devices.keys().next().cloned())
+        })
+        .unwrap_or_else(|| "bedroom".to_string());
+    output.push_str(&format!(
+        "\nExamples:
+- wled_devices {{}}
+- wled_status {{}}, or {{\"device\": \"{device}\"}} for one device as JSON
+- wled_on {{\"device\": \"{device}\"}}
+- wled_off {{}}
+- wled_toggle {{\"device\": \"192.168.1.40\"}}
+- wled_brightness {{\"percent\": 50, \"device\": \"{device}\"}}
+- wled_color {{\"color\": \"orange\"}}
+- wled_effects_list {{}}
+- wled_set_effect {{\"effect\": \"Rainbow\", \"speed\": 128}}
+- wled_presets_list {{}}
+- wled_apply_preset {{\"preset\": \"Movie\"}}
+- wled_nightlight {{\"duration_minutes\": 15}}
+- wled_set_state {{\"state\": {{\"seg\": [{{\"id\": 0, \"grp\": 2}}]}}}} (advanced)
+- wled_add_device {{\"name\": \"kitchen\", \"address\": \"192.168.1.40\"}}
+- wled_delete_device {{\"name\": \"kitchen\"}}
+- wled_set_default {{\"name\": \"{device}\"}}
+"
+    ));
+    output
+}
+
+/// Change the config with `change`, which returns what it did. The config is loaded
+/// fresh and locked for the change, so changes made with the CLI while the server is
+/// running aren't lost. The result lists the saved devices afterwards.
+async fn change_config<F>(change: F) -> Result<String, ToolError>
+where
+    F: FnOnce(&mut Config) -> Result<String, ToolError> + Send + 'static,
+{
+    blocking(move || {
+        let (mut config, _lock) = Config::load_locked()
+            .map_err(|e| ToolError::Config(format!("Failed to load configuration: {e}")))?;
+        let message = change(&mut config)?;
+        config
+            .save()
+            .map_err(|e| ToolError::Config(format!("Failed to save configuration: {e}")))?;
+        Ok(format!("{message}\n\n{}", describe_devices(&config)))
+    })
+    .await
 }
 
 #[tool_handler]
@@ -199,31 +1144,194 @@ impl ServerHandler for WledMcpServer {
     fn get_info(&self) -> ServerInfo {
         ServerInfo {
             protocol_version: ProtocolVersion::V_2025_03_26,
-            capabilities: ServerCapabilities::builder().enable_tools().build(),
+            capabilities: ServerCapabilities::builder()
+                .enable_logging()
+                .enable_resources()
+                .enable_tools()
+                .build(),
             server_info: Implementation::from_build_env(),
-            instructions: None,
+            // Built for each client as it connects, so it lists the devices saved then
+            instructions: Some(instructions(Config::load().ok().as_ref())),
         }
     }
+
+    async fn set_level(
+        &self,
+        request: SetLevelRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<(), McpError> {
+        *self.log_level.lock().unwrap() = request.level;
+        Ok(())
+    }
+
+    async fn list_resources(
+        &self,
+        _request: Option<PaginatedRequestParam>,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ListResourcesResult, McpError> {
+        let config = Config::load().map_err(|e| {
+            McpError::internal_error(format!("Failed to load configuration: {e}"), None)
+        })?;
+
+        let resources = config
+            .devices
+            .iter()
+            .map(|(name, device)| {
+                let default_marker = if config.default_device.as_ref() == Some(name) {
+                    " (default)"
+                } else {
+                    ""
+                };
+                let mut resource = RawResource::new(resource_uri(name), name.clone());
+                resource.description = Some(format!(
+                    "State of the WLED device at {}{default_marker}",
+                    device.ip
+                ));
+                resource.mime_type = Some("application/json".to_string());
+                resource.no_annotation()
+            })
+            .collect();
+        Ok(ListResourcesResult::with_all_items(resources))
+    }
+
+    async fn read_resource(
+        &self,
+        request: ReadResourceRequestParam,
+        _context: RequestContext<RoleServer>,
+    ) -> Result<ReadResourceResult, McpError> {
+        let uri = request.uri;
+        let config = Config::load().map_err(|e| {
+            McpError::internal_error(format!("Failed to load configuration: {e}"), None)
+        })?;
+        let Some(ip) = config
+            .devices
+            .iter()
+            .find(|(name, _)| resource_uri(name) == uri)
+            .map(|(_, device)| device.ip.clone())
+        else {
+            return Err(McpError::resource_not_found(
+                format!("No saved device has the URI {uri}"),
+                None,
+            ));
+        };
+
+        let contents = tokio::task::spawn_blocking(move || read_device(&ip))
+            .await
+            .map_err(|e| McpError::internal_error(format!("Task error: {e}"), None))?
+            .map_err(|e| McpError::internal_error(e, None))?;
+        Ok(ReadResourceResult {
+            contents: vec![ResourceContents::TextResourceContents {
+                uri,
+                mime_type: Some("application/json".to_string()),
+                text: contents,
+                meta: None,
+            }],
+        })
+    }
 }
 
-pub fn handle_mcp_command() -> Result<(), Box<dyn std::error::Error>> {
-    // Set up tracing for the MCP server
-    tracing_subscriber::fmt()
+/// The environment variable holding the bearer token HTTP clients must send, when one
+/// isn't given with `--token`
+pub const TOKEN_VARIABLE: &str = "WLD_MCP_TOKEN";
+
+/// The path the MCP server is served at over HTTP
+const HTTP_PATH: &str = "/mcp";
+
+/// Start the MCP server, over stdio, or over streamable HTTP at `http` if given. Over HTTP,
+/// clients must send `token` (or the one in `$WLD_MCP_TOKEN`) as a bearer token if there is one.
+pub fn handle_mcp_command(http: Option<SocketAddr>, token: Option<String>) -> Result<(), WldError> {
+    // Set up tracing for the MCP server, unless `-v` already has
+    let _ = tracing_subscriber::fmt()
         .with_env_filter(
             tracing_subscriber::EnvFilter::from_default_env()
                 .add_directive(tracing::Level::DEBUG.into()),
         )
         .with_writer(std::io::stderr)
         .with_ansi(false)
-        .init();
+        .try_init();
 
     // Create the MCP server
-    let rt = tokio::runtime::Runtime::new()?;
+    let rt = tokio::runtime::Runtime::new().map_err(|e| WldError::Server(e.to_string()))?;
     rt.block_on(async {
         tracing::info!("Starting WLED MCP server");
 
-        let service = WledMcpServer::new().serve(stdio()).await?;
-        service.waiting().await?;
+        if let Some(address) = http {
+            let token = token
+                .or_else(|| std::env::var(TOKEN_VARIABLE).ok())
+                .filter(|token| !token.is_empty());
+            return serve_http(address, token).await;
+        }
+
+        let service = WledMcpServer::new()
+            .serve(stdio())
+            .await
+            .map_err(|e| WldError::Server(e.to_string()))?;
+        service
+            .waiting()
+            .await
+            .map_err(|e| WldError::Server(e.to_string()))?;
         Ok(())
     })
 }
+
+/// Serve the MCP server over streamable HTTP until Ctrl-C is pressed. Each client gets its
+/// own session.
+async fn serve_http(address: SocketAddr, token: Option<String>) -> Result<(), WldError> {
+    let device_locks = DeviceLocks::default();
+    let service = StreamableHttpService::new(
+        move || Ok(WledMcpServer::with_device_locks(device_locks.clone())),
+        LocalSessionManager::default().into(),
+        Default::default(),
+    );
+    let mut router = axum::Router::new().nest_service(HTTP_PATH, service);
+    match token {
+        Some(token) => {
+            router = router.layer(middleware::from_fn_with_state(
+                Arc::new(format!("Bearer {token}")),
+                require_token,
+            ));
+        }
+        None if !address.ip().is_loopback() => tracing::warn!(
+            "No token is set, so anyone who can reach {address} can control your devices. Set one with --token or ${TOKEN_VARIABLE}"
+        ),
+        None => {}
+    }
+
+    // The address is given on the command line, so not being able to use it is a mistake
+    // in the command
+    let listener = tokio::net::TcpListener::bind(address)
+        .await
+        .map_err(|e| WldError::InvalidInput(format!("Couldn't listen on {address}: {e}")))?;
+    let local_address = listener
+        .local_addr()
+        .map_err(|e| WldError::Server(e.to_string()))?;
+    tracing::info!("Serving MCP over HTTP at http://{local_address}{HTTP_PATH}");
+    axum::serve(listener, router)
+        .with_graceful_shutdown(async {
+            // If Ctrl-C can't be caught, the server runs until it's killed
+            if tokio::signal::ctrl_c().await.is_err() {
+                std::future::pending::<()>().await;
+            }
+            tracing::info!("Shutting down");
+        })
+        .await
+        .map_err(|e| WldError::Server(e.to_string()))?;
+    Ok(())
+}
+
+/// Turn away HTTP requests without the bearer token in their `Authorization` header
+async fn require_token(
+    State(expected): State<Arc<String>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let authorized = request
+        .headers()
+        .get(header::AUTHORIZATION)
+        .is_some_and(|value| value.as_bytes() == expected.as_bytes());
+    if authorized {
+        next.run(request).await
+    } else {
+        StatusCode::UNAUTHORIZED.into_response()
+    }
+}
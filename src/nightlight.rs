@@ -0,0 +1,105 @@
+use std::time::Duration;
+
+use crate::duration;
+
+/// The longest nightlight WLED supports, since it stores the duration as a byte of minutes
+pub const MAX_MINUTES: u8 = 255;
+
+/// What the nightlight does over its duration, as WLED's `nl.mode` numbers them
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+    /// Wait, then jump to the target brightness
+    Instant = 0,
+    /// Fade the brightness to the target
+    Fade = 1,
+    /// Fade the brightness and the color to the target
+    ColorFade = 2,
+    /// Brighten with WLED's sunrise effect
+    Sunrise = 3,
+}
+
+impl Mode {
+    /// The names modes are given by, in order
+    pub const NAMES: [&'static str; 4] = ["instant", "fade", "color_fade", "sunrise"];
+
+    /// Parse a mode by name, like `fade`, ignoring case and allowing `color-fade`
+    pub fn parse(value: &str) -> Result<Mode, String> {
+        match value.to_lowercase().replace('-', "_").as_str() {
+            "instant" => Ok(Mode::Instant),
+            "fade" => Ok(Mode::Fade),
+            "color_fade" => Ok(Mode::ColorFade),
+            "sunrise" => Ok(Mode::Sunrise),
+            _ => Err(format!(
+                "Invalid nightlight mode '{value}': use one of {}",
+                Mode::NAMES.join(", ")
+            )),
+        }
+    }
+}
+
+/// A duration as a number of minutes for the nightlight, if it's a whole number of minutes
+/// that WLED can count down, since the nightlight can't count seconds
+pub fn whole_minutes(duration: Duration) -> Option<u8> {
+    let seconds = duration.as_secs();
+    if !seconds.is_multiple_of(60) || duration.subsec_nanos() != 0 {
+        return None;
+    }
+
+    u8::try_from(seconds / 60)
+        .ok()
+        .filter(|minutes| (1..=MAX_MINUTES).contains(minutes))
+}
+
+/// Parse a duration like `30m` for the nightlight, rounding to the nearest minute since it
+/// can't count seconds
+pub fn parse_minutes(value: &str) -> Result<u8, String> {
+    let duration = duration::parse_duration(value)?;
+    let minutes = (duration.as_secs() + 30) / 60;
+
+    u8::try_from(minutes)
+        .ok()
+        .filter(|minutes| (1..=MAX_MINUTES).contains(minutes))
+        .ok_or_else(|| {
+            format!(
+                "Invalid duration '{value}': the nightlight runs for between 1m and {}",
+                duration::format_duration(Duration::from_secs(u64::from(MAX_MINUTES) * 60))
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_whole_minutes() {
+        assert_eq!(whole_minutes(Duration::from_secs(20 * 60)), Some(20));
+        assert_eq!(whole_minutes(Duration::from_secs(255 * 60)), Some(255));
+        assert_eq!(whole_minutes(Duration::from_secs(90)), None);
+        assert_eq!(whole_minutes(Duration::from_secs(256 * 60)), None);
+        assert_eq!(whole_minutes(Duration::ZERO), None);
+    }
+
+    #[test]
+    fn test_parse_mode() {
+        assert_eq!(Mode::parse("fade"), Ok(Mode::Fade));
+        assert_eq!(Mode::parse("Color-Fade"), Ok(Mode::ColorFade));
+        assert_eq!(Mode::parse("SUNRISE"), Ok(Mode::Sunrise));
+        assert!(Mode::parse("slow")
+            .unwrap_err()
+            .contains("instant, fade, color_fade, sunrise"));
+    }
+
+    #[test]
+    fn test_parse_minutes() {
+        assert_eq!(parse_minutes("30m"), Ok(30));
+        assert_eq!(parse_minutes("4h15m"), Ok(255));
+        assert_eq!(parse_minutes("90s"), Ok(2));
+        assert_eq!(parse_minutes("30s"), Ok(1));
+        assert!(parse_minutes("29s").is_err());
+        assert!(parse_minutes("4h16m")
+            .unwrap_err()
+            .contains("between 1m and 4h15m"));
+        assert!(parse_minutes("soon").is_err());
+    }
+}
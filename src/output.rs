@@ -0,0 +1,225 @@
+// Types for `--json` output. Their shape is a contract with scripts that parse it, so
+// fields must not be renamed or removed.
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+
+use crate::device::DeviceStatus;
+
+/// A saved device, as printed by `wld ls --json`
+#[derive(Debug, Serialize)]
+pub struct DeviceEntry {
+    pub name: String,
+    pub ip: String,
+    pub default: bool,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_brightness: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_preset: Option<u8>,
+    /// When the device last answered a command, if it's been tracked
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_seen: Option<DateTime<Utc>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_version: Option<String>,
+    /// Only included with `--check`
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<StatusValue>,
+}
+
+/// The status of a device, as printed by `wld status --json`
+#[derive(Debug, Serialize)]
+pub struct StatusEntry {
+    pub name: String,
+    pub ip: String,
+    pub default: bool,
+    pub status: StatusValue,
+    /// The rest are only included when the device is reachable and reports them
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub effect: Option<EffectEntry>,
+    /// The active preset, left out if none is
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preset: Option<i32>,
+}
+
+/// The effect on a device's main segment, as printed by `wld status --json`
+#[derive(Debug, Serialize)]
+pub struct EffectEntry {
+    pub id: u16,
+    /// Left out if the device doesn't list its effects
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+}
+
+/// The result of a command that changes a device, as printed with `--json`. On failure,
+/// only `success` and `error` are included, plus the device if it's known.
+#[derive(Debug, Default, Serialize)]
+pub struct ActionResult {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ip: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub action: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub value: Option<serde_json::Value>,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StatusValue {
+    On,
+    Off,
+    Unreachable,
+}
+
+impl From<&DeviceStatus> for StatusValue {
+    fn from(status: &DeviceStatus) -> Self {
+        match status {
+            DeviceStatus::On => StatusValue::On,
+            DeviceStatus::Off => StatusValue::Off,
+            DeviceStatus::Unreachable => StatusValue::Unreachable,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_device_entry_shape() {
+        let mut entry = DeviceEntry {
+            name: "bedroom".to_string(),
+            ip: "192.168.1.101".to_string(),
+            default: true,
+            tags: Vec::new(),
+            default_brightness: None,
+            default_preset: None,
+            last_seen: None,
+            last_version: None,
+            status: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            json!({"name": "bedroom", "ip": "192.168.1.101", "default": true})
+        );
+
+        entry.last_seen = Some("2026-10-15T08:00:00Z".parse().unwrap());
+        entry.last_version = Some("0.15.0".to_string());
+        let value = serde_json::to_value(&entry).unwrap();
+        assert_eq!(value["last_seen"], "2026-10-15T08:00:00Z");
+        assert_eq!(value["last_version"], "0.15.0");
+
+        entry.status = Some(StatusValue::Unreachable);
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap()["status"],
+            json!("unreachable")
+        );
+    }
+
+    #[test]
+    fn test_status_entry_shape() {
+        let entry = StatusEntry {
+            name: "bedroom".to_string(),
+            ip: "192.168.1.101".to_string(),
+            default: false,
+            status: StatusValue::On,
+            brightness: Some(128),
+            effect: None,
+            preset: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            json!({
+                "name": "bedroom",
+                "ip": "192.168.1.101",
+                "default": false,
+                "status": "on",
+                "brightness": 128
+            })
+        );
+
+        let entry = StatusEntry {
+            effect: Some(EffectEntry {
+                id: 9,
+                name: Some("Rainbow".to_string()),
+            }),
+            preset: Some(3),
+            ..entry
+        };
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap()["effect"],
+            json!({"id": 9, "name": "Rainbow"})
+        );
+        assert_eq!(serde_json::to_value(&entry).unwrap()["preset"], 3);
+    }
+
+    #[test]
+    fn test_action_result_shape() {
+        let result = ActionResult {
+            device: Some("bedroom".to_string()),
+            ip: Some("192.168.1.101".to_string()),
+            action: Some("brightness".to_string()),
+            value: Some(json!(128)),
+            success: true,
+            error: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({
+                "device": "bedroom",
+                "ip": "192.168.1.101",
+                "action": "brightness",
+                "value": 128,
+                "success": true
+            })
+        );
+    }
+
+    #[test]
+    fn test_action_result_failure_shape() {
+        let result = ActionResult {
+            error: Some("Connection refused".to_string()),
+            ..Default::default()
+        };
+
+        assert_eq!(
+            serde_json::to_value(&result).unwrap(),
+            json!({"success": false, "error": "Connection refused"})
+        );
+    }
+
+    #[test]
+    fn test_status_entry_omits_unknown_brightness() {
+        let entry = StatusEntry {
+            name: "porch".to_string(),
+            ip: "192.168.1.102".to_string(),
+            default: false,
+            status: StatusValue::Unreachable,
+            brightness: None,
+            effect: None,
+            preset: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(&entry).unwrap(),
+            json!({
+                "name": "porch",
+                "ip": "192.168.1.102",
+                "default": false,
+                "status": "unreachable"
+            })
+        );
+    }
+}
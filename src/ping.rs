@@ -0,0 +1,90 @@
+use std::time::Duration;
+
+/// Pause between probes, as ICMP ping does
+pub const INTERVAL: Duration = Duration::from_secs(1);
+
+/// How long to wait for each probe before counting it as lost
+pub const TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Summary of a run of probes, where `None` is a lost probe
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub sent: usize,
+    pub received: usize,
+    /// Round trip times as (min, avg, max), if any probe got a reply
+    pub round_trip: Option<(Duration, Duration, Duration)>,
+}
+
+impl Stats {
+    pub fn new(results: &[Option<Duration>]) -> Self {
+        let replies: Vec<Duration> = results.iter().flatten().copied().collect();
+
+        let round_trip = match (replies.iter().min(), replies.iter().max()) {
+            (Some(&min), Some(&max)) => {
+                let total: Duration = replies.iter().sum();
+                Some((min, total / replies.len() as u32, max))
+            }
+            _ => None,
+        };
+
+        Stats {
+            sent: results.len(),
+            received: replies.len(),
+            round_trip,
+        }
+    }
+
+    /// Percentage of probes that got no reply, rounded down
+    pub fn loss_percent(&self) -> usize {
+        if self.sent == 0 {
+            return 0;
+        }
+        (self.sent - self.received) * 100 / self.sent
+    }
+}
+
+/// Format a round trip time as a number of milliseconds, e.g. `12.3`
+pub fn format_ms(duration: Duration) -> String {
+    format!("{:.1}", duration.as_secs_f64() * 1000.0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ms(millis: u64) -> Option<Duration> {
+        Some(Duration::from_millis(millis))
+    }
+
+    #[test]
+    fn test_stats() {
+        let stats = Stats::new(&[ms(10), None, ms(30), ms(20)]);
+
+        assert_eq!(stats.sent, 4);
+        assert_eq!(stats.received, 3);
+        assert_eq!(stats.loss_percent(), 25);
+        assert_eq!(
+            stats.round_trip,
+            Some((
+                Duration::from_millis(10),
+                Duration::from_millis(20),
+                Duration::from_millis(30)
+            ))
+        );
+    }
+
+    #[test]
+    fn test_stats_all_lost() {
+        let stats = Stats::new(&[None, None]);
+
+        assert_eq!(stats.received, 0);
+        assert_eq!(stats.loss_percent(), 100);
+        assert_eq!(stats.round_trip, None);
+    }
+
+    #[test]
+    fn test_format_ms() {
+        assert_eq!(format_ms(Duration::from_micros(12_345)), "12.3");
+        assert_eq!(format_ms(Duration::from_millis(250)), "250.0");
+    }
+}
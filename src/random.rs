@@ -0,0 +1,82 @@
+use rand::Rng;
+
+use crate::catalog;
+use crate::color::Rgb;
+
+/// Pick a random ID below `count`, the number of entries the device reports, skipping
+/// placeholders for removed entries. `names` is indexed by ID, and IDs beyond it are
+/// assumed to be usable.
+pub fn pick_id(rng: &mut impl Rng, names: &[String], count: usize) -> Option<u16> {
+    let candidates: Vec<u16> = (0..count.min(usize::from(u16::MAX)))
+        .filter(|&id| names.get(id).is_none_or(|name| !catalog::is_reserved(name)))
+        .map(|id| id as u16)
+        .collect();
+
+    if candidates.is_empty() {
+        return None;
+    }
+    Some(candidates[rng.gen_range(0..candidates.len())])
+}
+
+/// Pick a random fully saturated color, since random RGB values are mostly muddy or dim
+pub fn pick_color(rng: &mut impl Rng) -> Rgb {
+    let hue: u16 = rng.gen_range(0..360);
+
+    // Convert the hue to RGB at full saturation and brightness
+    let rising = ((hue % 60) * 255 / 60) as u8;
+    let falling = 255 - rising;
+    match hue / 60 {
+        0 => Rgb::new(255, rising, 0),
+        1 => Rgb::new(falling, 255, 0),
+        2 => Rgb::new(0, 255, rising),
+        3 => Rgb::new(0, falling, 255),
+        4 => Rgb::new(rising, 0, 255),
+        _ => Rgb::new(255, 0, falling),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::rngs::StdRng;
+    use rand::SeedableRng;
+
+    fn to_names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_pick_id_skips_reserved() {
+        let mut rng = StdRng::seed_from_u64(1);
+        let names = to_names(&["Solid", "RSVD", "-", "Rainbow"]);
+
+        for _ in 0..100 {
+            let id = pick_id(&mut rng, &names, names.len()).unwrap();
+            assert!(id == 0 || id == 3, "picked {id}");
+        }
+    }
+
+    #[test]
+    fn test_pick_id_respects_count() {
+        let mut rng = StdRng::seed_from_u64(2);
+        let names = to_names(&["Solid", "Blink", "Breathe", "Wipe"]);
+
+        for _ in 0..100 {
+            assert!(pick_id(&mut rng, &names, 2).unwrap() < 2);
+        }
+        assert_eq!(pick_id(&mut rng, &names, 0), None);
+        assert_eq!(pick_id(&mut rng, &to_names(&["RSVD"]), 1), None);
+    }
+
+    #[test]
+    fn test_pick_color_is_saturated() {
+        let mut rng = StdRng::seed_from_u64(3);
+
+        for _ in 0..100 {
+            let color = pick_color(&mut rng);
+            let channels = [color.r, color.g, color.b];
+            assert_eq!(channels.iter().max(), Some(&255), "{color}");
+            assert_eq!(channels.iter().min(), Some(&0), "{color}");
+        }
+    }
+}
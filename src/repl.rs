@@ -0,0 +1,93 @@
+use std::path::PathBuf;
+
+use directories::BaseDirs;
+
+/// Where `wld repl` keeps the commands entered, so history carries over between sessions
+pub fn history_path() -> Result<PathBuf, Box<dyn std::error::Error>> {
+    let base_dirs = BaseDirs::new().ok_or("Could not find home directory")?;
+    Ok(base_dirs.home_dir().join(".wld_history"))
+}
+
+/// Split a line typed into the REPL into arguments the way a shell would, so values with
+/// spaces can be quoted, e.g. `state set '{"on": true}'`
+pub fn split_args(line: &str) -> Result<Vec<String>, String> {
+    let mut args = Vec::new();
+    let mut current = String::new();
+    // Whether there's an argument in progress, which may be empty, e.g. `""`
+    let mut in_arg = false;
+    let mut quote: Option<char> = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (quote, c) {
+            (Some(q), c) if c == q => quote = None,
+            // Backslashes escape the next character, except inside single quotes
+            (Some('"') | None, '\\') => {
+                current.push(
+                    chars
+                        .next()
+                        .ok_or("Unfinished escape at the end of the line")?,
+                );
+                in_arg = true;
+            }
+            (Some(_), c) => current.push(c),
+            (None, '\'' | '"') => {
+                quote = Some(c);
+                in_arg = true;
+            }
+            (None, c) if c.is_whitespace() => {
+                if in_arg {
+                    args.push(std::mem::take(&mut current));
+                    in_arg = false;
+                }
+            }
+            (None, c) => {
+                current.push(c);
+                in_arg = true;
+            }
+        }
+    }
+
+    if let Some(q) = quote {
+        return Err(format!("Missing closing {q}"));
+    }
+    if in_arg {
+        args.push(current);
+    }
+    Ok(args)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_args() {
+        assert_eq!(
+            split_args("  on -d porch  ").unwrap(),
+            vec!["on", "-d", "porch"]
+        );
+        assert_eq!(
+            split_args(r#"state set '{"on": true}'"#).unwrap(),
+            vec!["state", "set", r#"{"on": true}"#]
+        );
+        assert_eq!(
+            split_args(r#"add "living room" 192.168.1.50"#).unwrap(),
+            vec!["add", "living room", "192.168.1.50"]
+        );
+        assert_eq!(
+            split_args(r#"color dark\ red "say \"hi\"" ''"#).unwrap(),
+            vec!["color", "dark red", r#"say "hi""#, ""]
+        );
+        assert!(split_args("").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_split_args_rejects_unfinished_input() {
+        assert_eq!(
+            split_args("add 'desk 192.168.1.50").unwrap_err(),
+            "Missing closing '"
+        );
+        assert!(split_args(r"on \").is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::time::Duration;
+
+use rand::Rng;
+
+use crate::config::Config;
+use crate::error::WldError;
+use crate::interrupt;
+
+/// How many times to ask a device again when a request doesn't reach it, unless the
+/// config or `--retries` says otherwise. ESP8266 devices often drop the first connection
+/// after waking from modem sleep, and answer the next one.
+pub const RETRIES: u32 = 1;
+
+/// How long to wait before the first retry. Each retry after it waits twice as long.
+const BACKOFF: Duration = Duration::from_millis(200);
+
+/// The longest to wait between retries, however many there have been
+const MAX_BACKOFF: Duration = Duration::from_secs(2);
+
+/// Up to how much longer than the backoff to wait, so devices asked at the same time
+/// aren't asked again at the same time too
+const JITTER: Duration = Duration::from_millis(100);
+
+/// Make a request to the device at `ip`, making it again after a short wait if it doesn't
+/// reach the device, up to as many times as the config or `--retries` allows
+///
+/// Only failures to get through are retried. A device that answered, even with an error,
+/// isn't asked again, nor is one that didn't answer in time, so an unplugged device is
+/// still given up on after one timeout. Only use this for requests that do the same thing
+/// however many times they're made.
+pub fn retrying<T>(
+    ip: &str,
+    mut request: impl FnMut() -> Result<T, WldError>,
+) -> Result<T, WldError> {
+    let mut retries = None;
+    let mut attempts = 1;
+
+    loop {
+        match request() {
+            Err(WldError::Http {
+                ip: failed, source, ..
+            }) if !source.is_timeout() => {
+                // The config is only needed once something has gone wrong
+                let retries = *retries.get_or_insert_with(|| {
+                    Config::load().map_or(RETRIES, |config| config.retries_for(ip))
+                });
+                if attempts > retries || !interrupt::sleep(backoff(attempts, jitter())) {
+                    return Err(WldError::Http {
+                        ip: failed,
+                        source,
+                        attempts,
+                    });
+                }
+                attempts += 1;
+                tracing::debug!(
+                    "Trying request to {ip} again ({attempts} of {})",
+                    retries + 1
+                );
+            }
+            result => return result,
+        }
+    }
+}
+
+/// How long to wait after the given attempt before the next one, plus `jitter`
+fn backoff(attempt: u32, jitter: Duration) -> Duration {
+    let doubled = BACKOFF.saturating_mul(2u32.saturating_pow(attempt.saturating_sub(1)));
+    doubled.min(MAX_BACKOFF) + jitter
+}
+
+fn jitter() -> Duration {
+    rand::thread_rng().gen_range(Duration::ZERO..=JITTER)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1, Duration::ZERO), Duration::from_millis(200));
+        assert_eq!(backoff(2, Duration::ZERO), Duration::from_millis(400));
+        assert_eq!(
+            backoff(3, Duration::from_millis(50)),
+            Duration::from_millis(850)
+        );
+        assert_eq!(backoff(5, Duration::ZERO), MAX_BACKOFF);
+        assert_eq!(backoff(40, Duration::ZERO), MAX_BACKOFF);
+    }
+
+    #[test]
+    fn test_jitter() {
+        for _ in 0..100 {
+            assert!(jitter() <= JITTER);
+        }
+    }
+}
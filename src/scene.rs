@@ -0,0 +1,151 @@
+use serde::{Deserialize, Serialize};
+use wled_json_api_library::structures::state::{Seg, State};
+
+use crate::color::{self, Rgb};
+
+/// How one device in a scene should look. Fields the device didn't report when the scene
+/// was saved are left alone when it's applied.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SceneDevice {
+    /// Saved device name
+    pub device: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub on: Option<bool>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brightness: Option<u8>,
+    /// Primary color of the main segment, as `#RRGGBB`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Effect ID of the main segment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub effect: Option<u16>,
+    /// Palette ID of the main segment
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub palette: Option<u16>,
+}
+
+impl SceneDevice {
+    /// Capture how a device looks from the state it reports
+    pub fn capture(device: &str, state: &State) -> Self {
+        let main_segment = main_segment(state);
+
+        SceneDevice {
+            device: device.to_string(),
+            on: state.on,
+            brightness: state.bri,
+            color: main_segment
+                .and_then(|s| s.col.as_ref())
+                .and_then(|colors| colors.first())
+                .filter(|c| c.len() >= 3)
+                .map(|c| Rgb::new(c[0], c[1], c[2]).to_hex()),
+            effect: main_segment.and_then(|s| s.fx),
+            palette: main_segment.and_then(|s| s.pal),
+        }
+    }
+
+    /// The state update that makes a device look like this, changing its main segment
+    /// `main_segment`
+    pub fn to_state(&self, main_segment: u8) -> Result<State, String> {
+        let color = self
+            .color
+            .as_deref()
+            .map(color::parse_color)
+            .transpose()
+            .map_err(|e| format!("Invalid color in scene for '{}': {e}", self.device))?;
+
+        Ok(State {
+            on: self.on,
+            bri: self.brightness,
+            seg: Some(vec![Seg {
+                id: Some(i16::from(main_segment)),
+                col: color.map(|c| vec![c.to_wled()]),
+                fx: self.effect,
+                pal: self.palette,
+                ..Default::default()
+            }]),
+            ..Default::default()
+        })
+    }
+}
+
+/// The main segment in a device's state
+fn main_segment(state: &State) -> Option<&Seg> {
+    let id = i16::from(state.mainseg.unwrap_or(0));
+    state
+        .seg
+        .as_ref()?
+        .iter()
+        .find(|segment| segment.id.unwrap_or(0) == id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn state() -> State {
+        serde_json::from_value(json!({
+            "on": true, "bri": 51, "mainseg": 1,
+            "seg": [
+                {"id": 0, "col": [[255, 0, 0]], "fx": 9, "pal": 2},
+                {"id": 1, "col": [[255, 180, 100], [0, 0, 0]], "fx": 0, "pal": 0}
+            ]
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_capture_uses_main_segment() {
+        assert_eq!(
+            SceneDevice::capture("tv", &state()),
+            SceneDevice {
+                device: "tv".to_string(),
+                on: Some(true),
+                brightness: Some(51),
+                color: Some("#FFB464".to_string()),
+                effect: Some(0),
+                palette: Some(0),
+            }
+        );
+    }
+
+    #[test]
+    fn test_to_state() {
+        let scene = SceneDevice::capture("tv", &state());
+
+        assert_eq!(
+            serde_json::to_value(scene.to_state(2).unwrap()).unwrap(),
+            json!({
+                "on": true, "bri": 51,
+                "seg": [{"id": 2, "col": [[255, 180, 100]], "fx": 0, "pal": 0}]
+            })
+        );
+    }
+
+    #[test]
+    fn test_to_state_leaves_missing_fields_alone() {
+        let scene = SceneDevice {
+            device: "ceiling".to_string(),
+            on: Some(false),
+            brightness: None,
+            color: None,
+            effect: None,
+            palette: None,
+        };
+
+        assert_eq!(
+            serde_json::to_value(scene.to_state(0).unwrap()).unwrap(),
+            json!({"on": false, "seg": [{"id": 0}]})
+        );
+    }
+
+    #[test]
+    fn test_to_state_rejects_bad_color() {
+        let scene = SceneDevice {
+            color: Some("#GG0000".to_string()),
+            ..SceneDevice::capture("tv", &state())
+        };
+
+        assert!(scene.to_state(0).unwrap_err().contains("scene for 'tv'"));
+    }
+}
@@ -0,0 +1,185 @@
+use chrono::{DateTime, LocalResult, NaiveDateTime, NaiveTime, TimeZone};
+
+/// Parse a wall-clock time like `22:30`, `7:05` or `06:00:30`
+pub fn parse_time(value: &str) -> Result<NaiveTime, String> {
+    let value = value.trim();
+
+    ["%H:%M", "%H:%M:%S"]
+        .iter()
+        .find_map(|format| NaiveTime::parse_from_str(value, format).ok())
+        .ok_or_else(|| format!("Invalid time '{value}': expected e.g. 22:30 or 7:05"))
+}
+
+/// The next time after `now` that the clock in `now`'s time zone reads `time`: later today
+/// if it hasn't passed yet, otherwise tomorrow.
+///
+/// On days the clocks change, a time that's skipped becomes the moment the clocks jump
+/// forward, and a time that happens twice means the first.
+pub fn next_occurrence<Tz: TimeZone>(now: &DateTime<Tz>, time: NaiveTime) -> DateTime<Tz> {
+    let timezone = now.timezone();
+    let mut date = now.date_naive();
+
+    loop {
+        let candidate = resolve(&timezone, date.and_time(time));
+        if candidate > *now {
+            return candidate;
+        }
+        date = date.succ_opt().expect("Dates this far ahead are in range");
+    }
+}
+
+/// Turn a local date and time into an exact moment, handling clock changes
+fn resolve<Tz: TimeZone>(timezone: &Tz, local: NaiveDateTime) -> DateTime<Tz> {
+    let mut local = local;
+
+    // Times skipped when the clocks go forward don't exist, so move on a minute at a time
+    // until the clocks have changed. Time zones never skip more than a day.
+    loop {
+        match timezone.from_local_datetime(&local) {
+            LocalResult::Single(moment) => return moment,
+            LocalResult::Ambiguous(earliest, _) => return earliest,
+            LocalResult::None => local += chrono::Duration::minutes(1),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{FixedOffset, NaiveDate, Offset, Timelike, Utc};
+
+    /// A time zone like Europe/London in 2026: UTC+1 from 1am UTC on 29 March until 1am UTC
+    /// on 25 October, UTC otherwise
+    #[derive(Debug, Clone, Copy)]
+    struct Summer;
+
+    impl Summer {
+        fn offset_at(utc: &NaiveDateTime) -> FixedOffset {
+            let start = NaiveDate::from_ymd_opt(2026, 3, 29)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap();
+            let end = NaiveDate::from_ymd_opt(2026, 10, 25)
+                .unwrap()
+                .and_hms_opt(1, 0, 0)
+                .unwrap();
+            let hours = if (start..end).contains(utc) { 1 } else { 0 };
+            FixedOffset::east_opt(hours * 3600).unwrap()
+        }
+    }
+
+    impl TimeZone for Summer {
+        type Offset = FixedOffset;
+
+        fn from_offset(_: &FixedOffset) -> Self {
+            Summer
+        }
+
+        fn offset_from_local_date(&self, local: &NaiveDate) -> LocalResult<FixedOffset> {
+            self.offset_from_local_datetime(&local.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_local_datetime(&self, local: &NaiveDateTime) -> LocalResult<FixedOffset> {
+            // Try both offsets, keeping the ones that are in effect at the moment they give
+            let mut offsets = [1, 0].into_iter().filter_map(|hours| {
+                let offset = FixedOffset::east_opt(hours * 3600).unwrap();
+                let utc = *local - chrono::Duration::hours(i64::from(hours));
+                (Self::offset_at(&utc) == offset).then_some(offset)
+            });
+            match (offsets.next(), offsets.next()) {
+                (Some(earliest), Some(latest)) => LocalResult::Ambiguous(earliest, latest),
+                (Some(offset), None) => LocalResult::Single(offset),
+                _ => LocalResult::None,
+            }
+        }
+
+        fn offset_from_utc_date(&self, utc: &NaiveDate) -> FixedOffset {
+            Self::offset_at(&utc.and_hms_opt(0, 0, 0).unwrap())
+        }
+
+        fn offset_from_utc_datetime(&self, utc: &NaiveDateTime) -> FixedOffset {
+            Self::offset_at(utc)
+        }
+    }
+
+    fn local(date: (i32, u32, u32), time: (u32, u32)) -> DateTime<Summer> {
+        let naive = NaiveDate::from_ymd_opt(date.0, date.1, date.2)
+            .unwrap()
+            .and_hms_opt(time.0, time.1, 0)
+            .unwrap();
+        Summer.from_local_datetime(&naive).earliest().unwrap()
+    }
+
+    fn time(value: &str) -> NaiveTime {
+        parse_time(value).unwrap()
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(time("22:30"), NaiveTime::from_hms_opt(22, 30, 0).unwrap());
+        assert_eq!(time("7:05"), NaiveTime::from_hms_opt(7, 5, 0).unwrap());
+        assert_eq!(time("06:00:30"), NaiveTime::from_hms_opt(6, 0, 30).unwrap());
+        assert!(parse_time("24:00").is_err());
+        assert!(parse_time("10pm").is_err());
+        assert!(parse_time("").is_err());
+    }
+
+    #[test]
+    fn test_next_occurrence_later_today() {
+        let now = local((2026, 6, 1), (21, 0));
+        assert_eq!(
+            next_occurrence(&now, time("22:30")),
+            local((2026, 6, 1), (22, 30))
+        );
+    }
+
+    #[test]
+    fn test_next_occurrence_rolls_over_midnight() {
+        let now = local((2026, 6, 1), (23, 50));
+        assert_eq!(
+            next_occurrence(&now, time("00:10")),
+            local((2026, 6, 2), (0, 10))
+        );
+        // A time that has just passed, or is exactly now, means tomorrow
+        assert_eq!(
+            next_occurrence(&now, time("23:50")),
+            local((2026, 6, 2), (23, 50))
+        );
+        // ...including across the end of a month or year
+        let now = local((2026, 12, 31), (23, 0));
+        assert_eq!(
+            next_occurrence(&now, time("22:00")),
+            local((2027, 1, 1), (22, 0))
+        );
+    }
+
+    #[test]
+    fn test_next_occurrence_when_clocks_go_forward() {
+        // 1:30am doesn't exist on 29 March, so it's 2am, when the clocks have jumped
+        let now = local((2026, 3, 28), (23, 0));
+        let next = next_occurrence(&now, time("1:30"));
+        assert_eq!(next, local((2026, 3, 29), (2, 0)));
+        assert_eq!(
+            next.with_timezone(&Utc).naive_utc().time(),
+            NaiveTime::from_hms_opt(1, 0, 0).unwrap()
+        );
+
+        // The day is an hour shorter, which exact moments account for
+        let next = next_occurrence(&now, time("22:00"));
+        assert_eq!(next, local((2026, 3, 29), (22, 0)));
+        assert_eq!((next - now).num_hours(), 22);
+    }
+
+    #[test]
+    fn test_next_occurrence_when_clocks_go_back() {
+        // 1:30am happens twice on 25 October, and the first is used
+        let now = local((2026, 10, 25), (0, 45));
+        let next = next_occurrence(&now, time("1:30"));
+        assert_eq!(next.offset().fix(), FixedOffset::east_opt(3600).unwrap());
+        assert_eq!((next - now).num_minutes(), 45);
+
+        let next = next_occurrence(&now, time("23:00"));
+        assert_eq!(next.hour(), 23);
+        assert_eq!((next - now).num_minutes(), 23 * 60 + 15);
+    }
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use crate::repl;
+use wld::duration;
+
+/// A line of a script for `wld run`
+#[derive(Debug, PartialEq)]
+pub enum Step {
+    /// The `sleep <duration>` built-in
+    Sleep(Duration),
+    /// A wld command, split into arguments without the leading `wld`
+    Command(Vec<String>),
+}
+
+/// Parse a script into steps, each with its line number counting from 1. Blank lines and
+/// lines starting with `#` are skipped. Lines that can't be parsed are returned as errors
+/// rather than failing the whole script, so the caller can choose to carry on.
+pub fn parse(script: &str) -> Vec<(usize, Result<Step, String>)> {
+    script
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| {
+            let line = line.trim();
+            !line.is_empty() && !line.starts_with('#')
+        })
+        .map(|(index, line)| (index + 1, parse_line(line)))
+        .collect()
+}
+
+fn parse_line(line: &str) -> Result<Step, String> {
+    let args = repl::split_args(line)?;
+
+    match args.first().map(String::as_str) {
+        Some("sleep") => match args.as_slice() {
+            [_, value] => Ok(Step::Sleep(duration::parse_duration(value)?)),
+            _ => Err("Usage: sleep <duration>, e.g. sleep 2s".to_string()),
+        },
+        _ => Ok(Step::Command(args)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let script = "# Porch flash\n\non -d porch\n  sleep 2s\ncolor -d porch '#ff0000'\n";
+
+        assert_eq!(
+            parse(script),
+            vec![
+                (
+                    3,
+                    Ok(Step::Command(vec![
+                        "on".into(),
+                        "-d".into(),
+                        "porch".into()
+                    ]))
+                ),
+                (4, Ok(Step::Sleep(Duration::from_secs(2)))),
+                (
+                    5,
+                    Ok(Step::Command(vec![
+                        "color".into(),
+                        "-d".into(),
+                        "porch".into(),
+                        "#ff0000".into()
+                    ]))
+                ),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_reports_bad_lines() {
+        let steps = parse("sleep\nsleep soon\nadd 'desk\noff");
+
+        assert_eq!(steps.len(), 4);
+        assert_eq!(
+            steps[0].1,
+            Err("Usage: sleep <duration>, e.g. sleep 2s".to_string())
+        );
+        assert!(steps[1]
+            .1
+            .as_ref()
+            .unwrap_err()
+            .contains("Invalid duration"));
+        assert_eq!(steps[2].1, Err("Missing closing '".to_string()));
+        assert_eq!(steps[3], (4, Ok(Step::Command(vec!["off".into()]))));
+    }
+}
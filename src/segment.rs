@@ -0,0 +1,240 @@
+use wled_json_api_library::structures::state::{Seg, State};
+
+use crate::color::Rgb;
+
+/// A change to a single segment
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SegmentChange {
+    Power(bool),
+    Brightness(u8),
+    Color(Rgb),
+    /// Set the LEDs the segment covers, creating the segment if it doesn't exist
+    Bounds {
+        start: u16,
+        stop: u16,
+        grouping: Option<u8>,
+        spacing: Option<u8>,
+    },
+    /// Remove the segment, which WLED does when its stop is set to 0
+    Delete,
+    /// Run effects from the other end of the segment
+    Reverse(bool),
+    /// Mirror effects around the middle of the segment
+    Mirror(bool),
+}
+
+/// A direction setting of a segment, which can be set or toggled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Orientation {
+    Reverse,
+    Mirror,
+}
+
+impl Orientation {
+    /// The segment's current setting, if the device reports it
+    pub fn get(self, segment: &Seg) -> Option<bool> {
+        match self {
+            Orientation::Reverse => segment.rev,
+            Orientation::Mirror => segment.mi,
+        }
+    }
+
+    /// The change that sets this to `value`
+    pub fn change(self, value: bool) -> SegmentChange {
+        match self {
+            Orientation::Reverse => SegmentChange::Reverse(value),
+            Orientation::Mirror => SegmentChange::Mirror(value),
+        }
+    }
+
+    /// How the setting reads when it's on or off, e.g. "reversed" or "not reversed"
+    pub fn describe(self, value: bool) -> &'static str {
+        match (self, value) {
+            (Orientation::Reverse, true) => "reversed",
+            (Orientation::Reverse, false) => "not reversed",
+            (Orientation::Mirror, true) => "mirrored",
+            (Orientation::Mirror, false) => "not mirrored",
+        }
+    }
+}
+
+impl SegmentChange {
+    /// The segment fields this change sets, without a segment ID
+    fn to_seg(self) -> Seg {
+        match self {
+            SegmentChange::Power(on) => Seg {
+                on: Some(on),
+                ..Default::default()
+            },
+            SegmentChange::Brightness(bri) => Seg {
+                bri: Some(bri),
+                ..Default::default()
+            },
+            SegmentChange::Color(color) => Seg {
+                col: Some(vec![color.to_wled()]),
+                ..Default::default()
+            },
+            SegmentChange::Bounds {
+                start,
+                stop,
+                grouping,
+                spacing,
+            } => Seg {
+                start: Some(start),
+                stop: Some(stop),
+                grp: grouping,
+                spc: spacing,
+                ..Default::default()
+            },
+            SegmentChange::Delete => Seg {
+                stop: Some(0),
+                ..Default::default()
+            },
+            SegmentChange::Reverse(rev) => Seg {
+                rev: Some(rev),
+                ..Default::default()
+            },
+            SegmentChange::Mirror(mi) => Seg {
+                mi: Some(mi),
+                ..Default::default()
+            },
+        }
+    }
+}
+
+/// Build a state update that only carries the segment with the given ID, so WLED leaves
+/// every other segment as it is
+pub fn update(id: i16, segment: Seg) -> State {
+    State {
+        seg: Some(vec![Seg {
+            id: Some(id),
+            ..segment
+        }]),
+        ..Default::default()
+    }
+}
+
+/// Build a state update applying `change` to the segment with the given ID
+pub fn change(id: u8, change: SegmentChange) -> State {
+    update(i16::from(id), change.to_seg())
+}
+
+/// Check that `start..stop` is a non-empty range of LEDs that fits on the device.
+/// `led_count` is the number of LEDs the device reports, if it reports one.
+pub fn validate_bounds(start: u16, stop: u16, led_count: Option<u16>) -> Result<(), String> {
+    if start >= stop {
+        return Err(format!(
+            "Segment start ({start}) must be less than its stop ({stop})"
+        ));
+    }
+
+    if let Some(count) = led_count {
+        if stop > count {
+            return Err(format!(
+                "Segment stop ({stop}) exceeds the device's LED count ({count})"
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn payload(state: &State) -> serde_json::Value {
+        serde_json::to_value(state).unwrap()
+    }
+
+    #[test]
+    fn test_power_only_carries_target_segment() {
+        let state = change(2, SegmentChange::Power(false));
+        assert_eq!(payload(&state), json!({"seg": [{"id": 2, "on": false}]}));
+    }
+
+    #[test]
+    fn test_brightness_only_carries_target_segment() {
+        let state = change(1, SegmentChange::Brightness(64));
+        assert_eq!(payload(&state), json!({"seg": [{"id": 1, "bri": 64}]}));
+    }
+
+    #[test]
+    fn test_color_only_carries_target_segment() {
+        let state = change(0, SegmentChange::Color(Rgb::new(255, 0, 128)));
+        assert_eq!(
+            payload(&state),
+            json!({"seg": [{"id": 0, "col": [[255, 0, 128]]}]})
+        );
+    }
+
+    #[test]
+    fn test_update_overrides_segment_id() {
+        let segment = Seg {
+            id: Some(7),
+            fx: Some(9),
+            ..Default::default()
+        };
+        assert_eq!(
+            payload(&update(3, segment)),
+            json!({"seg": [{"id": 3, "fx": 9}]})
+        );
+    }
+
+    #[test]
+    fn test_bounds_payload() {
+        let state = change(
+            3,
+            SegmentChange::Bounds {
+                start: 10,
+                stop: 20,
+                grouping: Some(2),
+                spacing: None,
+            },
+        );
+        assert_eq!(
+            payload(&state),
+            json!({"seg": [{"id": 3, "start": 10, "stop": 20, "grp": 2}]})
+        );
+    }
+
+    #[test]
+    fn test_delete_payload() {
+        let state = change(1, SegmentChange::Delete);
+        assert_eq!(payload(&state), json!({"seg": [{"id": 1, "stop": 0}]}));
+    }
+
+    #[test]
+    fn test_orientation_payloads() {
+        let state = change(2, Orientation::Reverse.change(true));
+        assert_eq!(payload(&state), json!({"seg": [{"id": 2, "rev": true}]}));
+
+        let state = change(0, Orientation::Mirror.change(false));
+        assert_eq!(payload(&state), json!({"seg": [{"id": 0, "mi": false}]}));
+    }
+
+    #[test]
+    fn test_orientation_get() {
+        let segment = Seg {
+            rev: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(Orientation::Reverse.get(&segment), Some(true));
+        assert_eq!(Orientation::Mirror.get(&segment), None);
+    }
+
+    #[test]
+    fn test_validate_bounds() {
+        assert!(validate_bounds(0, 30, Some(30)).is_ok());
+        assert!(validate_bounds(10, 20, None).is_ok());
+        assert_eq!(
+            validate_bounds(20, 20, Some(60)),
+            Err("Segment start (20) must be less than its stop (20)".to_string())
+        );
+        assert_eq!(
+            validate_bounds(0, 61, Some(60)),
+            Err("Segment stop (61) exceeds the device's LED count (60)".to_string())
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use std::time::{Duration, Instant};
+
+use crate::address;
+use crate::api;
+use crate::config::Config;
+use crate::device;
+use crate::discover;
+use crate::interrupt;
+
+/// Where a device in setup mode can be reached, by anything connected to its Wi-Fi network
+pub const AP_ADDRESS: &str = "4.3.2.1";
+
+/// The Wi-Fi network a device in setup mode opens, unless it's been renamed
+pub const AP_SSID: &str = "WLED-AP";
+
+/// The password for the Wi-Fi network a device in setup mode opens, unless it's been
+/// changed
+pub const AP_PASSWORD: &str = "wled1234";
+
+/// How long to wait for a device in setup mode to answer. It's on the same Wi-Fi network
+/// as the computer, if it's there at all.
+pub const AP_TIMEOUT: Duration = Duration::from_secs(3);
+
+/// How long each search for a device with mDNS lasts, between checks of the saved
+/// devices' lists of other devices
+const BROWSE_TIME: Duration = Duration::from_secs(3);
+
+/// Whether an address is the one devices in setup mode are at
+pub fn is_ap_address(ip: &str) -> bool {
+    address::same_address(ip, AP_ADDRESS)
+}
+
+/// A MAC address without separators, in lowercase, as `json/info` reports it
+fn normalize_mac(mac: &str) -> String {
+    mac.chars()
+        .filter(char::is_ascii_hexdigit)
+        .collect::<String>()
+        .to_lowercase()
+}
+
+/// Look for the device with the MAC address `mac` on the network, for up to `timeout`,
+/// returning its address. Devices that other saved devices have found are checked, as
+/// are devices found with mDNS.
+///
+/// Used to find a device once it has left setup mode and joined the network, where it's
+/// been given an address nobody knows yet.
+pub fn locate(config: &Config, mac: &str, timeout: Duration) -> Option<String> {
+    let mac = normalize_mac(mac);
+    let deadline = Instant::now() + timeout;
+    let is_device = |address: &String| {
+        !is_ap_address(address)
+            && api::probe_info(address, api::TIMEOUT, &api::ClientOptions::default())
+                .is_ok_and(|info| info.mac.as_deref().map(normalize_mac) == Some(mac.clone()))
+    };
+
+    while !interrupt::interrupted() {
+        // Saved devices may already know about it, which is quicker to ask than mDNS
+        let nodes = config
+            .devices
+            .values()
+            .filter(|saved| !is_ap_address(&saved.ip))
+            .filter_map(|saved| device::get_device_nodes(Some(&saved.ip)).ok())
+            .flat_map(|(_, nodes)| nodes.into_iter().filter_map(|node| node.ip));
+        if let Some(address) = nodes.into_iter().find(is_device) {
+            return Some(address);
+        }
+
+        let browse_time = BROWSE_TIME.min(deadline.saturating_duration_since(Instant::now()));
+        let found = match discover::browse(browse_time) {
+            Ok(found) => found,
+            // Without mDNS, wait as long as a search would have taken before trying again
+            Err(_) => {
+                interrupt::sleep(browse_time);
+                Vec::new()
+            }
+        };
+        if let Some(address) = found.into_iter().find(is_device) {
+            return Some(address);
+        }
+
+        if Instant::now() >= deadline {
+            break;
+        }
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_ap_address() {
+        assert!(is_ap_address("4.3.2.1"));
+        assert!(is_ap_address("http://4.3.2.1"));
+        assert!(!is_ap_address("192.168.1.40"));
+    }
+
+    #[test]
+    fn test_normalize_mac() {
+        assert_eq!(normalize_mac("a842e38d9828"), "a842e38d9828");
+        assert_eq!(normalize_mac("A8:42:E3:8D:98:28"), "a842e38d9828");
+    }
+}
@@ -0,0 +1,58 @@
+use std::time::Duration;
+
+use crate::color::Rgb;
+
+/// How long `wld sunrise --preview` takes
+pub const PREVIEW_LENGTH: Duration = Duration::from_secs(30);
+
+/// How often the preview sends an update, with a transition covering the gap
+pub const PREVIEW_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Colors a sunrise passes through, from first light to daylight
+const STOPS: [(u8, u8, u8); 4] = [(255, 20, 0), (255, 90, 0), (255, 160, 60), (255, 220, 170)];
+
+/// The brightness and color `progress` of the way through a sunrise (from 0.0 to 1.0)
+/// that ends at `target` brightness
+pub fn step(progress: f64, target: u8) -> (u8, Rgb) {
+    let progress = progress.clamp(0.0, 1.0);
+
+    // Never fully off, since a brightness of 0 turns the device off
+    let brightness = (f64::from(target) * progress).round().max(1.0) as u8;
+
+    let position = progress * (STOPS.len() - 1) as f64;
+    let index = (position.floor() as usize).min(STOPS.len() - 2);
+    let fraction = position - index as f64;
+    let (from, to) = (STOPS[index], STOPS[index + 1]);
+    let mix =
+        |a: u8, b: u8| (f64::from(a) + (f64::from(b) - f64::from(a)) * fraction).round() as u8;
+
+    (
+        brightness,
+        Rgb::new(mix(from.0, to.0), mix(from.1, to.1), mix(from.2, to.2)),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_ends() {
+        assert_eq!(step(0.0, 200), (1, Rgb::new(255, 20, 0)));
+        assert_eq!(step(1.0, 200), (200, Rgb::new(255, 220, 170)));
+        // Out of range progress is clamped
+        assert_eq!(step(1.5, 200), step(1.0, 200));
+    }
+
+    #[test]
+    fn test_step_brightens_and_warms() {
+        let steps: Vec<(u8, Rgb)> = (0..=10).map(|i| step(f64::from(i) / 10.0, 255)).collect();
+
+        for pair in steps.windows(2) {
+            let ((bri_a, a), (bri_b, b)) = (pair[0], pair[1]);
+            assert!(bri_b > bri_a);
+            assert!(b.g >= a.g && b.b >= a.b);
+        }
+        assert_eq!(steps[5].0, 128);
+    }
+}
@@ -0,0 +1,155 @@
+use std::io::IsTerminal;
+
+use clap::ValueEnum;
+
+/// Whether to color output, as given with `--color`
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum ColorChoice {
+    Always,
+    /// Only when printing to a terminal, and `$NO_COLOR` isn't set
+    #[default]
+    Auto,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether output to stdout should be colored
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            ColorChoice::Auto => {
+                std::io::stdout().is_terminal()
+                    && std::env::var_os("NO_COLOR").is_none_or(|value| value.is_empty())
+            }
+        }
+    }
+}
+
+/// How the text in a cell is colored, when color is on
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Style {
+    Plain,
+    Bold,
+    Green,
+    Grey,
+    Red,
+}
+
+impl Style {
+    /// The ANSI escape code that starts text in this style
+    fn code(self) -> Option<&'static str> {
+        match self {
+            Style::Plain => None,
+            Style::Bold => Some("\x1b[1m"),
+            Style::Green => Some("\x1b[32m"),
+            Style::Grey => Some("\x1b[90m"),
+            Style::Red => Some("\x1b[31m"),
+        }
+    }
+}
+
+/// Rows of text printed in columns under a header, each as wide as its longest cell, so
+/// they line up however long the text in them is
+pub struct Table {
+    header: Vec<&'static str>,
+    rows: Vec<Vec<(String, Style)>>,
+}
+
+impl Table {
+    pub fn new(header: &[&'static str]) -> Self {
+        Table {
+            header: header.to_vec(),
+            rows: Vec::new(),
+        }
+    }
+
+    /// Add a row, with a cell for each column in the header
+    pub fn row(&mut self, cells: Vec<(String, Style)>) {
+        debug_assert_eq!(cells.len(), self.header.len());
+        self.rows.push(cells);
+    }
+
+    /// The table as lines of text, indented like other lists. Columns with nothing in
+    /// them, like tags when no device has any, are left out.
+    pub fn render(&self, color: bool) -> String {
+        let columns: Vec<usize> = (0..self.header.len())
+            .filter(|&column| self.rows.iter().any(|row| !row[column].0.is_empty()))
+            .collect();
+        let widths: Vec<usize> = columns
+            .iter()
+            .map(|&column| {
+                self.rows
+                    .iter()
+                    .map(|row| row[column].0.chars().count())
+                    .chain([self.header[column].len()])
+                    .max()
+                    .unwrap_or_default()
+            })
+            .collect();
+
+        let header = self
+            .header
+            .iter()
+            .map(|heading| (heading.to_string(), Style::Bold));
+        let mut output = String::new();
+        for row in std::iter::once(header.collect()).chain(self.rows.iter().cloned()) {
+            let mut line = String::from(" ");
+            for (&column, width) in columns.iter().zip(&widths) {
+                let (text, style) = &row[column];
+                let padding = " ".repeat(width - text.chars().count());
+                match style.code().filter(|_| color) {
+                    Some(code) => line.push_str(&format!(" {code}{text}\x1b[0m{padding} ")),
+                    None => line.push_str(&format!(" {text}{padding} ")),
+                }
+            }
+            output.push_str(line.trim_end());
+            output.push('\n');
+        }
+        output
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table() -> Table {
+        let mut table = Table::new(&["NAME", "ADDRESS", "TAGS", "STATE"]);
+        table.row(vec![
+            ("porch".to_string(), Style::Plain),
+            ("192.168.1.40".to_string(), Style::Plain),
+            (String::new(), Style::Plain),
+            ("ON".to_string(), Style::Green),
+        ]);
+        table.row(vec![
+            ("a-very-long-device-name".to_string(), Style::Plain),
+            ("10.0.0.2".to_string(), Style::Plain),
+            (String::new(), Style::Plain),
+            ("UNREACHABLE".to_string(), Style::Red),
+        ]);
+        table
+    }
+
+    #[test]
+    fn test_render() {
+        assert_eq!(
+            table().render(false),
+            "  NAME                     ADDRESS       STATE\n  porch                    192.168.1.40  ON\n  a-very-long-device-name  10.0.0.2      UNREACHABLE\n"
+        );
+    }
+
+    #[test]
+    fn test_render_colored() {
+        let rendered = table().render(true);
+        let lines: Vec<&str> = rendered.lines().collect();
+        assert_eq!(
+            lines[0],
+            "  \x1b[1mNAME\x1b[0m                     \x1b[1mADDRESS\x1b[0m       \x1b[1mSTATE\x1b[0m"
+        );
+        assert_eq!(
+            lines[1],
+            "  porch                    192.168.1.40  \x1b[32mON\x1b[0m"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use std::collections::BTreeMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Mutex;
+
+use chrono::{DateTime, SubsecRound, Utc};
+
+use crate::config::Config;
+use crate::error::WldError;
+
+/// Whether `--no-track` was given, so when devices were last seen isn't saved
+static DISABLED: AtomicBool = AtomicBool::new(false);
+
+/// Devices that have answered a request since the config was last updated, by address:
+/// when each last answered, and the firmware version it reported, if it did
+static SEEN: Mutex<BTreeMap<String, Seen>> = Mutex::new(BTreeMap::new());
+
+struct Seen {
+    at: DateTime<Utc>,
+    version: Option<String>,
+}
+
+/// Don't save when devices were last seen, or their firmware versions, for the rest of
+/// the process, as given with `--no-track`
+pub fn disable() {
+    DISABLED.store(true, Ordering::Relaxed);
+    SEEN.lock().unwrap().clear();
+}
+
+/// Note that the device at an address answered a request just now, and which firmware
+/// version it's running if it said. It's kept in memory until `save` is called.
+pub fn seen(address: &str, version: Option<&str>) {
+    if DISABLED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    let mut seen = SEEN.lock().unwrap();
+    let at = Utc::now().trunc_subsecs(0);
+    let entry = seen
+        .entry(address.to_string())
+        .or_insert(Seen { at, version: None });
+    entry.at = at;
+    if let Some(version) = version {
+        entry.version = Some(version.to_string());
+    }
+}
+
+/// Save when each saved device that answered a request since the last save was seen,
+/// and its firmware version, in the config. The config is only written when that changes
+/// what it says (see `Config::mark_seen`), and it's locked and read again first, so
+/// changes made since it was loaded, by this process or another one, aren't undone.
+///
+/// Keeping track is only done on the side, so if the config is locked by another wld
+/// process, or can't be saved, it's left as it is rather than holding up or failing the
+/// command that talked to the devices.
+pub fn save() {
+    let seen = std::mem::take(&mut *SEEN.lock().unwrap());
+    if seen.is_empty() {
+        return;
+    }
+    if let Err(e) = update_config(&seen) {
+        tracing::debug!("Couldn't save when devices were last seen: {e}");
+    }
+}
+
+fn update_config(seen: &BTreeMap<String, Seen>) -> Result<(), WldError> {
+    // Without a config, no device is saved, and one shouldn't be created just for this
+    if !Config::config_path()?.exists() {
+        return Ok(());
+    }
+
+    // Most of the time nothing's changed, so check before locking the config
+    if !mark_seen(&mut Config::load()?, seen) {
+        return Ok(());
+    }
+    let Some((mut config, _lock)) = Config::try_load_locked()? else {
+        tracing::debug!("Not saving when devices were last seen, as the config is locked");
+        return Ok(());
+    };
+    if mark_seen(&mut config, seen) {
+        config.save()?;
+    }
+    Ok(())
+}
+
+/// Record the devices that were seen in a config, returning whether it changed
+fn mark_seen(config: &mut Config, seen: &BTreeMap<String, Seen>) -> bool {
+    let mut changed = false;
+    for (address, seen) in seen {
+        changed |= config.mark_seen(address, seen.at, seen.version.as_deref());
+    }
+    changed
+}
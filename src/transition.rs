@@ -0,0 +1,67 @@
+/// The longest transition WLED's `tt` field can hold in `wled-json-api-library`, which
+/// models it as a `u8` count of 100ms units
+const MAX_TENTHS: f64 = u8::MAX as f64;
+
+/// Parse a transition time in seconds (e.g. `1.5`) into WLED's 100ms units, rounding to
+/// the nearest unit
+pub fn parse_transition(value: &str) -> Result<u8, String> {
+    let seconds: f64 = value
+        .trim()
+        .parse()
+        .map_err(|_| format!("Invalid transition '{value}': expected a number of seconds"))?;
+
+    if !seconds.is_finite() || seconds < 0.0 {
+        return Err(format!(
+            "Invalid transition '{value}': must be between 0 and {} seconds",
+            MAX_TENTHS / 10.0
+        ));
+    }
+
+    let tenths = (seconds * 10.0).round();
+    if tenths > MAX_TENTHS {
+        return Err(format!(
+            "Invalid transition '{value}': must be between 0 and {} seconds",
+            MAX_TENTHS / 10.0
+        ));
+    }
+
+    Ok(tenths as u8)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_whole_seconds() {
+        assert_eq!(parse_transition("2"), Ok(20));
+        assert_eq!(parse_transition("0"), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_fractional_seconds() {
+        assert_eq!(parse_transition("1.5"), Ok(15));
+        assert_eq!(parse_transition("0.25"), Ok(3));
+        assert_eq!(parse_transition("0.04"), Ok(0));
+    }
+
+    #[test]
+    fn test_parse_maximum() {
+        assert_eq!(parse_transition("25.5"), Ok(255));
+        assert_eq!(
+            parse_transition("25.6"),
+            Err("Invalid transition '25.6': must be between 0 and 25.5 seconds".to_string())
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_negative_and_non_numbers() {
+        assert!(parse_transition("-1").is_err());
+        assert!(parse_transition("NaN").is_err());
+        assert!(parse_transition("inf").is_err());
+        assert_eq!(
+            parse_transition("soon"),
+            Err("Invalid transition 'soon': expected a number of seconds".to_string())
+        );
+    }
+}
@@ -0,0 +1,260 @@
+use std::time::Duration;
+
+use serde_json::{json, Value};
+use wled_json_api_library::wled::Wled;
+
+use crate::device::connect_to_device;
+use crate::error::WldError;
+use crate::{api, interrupt};
+
+/// A device whose state is being watched, as `wld watch` does
+pub struct Watch {
+    /// The device's IP address
+    pub ip: String,
+    /// The names of the device's effects, to label them with
+    pub effect_names: Vec<String>,
+    wled: Wled,
+}
+
+impl Watch {
+    /// Connect to a device to watch it. From then on, Ctrl-C stops `run` rather than the
+    /// whole process.
+    pub fn start(device: Option<&str>) -> Result<Self, WldError> {
+        interrupt::catch_ctrl_c()?;
+
+        let (ip, wled) = connect_to_device(device)?;
+        // Effect names are only used to label the effect, so carry on without them
+        let effect_names = api::get_effect_names(&wled).unwrap_or_default();
+
+        Ok(Watch {
+            ip,
+            effect_names,
+            wled,
+        })
+    }
+
+    /// Read the device's state every `interval` until Ctrl-C is pressed, calling `show`
+    /// with a snapshot of it each time, or why it couldn't be read
+    pub fn run<E>(
+        &self,
+        interval: Duration,
+        mut show: impl FnMut(Result<Snapshot, WldError>) -> Result<(), E>,
+    ) -> Result<(), E> {
+        loop {
+            let snapshot = api::get_json::<Value>(&self.wled, "json/state")
+                .map(|state| Snapshot::from_state(&state));
+            show(snapshot)?;
+
+            if !interrupt::sleep(interval) {
+                return Ok(());
+            }
+        }
+    }
+}
+
+/// The parts of a device's state `wld watch` shows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Snapshot {
+    pub on: Option<bool>,
+    pub brightness: Option<u8>,
+    /// Effect on the main segment
+    pub effect: Option<u16>,
+    /// Active preset, or -1 if none is
+    pub preset: Option<i16>,
+}
+
+impl Snapshot {
+    /// Pick out the watched fields from a raw `json/state` response
+    pub fn from_state(state: &Value) -> Self {
+        let main_segment = state["mainseg"].as_u64().unwrap_or(0);
+        let segment = state["seg"]
+            .as_array()
+            .and_then(|segments| {
+                segments
+                    .iter()
+                    .find(|s| s["id"].as_u64() == Some(main_segment))
+            })
+            .unwrap_or(&Value::Null);
+
+        Snapshot {
+            on: state["on"].as_bool(),
+            brightness: state["bri"].as_u64().and_then(|v| u8::try_from(v).ok()),
+            effect: segment["fx"].as_u64().and_then(|v| u16::try_from(v).ok()),
+            preset: state["ps"].as_i64().and_then(|v| i16::try_from(v).ok()),
+        }
+    }
+
+    /// A one-line summary, e.g. `ON | brightness 128 | effect 9 (Rainbow) | preset none`.
+    /// With `highlight`, values that differ from `previous` are shown in bold yellow.
+    pub fn render(
+        &self,
+        previous: Option<&Snapshot>,
+        effect_names: &[String],
+        highlight: bool,
+    ) -> String {
+        let field = |text: String, changed: bool| {
+            if highlight && changed {
+                format!("\x1b[1;33m{text}\x1b[0m")
+            } else {
+                text
+            }
+        };
+
+        [
+            field(self.power_text(), previous.is_some_and(|p| p.on != self.on)),
+            format!(
+                "brightness {}",
+                field(
+                    self.brightness_text(),
+                    previous.is_some_and(|p| p.brightness != self.brightness)
+                )
+            ),
+            format!(
+                "effect {}",
+                field(
+                    self.effect_text(effect_names),
+                    previous.is_some_and(|p| p.effect != self.effect)
+                )
+            ),
+            format!(
+                "preset {}",
+                field(
+                    self.preset_text(),
+                    previous.is_some_and(|p| p.preset != self.preset)
+                )
+            ),
+        ]
+        .join(" | ")
+    }
+
+    /// The snapshot as a JSON object for `--json` output, stamped with `time` (seconds
+    /// since the Unix epoch)
+    pub fn to_json(&self, effect_names: &[String], time: u64) -> Value {
+        let effect_name = self.effect.and_then(|id| effect_names.get(usize::from(id)));
+
+        json!({
+            "time": time,
+            "on": self.on,
+            "bri": self.brightness,
+            "fx": self.effect,
+            "effect": effect_name,
+            "ps": self.preset,
+        })
+    }
+
+    fn power_text(&self) -> String {
+        match self.on {
+            Some(true) => "ON".to_string(),
+            Some(false) => "OFF".to_string(),
+            None => "n/a".to_string(),
+        }
+    }
+
+    fn brightness_text(&self) -> String {
+        self.brightness
+            .map_or_else(|| "n/a".to_string(), |b| b.to_string())
+    }
+
+    fn effect_text(&self, effect_names: &[String]) -> String {
+        match self.effect {
+            Some(id) => match effect_names.get(usize::from(id)) {
+                Some(name) => format!("{id} ({name})"),
+                None => id.to_string(),
+            },
+            None => "n/a".to_string(),
+        }
+    }
+
+    fn preset_text(&self) -> String {
+        match self.preset {
+            Some(id) if id >= 0 => id.to_string(),
+            Some(_) => "none".to_string(),
+            None => "n/a".to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        vec!["Solid".to_string(), "Blink".to_string()]
+    }
+
+    #[test]
+    fn test_from_state_uses_main_segment() {
+        let state = json!({
+            "on": true, "bri": 128, "ps": 3, "mainseg": 1,
+            "seg": [{"id": 0, "fx": 0}, {"id": 1, "fx": 9}]
+        });
+
+        assert_eq!(
+            Snapshot::from_state(&state),
+            Snapshot {
+                on: Some(true),
+                brightness: Some(128),
+                effect: Some(9),
+                preset: Some(3),
+            }
+        );
+        assert_eq!(Snapshot::from_state(&json!({})), Snapshot::default());
+    }
+
+    #[test]
+    fn test_render() {
+        let snapshot = Snapshot {
+            on: Some(false),
+            brightness: Some(40),
+            effect: Some(1),
+            preset: Some(-1),
+        };
+
+        assert_eq!(
+            snapshot.render(None, &names(), true),
+            "OFF | brightness 40 | effect 1 (Blink) | preset none"
+        );
+        assert_eq!(
+            Snapshot::default().render(None, &[], false),
+            "n/a | brightness n/a | effect n/a | preset n/a"
+        );
+    }
+
+    #[test]
+    fn test_render_highlights_changes() {
+        let previous = Snapshot {
+            on: Some(true),
+            brightness: Some(40),
+            effect: Some(0),
+            preset: Some(-1),
+        };
+        let current = Snapshot {
+            brightness: Some(80),
+            ..previous.clone()
+        };
+
+        assert_eq!(
+            current.render(Some(&previous), &names(), true),
+            "ON | brightness \x1b[1;33m80\x1b[0m | effect 0 (Solid) | preset none"
+        );
+        assert_eq!(
+            current.render(Some(&previous), &names(), false),
+            "ON | brightness 80 | effect 0 (Solid) | preset none"
+        );
+    }
+
+    #[test]
+    fn test_to_json() {
+        let snapshot = Snapshot {
+            on: Some(true),
+            brightness: Some(255),
+            effect: Some(0),
+            preset: None,
+        };
+
+        assert_eq!(
+            snapshot.to_json(&names(), 1_700_000_000),
+            json!({"time": 1_700_000_000, "on": true, "bri": 255, "fx": 0, "effect": "Solid", "ps": null})
+        );
+    }
+}
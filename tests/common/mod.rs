@@ -0,0 +1,411 @@
+// Not every test binary uses every helper
+#![allow(dead_code)]
+
+use serde_json::{json, Value};
+use std::io::{BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+
+// A request received by the mock device
+#[derive(Debug, Clone)]
+pub struct RecordedRequest {
+    pub method: String,
+    pub path: String,
+    pub body: String,
+}
+
+#[derive(Debug)]
+struct DeviceState {
+    state: Value,
+    info: Value,
+    // Served as cfg.json, which older firmware doesn't have
+    cfg: Option<Value>,
+    presets: Value,
+    effects: Value,
+    palettes: Value,
+    nodes: Value,
+    requests: Vec<RecordedRequest>,
+    failing_posts: usize,
+    // State updates to hang up on without answering, like a device dropping a connection
+    dropped_posts: usize,
+    // The Authorization header every request must have, like a proxy asking for a login
+    authorization: Option<String>,
+    // How long to wait before answering each request, like a device on a slow network
+    response_delay: Duration,
+    // Whether to keep connections open for more requests, rather than closing each one
+    keep_alive: bool,
+    // How many connections have been opened to the device
+    connections: usize,
+}
+
+// A minimal fake WLED device serving the JSON API on a local port
+pub struct MockWled {
+    address: String,
+    device: Arc<Mutex<DeviceState>>,
+}
+
+impl MockWled {
+    pub fn start() -> Self {
+        Self::start_on("127.0.0.1:0")
+    }
+
+    // Start on a particular local address, e.g. "[::1]:0" for IPv6
+    pub fn start_on(address: &str) -> Self {
+        let listener = TcpListener::bind(address).expect("Failed to bind mock WLED server");
+        let address = listener.local_addr().unwrap().to_string();
+
+        let device = Arc::new(Mutex::new(DeviceState {
+            state: json!({
+                "on": true,
+                "bri": 128,
+                "ps": -1,
+                "pl": -1,
+                "mainseg": 0,
+                "lor": 0,
+                "udpn": {"send": false, "recv": true, "sgrp": 1, "rgrp": 1},
+                "seg": [{"id": 0, "start": 0, "stop": 30, "on": true, "bri": 255,
+                         "col": [[255, 160, 0], [0, 0, 0], [0, 0, 0]], "fx": 0, "pal": 0}]
+            }),
+            info: json!({"leds": {"count": 60}}),
+            cfg: Some(json!({"id": {"name": "mock"}})),
+            presets: json!({"0": {}}),
+            effects: json!(["Solid", "Blink", "Breathe", "RSVD", "Rainbow"]),
+            palettes: json!(["Default", "* Random Cycle", "Party", "Ocean"]),
+            nodes: json!({"nodes": []}),
+            requests: Vec::new(),
+            failing_posts: 0,
+            dropped_posts: 0,
+            authorization: None,
+            response_delay: Duration::ZERO,
+            keep_alive: false,
+            connections: 0,
+        }));
+
+        let shared = Arc::clone(&device);
+        thread::spawn(move || {
+            for stream in listener.incoming().flatten() {
+                let device = Arc::clone(&shared);
+                device.lock().unwrap().connections += 1;
+                thread::spawn(move || handle_connection(stream, &device));
+            }
+        });
+
+        MockWled { address, device }
+    }
+
+    // The address to save the device under, e.g. "127.0.0.1:54321"
+    pub fn address(&self) -> &str {
+        &self.address
+    }
+
+    pub fn state(&self) -> Value {
+        self.device.lock().unwrap().state.clone()
+    }
+
+    pub fn set_segments(&self, segments: Value) {
+        self.device.lock().unwrap().state["seg"] = segments;
+    }
+
+    pub fn set_info(&self, info: Value) {
+        self.device.lock().unwrap().info = info;
+    }
+
+    pub fn cfg(&self) -> Option<Value> {
+        self.device.lock().unwrap().cfg.clone()
+    }
+
+    pub fn set_cfg(&self, cfg: Option<Value>) {
+        self.device.lock().unwrap().cfg = cfg;
+    }
+
+    pub fn set_presets(&self, presets: Value) {
+        self.device.lock().unwrap().presets = presets;
+    }
+
+    pub fn set_effects(&self, effects: Value) {
+        self.device.lock().unwrap().effects = effects;
+    }
+
+    pub fn set_nodes(&self, nodes: Value) {
+        self.device.lock().unwrap().nodes = json!({ "nodes": nodes });
+    }
+
+    pub fn presets(&self) -> Value {
+        self.device.lock().unwrap().presets.clone()
+    }
+
+    // Turn down requests without this Authorization header, e.g. "Basic YWRtaW46aHVudGVyMg=="
+    pub fn require_authorization(&self, authorization: &str) {
+        self.device.lock().unwrap().authorization = Some(authorization.to_string());
+    }
+
+    // Answer every request after `delay`, so requests made at the same time overlap
+    pub fn set_response_delay(&self, delay: Duration) {
+        self.device.lock().unwrap().response_delay = delay;
+    }
+
+    // Keep connections open for more requests, like a device that supports keep-alive
+    pub fn keep_connections_alive(&self) {
+        self.device.lock().unwrap().keep_alive = true;
+    }
+
+    // How many connections have been opened to the device
+    pub fn connections(&self) -> usize {
+        self.device.lock().unwrap().connections
+    }
+
+    // Make the next `count` state updates fail with a server error
+    pub fn fail_next_posts(&self, count: usize) {
+        self.device.lock().unwrap().failing_posts = count;
+    }
+
+    // Hang up on the next `count` state updates without answering them
+    pub fn drop_next_posts(&self, count: usize) {
+        self.device.lock().unwrap().dropped_posts = count;
+    }
+
+    pub fn requests(&self) -> Vec<RecordedRequest> {
+        self.device.lock().unwrap().requests.clone()
+    }
+
+    // Bodies of the requests that posted state updates, parsed as JSON
+    pub fn state_posts(&self) -> Vec<Value> {
+        self.requests()
+            .into_iter()
+            .filter(|r| r.method == "POST" && r.path == "/json/state")
+            .map(|r| serde_json::from_str(&r.body).expect("State update should be valid JSON"))
+            .collect()
+    }
+}
+
+fn handle_connection(stream: TcpStream, device: &Mutex<DeviceState>) {
+    let mut reader = BufReader::new(stream.try_clone().unwrap());
+    let mut stream = stream;
+    while handle_request(&mut reader, &mut stream, device) {}
+}
+
+// Answer one request on a connection, returning whether to wait for another
+fn handle_request(
+    reader: &mut BufReader<TcpStream>,
+    stream: &mut TcpStream,
+    device: &Mutex<DeviceState>,
+) -> bool {
+    let mut request_line = String::new();
+    if !matches!(reader.read_line(&mut request_line), Ok(read) if read > 0) {
+        return false;
+    }
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default().to_string();
+    let path = parts.next().unwrap_or_default().to_string();
+
+    let mut content_length = 0;
+    let mut authorization = None;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).is_err() || header.trim().is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            if name.eq_ignore_ascii_case("content-length") {
+                content_length = value.trim().parse().unwrap_or(0);
+            } else if name.eq_ignore_ascii_case("authorization") {
+                authorization = Some(value.trim().to_string());
+            }
+        }
+    }
+
+    let mut body = vec![0; content_length];
+    if reader.read_exact(&mut body).is_err() {
+        return false;
+    }
+    let body = String::from_utf8_lossy(&body).to_string();
+
+    let (status, response, delay, keep_alive) = {
+        let mut device = device.lock().unwrap();
+        device.requests.push(RecordedRequest {
+            method: method.clone(),
+            path: path.clone(),
+            body: body.clone(),
+        });
+        if method == "POST" && path == "/json/state" && device.dropped_posts > 0 {
+            device.dropped_posts -= 1;
+            return false;
+        }
+        let (status, response) =
+            if device.authorization.is_some() && device.authorization != authorization {
+                ("401 Unauthorized", "Unauthorized".to_string())
+            } else {
+                route(&mut device, &method, &path, &body)
+            };
+        (status, response, device.response_delay, device.keep_alive)
+    };
+    thread::sleep(delay);
+
+    let connection = if keep_alive { "keep-alive" } else { "close" };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: {connection}\r\n\r\n{response}",
+        response.len()
+    );
+    stream.write_all(response.as_bytes()).is_ok() && keep_alive
+}
+
+fn route(device: &mut DeviceState, method: &str, path: &str, body: &str) -> (&'static str, String) {
+    match (method, path) {
+        ("GET", "/json/cfg") => ("200 OK", "{}".to_string()),
+        ("GET", "/json/state") => ("200 OK", device.state.to_string()),
+        ("GET", "/json/info") => ("200 OK", device.info.to_string()),
+        ("GET", "/presets.json") => ("200 OK", device.presets.to_string()),
+        ("GET", "/cfg.json") => match &device.cfg {
+            Some(cfg) => ("200 OK", cfg.to_string()),
+            None => ("404 Not Found", "Not Found".to_string()),
+        },
+        ("GET", "/json/eff") => ("200 OK", device.effects.to_string()),
+        ("GET", "/json/pal") => ("200 OK", device.palettes.to_string()),
+        ("GET", "/json/nodes") => ("200 OK", device.nodes.to_string()),
+        ("POST", "/json/state") if device.failing_posts > 0 => {
+            device.failing_posts -= 1;
+            (
+                "500 Internal Server Error",
+                r#"{"error":"Busy"}"#.to_string(),
+            )
+        }
+        ("POST", "/json/state") => match serde_json::from_str::<Value>(body) {
+            Ok(update) => ("200 OK", apply_update(device, update)),
+            Err(_) => ("400 Bad Request", r#"{"error":9}"#.to_string()),
+        },
+        ("POST", "/json/cfg") => match serde_json::from_str::<Value>(body) {
+            Ok(settings) => {
+                merge(device.cfg.get_or_insert_with(|| json!({})), settings);
+                ("200 OK", r#"{"success":true}"#.to_string())
+            }
+            Err(_) => ("400 Bad Request", r#"{"error":9}"#.to_string()),
+        },
+        ("POST", "/upload") => upload(device, body),
+        _ => ("404 Not Found", r#"{"error":"Not Found"}"#.to_string()),
+    }
+}
+
+// Change the settings given, leaving the rest as they were, like WLED does
+fn merge(settings: &mut Value, changes: Value) {
+    match (settings, changes) {
+        (Value::Object(settings), Value::Object(changes)) => {
+            for (key, value) in changes {
+                merge(settings.entry(key).or_insert(Value::Null), value);
+            }
+        }
+        (settings, changes) => *settings = changes,
+    }
+}
+
+// Save a file uploaded as multipart form data, as the file editor in the web UI does
+fn upload(device: &mut DeviceState, body: &str) -> (&'static str, String) {
+    let Some((name, contents)) = parse_upload(body) else {
+        return ("400 Bad Request", "Bad upload".to_string());
+    };
+    let Ok(contents) = serde_json::from_str::<Value>(contents) else {
+        return ("400 Bad Request", "Bad upload".to_string());
+    };
+
+    match name.trim_start_matches('/') {
+        "presets.json" => device.presets = contents,
+        "cfg.json" => {
+            device.cfg = Some(contents);
+            // Uploading the configuration reboots the device, which resets the uptime
+            device.info["uptime"] = json!(0);
+        }
+        _ => {}
+    }
+    ("200 OK", "File Uploaded!".to_string())
+}
+
+// The file name and contents of the single file in a multipart form body
+fn parse_upload(body: &str) -> Option<(&str, &str)> {
+    let (_, rest) = body.split_once("filename=\"")?;
+    let (name, rest) = rest.split_once('"')?;
+    let (_, rest) = rest.split_once("\r\n\r\n")?;
+    let (contents, _) = rest.rsplit_once("\r\n--")?;
+    Some((name, contents))
+}
+
+// Apply a state update the way WLED does, returning the response body
+fn apply_update(device: &mut DeviceState, update: Value) -> String {
+    let Value::Object(update) = update else {
+        return r#"{"error":9}"#.to_string();
+    };
+
+    let mut verbose = false;
+    let mut save_slot = None;
+    let mut save_name = None;
+
+    for (key, value) in update {
+        match key.as_str() {
+            "v" => verbose = value.as_bool().unwrap_or(false),
+            "psave" => save_slot = value.as_u64(),
+            "n" => save_name = value.as_str().map(str::to_string),
+            "on" if value == "t" => {
+                let on = device.state["on"].as_bool().unwrap_or(false);
+                device.state["on"] = Value::Bool(!on);
+            }
+            "ps" => {
+                let id = value.as_i64().unwrap_or(-1);
+                let preset = device.presets.get(id.to_string()).cloned();
+                let exists = preset
+                    .as_ref()
+                    .is_some_and(|p| p.as_object().is_some_and(|p| !p.is_empty()));
+                device.state["ps"] = json!(if exists { id } else { -1 });
+                // Applying a playlist's preset starts the playlist
+                if preset.is_some_and(|p| p.get("playlist").is_some()) {
+                    device.state["pl"] = json!(id);
+                }
+            }
+            // Rebooting resets the uptime
+            "rb" => device.info["uptime"] = json!(0),
+            "seg" => merge_segments(&mut device.state, value),
+            // Objects like "udpn" are merged rather than replaced
+            _ => match (&mut device.state[key.as_str()], value) {
+                (Value::Object(existing), Value::Object(update)) => existing.extend(update),
+                (existing, value) => *existing = value,
+            },
+        }
+    }
+
+    if let Some(slot) = save_slot {
+        let name = save_name.unwrap_or_else(|| format!("Preset {slot}"));
+        let mut preset = device.state.clone();
+        preset["n"] = Value::String(name);
+        device.presets[slot.to_string()] = preset;
+    }
+
+    if verbose {
+        device.state.to_string()
+    } else {
+        r#"{"success":true}"#.to_string()
+    }
+}
+
+// Merge segment updates into the existing segments by ID
+fn merge_segments(state: &mut Value, updates: Value) {
+    let Value::Array(updates) = updates else {
+        return;
+    };
+
+    for (index, update) in updates.into_iter().enumerate() {
+        let id = update["id"].as_u64().unwrap_or(index as u64);
+        let segments = state["seg"].as_array_mut().unwrap();
+        match segments.iter_mut().find(|s| s["id"].as_u64() == Some(id)) {
+            Some(segment) => {
+                if let (Value::Object(segment), Value::Object(update)) = (segment, update) {
+                    segment.extend(update);
+                }
+            }
+            None => segments.push(update),
+        }
+    }
+
+    // WLED deletes segments whose stop is set to 0
+    if let Some(segments) = state["seg"].as_array_mut() {
+        segments.retain(|s| s["stop"] != 0);
+    }
+}
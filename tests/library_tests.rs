@@ -0,0 +1,113 @@
+mod common;
+
+use common::MockWled;
+use std::env;
+use std::path::PathBuf;
+use std::process;
+use std::sync::OnceLock;
+use wld::brightness::BrightnessValue;
+use wld::color::Rgb;
+use wld::config::Config;
+use wld::device::{self, DeviceStatus};
+
+// The config is chosen once for the whole process, so every test shares a config file in
+// its own temporary directory rather than the user's
+fn use_temp_config() -> &'static PathBuf {
+    static PATH: OnceLock<PathBuf> = OnceLock::new();
+
+    PATH.get_or_init(|| {
+        let path = env::temp_dir()
+            .join(format!("wld_library_tests_{}", process::id()))
+            .join("config.toml");
+        Config::use_path(path.clone());
+        path
+    })
+}
+
+#[test]
+fn test_set_device_power_and_brightness() {
+    use_temp_config();
+    let device = MockWled::start();
+
+    let ip = device::set_device_power(Some(device.address()), false, None)
+        .expect("Should turn the device off");
+    assert_eq!(ip, device.address());
+    assert_eq!(device.state()["on"], false);
+
+    let (_, previous, brightness) = device::set_device_brightness(
+        Some(device.address()),
+        BrightnessValue::Relative(100),
+        None,
+        false,
+    )
+    .expect("Should set the brightness");
+    // An off device is turned up from 0, and turned on
+    assert_eq!((previous, brightness), (Some(0), 100));
+    assert_eq!(device.state()["bri"], 100);
+    assert_eq!(device.state()["on"], true);
+}
+
+#[test]
+fn test_connections_are_reused() {
+    use_temp_config();
+    let device = MockWled::start();
+    device.keep_connections_alive();
+
+    device::set_device_power(Some(device.address()), true, None).unwrap();
+    device::set_device_brightness(
+        Some(device.address()),
+        BrightnessValue::Relative(10),
+        None,
+        false,
+    )
+    .unwrap();
+    let (status, _) = device::get_device_status_and_brightness(device.address());
+    assert!(matches!(status, DeviceStatus::On));
+    device::set_device_power(Some(device.address()), false, None).unwrap();
+
+    assert!(device.requests().len() >= 5);
+    assert_eq!(device.connections(), 1);
+}
+
+#[test]
+fn test_device_status() {
+    use_temp_config();
+    let device = MockWled::start();
+
+    let (status, brightness) = device::get_device_status_and_brightness(device.address());
+    assert!(matches!(status, DeviceStatus::On));
+    assert_eq!(brightness, Some(128));
+
+    // Nothing listens on port 1, so the device can't be reached
+    let (status, brightness) = device::get_device_status_and_brightness("127.0.0.1:1");
+    assert!(matches!(status, DeviceStatus::Unreachable));
+    assert_eq!(brightness, None);
+}
+
+#[test]
+fn test_errors_are_returned() {
+    use_temp_config();
+
+    let error = device::set_device_power(Some("127.0.0.1:1"), true, None)
+        .expect_err("Should fail to reach the device");
+    assert!(error.to_string().contains("Connection refused"), "{error}");
+}
+
+#[test]
+fn test_saved_device_by_name() {
+    let path = use_temp_config();
+    let device = MockWled::start();
+
+    let (mut config, _lock) = Config::load_locked().expect("Should load the config");
+    config.add_device("porch".to_string(), device.address().to_string());
+    config.save().expect("Should save the config");
+    assert!(path.exists());
+
+    let ip = device::set_device_color(Some("porch"), Rgb::new(0, 128, 255), None)
+        .expect("Should set the color");
+    assert_eq!(ip, device.address());
+    assert_eq!(
+        device.state()["seg"][0]["col"][0],
+        serde_json::json!([0, 128, 255])
+    );
+}
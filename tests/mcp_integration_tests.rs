@@ -1,8 +1,12 @@
+mod common;
+
+use common::MockWled;
 use serde_json::Value;
 use std::env;
 use std::fs;
+use std::io::{BufRead, BufReader};
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::sync::atomic::{AtomicU64, Ordering};
 use std::time::{SystemTime, UNIX_EPOCH};
 
@@ -49,30 +53,77 @@ fn add_device_to_config(temp_home: &PathBuf, name: &str, ip: &str) {
     Command::new(binary_path)
         .args(["add", name, ip])
         .env("HOME", temp_home)
+        .env_remove("WLD_CONFIG")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("WLD_PROFILE")
+        .env_remove("WLD_DEVICE")
         .output()
         .expect("Failed to add device");
 }
 
+// Helper to check that a response is a JSON-RPC "invalid params" error, which tools give
+// for calls that don't make sense, and get its message
+fn invalid_params_message(response: &Value) -> String {
+    assert_eq!(response["error"]["code"], -32602, "{response}");
+    response["error"]["message"]
+        .as_str()
+        .unwrap_or_else(|| panic!("Should have a message: {response}"))
+        .to_string()
+}
+
 // Helper to send MCP requests via a bash script with timeout
 fn send_mcp_request_via_script(temp_home: &Path, requests: Vec<&str>) -> Result<String, String> {
+    send_mcp_request_with_env(temp_home, &[], requests)
+}
+
+// Helper to send MCP requests to a server started with extra environment variables, like
+// $WLD_CONFIG
+fn send_mcp_request_with_env(
+    temp_home: &Path,
+    env: &[(&str, &str)],
+    requests: Vec<&str>,
+) -> Result<String, String> {
+    run_mcp_script(temp_home, env, &requests, "\n")
+}
+
+// Helper to send MCP requests one at a time, for calls that depend on earlier ones. The
+// server handles requests at the same time, so each is given a second to finish first.
+fn send_mcp_requests_in_order(temp_home: &Path, requests: Vec<&str>) -> Result<String, String> {
+    run_mcp_script(temp_home, &[], &requests, "\n  sleep 1\n")
+}
+
+fn run_mcp_script(
+    temp_home: &Path,
+    env: &[(&str, &str)],
+    requests: &[&str],
+    separator: &str,
+) -> Result<String, String> {
     let binary_path = get_binary_path();
 
     // Create a temporary script to run the MCP server with input
-    // For tests involving network calls, we need to wait longer for timeouts
+    // Devices that can't be reached take up to the 5 second request timeout to fail, and
+    // several are checked at the same time, so the server is given a little longer than that
     let script = format!(
         r#"#!/bin/bash
 export HOME={}
+unset WLD_CONFIG WLD_PROFILE WLD_DEVICE XDG_CONFIG_HOME
+{}
 {{
 {}
-  sleep 12
-}} | timeout 20 {} mcp 2>/dev/null
+  sleep 7
+}} | timeout {} {} mcp 2>/dev/null
 "#,
         temp_home.display(),
+        env.iter()
+            .map(|(name, value)| format!("export {name}='{value}'"))
+            .collect::<Vec<_>>()
+            .join("\n"),
         requests
             .iter()
             .map(|r| format!("  echo '{r}'"))
             .collect::<Vec<_>>()
-            .join("\n"),
+            .join(separator),
+        15 + requests.len(),
         binary_path.display()
     );
 
@@ -191,6 +242,84 @@ fn test_mcp_wled_devices_with_devices() {
     );
 }
 
+#[test]
+fn test_mcp_uses_config_variable() {
+    let temp_home = setup_temp_home();
+    let config = temp_home.join("elsewhere.toml");
+
+    Command::new(get_binary_path())
+        .args([
+            "--config",
+            config.to_str().unwrap(),
+            "add",
+            "porch",
+            "192.168.1.40",
+        ])
+        .env("HOME", &temp_home)
+        .output()
+        .expect("Failed to add device");
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let call_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_devices","arguments":{}}}"#;
+
+    let output = send_mcp_request_with_env(
+        &temp_home,
+        &[("WLD_CONFIG", config.to_str().unwrap())],
+        vec![init_request, init_notification, call_request],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    assert!(
+        output.contains("porch") && output.contains("192.168.1.40"),
+        "Response should contain the device from $WLD_CONFIG"
+    );
+}
+
+#[test]
+fn test_mcp_uses_profile_variable() {
+    let temp_home = setup_temp_home();
+    add_device_to_config(&temp_home, "living_room", "192.168.1.100");
+
+    let wld = |args: &[&str]| {
+        Command::new(get_binary_path())
+            .args(args)
+            .env("HOME", &temp_home)
+            .env_remove("WLD_CONFIG")
+            .env_remove("XDG_CONFIG_HOME")
+            .env_remove("WLD_PROFILE")
+            .env_remove("WLD_DEVICE")
+            .output()
+            .expect("Failed to run wld")
+    };
+    wld(&["profile", "create", "work"]);
+    wld(&["--profile", "work", "add", "desk", "192.168.5.20"]);
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let call_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_devices","arguments":{}}}"#;
+
+    let output = send_mcp_request_with_env(
+        &temp_home,
+        &[("WLD_PROFILE", "work")],
+        vec![init_request, init_notification, call_request],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    assert!(
+        output.contains("desk") && output.contains("192.168.5.20"),
+        "Response should contain the device from the work profile"
+    );
+    assert!(
+        !output.contains("living_room"),
+        "Response shouldn't contain devices from the default profile"
+    );
+}
+
 #[test]
 fn test_mcp_wled_on_no_default() {
     let temp_home = setup_temp_home();
@@ -217,9 +346,11 @@ fn test_mcp_wled_on_no_default() {
 #[test]
 fn test_mcp_wled_on_with_device_parameter() {
     let temp_home = setup_temp_home();
+    let default = MockWled::start();
+    let device = MockWled::start();
 
-    // Add a test device
-    add_device_to_config(&temp_home, "test_light", "192.168.1.50");
+    add_device_to_config(&temp_home, "default_light", default.address());
+    add_device_to_config(&temp_home, "test_light", device.address());
 
     let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
     let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
@@ -233,36 +364,54 @@ fn test_mcp_wled_on_with_device_parameter() {
 
     cleanup_temp_home(&temp_home);
 
-    // The device doesn't actually exist, so we'll get a connection error,
-    // but the MCP call should still succeed in accepting the parameter
-    assert!(
-        output.contains("content") || output.contains("isError"),
-        "Response should contain result: {output}"
+    let response: Value = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|response| response["id"] == 6)
+        .unwrap_or_else(|| panic!("Should find response: {output}"));
+    assert_eq!(
+        response["result"]["content"][0]["text"],
+        "Device turned on successfully"
     );
+
+    // Only the device asked for is changed, not the default one
+    assert_eq!(device.state_posts(), [serde_json::json!({"on": true})]);
+    assert_eq!(device.state()["on"], true);
+    assert!(default.state_posts().is_empty());
 }
 
 #[test]
 fn test_mcp_wled_off_with_ip_address() {
     let temp_home = setup_temp_home();
+    // A device that isn't saved can be given by its address
+    let device = MockWled::start();
 
     let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
     let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
-    let call_request = r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"wled_off","arguments":{"device":"192.168.1.99"}}}"#;
+    let call_request = format!(
+        r#"{{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{{"name":"wled_off","arguments":{{"device":"{}"}}}}}}"#,
+        device.address()
+    );
 
     let output = send_mcp_request_via_script(
         &temp_home,
-        vec![init_request, init_notification, call_request],
+        vec![init_request, init_notification, &call_request],
     )
     .expect("Failed to send request");
 
     cleanup_temp_home(&temp_home);
 
-    // The device doesn't actually exist, so we'll get a connection error,
-    // but the MCP call should still succeed in accepting the IP parameter
-    assert!(
-        output.contains("content") || output.contains("isError"),
-        "Response should contain result: {output}"
+    let response: Value = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|response| response["id"] == 7)
+        .unwrap_or_else(|| panic!("Should find response: {output}"));
+    assert_eq!(
+        response["result"]["content"][0]["text"],
+        "Device turned off successfully"
     );
+    assert_eq!(device.state_posts(), [serde_json::json!({"on": false})]);
+    assert_eq!(device.state()["on"], false);
 }
 
 #[test]
@@ -336,6 +485,58 @@ fn test_mcp_tools_have_valid_schemas() {
         tool_names.contains(&"wled_off"),
         "Should have wled_off tool"
     );
+
+    // Clients use these hints to decide which calls to approve automatically, so every
+    // tool should say whether it's read-only, and changes say whether they're destructive
+    let hints = |name: &str| -> (Value, Value) {
+        let tool = tools
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap_or_else(|| panic!("Should have {name} tool"));
+        let annotations = &tool["annotations"];
+        (
+            annotations["readOnlyHint"].clone(),
+            annotations["destructiveHint"].clone(),
+        )
+    };
+    for tool in tools {
+        assert!(
+            tool["annotations"]["readOnlyHint"].is_boolean(),
+            "Tool '{}' should have readOnlyHint",
+            tool["name"]
+        );
+    }
+    for name in [
+        "wled_devices",
+        "wled_status",
+        "wled_effects_list",
+        "wled_presets_list",
+    ] {
+        assert_eq!(hints(name), (Value::Bool(true), Value::Null), "{name}");
+    }
+    for name in [
+        "wled_on",
+        "wled_off",
+        "wled_toggle",
+        "wled_brightness",
+        "wled_color",
+        "wled_set_effect",
+        "wled_apply_preset",
+        "wled_set_default",
+    ] {
+        assert_eq!(
+            hints(name),
+            (Value::Bool(false), Value::Bool(false)),
+            "{name}"
+        );
+    }
+    for name in ["wled_add_device", "wled_delete_device", "wled_set_state"] {
+        assert_eq!(
+            hints(name),
+            (Value::Bool(false), Value::Bool(true)),
+            "{name}"
+        );
+    }
 }
 
 #[test]
@@ -494,60 +695,1185 @@ fn test_mcp_wled_status_no_devices() {
 #[test]
 fn test_mcp_wled_status_with_devices() {
     let temp_home = setup_temp_home();
+    let living_room = MockWled::start();
+    let bedroom = MockWled::start();
+
+    // Add test devices, one of which can't be reached
+    add_device_to_config(&temp_home, "living_room", living_room.address());
+    add_device_to_config(&temp_home, "bedroom", bedroom.address());
+    add_device_to_config(&temp_home, "attic", "127.0.0.1:9");
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":10,"method":"tools/call","params":{"name":"wled_off","arguments":{"device":"bedroom"}}}"#,
+        r#"{"jsonrpc":"2.0","id":11,"method":"tools/call","params":{"name":"wled_status","arguments":{}}}"#,
+    ];
+    let output =
+        send_mcp_requests_in_order(&temp_home, requests.to_vec()).expect("Failed to send request");
 
-    // Add test devices
-    add_device_to_config(&temp_home, "living_room", "192.168.1.100");
-    add_device_to_config(&temp_home, "bedroom", "192.168.1.101");
+    cleanup_temp_home(&temp_home);
+
+    let response: Value = output
+        .lines()
+        .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+        .find(|response| response["id"] == 11)
+        .unwrap_or_else(|| panic!("Should find response: {output}"));
+    let text = response["result"]["content"][0]["text"]
+        .as_str()
+        .expect("Status should be text");
+
+    assert!(text.starts_with("Checking status of all devices"), "{text}");
+    assert!(text.contains(&format!(
+        "  living_room ({}) (default): ON",
+        living_room.address()
+    )));
+    assert!(text.contains(&format!("  bedroom ({}): OFF", bedroom.address())));
+    assert!(text.contains("  attic (127.0.0.1:9): UNREACHABLE"));
+    assert!(
+        text.ends_with("Warning: Some devices are unreachable"),
+        "{text}"
+    );
+
+    // Checking the status doesn't change any device
+    assert!(living_room.state_posts().is_empty());
+    assert_eq!(bedroom.state_posts(), [serde_json::json!({"on": false})]);
+}
+
+#[test]
+fn test_mcp_tools_list_includes_status() {
+    let temp_home = setup_temp_home();
 
     let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
     let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
-    let call_request = r#"{"jsonrpc":"2.0","id":11,"method":"tools/call","params":{"name":"wled_status","arguments":{}}}"#;
+    let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
 
     let output = send_mcp_request_via_script(
         &temp_home,
-        vec![init_request, init_notification, call_request],
+        vec![init_request, init_notification, tools_request],
     )
     .expect("Failed to send request");
 
     cleanup_temp_home(&temp_home);
 
     assert!(
-        output.contains("Checking status of all devices"),
-        "Response should contain status check message"
+        output.contains("wled_status"),
+        "Response should list wled_status tool"
     );
+}
+
+#[test]
+fn test_mcp_wled_brightness_value_and_percent_are_exclusive() {
+    let temp_home = setup_temp_home();
+    add_device_to_config(&temp_home, "test_light", "192.168.1.99");
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
+    let both_request = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_brightness","arguments":{"value":128,"percent":50}}}"#;
+    let neither_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_brightness","arguments":{}}}"#;
+
+    let output = send_mcp_request_via_script(
+        &temp_home,
+        vec![
+            init_request,
+            init_notification,
+            tools_request,
+            both_request,
+            neither_request,
+        ],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let brightness = tools["result"]["tools"]
+        .as_array()
+        .expect("Should have tools array")
+        .iter()
+        .find(|t| t["name"] == "wled_brightness")
+        .expect("Should have wled_brightness tool")
+        .clone();
+    let properties = &brightness["inputSchema"]["properties"];
     assert!(
-        output.contains("living_room") && output.contains("192.168.1.100"),
-        "Response should contain living_room device"
+        properties["value"].is_object(),
+        "Should have value property"
     );
     assert!(
-        output.contains("bedroom") && output.contains("192.168.1.101"),
-        "Response should contain bedroom device"
+        properties["percent"].is_object(),
+        "Should have percent property"
+    );
+
+    assert!(invalid_params_message(&response(3)).contains("not both"));
+    assert_eq!(
+        invalid_params_message(&response(4)),
+        "Give either value or percent"
+    );
+}
+
+#[test]
+fn test_mcp_wled_color() {
+    let temp_home = setup_temp_home();
+    add_device_to_config(&temp_home, "test_light", "127.0.0.1:9");
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
+    let invalid_request = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_color","arguments":{"color":"blurple"}}}"#;
+    let unreachable_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_color","arguments":{"color":"orange","device":"test_light"}}}"#;
+
+    let output = send_mcp_request_via_script(
+        &temp_home,
+        vec![
+            init_request,
+            init_notification,
+            tools_request,
+            invalid_request,
+            unreachable_request,
+        ],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let color = tools["result"]["tools"]
+        .as_array()
+        .expect("Should have tools array")
+        .iter()
+        .find(|t| t["name"] == "wled_color")
+        .expect("Should have wled_color tool")
+        .clone();
+    assert!(
+        color["description"]
+            .as_str()
+            .unwrap()
+            .contains("CSS color name"),
+        "Description should say which formats are accepted"
+    );
+    let schema = &color["inputSchema"];
+    assert_eq!(schema["properties"]["color"]["type"], "string", "{schema}");
+    assert_eq!(schema["properties"]["device"]["type"], "string", "{schema}");
+    assert_eq!(schema["properties"]["device"]["nullable"], true, "{schema}");
+    assert_eq!(schema["required"], serde_json::json!(["color"]), "{schema}");
+
+    assert!(invalid_params_message(&response(3)).starts_with("Invalid color"));
+
+    let unreachable = response(4);
+    assert_eq!(unreachable["result"]["isError"], true, "{unreachable}");
+    assert_eq!(
+        unreachable["result"]["structuredContent"]["category"],
+        "device"
     );
-    // Since devices don't actually exist, they should be unreachable
     assert!(
-        output.contains("UNREACHABLE"),
-        "Response should indicate devices are unreachable"
+        unreachable
+            .to_string()
+            .contains("Connection refused by device at 127.0.0.1:9"),
+        "{unreachable}"
     );
 }
 
 #[test]
-fn test_mcp_tools_list_includes_status() {
+fn test_mcp_wled_effects() {
     let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    add_device_to_config(&temp_home, "test_light", device.address());
 
     let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
     let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
     let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
+    let list_request = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_effects_list","arguments":{}}}"#;
+    let by_id_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_set_effect","arguments":{"effect":2,"speed":40}}}"#;
+    let by_name_request = r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"wled_set_effect","arguments":{"effect":"rain","intensity":200,"device":"test_light"}}}"#;
+    let unknown_request = r#"{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{"name":"wled_set_effect","arguments":{"effect":"Sparkle"}}}"#;
 
     let output = send_mcp_request_via_script(
         &temp_home,
-        vec![init_request, init_notification, tools_request],
+        vec![
+            init_request,
+            init_notification,
+            tools_request,
+            list_request,
+            by_id_request,
+            by_name_request,
+            unknown_request,
+        ],
     )
     .expect("Failed to send request");
 
     cleanup_temp_home(&temp_home);
 
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let tool = |name: &str| -> Value {
+        tools["result"]["tools"]
+            .as_array()
+            .expect("Should have tools array")
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap_or_else(|| panic!("Should have {name} tool"))
+            .clone()
+    };
+    let list_schema = &tool("wled_effects_list")["inputSchema"];
+    assert_eq!(list_schema["properties"]["device"]["type"], "string");
+    let set_schema = &tool("wled_set_effect")["inputSchema"];
+    for property in ["effect", "speed", "intensity", "device"] {
+        assert!(
+            set_schema["properties"][property].is_object(),
+            "Should have {property} property: {set_schema}"
+        );
+    }
+    assert_eq!(set_schema["required"], serde_json::json!(["effect"]));
+
+    // One effect per line with its ID, skipping reserved entries
+    let list = response(3);
+    let text = list["result"]["content"][0]["text"].as_str().unwrap();
     assert!(
-        output.contains("wled_status"),
-        "Response should list wled_status tool"
+        text.ends_with("0: Solid\n1: Blink\n2: Breathe\n4: Rainbow\n"),
+        "{text}"
+    );
+
+    let by_id = response(4);
+    assert!(
+        by_id.to_string().contains("Set effect 2 (Breathe)"),
+        "{by_id}"
+    );
+    let by_name = response(5);
+    assert!(
+        by_name.to_string().contains("Set effect 4 (Rainbow)"),
+        "{by_name}"
+    );
+    // The calls can run at the same time, so the updates can arrive in either order
+    let posts = device.state_posts();
+    let segment = |fx: u64| -> Value {
+        posts
+            .iter()
+            .map(|post| post["seg"][0].clone())
+            .find(|segment| segment["fx"] == fx)
+            .unwrap_or_else(|| panic!("Should set effect {fx}: {posts:?}"))
+    };
+    assert_eq!(segment(2)["sx"], 40);
+    assert_eq!(segment(4)["ix"], 200);
+
+    // The device doesn't have the effect
+    assert!(invalid_params_message(&response(6)).contains("Sparkle"));
+}
+
+#[test]
+fn test_mcp_wled_presets() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    device.set_presets(serde_json::json!({
+        "0": {},
+        "1": {"n": "Movie", "on": true, "bri": 40},
+        "2": {"n": "Reading", "on": true, "bri": 255}
+    }));
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
+    // No devices are saved, so these have nothing to act on
+    let list_no_device = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_presets_list","arguments":{}}}"#;
+    let apply_no_device = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_apply_preset","arguments":{"preset":1}}}"#;
+    let list_request = format!(
+        r#"{{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{{"name":"wled_presets_list","arguments":{{"device":"{}"}}}}}}"#,
+        device.address()
+    );
+    let apply_by_name = format!(
+        r#"{{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{{"name":"wled_apply_preset","arguments":{{"preset":"READING","device":"{}"}}}}}}"#,
+        device.address()
+    );
+    let apply_unknown = format!(
+        r#"{{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{{"name":"wled_apply_preset","arguments":{{"preset":"Party","device":"{}"}}}}}}"#,
+        device.address()
+    );
+
+    let output = send_mcp_request_via_script(
+        &temp_home,
+        vec![
+            init_request,
+            init_notification,
+            tools_request,
+            list_no_device,
+            apply_no_device,
+            &list_request,
+            &apply_by_name,
+            &apply_unknown,
+        ],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let tool = |name: &str| -> Value {
+        tools["result"]["tools"]
+            .as_array()
+            .expect("Should have tools array")
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap_or_else(|| panic!("Should have {name} tool"))
+            .clone()
+    };
+    let list_schema = &tool("wled_presets_list")["inputSchema"];
+    assert_eq!(list_schema["properties"]["device"]["type"], "string");
+    let apply_schema = &tool("wled_apply_preset")["inputSchema"];
+    assert!(
+        apply_schema["properties"]["preset"].is_object(),
+        "{apply_schema}"
+    );
+    assert_eq!(apply_schema["properties"]["device"]["type"], "string");
+    assert_eq!(apply_schema["required"], serde_json::json!(["preset"]));
+
+    for id in [3, 4] {
+        assert!(invalid_params_message(&response(id)).contains("No device specified"));
+    }
+
+    let list = response(5);
+    let text = list["result"]["content"][0]["text"].as_str().unwrap();
+    assert!(text.ends_with("1: Movie\n2: Reading\n"), "{text}");
+
+    let by_name = response(6);
+    assert!(
+        by_name.to_string().contains("Applied preset 2"),
+        "{by_name}"
+    );
+
+    assert!(invalid_params_message(&response(7)).contains("'Movie' (1), 'Reading' (2)"));
+}
+
+#[test]
+fn test_mcp_device_management() {
+    let temp_home = setup_temp_home();
+    // Saved with the CLI before the server starts, so the server has to re-read the config
+    // rather than clobber it
+    add_device_to_config(&temp_home, "attic", "192.168.1.30");
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_add_device","arguments":{"name":"porch","address":"192.168.1.40"}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_add_device","arguments":{"name":"porch","address":"192.168.1.41"}}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"wled_add_device","arguments":{"name":"porch","address":"192.168.1.41","replace":true}}}"#,
+        r#"{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{"name":"wled_add_device","arguments":{"name":"tv","address":"192.168.1.400"}}}"#,
+        r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"wled_set_default","arguments":{"name":"porch"}}}"#,
+        r#"{"jsonrpc":"2.0","id":8,"method":"tools/call","params":{"name":"wled_delete_device","arguments":{"name":"attic"}}}"#,
+        r#"{"jsonrpc":"2.0","id":9,"method":"tools/call","params":{"name":"wled_delete_device","arguments":{"name":"attic"}}}"#,
+    ];
+    let output =
+        send_mcp_requests_in_order(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    let config = fs::read_to_string(temp_home.join(".config/wld/config.toml")).unwrap();
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+    let text = |id: u64| -> String {
+        let response = response(id);
+        response["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_else(|| panic!("Should have text: {response}"))
+            .to_string()
+    };
+    let is_error = |id: u64| response(id)["result"]["isError"] == true;
+
+    let tools = response(2);
+    let tool = |name: &str| -> Value {
+        tools["result"]["tools"]
+            .as_array()
+            .expect("Should have tools array")
+            .iter()
+            .find(|t| t["name"] == name)
+            .unwrap_or_else(|| panic!("Should have {name} tool"))
+            .clone()
+    };
+    let add_schema = &tool("wled_add_device")["inputSchema"];
+    assert_eq!(add_schema["properties"]["name"]["type"], "string");
+    assert_eq!(add_schema["properties"]["address"]["type"], "string");
+    assert_eq!(add_schema["properties"]["replace"]["type"], "boolean");
+    assert_eq!(
+        add_schema["required"],
+        serde_json::json!(["name", "address"])
+    );
+    for name in ["wled_delete_device", "wled_set_default"] {
+        let schema = &tool(name)["inputSchema"];
+        assert_eq!(schema["properties"]["name"]["type"], "string", "{name}");
+        assert_eq!(schema["required"], serde_json::json!(["name"]), "{name}");
+    }
+
+    // Each change confirms the devices saved afterwards
+    assert!(!is_error(3));
+    assert_eq!(
+        text(3),
+        "Added device 'porch' with IP 192.168.1.40\n\nSaved devices:\n  attic - 192.168.1.30 (default)\n  porch - 192.168.1.40\n"
+    );
+    assert!(invalid_params_message(&response(4)).contains("Set replace to true"));
+    assert!(!is_error(5));
+    assert!(text(5).contains("from 192.168.1.40 to 192.168.1.41"));
+    assert!(invalid_params_message(&response(6)).contains("Invalid device address"));
+    assert!(text(7).contains("porch - 192.168.1.41 (default)"));
+    assert_eq!(
+        text(8),
+        "Deleted device 'attic'\n\nSaved devices:\n  porch - 192.168.1.41 (default)\n"
+    );
+    assert_eq!(
+        invalid_params_message(&response(9)),
+        "Device 'attic' not found"
+    );
+
+    assert!(
+        config.contains("[devices.porch]\nip = \"192.168.1.41\""),
+        "{config}"
+    );
+    assert!(!config.contains("attic"), "{config}");
+}
+
+#[test]
+fn test_mcp_wled_toggle() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    add_device_to_config(&temp_home, "test_light", device.address());
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_toggle","arguments":{}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_toggle","arguments":{"device":"test_light"}}}"#,
+    ];
+    let output =
+        send_mcp_requests_in_order(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let toggle = tools["result"]["tools"]
+        .as_array()
+        .expect("Should have tools array")
+        .iter()
+        .find(|t| t["name"] == "wled_toggle")
+        .expect("Should have wled_toggle tool")
+        .clone();
+    assert_eq!(
+        toggle["inputSchema"]["properties"]["device"]["type"],
+        "string"
+    );
+
+    // The mock device starts on
+    assert_eq!(
+        response(3)["result"]["content"][0]["text"],
+        format!("Device at {} is now OFF", device.address())
+    );
+    assert_eq!(
+        response(4)["result"]["content"][0]["text"],
+        format!("Device at {} is now ON", device.address())
+    );
+    assert_eq!(device.state()["on"], true);
+}
+
+#[test]
+fn test_mcp_wled_status_for_one_device() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    device.set_segments(serde_json::json!([
+        {"id": 0, "start": 0, "stop": 30, "on": true, "bri": 255, "fx": 4, "pal": 0}
+    ]));
+    add_device_to_config(&temp_home, "test_light", device.address());
+    add_device_to_config(&temp_home, "attic", "127.0.0.1:9");
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let init_notification = r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#;
+    let tools_request = r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#;
+    let reachable_request = r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_status","arguments":{"device":"test_light"}}}"#;
+    let unreachable_request = r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_status","arguments":{"device":"attic"}}}"#;
+    let unknown_request = r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"wled_status","arguments":{"device":"atic"}}}"#;
+
+    let output = send_mcp_request_via_script(
+        &temp_home,
+        vec![
+            init_request,
+            init_notification,
+            tools_request,
+            reachable_request,
+            unreachable_request,
+            unknown_request,
+        ],
+    )
+    .expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let status = tools["result"]["tools"]
+        .as_array()
+        .expect("Should have tools array")
+        .iter()
+        .find(|t| t["name"] == "wled_status")
+        .expect("Should have wled_status tool")
+        .clone();
+    assert_eq!(
+        status["inputSchema"]["properties"]["device"]["type"],
+        "string"
+    );
+
+    // The status comes back as structured content, and as the same JSON in the text
+    let reachable = response(3);
+    let expected = serde_json::json!({
+        "name": "test_light",
+        "ip": device.address(),
+        "default": true,
+        "status": "on",
+        "brightness": 128,
+        "effect": {"id": 4, "name": "Rainbow"},
+    });
+    assert_eq!(reachable["result"]["structuredContent"], expected);
+    let text = reachable["result"]["content"][0]["text"].as_str().unwrap();
+    assert_eq!(serde_json::from_str::<Value>(text).unwrap(), expected);
+
+    let unreachable = response(4);
+    assert_eq!(
+        unreachable["result"]["structuredContent"],
+        serde_json::json!({
+            "name": "attic",
+            "ip": "127.0.0.1:9",
+            "default": false,
+            "status": "unreachable",
+        })
+    );
+
+    // A typo of a saved name is an error, rather than an address that can't be reached
+    assert!(invalid_params_message(&response(5)).contains("did you mean 'attic'"));
+}
+
+#[test]
+fn test_mcp_device_resources() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    add_device_to_config(&temp_home, "test_light", device.address());
+    add_device_to_config(&temp_home, "attic", "127.0.0.1:9");
+    add_device_to_config(&temp_home, "front porch", device.address());
+    device.set_info(serde_json::json!({
+        "name": "Test Light",
+        "ver": "0.14.0",
+        "leds": {"count": 60},
+        "fs": {"u": 12, "t": 983}
+    }));
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"resources/list","params":{}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"resources/read","params":{"uri":"wled://test_light"}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"resources/read","params":{"uri":"wled://front%20porch"}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"resources/read","params":{"uri":"wled://attic"}}"#,
+        r#"{"jsonrpc":"2.0","id":6,"method":"resources/read","params":{"uri":"wled://garage"}}"#,
+    ];
+    let output =
+        send_mcp_request_via_script(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    assert!(response(1)["result"]["capabilities"]["resources"].is_object());
+
+    // Every saved device is listed, including ones that can't be reached
+    let resources = response(2)["result"]["resources"].clone();
+    let resources = resources.as_array().expect("Should have resources array");
+    let uris: Vec<&str> = resources.iter().filter_map(|r| r["uri"].as_str()).collect();
+    assert_eq!(
+        uris,
+        vec!["wled://attic", "wled://front%20porch", "wled://test_light"]
+    );
+    assert_eq!(resources[2]["name"], "test_light");
+    assert_eq!(resources[2]["mimeType"], "application/json");
+    assert_eq!(
+        resources[2]["description"],
+        format!("State of the WLED device at {} (default)", device.address())
+    );
+    assert_eq!(
+        resources[0]["description"],
+        "State of the WLED device at 127.0.0.1:9"
+    );
+
+    for id in [3, 4] {
+        let contents = response(id)["result"]["contents"][0].clone();
+        assert_eq!(contents["mimeType"], "application/json");
+        let json: Value = serde_json::from_str(contents["text"].as_str().unwrap())
+            .expect("Should read the device as JSON");
+        assert_eq!(json["state"]["on"], true);
+        assert_eq!(json["state"]["bri"], 128);
+        // Only some of the device's info is included
+        assert_eq!(
+            json["info"],
+            serde_json::json!({"name": "Test Light", "ver": "0.14.0", "leds": {"count": 60}})
+        );
+    }
+    assert_eq!(
+        response(4)["result"]["contents"][0]["uri"],
+        "wled://front%20porch"
+    );
+
+    let unreachable = response(5)["error"]["message"].clone();
+    assert!(
+        unreachable
+            .as_str()
+            .unwrap()
+            .starts_with("Device unreachable: couldn't read the state of 127.0.0.1:9"),
+        "Unexpected error: {unreachable}"
+    );
+    assert_eq!(
+        response(6)["error"]["message"],
+        "No saved device has the URI wled://garage"
+    );
+}
+
+#[test]
+fn test_mcp_error_categories() {
+    let temp_home = setup_temp_home();
+    add_device_to_config(&temp_home, "attic", "127.0.0.1:9");
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"wled_on","arguments":{"device":"atic"}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_on","arguments":{"device":"attic"}}}"#,
+    ];
+    let output =
+        send_mcp_request_via_script(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    // A config that can't be read
+    fs::write(temp_home.join(".config/wld/config.toml"), "devices = [").unwrap();
+    let requests = [
+        requests[0],
+        requests[1],
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_devices","arguments":{}}}"#,
+    ];
+    let broken_output =
+        send_mcp_request_via_script(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .chain(broken_output.lines())
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}{broken_output}"))
+    };
+
+    // A device name that's clearly a typo is a mistake in the call
+    assert!(invalid_params_message(&response(2)).contains("did you mean 'attic'"));
+
+    // Failures while carrying out the call are tool errors, with a category to tell them apart
+    let unreachable = response(3);
+    assert_eq!(unreachable["result"]["isError"], true, "{unreachable}");
+    let structured = &unreachable["result"]["structuredContent"];
+    assert_eq!(structured["category"], "device", "{unreachable}");
+    assert_eq!(
+        structured["message"],
+        unreachable["result"]["content"][0]["text"]
+    );
+
+    let broken = response(4);
+    assert_eq!(broken["result"]["isError"], true, "{broken}");
+    assert_eq!(
+        broken["result"]["structuredContent"]["category"], "config",
+        "{broken}"
+    );
+    assert!(broken["result"]["structuredContent"]["message"]
+        .as_str()
+        .unwrap()
+        .starts_with("Failed to load configuration"));
+}
+
+#[test]
+fn test_mcp_over_http() {
+    let temp_home = setup_temp_home();
+    add_device_to_config(&temp_home, "test_light", "192.168.1.50");
+
+    let mut server = Command::new(get_binary_path())
+        .args(["mcp", "--http", "127.0.0.1:0"])
+        .env("HOME", &temp_home)
+        .env("WLD_MCP_TOKEN", "secret")
+        .env_remove("WLD_CONFIG")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("WLD_PROFILE")
+        .env_remove("WLD_DEVICE")
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start server");
+
+    // The server logs the address it's listening on, which has the port it was given
+    let mut log = BufReader::new(server.stderr.take().unwrap()).lines();
+    let url = log
+        .by_ref()
+        .map_while(Result::ok)
+        .find_map(|line| {
+            line.split_once("Serving MCP over HTTP at ")
+                .map(|(_, url)| url.to_string())
+        })
+        .expect("Should log the address it's serving at");
+    // Keep reading the log, so the server doesn't block writing to it
+    let log = std::thread::spawn(move || log.map_while(Result::ok).collect::<Vec<_>>());
+
+    let client = reqwest::blocking::Client::new();
+    let post = |body: &str, token: Option<&str>, session: Option<&str>| {
+        let mut request = client
+            .post(&url)
+            .header("Content-Type", "application/json")
+            .header("Accept", "application/json, text/event-stream")
+            .body(body.to_string());
+        if let Some(token) = token {
+            request = request.bearer_auth(token);
+        }
+        if let Some(session) = session {
+            request = request.header("Mcp-Session-Id", session);
+        }
+        request.send().expect("Failed to send request")
+    };
+    // Responses are sent as server-sent events
+    let data = |response: reqwest::blocking::Response| -> Value {
+        let body = response.text().unwrap();
+        body.lines()
+            .filter_map(|line| line.strip_prefix("data: "))
+            .find_map(|data| serde_json::from_str(data).ok())
+            .unwrap_or_else(|| panic!("Should have a JSON-RPC response: {body}"))
+    };
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    assert_eq!(post(init_request, None, None).status(), 401);
+    assert_eq!(post(init_request, Some("wrong"), None).status(), 401);
+
+    let response = post(init_request, Some("secret"), None);
+    assert_eq!(response.status(), 200);
+    let session = response
+        .headers()
+        .get("mcp-session-id")
+        .expect("Should start a session")
+        .to_str()
+        .unwrap()
+        .to_string();
+    assert!(data(response)["result"]["capabilities"]["tools"].is_object());
+
+    post(
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        Some("secret"),
+        Some(&session),
+    );
+    let devices = data(post(
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"wled_devices","arguments":{}}}"#,
+        Some("secret"),
+        Some(&session),
+    ));
+    assert!(
+        devices["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap()
+            .contains("test_light - 192.168.1.50"),
+        "{devices}"
+    );
+
+    // Ctrl-C stops the server cleanly
+    Command::new("kill")
+        .args(["-INT", &server.id().to_string()])
+        .status()
+        .expect("Failed to interrupt server");
+    let status = server.wait().expect("Failed to wait for server");
+    cleanup_temp_home(&temp_home);
+
+    assert!(status.success(), "Server should exit cleanly: {status}");
+    let log = log.join().unwrap();
+    assert!(
+        log.iter().any(|line| line.contains("Shutting down")),
+        "{log:?}"
+    );
+}
+
+#[test]
+fn test_mcp_over_http_address_in_use() {
+    let temp_home = setup_temp_home();
+    let taken = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+    let address = taken.local_addr().unwrap().to_string();
+
+    let output = Command::new(get_binary_path())
+        .args(["mcp", "--http", &address])
+        .env("HOME", &temp_home)
+        .env_remove("WLD_CONFIG")
+        .env_remove("XDG_CONFIG_HOME")
+        .env_remove("WLD_PROFILE")
+        .env_remove("WLD_DEVICE")
+        .output()
+        .expect("Failed to run server");
+
+    cleanup_temp_home(&temp_home);
+
+    assert_eq!(output.status.code(), Some(1), "{output:?}");
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains(&format!("Couldn't listen on {address}"))
+    );
+}
+
+#[test]
+fn test_mcp_instructions() {
+    let temp_home = setup_temp_home();
+
+    let init_request = r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#;
+    let instructions = |temp_home: &Path| -> String {
+        let output = send_mcp_request_via_script(temp_home, vec![init_request])
+            .expect("Failed to send request");
+        let response: Value = output
+            .lines()
+            .find_map(|line| serde_json::from_str(line).ok())
+            .unwrap_or_else(|| panic!("Should find initialize response: {output}"));
+        response["result"]["instructions"]
+            .as_str()
+            .unwrap_or_else(|| panic!("Should have instructions: {response}"))
+            .to_string()
+    };
+
+    let empty = instructions(&temp_home);
+    assert!(empty.contains("No devices are saved yet"), "{empty}");
+
+    add_device_to_config(&temp_home, "porch", "192.168.1.40");
+    add_device_to_config(&temp_home, "bedroom", "192.168.1.41");
+    let saved = instructions(&temp_home);
+
+    cleanup_temp_home(&temp_home);
+
+    assert!(
+        saved.contains("Saved devices: bedroom, porch (default)."),
+        "{saved}"
+    );
+    assert!(saved.contains("0-255"), "{saved}");
+    // An example call for every tool, using a saved device
+    for tool in [
+        "wled_devices",
+        "wled_on",
+        "wled_off",
+        "wled_toggle",
+        "wled_brightness",
+        "wled_color",
+        "wled_effects_list",
+        "wled_set_effect",
+        "wled_presets_list",
+        "wled_apply_preset",
+        "wled_nightlight",
+        "wled_set_state",
+        "wled_status",
+        "wled_add_device",
+        "wled_delete_device",
+        "wled_set_default",
+    ] {
+        assert!(saved.contains(&format!("- {tool} {{")), "{tool}: {saved}");
+    }
+    assert!(saved.contains(r#"wled_on {"device": "porch"}"#), "{saved}");
+}
+
+#[test]
+fn test_mcp_wled_status_reports_progress() {
+    let temp_home = setup_temp_home();
+    let kitchen = MockWled::start();
+    let porch = MockWled::start();
+    add_device_to_config(&temp_home, "attic", "127.0.0.1:9");
+    add_device_to_config(&temp_home, "kitchen", kitchen.address());
+    add_device_to_config(&temp_home, "porch", porch.address());
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"wled_status","arguments":{},"_meta":{"progressToken":"status"}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"logging/setLevel","params":{"level":"warning"}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_status","arguments":{}}}"#,
+    ];
+    let output =
+        send_mcp_requests_in_order(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let frames: Vec<Value> = output
+        .lines()
+        .filter_map(|line| serde_json::from_str(line).ok())
+        .collect();
+    let position = |id: u64| {
+        frames
+            .iter()
+            .position(|frame| frame["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+    let notifications = |method: &str, frames: &[Value]| -> Vec<Value> {
+        frames
+            .iter()
+            .filter(|frame| frame["method"] == method)
+            .map(|frame| frame["params"].clone())
+            .collect()
+    };
+    assert!(frames[0]["result"]["capabilities"]["logging"].is_object());
+
+    // Each device is logged and counted as it's checked, before the result
+    let first_call = &frames[..position(2)];
+    let mut messages: Vec<(String, String)> = notifications("notifications/message", first_call)
+        .iter()
+        .map(|params| {
+            (
+                params["level"].as_str().unwrap().to_string(),
+                params["data"].as_str().unwrap().to_string(),
+            )
+        })
+        .collect();
+    messages.sort();
+    assert_eq!(
+        messages,
+        vec![
+            (
+                "info".to_string(),
+                format!("kitchen ({}): ON", kitchen.address())
+            ),
+            (
+                "info".to_string(),
+                format!("porch ({}): ON", porch.address())
+            ),
+            (
+                "warning".to_string(),
+                "attic (127.0.0.1:9): UNREACHABLE".to_string()
+            ),
+        ]
+    );
+    let progress = notifications("notifications/progress", first_call);
+    assert_eq!(progress.len(), 3, "{output}");
+    for (done, params) in progress.iter().enumerate() {
+        assert_eq!(params["progressToken"], "status");
+        assert_eq!(params["progress"], done as f64 + 1.0);
+        assert_eq!(params["total"], 3.0);
+    }
+
+    // Only warnings once the client asks for them, and no progress without a token
+    let second_call = &frames[position(3)..position(4)];
+    let messages = notifications("notifications/message", second_call);
+    assert_eq!(messages.len(), 1, "{output}");
+    assert_eq!(messages[0]["level"], "warning");
+    assert!(notifications("notifications/progress", second_call).is_empty());
+}
+
+#[test]
+fn test_mcp_calls_to_one_device_take_turns() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    // Both calls read the device's state and send it back changed, so if they overlapped,
+    // the second to finish would undo the first
+    device.set_response_delay(std::time::Duration::from_millis(500));
+    add_device_to_config(&temp_home, "test_light", device.address());
+
+    let requests = [
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"wled_off","arguments":{}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_brightness","arguments":{"value":40,"device":"test_light"}}}"#,
+    ];
+    let output =
+        send_mcp_request_via_script(&temp_home, requests.to_vec()).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    for id in [2, 3] {
+        let response: Value = output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"));
+        assert_eq!(response["result"]["isError"], false, "{response}");
+    }
+
+    let state = device.state();
+    assert_eq!(state["on"], false, "{state}");
+    assert_eq!(state["bri"], 40, "{state}");
+}
+
+#[test]
+fn test_mcp_wled_set_state() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    add_device_to_config(&temp_home, "test_light", device.address());
+
+    let big_state = format!(
+        r#"{{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{{"name":"wled_set_state","arguments":{{"state":{{"seg":[{{"id":0,"n":"{}"}}]}}}}}}}}"#,
+        "x".repeat(20_000)
+    );
+    let requests = vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/list","params":{}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_set_state","arguments":{"state":{"bri":99,"v":true}}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_set_state","arguments":{"state":[{"bri":1}]}}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"wled_set_state","arguments":{"state":"on"}}}"#,
+        &big_state,
+    ];
+    let output = send_mcp_request_via_script(&temp_home, requests).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+
+    let tools = response(2);
+    let tool = tools["result"]["tools"]
+        .as_array()
+        .expect("Should have tools array")
+        .iter()
+        .find(|t| t["name"] == "wled_set_state")
+        .expect("Should have wled_set_state tool")
+        .clone();
+    assert!(tool["description"]
+        .as_str()
+        .unwrap()
+        .starts_with("Advanced"));
+    let schema = &tool["inputSchema"];
+    assert_eq!(schema["properties"]["state"]["type"], "object", "{schema}");
+    assert_eq!(schema["required"], serde_json::json!(["state"]), "{schema}");
+
+    // The device's response comes back as it is
+    let text = response(3)["result"]["content"][0]["text"]
+        .as_str()
+        .unwrap()
+        .to_string();
+    let reply: Value = serde_json::from_str(&text).expect("Should return the device's JSON");
+    assert_eq!(reply["bri"], 99);
+    assert_eq!(device.state()["bri"], 99);
+
+    for id in [4, 5] {
+        assert!(invalid_params_message(&response(id)).contains("must be a JSON object"));
+    }
+    assert!(invalid_params_message(&response(6)).contains("at most 16384"));
+    // Only the valid update reached the device
+    assert_eq!(device.state_posts().len(), 1);
+}
+
+#[test]
+fn test_mcp_wled_nightlight() {
+    let temp_home = setup_temp_home();
+    let device = MockWled::start();
+    add_device_to_config(&temp_home, "test_light", device.address());
+
+    let requests = vec![
+        r#"{"jsonrpc":"2.0","id":1,"method":"initialize","params":{"protocolVersion":"2025-03-26","capabilities":{},"clientInfo":{"name":"test","version":"1.0.0"}}}"#,
+        r#"{"jsonrpc":"2.0","method":"notifications/initialized"}"#,
+        r#"{"jsonrpc":"2.0","id":2,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"duration_minutes":15}}}"#,
+        r#"{"jsonrpc":"2.0","id":3,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"duration_minutes":30,"target_brightness":200,"mode":"sunrise"}}}"#,
+        r#"{"jsonrpc":"2.0","id":4,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"cancel":true}}}"#,
+        r#"{"jsonrpc":"2.0","id":5,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"duration_minutes":0}}}"#,
+        r#"{"jsonrpc":"2.0","id":6,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"duration_minutes":256}}}"#,
+        r#"{"jsonrpc":"2.0","id":7,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{"duration_minutes":10,"mode":"slow"}}}"#,
+        r#"{"jsonrpc":"2.0","id":8,"method":"tools/call","params":{"name":"wled_nightlight","arguments":{}}}"#,
+    ];
+    let output = send_mcp_request_via_script(&temp_home, requests).expect("Failed to send request");
+
+    cleanup_temp_home(&temp_home);
+
+    let response = |id: u64| -> Value {
+        output
+            .lines()
+            .filter_map(|line| serde_json::from_str::<Value>(line).ok())
+            .find(|response| response["id"] == id)
+            .unwrap_or_else(|| panic!("Should find response {id}: {output}"))
+    };
+    let text = |id: u64| -> String {
+        response(id)["result"]["content"][0]["text"]
+            .as_str()
+            .unwrap_or_else(|| panic!("Should have text for {id}: {output}"))
+            .to_string()
+    };
+
+    assert!(text(2).contains("will turn off at "), "{}", text(2));
+    assert!(text(2).contains("(in 15m)"), "{}", text(2));
+    assert!(
+        text(3).contains("will reach brightness 200 at "),
+        "{}",
+        text(3)
+    );
+    assert!(text(4).contains("Stopped the nightlight"), "{}", text(4));
+
+    assert!(invalid_params_message(&response(5)).contains("between 1 and 255"));
+    assert!(invalid_params_message(&response(6)).contains("between 1 and 255"));
+    assert!(invalid_params_message(&response(7)).contains("Invalid nightlight mode 'slow'"));
+    assert!(invalid_params_message(&response(8)).contains("Give duration_minutes"));
+
+    // Only the valid calls reached the device, each as a change to the nightlight
+    let posts = device.state_posts();
+    assert_eq!(posts.len(), 3, "{posts:?}");
+    assert_eq!(
+        posts[0]["nl"],
+        serde_json::json!({"on": true, "dur": 15, "mode": 0, "tbri": 0})
+    );
+    assert_eq!(
+        posts[1]["nl"],
+        serde_json::json!({"on": true, "dur": 30, "mode": 3, "tbri": 200})
     );
+    assert_eq!(posts[1]["on"], true);
+    assert_eq!(posts[2]["nl"], serde_json::json!({"on": false}));
 }